@@ -0,0 +1,134 @@
+//! [`Arbitrary`] implementations which can't simply be derived,
+//! generating values which are consistent enough to be serialized.
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+use grid::Grid;
+
+use crate::{
+    map::{FieldMapChunk, FieldMapProperties, Tile, TileLayer, TilesetsProperties},
+    misc::{Bgr555, MaybeCompressedData, Palette, Rgb555},
+};
+
+impl<'a> Arbitrary<'a> for Rgb555 {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self::from_bits(u.arbitrary::<u16>()?.into()))
+    }
+}
+impl<'a> Arbitrary<'a> for Bgr555 {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self::from_bits(u.arbitrary::<u16>()?.into()))
+    }
+}
+
+impl<'a> Arbitrary<'a> for Palette {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self(u.arbitrary()?))
+    }
+}
+/// Never empty, since empty palettes are stored as `None` in [`FieldMapChunk`].
+fn non_empty_palette(u: &mut Unstructured<'_>) -> Result<Palette> {
+    let mut palette = Palette::arbitrary(u)?;
+    if palette.0.is_empty() {
+        palette.0.push(u.arbitrary()?);
+    }
+    Ok(palette)
+}
+
+impl<'a> Arbitrary<'a> for Tile {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self::from_bits(u.arbitrary::<u16>()?.into()))
+    }
+}
+
+impl TileLayer {
+    /// Generates a non-empty layer with the given width.
+    fn arbitrary_with_width(u: &mut Unstructured<'_>, width: usize) -> Result<Self> {
+        let height = u.int_in_range(1..=64)?;
+        Ok(Self(Grid::from_vec(
+            (0..width * height)
+                .map(|_| u.arbitrary())
+                .collect::<Result<_>>()?,
+            width,
+        )))
+    }
+}
+impl<'a> Arbitrary<'a> for TileLayer {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let width = u.int_in_range(1..=64)?;
+        Self::arbitrary_with_width(u, width)
+    }
+}
+
+impl<'a> Arbitrary<'a> for TilesetsProperties {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self::from_bits(u.arbitrary()?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for FieldMapProperties {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self {
+            // Must be non-zero for the tile layers to be deserializable.
+            width: u.int_in_range(1..=64)?,
+            height: u.arbitrary()?,
+            unk_0x04: u.arbitrary()?,
+            tilesets_properties: u.arbitrary()?,
+            unk_0x06: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for FieldMapChunk {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let properties = FieldMapProperties::arbitrary(u)?;
+        let width = properties.width.into();
+        Ok(Self {
+            tile_layers: [
+                u.arbitrary::<bool>()?
+                    .then(|| TileLayer::arbitrary_with_width(u, width))
+                    .transpose()?,
+                u.arbitrary::<bool>()?
+                    .then(|| TileLayer::arbitrary_with_width(u, width))
+                    .transpose()?,
+                u.arbitrary::<bool>()?
+                    .then(|| TileLayer::arbitrary_with_width(u, width))
+                    .transpose()?,
+            ],
+            palettes: [
+                u.arbitrary::<bool>()?
+                    .then(|| non_empty_palette(u))
+                    .transpose()?,
+                u.arbitrary::<bool>()?
+                    .then(|| non_empty_palette(u))
+                    .transpose()?,
+                u.arbitrary::<bool>()?
+                    .then(|| non_empty_palette(u))
+                    .transpose()?,
+            ],
+            properties,
+            unk7: u.arbitrary()?,
+            unk8: u.arbitrary()?,
+            unk9: u.arbitrary()?,
+            unk10: u.arbitrary()?,
+            unk11: u.arbitrary()?,
+            unk12: u.arbitrary()?,
+            unk13: u.arbitrary()?,
+            unk14: u.arbitrary()?,
+            unk15: u.arbitrary()?,
+            unk16: u.arbitrary()?,
+            padding: u.arbitrary()?,
+        })
+    }
+}
+
+/// Only generates [`MaybeCompressedData::Uncompressed`], which is valid input for compression,
+/// and [`MaybeCompressedData::Compressed`], which is likely hostile input for decompression.
+impl<'a> Arbitrary<'a> for MaybeCompressedData {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(if u.arbitrary()? {
+            Self::Compressed(u.arbitrary()?)
+        } else {
+            Self::Uncompressed(u.arbitrary()?)
+        })
+    }
+}
@@ -0,0 +1,515 @@
+//! Reading of [Aseprite](https://www.aseprite.org) files (`.aseprite` / `.ase`),
+//! to import tilesets and sprite frames drawn in it.
+//!
+//! See <https://github.com/aseprite/aseprite/blob/main/docs/ase-file-specs.md>.
+
+use std::io::{self, Cursor, Read};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use flate2::read::ZlibDecoder;
+use grid::Grid;
+use rgb::Rgba;
+use thiserror::Error;
+
+use crate::{
+    map::{Tileset, TilesetTileFromColorsError},
+    misc::{Palette, Rgb555},
+    sprites::{ImportOptions, Sprite, SpriteImportError},
+};
+
+const HEADER_MAGIC: u16 = 0xA5E0;
+const FRAME_MAGIC: u16 = 0xF1FA;
+const HEADER_SIZE: u64 = 128;
+const FRAME_HEADER_SIZE: u32 = 16;
+const CHUNK_HEADER_SIZE: u32 = 6;
+
+const CHUNK_OLD_PALETTE: u16 = 0x0004;
+const CHUNK_LAYER: u16 = 0x2004;
+const CHUNK_CEL: u16 = 0x2005;
+const CHUNK_PALETTE: u16 = 0x2019;
+
+const LAYER_FLAG_VISIBLE: u16 = 1;
+const LAYER_FLAG_BACKGROUND: u16 = 8;
+const PALETTE_ENTRY_FLAG_HAS_NAME: u16 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AsepriteColorDepth {
+    Rgba,
+    Grayscale,
+    Indexed,
+}
+
+impl AsepriteColorDepth {
+    fn from_bits_per_pixel(value: u16) -> Option<Self> {
+        match value {
+            32 => Some(Self::Rgba),
+            16 => Some(Self::Grayscale),
+            8 => Some(Self::Indexed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AsepriteLayerKind {
+    Image,
+    Group,
+    /// Tilemap layers aren't read, and their cels are [`AsepriteCelContent::Unsupported`].
+    Tilemap,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AsepriteLayer {
+    pub name: String,
+    pub kind: AsepriteLayerKind,
+    pub flags: u16,
+    /// How deeply the layer is nested in groups, each of which is the closest
+    /// preceding layer with a lower level.
+    pub child_level: u16,
+    pub blend_mode: u16,
+    pub opacity: u8,
+}
+
+impl AsepriteLayer {
+    #[inline]
+    pub fn is_visible(&self) -> bool {
+        self.flags & LAYER_FLAG_VISIBLE != 0
+    }
+    /// Background layers are opaque even where their pixels are
+    /// the transparent palette index.
+    #[inline]
+    pub fn is_background(&self) -> bool {
+        self.flags & LAYER_FLAG_BACKGROUND != 0
+    }
+}
+
+/// The pixels of a cel, in the [`AsepriteColorDepth`] of the file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AsepritePixels {
+    /// Grayscale pixels are expanded into RGBA.
+    Rgba(Grid<Rgba<u8>>),
+    Indexed(Grid<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AsepriteCelContent {
+    Image(AsepritePixels),
+    /// The same content as the cel of the same layer in the given frame.
+    Linked(usize),
+    Unsupported(u16),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AsepriteCel {
+    /// An index into [`AsepriteFile::layers`].
+    pub layer: usize,
+    pub x: i16,
+    pub y: i16,
+    pub opacity: u8,
+    pub content: AsepriteCelContent,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AsepriteFrame {
+    /// In milliseconds.
+    pub duration: u16,
+    pub cels: Vec<AsepriteCel>,
+}
+
+/// The parts of an Aseprite file which are relevant for importing pixel art.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AsepriteFile {
+    pub width: u16,
+    pub height: u16,
+    pub color_depth: AsepriteColorDepth,
+    /// The palette index which is transparent in [`AsepriteColorDepth::Indexed`] files.
+    pub transparent_index: u8,
+    pub palette: Vec<Rgba<u8>>,
+    /// From the bottom to the top.
+    pub layers: Vec<AsepriteLayer>,
+    pub frames: Vec<AsepriteFrame>,
+}
+
+#[derive(Error, Debug)]
+pub enum AsepriteError {
+    #[error("the file has the wrong magic number {0:#06X}")]
+    InvalidMagic(u16),
+    #[error("frame {frame} has the wrong magic number {magic:#06X}")]
+    InvalidFrameMagic { frame: usize, magic: u16 },
+    #[error("unsupported color depth of {0} bits per pixel")]
+    UnsupportedColorDepth(u16),
+    #[error("chunk {chunk_type:#06X} of frame {frame} is too small")]
+    ChunkTooSmall { frame: usize, chunk_type: u16 },
+    #[error("there's no frame {0}")]
+    MissingFrame(usize),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+fn read_string(mut inp: impl Read) -> io::Result<String> {
+    let len = inp.read_u16::<LittleEndian>()?;
+    let mut buf = vec![0u8; len.into()];
+    inp.read_exact(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+impl AsepriteFile {
+    pub fn from_reader(mut inp: impl Read) -> Result<Self, AsepriteError> {
+        let mut header = [0u8; HEADER_SIZE as usize];
+        inp.read_exact(&mut header)?;
+        let mut header = Cursor::new(&header[..]);
+        let _file_size = header.read_u32::<LittleEndian>()?;
+        let magic = header.read_u16::<LittleEndian>()?;
+        if magic != HEADER_MAGIC {
+            return Err(AsepriteError::InvalidMagic(magic));
+        }
+        let num_frames = header.read_u16::<LittleEndian>()?;
+        let width = header.read_u16::<LittleEndian>()?;
+        let height = header.read_u16::<LittleEndian>()?;
+        let bits_per_pixel = header.read_u16::<LittleEndian>()?;
+        let color_depth = AsepriteColorDepth::from_bits_per_pixel(bits_per_pixel)
+            .ok_or(AsepriteError::UnsupportedColorDepth(bits_per_pixel))?;
+        header.set_position(28);
+        let transparent_index = header.read_u8()?;
+
+        let mut file = Self {
+            width,
+            height,
+            color_depth,
+            transparent_index,
+            palette: Vec::new(),
+            layers: Vec::new(),
+            frames: Vec::new(),
+        };
+        for frame in 0..num_frames.into() {
+            let frame_size = inp.read_u32::<LittleEndian>()?;
+            let magic = inp.read_u16::<LittleEndian>()?;
+            if magic != FRAME_MAGIC {
+                return Err(AsepriteError::InvalidFrameMagic { frame, magic });
+            }
+            let old_num_chunks = inp.read_u16::<LittleEndian>()?;
+            let duration = inp.read_u16::<LittleEndian>()?;
+            inp.read_u16::<LittleEndian>()?;
+            let num_chunks = match inp.read_u32::<LittleEndian>()? {
+                0 => old_num_chunks.into(),
+                x => x,
+            };
+
+            let mut data = vec![0u8; frame_size.saturating_sub(FRAME_HEADER_SIZE) as usize];
+            inp.read_exact(&mut data)?;
+            let mut data = Cursor::new(&data[..]);
+            let mut cels = Vec::new();
+            for _ in 0..num_chunks {
+                let chunk_size = data.read_u32::<LittleEndian>()?;
+                let chunk_type = data.read_u16::<LittleEndian>()?;
+                let chunk_size = chunk_size
+                    .checked_sub(CHUNK_HEADER_SIZE)
+                    .ok_or(AsepriteError::ChunkTooSmall { frame, chunk_type })?;
+                let start = data.position() as usize;
+                let chunk = data
+                    .get_ref()
+                    .get(start..start + chunk_size as usize)
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+                data.set_position((start + chunk_size as usize) as u64);
+                match chunk_type {
+                    CHUNK_OLD_PALETTE => file.read_old_palette(chunk)?,
+                    CHUNK_PALETTE => file.read_palette(chunk)?,
+                    CHUNK_LAYER => file.layers.push(Self::read_layer(chunk)?),
+                    CHUNK_CEL => cels.push(file.read_cel(chunk)?),
+                    _ => {}
+                }
+            }
+            file.frames.push(AsepriteFrame { duration, cels });
+        }
+        Ok(file)
+    }
+
+    fn read_old_palette(&mut self, chunk: &[u8]) -> io::Result<()> {
+        // Superseded by the new palette chunk if there's one.
+        if !self.palette.is_empty() {
+            return Ok(());
+        }
+        let mut inp = chunk;
+        let num_packets = inp.read_u16::<LittleEndian>()?;
+        let mut index = 0;
+        for _ in 0..num_packets {
+            index += usize::from(inp.read_u8()?);
+            let num_colors = match inp.read_u8()? {
+                0 => 256,
+                x => usize::from(x),
+            };
+            for _ in 0..num_colors {
+                let mut rgb = [0u8; 3];
+                inp.read_exact(&mut rgb)?;
+                if self.palette.len() <= index {
+                    self.palette.resize(index + 1, Rgba::new(0, 0, 0, 0xFF));
+                }
+                self.palette[index] = Rgba::new(rgb[0], rgb[1], rgb[2], 0xFF);
+                index += 1;
+            }
+        }
+        Ok(())
+    }
+    fn read_palette(&mut self, chunk: &[u8]) -> io::Result<()> {
+        let mut inp = chunk;
+        let size = inp.read_u32::<LittleEndian>()? as usize;
+        let first = inp.read_u32::<LittleEndian>()? as usize;
+        let last = inp.read_u32::<LittleEndian>()? as usize;
+        inp.read_exact(&mut [0u8; 8])?;
+        self.palette.resize(size, Rgba::new(0, 0, 0, 0xFF));
+        for index in first..=last {
+            let flags = inp.read_u16::<LittleEndian>()?;
+            let mut rgba = [0u8; 4];
+            inp.read_exact(&mut rgba)?;
+            if flags & PALETTE_ENTRY_FLAG_HAS_NAME != 0 {
+                read_string(&mut inp)?;
+            }
+            if let Some(color) = self.palette.get_mut(index) {
+                *color = Rgba::new(rgba[0], rgba[1], rgba[2], rgba[3]);
+            }
+        }
+        Ok(())
+    }
+    fn read_layer(chunk: &[u8]) -> io::Result<AsepriteLayer> {
+        let mut inp = chunk;
+        let flags = inp.read_u16::<LittleEndian>()?;
+        let kind = match inp.read_u16::<LittleEndian>()? {
+            0 => AsepriteLayerKind::Image,
+            1 => AsepriteLayerKind::Group,
+            _ => AsepriteLayerKind::Tilemap,
+        };
+        let child_level = inp.read_u16::<LittleEndian>()?;
+        // The default width and height are ignored.
+        inp.read_u32::<LittleEndian>()?;
+        let blend_mode = inp.read_u16::<LittleEndian>()?;
+        let opacity = inp.read_u8()?;
+        inp.read_exact(&mut [0u8; 3])?;
+        Ok(AsepriteLayer {
+            name: read_string(&mut inp)?,
+            kind,
+            flags,
+            child_level,
+            blend_mode,
+            opacity,
+        })
+    }
+    fn read_cel(&self, chunk: &[u8]) -> io::Result<AsepriteCel> {
+        let mut inp = chunk;
+        let layer = inp.read_u16::<LittleEndian>()?.into();
+        let x = inp.read_i16::<LittleEndian>()?;
+        let y = inp.read_i16::<LittleEndian>()?;
+        let opacity = inp.read_u8()?;
+        let cel_type = inp.read_u16::<LittleEndian>()?;
+        // The z-index is ignored, so cels are drawn in the order of their layers.
+        inp.read_exact(&mut [0u8; 7])?;
+        let content = match cel_type {
+            0 | 2 => {
+                let width = usize::from(inp.read_u16::<LittleEndian>()?);
+                let height = usize::from(inp.read_u16::<LittleEndian>()?);
+                let mut pixels = Vec::new();
+                if cel_type == 0 {
+                    pixels = inp.to_vec();
+                } else {
+                    ZlibDecoder::new(inp).read_to_end(&mut pixels)?;
+                }
+                AsepriteCelContent::Image(self.pixels_from_bytes(&pixels, width, height)?)
+            }
+            1 => AsepriteCelContent::Linked(inp.read_u16::<LittleEndian>()?.into()),
+            _ => AsepriteCelContent::Unsupported(cel_type),
+        };
+        Ok(AsepriteCel {
+            layer,
+            x,
+            y,
+            opacity,
+            content,
+        })
+    }
+    fn pixels_from_bytes(
+        &self,
+        data: &[u8],
+        width: usize,
+        height: usize,
+    ) -> io::Result<AsepritePixels> {
+        let bytes_per_pixel = match self.color_depth {
+            AsepriteColorDepth::Rgba => 4,
+            AsepriteColorDepth::Grayscale => 2,
+            AsepriteColorDepth::Indexed => 1,
+        };
+        let data = data
+            .get(..width * height * bytes_per_pixel)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        Ok(match self.color_depth {
+            AsepriteColorDepth::Rgba => AsepritePixels::Rgba(Grid::from_vec(
+                data.chunks_exact(4)
+                    .map(|x| Rgba::new(x[0], x[1], x[2], x[3]))
+                    .collect(),
+                width,
+            )),
+            AsepriteColorDepth::Grayscale => AsepritePixels::Rgba(Grid::from_vec(
+                data.chunks_exact(2)
+                    .map(|x| Rgba::new(x[0], x[0], x[0], x[1]))
+                    .collect(),
+                width,
+            )),
+            AsepriteColorDepth::Indexed => {
+                AsepritePixels::Indexed(Grid::from_vec(data.to_vec(), width))
+            }
+        })
+    }
+
+    /// Whether `layer` and all of the groups containing it are visible.
+    fn is_layer_visible(&self, layer: usize) -> bool {
+        let mut level = self.layers[layer].child_level;
+        if !self.layers[layer].is_visible() {
+            return false;
+        }
+        for group in self.layers[..layer].iter().rev() {
+            if group.child_level < level {
+                if !group.is_visible() {
+                    return false;
+                }
+                level = group.child_level;
+            }
+        }
+        true
+    }
+
+    /// Follows links to the cel with the actual content.
+    fn resolve_cel<'a>(&'a self, cel: &'a AsepriteCel) -> &'a AsepriteCel {
+        let mut cel = cel;
+        while let AsepriteCelContent::Linked(frame) = cel.content {
+            match self
+                .frames
+                .get(frame)
+                .and_then(|x| x.cels.iter().find(|x| x.layer == cel.layer))
+            {
+                Some(linked) if !std::ptr::eq(linked, cel) => cel = linked,
+                _ => break,
+            }
+        }
+        cel
+    }
+
+    /// Composites the visible image layers of frame `frame_index`,
+    /// using the normal blend mode for all of them.
+    pub fn render_frame(&self, frame_index: usize) -> Result<Grid<Rgba<u8>>, AsepriteError> {
+        let frame = self
+            .frames
+            .get(frame_index)
+            .ok_or(AsepriteError::MissingFrame(frame_index))?;
+        let mut image = Grid::init(self.height.into(), self.width.into(), Rgba::new(0, 0, 0, 0));
+        for (layer_index, layer) in self.layers.iter().enumerate() {
+            if layer.kind != AsepriteLayerKind::Image || !self.is_layer_visible(layer_index) {
+                continue;
+            }
+            let Some(cel) = frame.cels.iter().find(|x| x.layer == layer_index) else {
+                continue;
+            };
+            let cel = self.resolve_cel(cel);
+            let AsepriteCelContent::Image(pixels) = &cel.content else {
+                continue;
+            };
+            let opacity = u32::from(cel.opacity) * u32::from(layer.opacity) / 0xFF;
+            let (rows, cols) = match pixels {
+                AsepritePixels::Rgba(x) => x.size(),
+                AsepritePixels::Indexed(x) => x.size(),
+            };
+            for row in 0..rows {
+                for col in 0..cols {
+                    let src = match pixels {
+                        AsepritePixels::Rgba(x) => x[(row, col)],
+                        AsepritePixels::Indexed(x) => {
+                            let index = x[(row, col)];
+                            if index == self.transparent_index && !layer.is_background() {
+                                continue;
+                            }
+                            self.palette
+                                .get(usize::from(index))
+                                .copied()
+                                .unwrap_or(Rgba::new(0, 0, 0, 0))
+                        }
+                    };
+                    let (Ok(y), Ok(x)) = (
+                        usize::try_from(i64::from(cel.y) + row as i64),
+                        usize::try_from(i64::from(cel.x) + col as i64),
+                    ) else {
+                        continue;
+                    };
+                    if let Some(dst) = image.get_mut(y, x) {
+                        *dst = blend(*dst, src, opacity);
+                    }
+                }
+            }
+        }
+        Ok(image)
+    }
+
+    /// The palette converted to [`Rgb555`], whose index 0 is transparent
+    /// in tilesets and sprites, like [`Self::transparent_index`] usually is.
+    pub fn to_palette(&self) -> Palette {
+        Palette(self.palette.iter().map(|x| Rgb555::from(x.rgb())).collect())
+    }
+
+    /// Splits frame `frame_index` into tiles of a [`Tileset`],
+    /// whose colors must all be in `palette`, e.g. one from [`Self::to_palette`].
+    pub fn frame_to_tileset(
+        &self,
+        frame_index: usize,
+        palette: &Palette,
+    ) -> Result<Tileset, AsepriteImportError> {
+        Ok(Tileset::from_rgba8888_image(
+            &self.render_frame(frame_index)?,
+            palette,
+        )?)
+    }
+
+    /// Imports each frame into a cell of `sprite` with [`Sprite::import_cell`],
+    /// starting with the cell `first_cell`.
+    pub fn import_into_sprite(
+        &self,
+        sprite: &mut Sprite,
+        first_cell: usize,
+        origin: (usize, usize),
+        options: &ImportOptions,
+    ) -> Result<(), AsepriteImportError> {
+        for frame_index in 0..self.frames.len() {
+            sprite.import_cell(
+                first_cell + frame_index,
+                &self.render_frame(frame_index)?,
+                origin,
+                options,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Draws `src` over `dst` with `opacity` out of 0xFF.
+fn blend(dst: Rgba<u8>, src: Rgba<u8>, opacity: u32) -> Rgba<u8> {
+    let src_alpha = u32::from(src.a) * opacity / 0xFF;
+    if src_alpha == 0 {
+        return dst;
+    }
+    let dst_alpha = u32::from(dst.a) * (0xFF - src_alpha) / 0xFF;
+    let alpha = src_alpha + dst_alpha;
+    let channel = |src: u8, dst: u8| {
+        ((u32::from(src) * src_alpha + u32::from(dst) * dst_alpha) / alpha) as u8
+    };
+    Rgba::new(
+        channel(src.r, dst.r),
+        channel(src.g, dst.g),
+        channel(src.b, dst.b),
+        alpha as u8,
+    )
+}
+
+#[derive(Error, Debug)]
+pub enum AsepriteImportError {
+    #[error(transparent)]
+    Aseprite(#[from] AsepriteError),
+    #[error(transparent)]
+    TilesetTileFromColors(#[from] TilesetTileFromColorsError),
+    #[error(transparent)]
+    SpriteImport(#[from] SpriteImportError),
+}
@@ -0,0 +1,1248 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Cursor, Read, Write},
+    num::TryFromIntError,
+};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use thiserror::Error;
+
+/// An event of an [`Sseq`].
+///
+/// Offsets are relative to the start of the sequence data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SseqEvent {
+    Note {
+        key: u8,
+        velocity: u8,
+        duration: u32,
+    },
+    Rest(u32),
+    ProgramChange(u32),
+    OpenTrack {
+        track: u8,
+        offset: u32,
+    },
+    Jump(u32),
+    Call(u32),
+    /// Runs `command` with its last argument chosen randomly from `min..=max`.
+    /// `velocity` is only present for notes.
+    Random {
+        command: u8,
+        velocity: Option<u8>,
+        min: i16,
+        max: i16,
+    },
+    /// Runs `command` with its last argument taken from a variable.
+    /// `velocity` is only present for notes.
+    FromVariable {
+        command: u8,
+        velocity: Option<u8>,
+        variable: u8,
+    },
+    /// Only runs the next event if the last comparison was true.
+    If,
+    /// An arithmetic or comparison operation on a variable (`0xB0..=0xBD`).
+    Variable {
+        command: u8,
+        variable: u8,
+        value: i16,
+    },
+    /// A command with a single byte argument (`0xC0..=0xD6`), such as [`SseqEvent::PAN`].
+    Byte {
+        command: u8,
+        value: u8,
+    },
+    /// A command with a single 16-bit argument (`0xE0` and `0xE3`).
+    Short {
+        command: u8,
+        value: i16,
+    },
+    Tempo(u16),
+    /// A bitmask of the tracks which are used.
+    AllocateTracks(u16),
+    LoopEnd,
+    Return,
+    EndOfTrack,
+}
+
+impl SseqEvent {
+    pub const PAN: u8 = 0xC0;
+    pub const VOLUME: u8 = 0xC1;
+    pub const TRANSPOSE: u8 = 0xC3;
+    pub const PITCH_BEND: u8 = 0xC4;
+    pub const BEND_RANGE: u8 = 0xC5;
+    /// 0 lets notes overlap, while 1 makes every note wait for its duration.
+    pub const NOTE_WAIT: u8 = 0xC7;
+    /// Repeats the events up to the next [`SseqEvent::LoopEnd`],
+    /// 0 meaning forever.
+    pub const LOOP_START: u8 = 0xD4;
+    pub const EXPRESSION: u8 = 0xD5;
+
+    const NOTE_END: u8 = 0x80;
+    const REST: u8 = 0x80;
+    const PROGRAM_CHANGE: u8 = 0x81;
+    const OPEN_TRACK: u8 = 0x93;
+    const JUMP: u8 = 0x94;
+    const CALL: u8 = 0x95;
+    const RANDOM: u8 = 0xA0;
+    const FROM_VARIABLE: u8 = 0xA1;
+    const IF: u8 = 0xA2;
+    const TEMPO: u8 = 0xE1;
+    const ALLOCATE_TRACKS: u8 = 0xFE;
+    const LOOP_END: u8 = 0xFC;
+    const RETURN: u8 = 0xFD;
+    const END_OF_TRACK: u8 = 0xFF;
+
+    fn read_prefixed_velocity(mut inp: impl Read, command: u8) -> io::Result<Option<u8>> {
+        Ok(if command < Self::NOTE_END {
+            Some(inp.read_u8()?)
+        } else {
+            None
+        })
+    }
+
+    /// Returns `None` for unknown commands.
+    pub fn from_reader(mut inp: impl Read) -> io::Result<Option<Self>> {
+        let command = inp.read_u8()?;
+        Ok(Some(match command {
+            0x00..Self::NOTE_END => Self::Note {
+                key: command,
+                velocity: inp.read_u8()?,
+                duration: read_var_len(&mut inp)?,
+            },
+            Self::REST => Self::Rest(read_var_len(&mut inp)?),
+            Self::PROGRAM_CHANGE => Self::ProgramChange(read_var_len(&mut inp)?),
+            Self::OPEN_TRACK => Self::OpenTrack {
+                track: inp.read_u8()?,
+                offset: inp.read_u24::<LittleEndian>()?,
+            },
+            Self::JUMP => Self::Jump(inp.read_u24::<LittleEndian>()?),
+            Self::CALL => Self::Call(inp.read_u24::<LittleEndian>()?),
+            Self::RANDOM => {
+                let command = inp.read_u8()?;
+                Self::Random {
+                    command,
+                    velocity: Self::read_prefixed_velocity(&mut inp, command)?,
+                    min: inp.read_i16::<LittleEndian>()?,
+                    max: inp.read_i16::<LittleEndian>()?,
+                }
+            }
+            Self::FROM_VARIABLE => {
+                let command = inp.read_u8()?;
+                Self::FromVariable {
+                    command,
+                    velocity: Self::read_prefixed_velocity(&mut inp, command)?,
+                    variable: inp.read_u8()?,
+                }
+            }
+            Self::IF => Self::If,
+            0xB0..=0xBD => Self::Variable {
+                command,
+                variable: inp.read_u8()?,
+                value: inp.read_i16::<LittleEndian>()?,
+            },
+            0xC0..=0xD6 => Self::Byte {
+                command,
+                value: inp.read_u8()?,
+            },
+            0xE0 | 0xE3 => Self::Short {
+                command,
+                value: inp.read_i16::<LittleEndian>()?,
+            },
+            Self::TEMPO => Self::Tempo(inp.read_u16::<LittleEndian>()?),
+            Self::ALLOCATE_TRACKS => Self::AllocateTracks(inp.read_u16::<LittleEndian>()?),
+            Self::LOOP_END => Self::LoopEnd,
+            Self::RETURN => Self::Return,
+            Self::END_OF_TRACK => Self::EndOfTrack,
+            _ => return Ok(None),
+        }))
+    }
+
+    /// The number of bytes which the event takes up.
+    pub fn size(&self) -> usize {
+        let mut buf = Vec::new();
+        self.to_writer(&mut buf).unwrap();
+        buf.len()
+    }
+
+    pub fn to_writer(&self, mut out: impl Write) -> io::Result<()> {
+        match *self {
+            Self::Note {
+                key,
+                velocity,
+                duration,
+            } => {
+                out.write_all(&[key, velocity])?;
+                write_var_len(out, duration)?;
+            }
+            Self::Rest(duration) => {
+                out.write_u8(Self::REST)?;
+                write_var_len(out, duration)?;
+            }
+            Self::ProgramChange(program) => {
+                out.write_u8(Self::PROGRAM_CHANGE)?;
+                write_var_len(out, program)?;
+            }
+            Self::OpenTrack { track, offset } => {
+                out.write_all(&[Self::OPEN_TRACK, track])?;
+                out.write_u24::<LittleEndian>(offset)?;
+            }
+            Self::Jump(offset) => {
+                out.write_u8(Self::JUMP)?;
+                out.write_u24::<LittleEndian>(offset)?;
+            }
+            Self::Call(offset) => {
+                out.write_u8(Self::CALL)?;
+                out.write_u24::<LittleEndian>(offset)?;
+            }
+            Self::Random {
+                command,
+                velocity,
+                min,
+                max,
+            } => {
+                out.write_all(&[Self::RANDOM, command])?;
+                if let Some(velocity) = velocity {
+                    out.write_u8(velocity)?;
+                }
+                out.write_i16::<LittleEndian>(min)?;
+                out.write_i16::<LittleEndian>(max)?;
+            }
+            Self::FromVariable {
+                command,
+                velocity,
+                variable,
+            } => {
+                out.write_all(&[Self::FROM_VARIABLE, command])?;
+                if let Some(velocity) = velocity {
+                    out.write_u8(velocity)?;
+                }
+                out.write_u8(variable)?;
+            }
+            Self::If => out.write_u8(Self::IF)?,
+            Self::Variable {
+                command,
+                variable,
+                value,
+            } => {
+                out.write_all(&[command, variable])?;
+                out.write_i16::<LittleEndian>(value)?;
+            }
+            Self::Byte { command, value } => out.write_all(&[command, value])?,
+            Self::Short { command, value } => {
+                out.write_u8(command)?;
+                out.write_i16::<LittleEndian>(value)?;
+            }
+            Self::Tempo(tempo) => {
+                out.write_u8(Self::TEMPO)?;
+                out.write_u16::<LittleEndian>(tempo)?;
+            }
+            Self::AllocateTracks(tracks) => {
+                out.write_u8(Self::ALLOCATE_TRACKS)?;
+                out.write_u16::<LittleEndian>(tracks)?;
+            }
+            Self::LoopEnd => out.write_u8(Self::LOOP_END)?,
+            Self::Return => out.write_u8(Self::RETURN)?,
+            Self::EndOfTrack => out.write_u8(Self::END_OF_TRACK)?,
+        }
+        Ok(())
+    }
+}
+
+/// Reads a big-endian variable-length quantity, as used by SSEQ and MIDI.
+fn read_var_len(mut inp: impl Read) -> io::Result<u32> {
+    let mut value = 0u32;
+    for _ in 0..4 {
+        let byte = inp.read_u8()?;
+        value = (value << 7) | u32::from(byte & 0x7F);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "variable-length quantity is too long",
+    ))
+}
+fn write_var_len(mut out: impl Write, value: u32) -> io::Result<()> {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut rest = value >> 7;
+    while rest != 0 {
+        bytes.push((rest & 0x7F) as u8 | 0x80);
+        rest >>= 7;
+    }
+    bytes.reverse();
+    out.write_all(&bytes)
+}
+
+/// A sequence of the NDS sound system, i.e. an `.sseq` file,
+/// such as the ones inside the game's SDAT.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Sseq {
+    /// With their offsets relative to the start of the sequence data.
+    pub events: Vec<(u32, SseqEvent)>,
+    /// The zero bytes after the last event.
+    pub padding: Vec<u8>,
+}
+
+#[derive(Error, Debug)]
+pub enum SseqDeserializationError {
+    #[error("invalid SSEQ header")]
+    InvalidHeader,
+    #[error("unknown command {command:#04X} at offset {offset:#X}")]
+    UnknownCommand { offset: u32, command: u8 },
+    #[error(transparent)]
+    TryFromInt(#[from] TryFromIntError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+#[derive(Error, Debug)]
+pub enum SseqSerializationError {
+    #[error("event {index} is at offset {actual:#X} instead of {expected:#X}")]
+    WrongOffset {
+        index: usize,
+        expected: u32,
+        actual: u32,
+    },
+    #[error(transparent)]
+    TryFromInt(#[from] TryFromIntError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl Sseq {
+    pub const MAGIC: &[u8; 4] = b"SSEQ";
+    pub const DATA_MAGIC: &[u8; 4] = b"DATA";
+    const BYTE_ORDER_AND_VERSION: u32 = 0x0100FEFF;
+    const HEADER_SIZE: u16 = 0x10;
+    /// Where the sequence data starts in the file.
+    pub const DATA_OFFSET: u32 = 0x1C;
+    /// The number of ticks per quarter note.
+    pub const TICKS_PER_QUARTER_NOTE: u16 = 48;
+    pub const MAX_TRACKS: usize = 16;
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, SseqDeserializationError> {
+        let mut inp = Cursor::new(data);
+        let mut magic = [0u8; 4];
+        inp.read_exact(&mut magic)?;
+        let byte_order_and_version = inp.read_u32::<LittleEndian>()?;
+        let _file_size = inp.read_u32::<LittleEndian>()?;
+        let header_size = inp.read_u16::<LittleEndian>()?;
+        let _num_blocks = inp.read_u16::<LittleEndian>()?;
+        let mut data_magic = [0u8; 4];
+        inp.read_exact(&mut data_magic)?;
+        let _block_size = inp.read_u32::<LittleEndian>()?;
+        let data_offset = inp.read_u32::<LittleEndian>()?;
+        if &magic != Self::MAGIC
+            || byte_order_and_version != Self::BYTE_ORDER_AND_VERSION
+            || header_size != Self::HEADER_SIZE
+            || &data_magic != Self::DATA_MAGIC
+            || data_offset != Self::DATA_OFFSET
+        {
+            return Err(SseqDeserializationError::InvalidHeader);
+        }
+
+        let sequence = &data[Self::DATA_OFFSET as usize..];
+        let mut inp = Cursor::new(sequence);
+        let mut events = Vec::new();
+        while (inp.position() as usize) < sequence.len() {
+            let rest = &sequence[inp.position() as usize..];
+            if rest.len() < 4 && rest.iter().all(|&x| x == 0) {
+                break;
+            }
+            let offset = u32::try_from(inp.position())?;
+            let event = SseqEvent::from_reader(&mut inp)?.ok_or(
+                SseqDeserializationError::UnknownCommand {
+                    offset,
+                    command: rest[0],
+                },
+            )?;
+            events.push((offset, event));
+        }
+
+        Ok(Self {
+            events,
+            padding: sequence[inp.position() as usize..].to_vec(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SseqSerializationError> {
+        let mut sequence = Vec::new();
+        for (index, &(offset, event)) in self.events.iter().enumerate() {
+            let actual = u32::try_from(sequence.len())?;
+            if actual != offset {
+                return Err(SseqSerializationError::WrongOffset {
+                    index,
+                    expected: offset,
+                    actual,
+                });
+            }
+            event.to_writer(&mut sequence)?;
+        }
+        sequence.extend_from_slice(&self.padding);
+
+        let file_size = u32::try_from(sequence.len())? + Self::DATA_OFFSET;
+        let mut out = Vec::with_capacity(file_size as usize);
+        out.write_all(Self::MAGIC)?;
+        out.write_u32::<LittleEndian>(Self::BYTE_ORDER_AND_VERSION)?;
+        out.write_u32::<LittleEndian>(file_size)?;
+        out.write_u16::<LittleEndian>(Self::HEADER_SIZE)?;
+        out.write_u16::<LittleEndian>(1)?;
+        out.write_all(Self::DATA_MAGIC)?;
+        out.write_u32::<LittleEndian>(file_size - u32::from(Self::HEADER_SIZE))?;
+        out.write_u32::<LittleEndian>(Self::DATA_OFFSET)?;
+        out.write_all(&sequence)?;
+        Ok(out)
+    }
+
+    /// Builds a sequence out of the events of each track,
+    /// the first of which opens all the others.
+    ///
+    /// Offsets in the events, such as jump targets, must already be final.
+    pub fn from_tracks(tracks: &[Vec<SseqEvent>]) -> Result<Self, SseqSerializationError> {
+        let mut header = Vec::new();
+        if tracks.len() > 1 {
+            header.push(SseqEvent::AllocateTracks(
+                ((1u32 << tracks.len()) - 1).try_into()?,
+            ));
+        }
+        let header_len = header.len() * 3 + tracks.len().saturating_sub(1) * 5;
+        let mut track_offset = header_len;
+        let mut track_offsets = Vec::with_capacity(tracks.len());
+        for track in tracks {
+            track_offsets.push(track_offset);
+            for event in track {
+                track_offset += event.size();
+            }
+        }
+        for (track, &offset) in track_offsets.iter().enumerate().skip(1) {
+            header.push(SseqEvent::OpenTrack {
+                track: track.try_into()?,
+                offset: offset.try_into()?,
+            });
+        }
+
+        let mut events = Vec::new();
+        let mut offset = 0;
+        for event in header.into_iter().chain(tracks.iter().flatten().copied()) {
+            events.push((offset.try_into()?, event));
+            offset += event.size();
+        }
+        Ok(Self {
+            events,
+            padding: Vec::new(),
+        })
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SseqToMidiError {
+    #[error("track {track} goes to offset {offset:#X}, which isn't the start of an event")]
+    InvalidOffset { track: u8, offset: u32 },
+    #[error(transparent)]
+    TryFromInt(#[from] TryFromIntError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+#[derive(Error, Debug)]
+pub enum MidiToSseqError {
+    #[error("invalid MIDI header")]
+    InvalidHeader,
+    #[error("SMPTE time division isn't supported")]
+    SmpteTimeDivision,
+    #[error("invalid event at offset {offset:#X} of MIDI track {track}")]
+    InvalidEvent { track: usize, offset: u64 },
+    #[error(transparent)]
+    SseqSerialization(#[from] SseqSerializationError),
+    #[error(transparent)]
+    TryFromInt(#[from] TryFromIntError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+const MIDI_HEADER_MAGIC: &[u8; 4] = b"MThd";
+const MIDI_TRACK_MAGIC: &[u8; 4] = b"MTrk";
+const MIDI_CC_DATA_ENTRY: u8 = 6;
+const MIDI_CC_VOLUME: u8 = 7;
+const MIDI_CC_PAN: u8 = 10;
+const MIDI_CC_EXPRESSION: u8 = 11;
+const MIDI_CC_RPN_LSB: u8 = 100;
+const MIDI_CC_RPN_MSB: u8 = 101;
+const MIDI_META_TEMPO: u8 = 0x51;
+const MIDI_META_END_OF_TRACK: u8 = 0x2F;
+const MIDI_PITCH_BEND_CENTER: i32 = 0x2000;
+const MICROSECONDS_PER_MINUTE: u32 = 60_000_000;
+/// An upper bound for the events played per track, in case of unusual control flow.
+const MAX_PLAYED_EVENTS: usize = 1 << 20;
+
+/// A MIDI event at an absolute tick.
+type TimedMidiEvent = (u64, Vec<u8>);
+
+fn write_midi_track(mut out: impl Write, events: &mut [TimedMidiEvent]) -> io::Result<()> {
+    // Note offs come first, so that repeated notes don't cut each other off.
+    events.sort_by_key(|(tick, event)| (*tick, event[0] & 0xF0 != 0x80));
+    let mut data = Vec::new();
+    let mut previous_tick = 0;
+    for (tick, event) in events.iter() {
+        write_var_len(
+            &mut data,
+            (tick - previous_tick).try_into().unwrap_or(u32::MAX),
+        )?;
+        data.write_all(event)?;
+        previous_tick = *tick;
+    }
+    data.write_all(&[0x00, 0xFF, MIDI_META_END_OF_TRACK, 0x00])?;
+
+    out.write_all(MIDI_TRACK_MAGIC)?;
+    out.write_u32::<BigEndian>(data.len().try_into().unwrap_or(u32::MAX))?;
+    out.write_all(&data)
+}
+
+impl Sseq {
+    /// Converts the sequence into a format 1 standard MIDI file
+    /// with a tempo track followed by one track per SSEQ track,
+    /// whose channel is the SSEQ track number.
+    ///
+    /// Loops are played as many times as their count says, except infinite loops
+    /// (a count of 0), which are played once. Events with random or variable arguments,
+    /// variable operations and conditions have no MIDI equivalent and are ignored.
+    pub fn to_midi(&self) -> Result<Vec<u8>, SseqToMidiError> {
+        let index_of: HashMap<u32, usize> = self
+            .events
+            .iter()
+            .enumerate()
+            .map(|(i, (offset, _))| (*offset, i))
+            .collect();
+        let mut track_starts = BTreeMap::from([(0u8, 0u32)]);
+        for (_, event) in &self.events {
+            if let SseqEvent::OpenTrack { track, offset } = *event {
+                track_starts.entry(track).or_insert(offset);
+            }
+        }
+
+        let mut tempo_events: Vec<TimedMidiEvent> = Vec::new();
+        let mut tracks = Vec::new();
+        for (&track, &start) in &track_starts {
+            let index = |offset: u32| {
+                index_of
+                    .get(&offset)
+                    .copied()
+                    .ok_or(SseqToMidiError::InvalidOffset { track, offset })
+            };
+            let channel = track & 0x0F;
+            let mut events: Vec<TimedMidiEvent> = Vec::new();
+            let mut tick = 0u64;
+            let mut note_wait = true;
+            let mut transpose = 0i32;
+            let mut call_stack = Vec::new();
+            // The index after the loop start, and the remaining repetitions.
+            let mut loop_stack: Vec<(usize, u8)> = Vec::new();
+            let mut i = index(start)?;
+            for _ in 0..MAX_PLAYED_EVENTS {
+                let Some(&(offset, event)) = self.events.get(i) else {
+                    break;
+                };
+                i += 1;
+                match event {
+                    SseqEvent::Note {
+                        key,
+                        velocity,
+                        duration,
+                    } => {
+                        let key = (i32::from(key) + transpose).clamp(0, 0x7F) as u8;
+                        events.push((tick, vec![0x90 | channel, key, velocity & 0x7F]));
+                        events.push((tick + u64::from(duration), vec![0x80 | channel, key, 0]));
+                        if note_wait {
+                            tick += u64::from(duration);
+                        }
+                    }
+                    SseqEvent::Rest(duration) => tick += u64::from(duration),
+                    SseqEvent::ProgramChange(program) => {
+                        events.push((tick, vec![0xC0 | channel, (program & 0x7F) as u8]));
+                    }
+                    SseqEvent::Jump(target) => {
+                        // Jumping backwards loops forever.
+                        if target <= offset {
+                            break;
+                        }
+                        i = index(target)?;
+                    }
+                    SseqEvent::Call(target) => {
+                        call_stack.push(i);
+                        i = index(target)?;
+                    }
+                    SseqEvent::Return => {
+                        let Some(return_index) = call_stack.pop() else {
+                            break;
+                        };
+                        i = return_index;
+                    }
+                    SseqEvent::Byte { command, value } => match command {
+                        SseqEvent::PAN => {
+                            events.push((tick, vec![0xB0 | channel, MIDI_CC_PAN, value & 0x7F]))
+                        }
+                        SseqEvent::VOLUME => {
+                            events.push((tick, vec![0xB0 | channel, MIDI_CC_VOLUME, value & 0x7F]))
+                        }
+                        SseqEvent::EXPRESSION => events
+                            .push((tick, vec![0xB0 | channel, MIDI_CC_EXPRESSION, value & 0x7F])),
+                        SseqEvent::TRANSPOSE => transpose = i32::from(value as i8),
+                        SseqEvent::PITCH_BEND => {
+                            let bend = (MIDI_PITCH_BEND_CENTER + i32::from(value as i8) * 64)
+                                .clamp(0, 0x3FFF);
+                            events.push((
+                                tick,
+                                vec![0xE0 | channel, (bend & 0x7F) as u8, (bend >> 7) as u8],
+                            ));
+                        }
+                        SseqEvent::BEND_RANGE => {
+                            for (controller, value) in [
+                                (MIDI_CC_RPN_MSB, 0),
+                                (MIDI_CC_RPN_LSB, 0),
+                                (MIDI_CC_DATA_ENTRY, value & 0x7F),
+                            ] {
+                                events.push((tick, vec![0xB0 | channel, controller, value]));
+                            }
+                        }
+                        SseqEvent::NOTE_WAIT => note_wait = value != 0,
+                        SseqEvent::LOOP_START => loop_stack.push((i, value.saturating_sub(1))),
+                        _ => {}
+                    },
+                    SseqEvent::LoopEnd => match loop_stack.last_mut() {
+                        Some((start, remaining)) if *remaining > 0 => {
+                            *remaining -= 1;
+                            i = *start;
+                        }
+                        _ => {
+                            loop_stack.pop();
+                        }
+                    },
+                    SseqEvent::Tempo(tempo) if tempo != 0 => {
+                        let microseconds = MICROSECONDS_PER_MINUTE / u32::from(tempo);
+                        let mut event = vec![0xFF, MIDI_META_TEMPO, 3];
+                        event.extend_from_slice(&microseconds.to_be_bytes()[1..]);
+                        tempo_events.push((tick, event));
+                    }
+                    SseqEvent::EndOfTrack => break,
+                    _ => {}
+                }
+            }
+            tracks.push(events);
+        }
+
+        let mut out = Vec::new();
+        out.write_all(MIDI_HEADER_MAGIC)?;
+        out.write_u32::<BigEndian>(6)?;
+        out.write_u16::<BigEndian>(1)?;
+        out.write_u16::<BigEndian>(u16::try_from(tracks.len())? + 1)?;
+        out.write_u16::<BigEndian>(Self::TICKS_PER_QUARTER_NOTE)?;
+        write_midi_track(&mut out, &mut tempo_events)?;
+        for mut events in tracks {
+            write_midi_track(&mut out, &mut events)?;
+        }
+        Ok(out)
+    }
+
+    /// Converts a standard MIDI file into a sequence with one track per used channel.
+    ///
+    /// Notes, program changes, volume, pan, expression, pitch bends,
+    /// pitch bend ranges and tempo changes are supported, and everything else is ignored.
+    pub fn from_midi(data: &[u8]) -> Result<Self, MidiToSseqError> {
+        let mut inp = Cursor::new(data);
+        let mut magic = [0u8; 4];
+        inp.read_exact(&mut magic)?;
+        if &magic != MIDI_HEADER_MAGIC || inp.read_u32::<BigEndian>()? != 6 {
+            return Err(MidiToSseqError::InvalidHeader);
+        }
+        let _format = inp.read_u16::<BigEndian>()?;
+        let num_tracks = inp.read_u16::<BigEndian>()?;
+        let division = inp.read_u16::<BigEndian>()?;
+        if division & 0x8000 != 0 {
+            return Err(MidiToSseqError::SmpteTimeDivision);
+        }
+        let division = u64::from(division.max(1));
+        let scale =
+            |tick: u64| (tick * u64::from(Self::TICKS_PER_QUARTER_NOTE) + division / 2) / division;
+
+        // The events of each channel, in the order they appear, along with their tick
+        // and the tick at which notes end.
+        let mut channels: BTreeMap<u8, Vec<(u64, SseqEvent)>> = BTreeMap::new();
+        let mut tempo_events: Vec<(u64, SseqEvent)> = Vec::new();
+        for track in 0..usize::from(num_tracks) {
+            inp.read_exact(&mut magic)?;
+            let length = inp.read_u32::<BigEndian>()?;
+            let start = inp.position();
+            let end = start + u64::from(length);
+            if &magic != MIDI_TRACK_MAGIC {
+                inp.set_position(end);
+                continue;
+            }
+
+            let invalid_event = |inp: &Cursor<&[u8]>| MidiToSseqError::InvalidEvent {
+                track,
+                offset: inp.position() - start,
+            };
+            let mut tick = 0u64;
+            let mut running_status = None;
+            // Note ons waiting for their note off, by channel and key.
+            let mut pending_notes: HashMap<(u8, u8), Vec<(usize, u64)>> = HashMap::new();
+            let mut rpn = HashMap::new();
+            while inp.position() < end {
+                tick += u64::from(read_var_len(&mut inp)?);
+                let mut status = inp.read_u8()?;
+                if status < 0x80 {
+                    status = running_status.ok_or_else(|| invalid_event(&inp))?;
+                    inp.set_position(inp.position() - 1);
+                }
+                match status {
+                    0xFF => {
+                        let kind = inp.read_u8()?;
+                        let length = read_var_len(&mut inp)?;
+                        let mut meta = vec![0u8; length as usize];
+                        inp.read_exact(&mut meta)?;
+                        if kind == MIDI_META_TEMPO && length == 3 {
+                            let microseconds = u32::from_be_bytes([0, meta[0], meta[1], meta[2]]);
+                            if let Some(tempo) = (MICROSECONDS_PER_MINUTE + microseconds / 2)
+                                .checked_div(microseconds)
+                            {
+                                tempo_events.push((
+                                    scale(tick),
+                                    SseqEvent::Tempo(tempo.try_into().unwrap_or(u16::MAX)),
+                                ));
+                            }
+                        }
+                        continue;
+                    }
+                    0xF0 | 0xF7 => {
+                        let length = read_var_len(&mut inp)?;
+                        inp.set_position(inp.position() + u64::from(length));
+                        continue;
+                    }
+                    0xF1..=0xFE => return Err(invalid_event(&inp)),
+                    _ => running_status = Some(status),
+                }
+
+                let channel = status & 0x0F;
+                let first = inp.read_u8()?;
+                let second = match status & 0xF0 {
+                    0xC0 | 0xD0 => 0,
+                    _ => inp.read_u8()?,
+                };
+                let events = channels.entry(channel).or_default();
+                let scaled_tick = scale(tick);
+                match status & 0xF0 {
+                    0x90 if second != 0 => {
+                        pending_notes
+                            .entry((channel, first))
+                            .or_default()
+                            .push((events.len(), scaled_tick));
+                        events.push((
+                            scaled_tick,
+                            SseqEvent::Note {
+                                key: first,
+                                velocity: second,
+                                duration: 0,
+                            },
+                        ));
+                    }
+                    0x80 | 0x90 => {
+                        let Some(notes) = pending_notes.get_mut(&(channel, first)) else {
+                            continue;
+                        };
+                        if notes.is_empty() {
+                            continue;
+                        }
+                        let (index, start_tick) = notes.remove(0);
+                        if let SseqEvent::Note { duration, .. } = &mut events[index].1 {
+                            *duration = (scaled_tick - start_tick).try_into()?;
+                        }
+                    }
+                    0xB0 => {
+                        let command = match first {
+                            MIDI_CC_VOLUME => Some(SseqEvent::VOLUME),
+                            MIDI_CC_PAN => Some(SseqEvent::PAN),
+                            MIDI_CC_EXPRESSION => Some(SseqEvent::EXPRESSION),
+                            MIDI_CC_RPN_MSB | MIDI_CC_RPN_LSB => {
+                                rpn.insert((channel, first), second);
+                                None
+                            }
+                            MIDI_CC_DATA_ENTRY
+                                if rpn.get(&(channel, MIDI_CC_RPN_MSB)) == Some(&0)
+                                    && rpn.get(&(channel, MIDI_CC_RPN_LSB)) == Some(&0) =>
+                            {
+                                Some(SseqEvent::BEND_RANGE)
+                            }
+                            _ => None,
+                        };
+                        if let Some(command) = command {
+                            events.push((
+                                scaled_tick,
+                                SseqEvent::Byte {
+                                    command,
+                                    value: second,
+                                },
+                            ));
+                        }
+                    }
+                    0xC0 => events.push((scaled_tick, SseqEvent::ProgramChange(first.into()))),
+                    0xE0 => {
+                        let bend =
+                            (i32::from(second) << 7 | i32::from(first)) - MIDI_PITCH_BEND_CENTER;
+                        events.push((
+                            scaled_tick,
+                            SseqEvent::Byte {
+                                command: SseqEvent::PITCH_BEND,
+                                value: (bend / 64).clamp(-0x80, 0x7F) as i8 as u8,
+                            },
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+            inp.set_position(end);
+        }
+
+        if channels.is_empty() {
+            channels.insert(0, Vec::new());
+        }
+        let tracks = channels
+            .into_values()
+            .enumerate()
+            .map(|(i, mut events)| -> Result<_, MidiToSseqError> {
+                if i == 0 {
+                    events.append(&mut tempo_events);
+                }
+                events.sort_by_key(|(tick, _)| *tick);
+                let mut track = vec![SseqEvent::Byte {
+                    command: SseqEvent::NOTE_WAIT,
+                    value: 0,
+                }];
+                let mut previous_tick = 0;
+                for (tick, event) in events {
+                    if tick > previous_tick {
+                        track.push(SseqEvent::Rest((tick - previous_tick).try_into()?));
+                        previous_tick = tick;
+                    }
+                    track.push(event);
+                }
+                track.push(SseqEvent::EndOfTrack);
+                Ok(track)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::from_tracks(&tracks)?)
+    }
+}
+
+/// How the samples of a [`Swav`] are encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, TryFromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum WaveEncoding {
+    Pcm8 = 0,
+    Pcm16 = 1,
+    /// IMA-ADPCM, starting with a 4-byte header of the initial sample and step index.
+    ImaAdpcm = 2,
+}
+
+/// A sample of the NDS sound system, i.e. an `.swav` file or an entry of a [`Swar`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Swav {
+    pub encoding: WaveEncoding,
+    pub looped: bool,
+    pub sample_rate: u16,
+    /// The hardware timer value, which corresponds to `sample_rate`.
+    pub timer: u16,
+    /// In 4-byte words from the start of `data`.
+    pub loop_offset: u16,
+    /// In 4-byte words after `loop_offset`.
+    pub loop_length: u32,
+    pub data: Vec<u8>,
+}
+
+#[derive(Error, Debug)]
+pub enum SwavDeserializationError {
+    #[error("invalid SWAV header")]
+    InvalidHeader,
+    #[error("unknown wave encoding {0}")]
+    UnknownEncoding(u8),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+#[derive(Error, Debug)]
+pub enum SwarDeserializationError {
+    #[error("invalid SWAR header")]
+    InvalidHeader,
+    #[error("invalid offset of wave {index}")]
+    InvalidOffset { index: usize },
+    #[error("failed to deserialize wave {index}")]
+    Wave {
+        index: usize,
+        #[source]
+        source: SwavDeserializationError,
+    },
+    #[error(transparent)]
+    TryFromInt(#[from] TryFromIntError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+#[derive(Error, Debug)]
+pub enum WaveSerializationError {
+    #[error(transparent)]
+    TryFromInt(#[from] TryFromIntError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+#[derive(Error, Debug)]
+pub enum WavDeserializationError {
+    #[error("invalid WAV header")]
+    InvalidHeader,
+    #[error("unsupported WAV format {format} with {bits_per_sample} bits per sample")]
+    UnsupportedFormat { format: u16, bits_per_sample: u16 },
+    #[error("the WAV file has no data")]
+    MissingData,
+    #[error(transparent)]
+    TryFromInt(#[from] TryFromIntError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+const IMA_ADPCM_STEPS: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+    73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449,
+    494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272,
+    2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493,
+    10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+const IMA_ADPCM_INDEX_CHANGES: [i32; 8] = [-1, -1, -1, -1, 2, 4, 6, 8];
+const IMA_ADPCM_HEADER_SIZE: usize = 4;
+
+/// The state of an IMA-ADPCM decoder or encoder.
+struct ImaAdpcmState {
+    sample: i32,
+    index: i32,
+}
+
+impl ImaAdpcmState {
+    fn apply(&mut self, nibble: u8) -> i16 {
+        let step = IMA_ADPCM_STEPS[self.index as usize];
+        let mut difference = step >> 3;
+        if nibble & 1 != 0 {
+            difference += step >> 2;
+        }
+        if nibble & 2 != 0 {
+            difference += step >> 1;
+        }
+        if nibble & 4 != 0 {
+            difference += step;
+        }
+        if nibble & 8 != 0 {
+            difference = -difference;
+        }
+        self.sample = (self.sample + difference).clamp(-0x7FFF, 0x7FFF);
+        self.index = (self.index + IMA_ADPCM_INDEX_CHANGES[usize::from(nibble & 7)]).clamp(0, 88);
+        self.sample as i16
+    }
+
+    fn encode(&mut self, sample: i16) -> u8 {
+        let step = IMA_ADPCM_STEPS[self.index as usize];
+        let mut difference = i32::from(sample) - self.sample;
+        let mut nibble = 0;
+        if difference < 0 {
+            nibble = 8;
+            difference = -difference;
+        }
+        for (bit, threshold) in [(4, step), (2, step >> 1), (1, step >> 2)] {
+            if difference >= threshold {
+                nibble |= bit;
+                difference -= threshold;
+            }
+        }
+        self.apply(nibble);
+        nibble
+    }
+}
+
+impl Swav {
+    pub const MAGIC: &[u8; 4] = b"SWAV";
+    const INFO_SIZE: usize = 0x0C;
+    /// Divided by the sample rate to get the timer value.
+    pub const TIMER_CLOCK: u32 = 16_756_991;
+
+    fn from_info_and_data(mut inp: impl Read) -> Result<Self, SwavDeserializationError> {
+        let encoding = inp.read_u8()?;
+        let mut swav = Self {
+            encoding: encoding
+                .try_into()
+                .map_err(|_| SwavDeserializationError::UnknownEncoding(encoding))?,
+            looped: inp.read_u8()? != 0,
+            sample_rate: inp.read_u16::<LittleEndian>()?,
+            timer: inp.read_u16::<LittleEndian>()?,
+            loop_offset: inp.read_u16::<LittleEndian>()?,
+            loop_length: inp.read_u32::<LittleEndian>()?,
+            data: Vec::new(),
+        };
+        inp.read_to_end(&mut swav.data)?;
+        Ok(swav)
+    }
+    fn to_info_and_data(&self, mut out: impl Write) -> io::Result<()> {
+        out.write_u8(self.encoding.into())?;
+        out.write_u8(self.looped.into())?;
+        out.write_u16::<LittleEndian>(self.sample_rate)?;
+        out.write_u16::<LittleEndian>(self.timer)?;
+        out.write_u16::<LittleEndian>(self.loop_offset)?;
+        out.write_u32::<LittleEndian>(self.loop_length)?;
+        out.write_all(&self.data)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, SwavDeserializationError> {
+        let (header, block_size) =
+            read_nitro_header(data, Self::MAGIC).ok_or(SwavDeserializationError::InvalidHeader)?;
+        let block = data
+            .get(NITRO_HEADER_SIZE + 8..header + block_size)
+            .ok_or(SwavDeserializationError::InvalidHeader)?;
+        Self::from_info_and_data(block)
+    }
+    pub fn to_bytes(&self) -> Result<Vec<u8>, WaveSerializationError> {
+        let mut block = Vec::new();
+        self.to_info_and_data(&mut block)?;
+        write_nitro_file(Self::MAGIC, &block)
+    }
+
+    /// Decodes the samples, including the looped part once.
+    pub fn decode(&self) -> Vec<i16> {
+        match self.encoding {
+            WaveEncoding::Pcm8 => self.data.iter().map(|&x| i16::from(x as i8) << 8).collect(),
+            WaveEncoding::Pcm16 => self
+                .data
+                .chunks_exact(2)
+                .map(|x| i16::from_le_bytes([x[0], x[1]]))
+                .collect(),
+            WaveEncoding::ImaAdpcm => {
+                let Some(header) = self.data.get(..IMA_ADPCM_HEADER_SIZE) else {
+                    return Vec::new();
+                };
+                let mut state = ImaAdpcmState {
+                    sample: i32::from(i16::from_le_bytes([header[0], header[1]])),
+                    index: i32::from(header[2]).clamp(0, 88),
+                };
+                self.data[IMA_ADPCM_HEADER_SIZE..]
+                    .iter()
+                    .flat_map(|&x| [x & 0x0F, x >> 4])
+                    .map(|nibble| state.apply(nibble))
+                    .collect()
+            }
+        }
+    }
+
+    /// Encodes `samples` into a sample which doesn't loop.
+    pub fn encode(samples: &[i16], sample_rate: u16, encoding: WaveEncoding) -> Self {
+        let (mut data, header_words) = match encoding {
+            WaveEncoding::Pcm8 => (samples.iter().map(|&x| (x >> 8) as u8).collect(), 0),
+            WaveEncoding::Pcm16 => (samples.iter().flat_map(|x| x.to_le_bytes()).collect(), 0),
+            WaveEncoding::ImaAdpcm => {
+                let first = samples.first().copied().unwrap_or_default();
+                let mut state = ImaAdpcmState {
+                    sample: first.into(),
+                    index: 0,
+                };
+                let mut data = Vec::with_capacity(IMA_ADPCM_HEADER_SIZE + samples.len() / 2);
+                data.extend_from_slice(&first.to_le_bytes());
+                data.extend_from_slice(&[0, 0]);
+                for pair in samples.chunks(2) {
+                    let low = state.encode(pair[0]);
+                    let high = pair.get(1).map_or(0, |&x| state.encode(x));
+                    data.push(low | (high << 4));
+                }
+                (data, (IMA_ADPCM_HEADER_SIZE / 4) as u16)
+            }
+        };
+        data.resize(data.len().next_multiple_of(4), 0);
+        Self {
+            encoding,
+            looped: false,
+            sample_rate,
+            timer: (Self::TIMER_CLOCK / u32::from(sample_rate.max(1)))
+                .try_into()
+                .unwrap_or(u16::MAX),
+            loop_offset: header_words,
+            loop_length: (data.len() / 4) as u32 - u32::from(header_words),
+            data,
+        }
+    }
+
+    /// Converts the sample into a 16-bit mono WAV file.
+    pub fn to_wav(&self) -> Result<Vec<u8>, WaveSerializationError> {
+        let samples = self.decode();
+        let data_size = u32::try_from(samples.len() * 2)?;
+        let mut out = Vec::with_capacity(44 + samples.len() * 2);
+        out.write_all(b"RIFF")?;
+        out.write_u32::<LittleEndian>(36 + data_size)?;
+        out.write_all(b"WAVEfmt ")?;
+        out.write_u32::<LittleEndian>(16)?;
+        out.write_u16::<LittleEndian>(WAV_FORMAT_PCM)?;
+        out.write_u16::<LittleEndian>(1)?;
+        out.write_u32::<LittleEndian>(self.sample_rate.into())?;
+        out.write_u32::<LittleEndian>(u32::from(self.sample_rate) * 2)?;
+        out.write_u16::<LittleEndian>(2)?;
+        out.write_u16::<LittleEndian>(16)?;
+        out.write_all(b"data")?;
+        out.write_u32::<LittleEndian>(data_size)?;
+        for sample in samples {
+            out.write_i16::<LittleEndian>(sample)?;
+        }
+        Ok(out)
+    }
+
+    /// Encodes an 8-bit or 16-bit PCM WAV file, mixing multiple channels into one.
+    pub fn from_wav(data: &[u8], encoding: WaveEncoding) -> Result<Self, WavDeserializationError> {
+        let mut inp = Cursor::new(data);
+        let mut magic = [0u8; 4];
+        inp.read_exact(&mut magic)?;
+        let _size = inp.read_u32::<LittleEndian>()?;
+        let mut wave_magic = [0u8; 4];
+        inp.read_exact(&mut wave_magic)?;
+        if &magic != b"RIFF" || &wave_magic != b"WAVE" {
+            return Err(WavDeserializationError::InvalidHeader);
+        }
+
+        let mut format = None;
+        while (inp.position() as usize) < data.len() {
+            inp.read_exact(&mut magic)?;
+            let size = inp.read_u32::<LittleEndian>()?;
+            let start = inp.position();
+            match &magic {
+                b"fmt " => {
+                    let tag = inp.read_u16::<LittleEndian>()?;
+                    let channels = inp.read_u16::<LittleEndian>()?.max(1);
+                    let sample_rate = inp.read_u32::<LittleEndian>()?;
+                    let _byte_rate = inp.read_u32::<LittleEndian>()?;
+                    let _block_align = inp.read_u16::<LittleEndian>()?;
+                    let bits_per_sample = inp.read_u16::<LittleEndian>()?;
+                    if tag != WAV_FORMAT_PCM || !matches!(bits_per_sample, 8 | 16) {
+                        return Err(WavDeserializationError::UnsupportedFormat {
+                            format: tag,
+                            bits_per_sample,
+                        });
+                    }
+                    format = Some((channels, sample_rate, bits_per_sample));
+                }
+                b"data" => {
+                    let (channels, sample_rate, bits_per_sample) =
+                        format.ok_or(WavDeserializationError::InvalidHeader)?;
+                    let bytes = data
+                        .get(start as usize..start as usize + size as usize)
+                        .ok_or(WavDeserializationError::InvalidHeader)?;
+                    let values: Vec<i32> = if bits_per_sample == 8 {
+                        bytes.iter().map(|&x| (i32::from(x) - 0x80) << 8).collect()
+                    } else {
+                        bytes
+                            .chunks_exact(2)
+                            .map(|x| i32::from(i16::from_le_bytes([x[0], x[1]])))
+                            .collect()
+                    };
+                    let samples: Vec<i16> = values
+                        .chunks_exact(usize::from(channels))
+                        .map(|x| (x.iter().sum::<i32>() / i32::from(channels)) as i16)
+                        .collect();
+                    return Ok(Self::encode(&samples, sample_rate.try_into()?, encoding));
+                }
+                _ => {}
+            }
+            // Chunks are padded to an even size.
+            inp.set_position(start + u64::from(size) + u64::from(size % 2));
+        }
+        Err(WavDeserializationError::MissingData)
+    }
+}
+
+/// A wave archive of the NDS sound system, i.e. an `.swar` file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Swar {
+    pub waves: Vec<Swav>,
+    pub reserved: [u8; 32],
+}
+
+impl Swar {
+    pub const MAGIC: &[u8; 4] = b"SWAR";
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, SwarDeserializationError> {
+        let (header, block_size) =
+            read_nitro_header(data, Self::MAGIC).ok_or(SwarDeserializationError::InvalidHeader)?;
+        let block_end = header + block_size;
+        let mut inp = Cursor::new(data);
+        inp.set_position((NITRO_HEADER_SIZE + 8) as u64);
+        let mut reserved = [0u8; 32];
+        inp.read_exact(&mut reserved)?;
+        let num_waves = inp.read_u32::<LittleEndian>()?;
+        let offsets = (0..num_waves)
+            .map(|_| Ok(usize::try_from(inp.read_u32::<LittleEndian>()?)?))
+            .collect::<Result<Vec<_>, SwarDeserializationError>>()?;
+
+        Ok(Self {
+            waves: offsets
+                .iter()
+                .enumerate()
+                .map(|(index, &offset)| {
+                    let end = offsets.get(index + 1).copied().unwrap_or(block_end);
+                    let wave = data
+                        .get(offset..end)
+                        .filter(|x| x.len() >= Swav::INFO_SIZE)
+                        .ok_or(SwarDeserializationError::InvalidOffset { index })?;
+                    Swav::from_info_and_data(wave)
+                        .map_err(|source| SwarDeserializationError::Wave { index, source })
+                })
+                .collect::<Result<_, _>>()?,
+            reserved,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, WaveSerializationError> {
+        let mut block = Vec::new();
+        block.write_all(&self.reserved)?;
+        block.write_u32::<LittleEndian>(self.waves.len().try_into()?)?;
+        let mut offset = NITRO_HEADER_SIZE + 8 + block.len() + self.waves.len() * 4;
+        for wave in &self.waves {
+            block.write_u32::<LittleEndian>(offset.try_into()?)?;
+            offset += Swav::INFO_SIZE + wave.data.len();
+        }
+        for wave in &self.waves {
+            wave.to_info_and_data(&mut block)?;
+        }
+        write_nitro_file(Self::MAGIC, &block)
+    }
+}
+
+const WAV_FORMAT_PCM: u16 = 1;
+const NITRO_HEADER_SIZE: usize = 0x10;
+const NITRO_BYTE_ORDER_AND_VERSION: u32 = 0x0100FEFF;
+const NITRO_DATA_MAGIC: &[u8; 4] = b"DATA";
+
+/// Checks the header of a single-block Nitro file,
+/// and returns the offset and size of its `DATA` block.
+fn read_nitro_header(data: &[u8], magic: &[u8; 4]) -> Option<(usize, usize)> {
+    let header = data.get(..NITRO_HEADER_SIZE + 8)?;
+    if &header[..4] != magic
+        || LittleEndian::read_u32(&header[4..]) != NITRO_BYTE_ORDER_AND_VERSION
+        || usize::from(LittleEndian::read_u16(&header[0x0C..])) != NITRO_HEADER_SIZE
+        || &header[NITRO_HEADER_SIZE..NITRO_HEADER_SIZE + 4] != NITRO_DATA_MAGIC
+    {
+        return None;
+    }
+    let block_size = LittleEndian::read_u32(&header[NITRO_HEADER_SIZE + 4..]) as usize;
+    (NITRO_HEADER_SIZE + block_size <= data.len()).then_some((NITRO_HEADER_SIZE, block_size))
+}
+/// Writes a single-block Nitro file whose `DATA` block contains `block`.
+fn write_nitro_file(magic: &[u8; 4], block: &[u8]) -> Result<Vec<u8>, WaveSerializationError> {
+    let block_size = u32::try_from(block.len() + 8)?;
+    let mut out = Vec::with_capacity(NITRO_HEADER_SIZE + 8 + block.len());
+    out.write_all(magic)?;
+    out.write_u32::<LittleEndian>(NITRO_BYTE_ORDER_AND_VERSION)?;
+    out.write_u32::<LittleEndian>(NITRO_HEADER_SIZE as u32 + block_size)?;
+    out.write_u16::<LittleEndian>(NITRO_HEADER_SIZE as u16)?;
+    out.write_u16::<LittleEndian>(1)?;
+    out.write_all(NITRO_DATA_MAGIC)?;
+    out.write_u32::<LittleEndian>(block_size)?;
+    out.write_all(block)?;
+    Ok(out)
+}
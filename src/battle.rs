@@ -0,0 +1,97 @@
+use thiserror::Error;
+
+use crate::{
+    misc::{DataWithOffsetTable, MaybeSerialized},
+    script::{AssemblyError, CommandTable, DisassemblyError, Script},
+};
+
+/// A file of battle scripts, such as the enemy AI in `BAI/*.dat`,
+/// stored as a [`DataWithOffsetTable`] with one script per chunk.
+///
+/// Scripts stay serialized until they're [disassembled](Self::disassemble),
+/// and commands with opcodes unknown to the [`CommandTable`] are preserved
+/// as [`Instruction::Data`](crate::script::Instruction::Data).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct BattleScriptFile {
+    pub scripts: Vec<MaybeSerialized<Script>>,
+    pub padding: Vec<u8>,
+}
+
+#[derive(Error, Debug)]
+pub enum BattleScriptDisassemblyError {
+    #[error("failed to disassemble battle script {index}")]
+    Script {
+        index: usize,
+        #[source]
+        source: DisassemblyError,
+    },
+}
+#[derive(Error, Debug)]
+pub enum BattleScriptAssemblyError {
+    #[error("failed to assemble battle script {index}")]
+    Script {
+        index: usize,
+        #[source]
+        source: AssemblyError,
+    },
+}
+
+impl From<DataWithOffsetTable> for BattleScriptFile {
+    fn from(value: DataWithOffsetTable) -> Self {
+        Self {
+            scripts: value
+                .chunks
+                .into_iter()
+                .map(MaybeSerialized::Serialized)
+                .collect(),
+            padding: value.footer,
+        }
+    }
+}
+
+impl BattleScriptFile {
+    /// Disassembles the script `index` if it isn't already,
+    /// and returns a mutable reference to it.
+    pub fn disassemble(
+        &mut self,
+        index: usize,
+        table: &CommandTable,
+    ) -> Result<Option<&mut Script>, BattleScriptDisassemblyError> {
+        self.scripts
+            .get_mut(index)
+            .map(|script| {
+                script.get_or_deserialize_with(|data| Script::disassemble_lenient(data, table))
+            })
+            .transpose()
+            .map_err(|source| BattleScriptDisassemblyError::Script { index, source })
+    }
+    pub fn disassemble_all(
+        &mut self,
+        table: &CommandTable,
+    ) -> Result<(), BattleScriptDisassemblyError> {
+        for index in 0..self.scripts.len() {
+            self.disassemble(index, table)?;
+        }
+        Ok(())
+    }
+
+    pub fn to_table(
+        &self,
+        table: &CommandTable,
+    ) -> Result<DataWithOffsetTable, BattleScriptAssemblyError> {
+        Ok(DataWithOffsetTable {
+            chunks: self
+                .scripts
+                .iter()
+                .enumerate()
+                .map(|(index, script)| {
+                    script
+                        .serialize_with(|x| x.assemble(table))
+                        .map(|x| x.into_owned())
+                        .map_err(|source| BattleScriptAssemblyError::Script { index, source })
+                })
+                .collect::<Result<_, _>>()?,
+            footer: self.padding.clone(),
+        })
+    }
+}
@@ -0,0 +1,349 @@
+//! Command-line tool for working with dumps of the Mario & Luigi games.
+
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{BufReader, BufWriter, Cursor, Write},
+    path::Path,
+    process::ExitCode,
+};
+
+use mnllib::{
+    compress, decompress,
+    export::{render_map_layers, MapExport},
+    kaitai::all_ksy,
+    map::FieldMaps,
+    misc::{DataWithOffsetTable, ProjectPaths, SaveOptions},
+    rom::NdsRom,
+    symbols::SymbolTable,
+    vfs::Vfs,
+    vram::VramDump,
+};
+
+const USAGE: &str = "\
+Usage:
+  mnltool decompress <input> <output>
+  mnltool compress <input> <output>
+  mnltool unpack-table <input> <output directory>
+  mnltool export-map [--root <game>] [--layers <list>] <map index> --png <output>
+  mnltool export-map [--root <game>] <map index> --json <output>
+  mnltool export-vram [--root <game>] <map index> <output directory>
+  mnltool rebuild [--root <dump>] <original ROM> <output ROM>
+  mnltool export-ksy <output directory>
+  mnltool export-symbols <ROM> <output>
+
+<dump> is an extracted dump with `data/data` and `data/overlay.dec`,
+which defaults to the current directory.
+<game> is a dump, a `.nds` ROM, or a dump in a `.zip` archive.
+With --png, only the comma-separated --layers are drawn, and the map index,
+layers and CRC-32s of the source chunks are embedded in the PNG file.
+With --json, the tilesets are written next to the output as PNG files.
+export-symbols writes a linker script if <output> ends with `.ld`,
+or else a `.sym` file.";
+
+type CommandResult = Result<(), Box<dyn Error>>;
+
+/// Removes `--name <value>` from `args`.
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    let Some(position) = args.iter().position(|x| x == name) else {
+        return Ok(None);
+    };
+    if position + 1 >= args.len() {
+        return Err(format!("{name} requires a value"));
+    }
+    let value = args.remove(position + 1);
+    args.remove(position);
+    Ok(Some(value))
+}
+
+fn positional<const N: usize>(args: Vec<String>) -> Result<[String; N], String> {
+    let len = args.len();
+    args.try_into()
+        .map_err(|_| format!("expected {N} arguments, not {len}"))
+}
+
+fn project_paths(args: &mut Vec<String>) -> Result<ProjectPaths, String> {
+    Ok(take_option(args, "--root")?.map_or_else(ProjectPaths::default, ProjectPaths::new))
+}
+
+fn decompress_file(input: &Path, output: &Path) -> CommandResult {
+    let mut data = Cursor::new(Vec::new());
+    decompress(Cursor::new(fs::read(input)?), &mut data, true)?;
+    fs::write(output, data.into_inner())?;
+    Ok(())
+}
+
+fn compress_file(input: &Path, output: &Path) -> CommandResult {
+    let mut data = Cursor::new(Vec::new());
+    compress(&fs::read(input)?, &mut data)?;
+    fs::write(output, data.into_inner())?;
+    Ok(())
+}
+
+/// Writes each chunk to `<index>.bin`, and the footer to `footer.bin`.
+fn unpack_table(input: &Path, output: &Path) -> CommandResult {
+    let table = DataWithOffsetTable::from_reader(BufReader::new(File::open(input)?))?;
+    fs::create_dir_all(output)?;
+    for (index, chunk) in table.chunks.iter().enumerate() {
+        fs::write(output.join(format!("{index:04}.bin")), chunk)?;
+    }
+    fs::write(output.join("footer.bin"), &table.footer)?;
+    println!("Unpacked {} chunks.", table.chunks.len());
+    Ok(())
+}
+
+fn parse_map_index(map_index: &str) -> Result<usize, String> {
+    map_index
+        .parse()
+        .map_err(|_| format!("invalid map index: {map_index}"))
+}
+
+/// Opens `--root` by its extension.
+fn game_files(args: &mut Vec<String>) -> Result<Box<dyn Vfs>, Box<dyn Error>> {
+    let Some(root) = take_option(args, "--root")? else {
+        return Ok(Box::new(ProjectPaths::default()));
+    };
+    let extension = Path::new(&root)
+        .extension()
+        .map(|x| x.to_string_lossy().to_lowercase());
+    Ok(match extension.as_deref() {
+        Some("nds") => Box::new(NdsRom::load(&root)?),
+        Some("zip") => open_zip(&root)?,
+        _ => Box::new(ProjectPaths::new(root)),
+    })
+}
+
+#[cfg(feature = "zip")]
+fn open_zip(path: &str) -> Result<Box<dyn Vfs>, Box<dyn Error>> {
+    let archive = mnllib::zip::ZipArchive::from_bytes(fs::read(path)?)?;
+    Ok(Box::new(mnllib::vfs::ZipVfs::new(archive)))
+}
+#[cfg(not(feature = "zip"))]
+fn open_zip(_path: &str) -> Result<Box<dyn Vfs>, Box<dyn Error>> {
+    Err("mnltool was built without the `zip` feature".into())
+}
+
+/// Parses a list of tile layers like `0,2` into which ones to draw.
+fn parse_layers(layers: Option<&str>) -> Result<[bool; 3], String> {
+    let Some(layers) = layers else {
+        return Ok([true; 3]);
+    };
+    let mut enabled = [false; 3];
+    for layer in layers.split(',') {
+        match layer.trim().parse::<usize>() {
+            Ok(layer @ 0..3) => enabled[layer] = true,
+            _ => return Err(format!("invalid layer: {layer}")),
+        }
+    }
+    Ok(enabled)
+}
+
+/// Embeds the map index, layers and hashes of the source data.
+fn export_map(game: &dyn Vfs, map_index: &str, layers: [bool; 3], output: &Path) -> CommandResult {
+    let (image, metadata) = render_map_layers(
+        &FieldMaps::load_from_vfs(game)?,
+        parse_map_index(map_index)?,
+        layers,
+    )?;
+    write_png_file(&image, &metadata.to_text(), output)
+}
+
+/// Writes the tilesets to `<output stem>.tileset<index>.png`.
+fn export_map_json(game: &dyn Vfs, map_index: &str, output: &Path) -> CommandResult {
+    let (map_chunk, tilesets) =
+        FieldMaps::load_from_vfs(game)?.decode_map(parse_map_index(map_index)?)?;
+    let export = MapExport::new(&map_chunk, tilesets.each_ref().map(Option::as_ref), 16)?;
+    let stem = output
+        .file_stem()
+        .ok_or("the output has no file name")?
+        .to_string_lossy()
+        .into_owned();
+    let image_name = |index: usize| format!("{stem}.tileset{index}.png");
+    for (index, tileset) in export.tilesets.iter().enumerate() {
+        write_png_file(
+            &tileset.image,
+            &[],
+            &output.with_file_name(image_name(index)),
+        )?;
+    }
+    fs::write(output, export.to_json(image_name))?;
+    Ok(())
+}
+
+/// Writes `bg_vram.bin` and `bg_extended_palettes.bin`.
+fn export_vram(game: &dyn Vfs, map_index: &str, output: &Path) -> CommandResult {
+    let (map_chunk, tilesets) =
+        FieldMaps::load_from_vfs(game)?.decode_map(parse_map_index(map_index)?)?;
+    let dump = VramDump::new(&map_chunk, tilesets.each_ref().map(Option::as_ref))?;
+    fs::create_dir_all(output)?;
+    fs::write(output.join("bg_vram.bin"), &dump.bg_vram)?;
+    fs::write(
+        output.join("bg_extended_palettes.bin"),
+        &dump.bg_extended_palettes,
+    )?;
+    for (index, layer) in dump.layers.iter().enumerate() {
+        if let Some(layer) = layer {
+            println!(
+                "Layer {index}: characters at {:#07X}, screen at {:#07X}",
+                layer.character_base, layer.screen_base
+            );
+        }
+    }
+    Ok(())
+}
+
+fn write_png_file(
+    image: &grid::Grid<rgb::Rgba<u8>>,
+    text: &[(String, String)],
+    path: &Path,
+) -> CommandResult {
+    let mut file = BufWriter::new(File::create(path)?);
+    write_png(image, text, &mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+#[cfg(feature = "png")]
+fn write_png(
+    image: &grid::Grid<rgb::Rgba<u8>>,
+    text: &[(String, String)],
+    out: impl Write,
+) -> CommandResult {
+    Ok(mnllib::sprites::image_to_png_with_text(image, text, out)?)
+}
+#[cfg(not(feature = "png"))]
+fn write_png(
+    _image: &grid::Grid<rgb::Rgba<u8>>,
+    _text: &[(String, String)],
+    _out: impl Write,
+) -> CommandResult {
+    Err("mnltool was built without the `png` feature".into())
+}
+
+/// Replaces every file and ARM9 overlay of the original ROM
+/// which differs in the dump.
+fn rebuild(paths: &ProjectPaths, original: &Path, output: &Path) -> CommandResult {
+    let mut rom = NdsRom::load(original)?;
+    let mut num_replaced = 0;
+
+    let file_names: Vec<String> = rom.file_names.keys().cloned().collect();
+    for path in file_names {
+        let Ok(data) = fs::read(paths.data_path(&path)) else {
+            continue;
+        };
+        if rom.file(&path)? != data {
+            rom.replace_file(&path, data)?;
+            num_replaced += 1;
+        }
+    }
+    let overlay_numbers: Vec<u32> = rom.arm9_overlays.iter().map(|x| x.overlay_number).collect();
+    for overlay_number in overlay_numbers {
+        let Ok(data) = fs::read(paths.overlay_path(overlay_number)) else {
+            continue;
+        };
+        if *rom.overlay(overlay_number)? != data {
+            rom.replace_overlay(overlay_number, data)?;
+            num_replaced += 1;
+        }
+    }
+
+    rom.save(output, &SaveOptions::default())?;
+    println!("Replaced {num_replaced} files.");
+    Ok(())
+}
+
+/// Writes a `.ksy` file for each format.
+fn export_ksy(output: &Path) -> CommandResult {
+    fs::create_dir_all(output)?;
+    for (id, ksy) in all_ksy() {
+        fs::write(output.join(format!("{id}.ksy")), ksy)?;
+    }
+    Ok(())
+}
+
+/// Writes the symbols of the ROM's overlays.
+fn export_symbols(rom: &Path, output: &Path) -> CommandResult {
+    let symbols = SymbolTable::new(&NdsRom::load(rom)?.arm9_overlays);
+    fs::write(
+        output,
+        if output.extension().is_some_and(|x| x == "ld") {
+            symbols.to_linker_script()
+        } else {
+            symbols.to_sym()
+        },
+    )?;
+    Ok(())
+}
+
+fn run(mut args: Vec<String>) -> CommandResult {
+    if args.is_empty() {
+        return Err("no command given".into());
+    }
+    let command = args.remove(0);
+    match command.as_str() {
+        "decompress" => {
+            let [input, output] = positional(args)?;
+            decompress_file(input.as_ref(), output.as_ref())
+        }
+        "compress" => {
+            let [input, output] = positional(args)?;
+            compress_file(input.as_ref(), output.as_ref())
+        }
+        "unpack-table" => {
+            let [input, output] = positional(args)?;
+            unpack_table(input.as_ref(), output.as_ref())
+        }
+        "export-map" => {
+            let game = game_files(&mut args)?;
+            let png = take_option(&mut args, "--png")?;
+            let json = take_option(&mut args, "--json")?;
+            let layers = parse_layers(take_option(&mut args, "--layers")?.as_deref())?;
+            let [map_index] = positional(args)?;
+            match (png, json) {
+                (Some(output), None) => export_map(&*game, &map_index, layers, output.as_ref()),
+                (None, Some(output)) => export_map_json(&*game, &map_index, output.as_ref()),
+                _ => Err("export-map requires either --png <output> or --json <output>".into()),
+            }
+        }
+        "export-vram" => {
+            let game = game_files(&mut args)?;
+            let [map_index, output] = positional(args)?;
+            export_vram(&*game, &map_index, output.as_ref())
+        }
+        "rebuild" => {
+            let paths = project_paths(&mut args)?;
+            let [original, output] = positional(args)?;
+            rebuild(&paths, original.as_ref(), output.as_ref())
+        }
+        "export-ksy" => {
+            let [output] = positional(args)?;
+            export_ksy(output.as_ref())
+        }
+        "export-symbols" => {
+            let [rom, output] = positional(args)?;
+            export_symbols(rom.as_ref(), output.as_ref())
+        }
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        _ => Err(format!("unknown command: {command}").into()),
+    }
+}
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1).collect()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprint!("error: {error}");
+            let mut source = error.source();
+            while let Some(error) = source {
+                eprint!(": {error}");
+                source = error.source();
+            }
+            eprintln!();
+            eprintln!("Run `mnltool help` for usage.");
+            ExitCode::FAILURE
+        }
+    }
+}
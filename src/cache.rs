@@ -0,0 +1,270 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Debug,
+    io::{self, Read, Write},
+    num::TryFromIntError,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use thiserror::Error;
+
+use crate::{
+    misc::{decompress_to_vec, MaybeCompressedData},
+    utils::hash_bytes,
+    CompressionError, Compressor, DecompressionError,
+};
+#[cfg(feature = "fs")]
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+/// The [`hash_bytes`] and length of some data, to look it up in a cache.
+type ContentKey = (u64, usize);
+
+#[inline]
+fn content_key(data: &[u8]) -> ContentKey {
+    (hash_bytes(data), data.len())
+}
+
+#[derive(Error, Debug)]
+pub enum CompressionCacheError {
+    #[error("invalid magic {0:02X?}")]
+    InvalidMagic([u8; 4]),
+    #[error("unsupported version {0}")]
+    UnsupportedVersion(u32),
+    #[error(transparent)]
+    TryFromInt(#[from] TryFromIntError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Compressed data by the [`hash_bytes`] and length of its uncompressed contents,
+/// so that data which was already compressed once doesn't have to be compressed again,
+/// such as unchanged tilesets when saving repeatedly.
+///
+/// Entries are checked against the uncompressed data before they're used,
+/// so a stale or corrupted cache only costs time.
+/// The cache can be shared between threads, and [saved](Self::to_writer)
+/// to be reused across sessions.
+#[derive(Debug, Default)]
+pub struct CompressionCache {
+    entries: Mutex<HashMap<ContentKey, Vec<u8>>>,
+}
+
+impl CompressionCache {
+    const MAGIC: [u8; 4] = *b"MLCC";
+    const VERSION: u32 = 1;
+
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the compressed `data` from the cache,
+    /// or compresses it and adds it to the cache.
+    #[inline]
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        self.compress_with(data, &mut Compressor::new())
+    }
+    /// Like [`Self::compress`], but reuses `compressor`.
+    pub fn compress_with(
+        &self,
+        data: &[u8],
+        compressor: &mut Compressor,
+    ) -> Result<Vec<u8>, CompressionError> {
+        let key = content_key(data);
+        let cached = self.lock().get(&key).cloned();
+        if let Some(compressed) = cached {
+            if decompress_to_vec(&compressed, true).is_ok_and(|x| x == data) {
+                return Ok(compressed);
+            }
+        }
+        // Not holding the lock while compressing, so that other threads can use the cache.
+        let compressed = compressor.compress_to_vec(data)?;
+        self.lock().insert(key, compressed.clone());
+        Ok(compressed)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+    #[inline]
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<ContentKey, Vec<u8>>> {
+        // The map can't be left in an inconsistent state by a panic.
+        self.entries.lock().unwrap_or_else(|x| x.into_inner())
+    }
+
+    pub fn from_reader(mut inp: impl Read) -> Result<Self, CompressionCacheError> {
+        let mut magic = [0; 4];
+        inp.read_exact(&mut magic)?;
+        if magic != Self::MAGIC {
+            return Err(CompressionCacheError::InvalidMagic(magic));
+        }
+        let version = inp.read_u32::<LittleEndian>()?;
+        if version != Self::VERSION {
+            return Err(CompressionCacheError::UnsupportedVersion(version));
+        }
+
+        let num_entries = inp.read_u32::<LittleEndian>()?;
+        let mut entries = HashMap::new();
+        for _ in 0..num_entries {
+            let hash = inp.read_u64::<LittleEndian>()?;
+            let uncompressed_len = inp.read_u32::<LittleEndian>()?.try_into()?;
+            let compressed_len = inp.read_u32::<LittleEndian>()?;
+            let mut compressed = Vec::new();
+            inp.by_ref()
+                .take(compressed_len.into())
+                .read_to_end(&mut compressed)?;
+            if compressed.len() != usize::try_from(compressed_len)? {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            entries.insert((hash, uncompressed_len), compressed);
+        }
+        Ok(Self {
+            entries: Mutex::new(entries),
+        })
+    }
+    pub fn to_writer(&self, mut out: impl Write) -> Result<(), CompressionCacheError> {
+        let entries = self.lock();
+        out.write_all(&Self::MAGIC)?;
+        out.write_u32::<LittleEndian>(Self::VERSION)?;
+        out.write_u32::<LittleEndian>(entries.len().try_into()?)?;
+        for (&(hash, uncompressed_len), compressed) in entries.iter() {
+            out.write_u64::<LittleEndian>(hash)?;
+            out.write_u32::<LittleEndian>(uncompressed_len.try_into()?)?;
+            out.write_u32::<LittleEndian>(compressed.len().try_into()?)?;
+            out.write_all(compressed)?;
+        }
+        Ok(())
+    }
+
+    /// Returns an empty cache if `path` doesn't exist yet.
+    #[cfg(feature = "fs")]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CompressionCacheError> {
+        match File::open(path) {
+            Ok(file) => Self::from_reader(BufReader::new(file)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+    #[cfg(feature = "fs")]
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CompressionCacheError> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.to_writer(&mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// A bounded cache of decompressed [`MaybeCompressedData::Compressed`] data,
+/// which evicts the least recently used entry once it's full.
+///
+/// Every entry keeps a copy of the compressed data, which a lookup compares
+/// in full, so modified chunks are never returned stale.
+#[derive(Debug, Clone, Default)]
+pub struct DecompressedChunkCache {
+    capacity: usize,
+    /// The most recently used entry is at the back.
+    entries: VecDeque<DecompressedChunkCacheEntry>,
+}
+
+#[derive(Debug, Clone)]
+struct DecompressedChunkCacheEntry {
+    /// Only to skip comparing `compressed` with most chunks.
+    key: ContentKey,
+    compressed: Box<[u8]>,
+    uncompressed: Arc<[u8]>,
+}
+
+impl DecompressedChunkCache {
+    /// A `capacity` of 0 disables the cache.
+    #[inline]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the uncompressed contents of `chunk`, decompressing it
+    /// only if it isn't in the cache already.
+    pub fn get(
+        &mut self,
+        chunk: &MaybeCompressedData,
+        strict: bool,
+    ) -> Result<Arc<[u8]>, DecompressionError> {
+        let MaybeCompressedData::Compressed(data) = chunk else {
+            return Ok(chunk.to_uncompressed(strict)?.into());
+        };
+        let key = content_key(data);
+        if let Some(position) = self.position(key, data) {
+            let entry = self.entries.remove(position).unwrap();
+            let uncompressed = Arc::clone(&entry.uncompressed);
+            self.entries.push_back(entry);
+            return Ok(uncompressed);
+        }
+
+        let uncompressed: Arc<[u8]> = decompress_to_vec(data, strict)?.into();
+        if self.capacity > 0 {
+            if self.entries.len() >= self.capacity {
+                self.entries.pop_front();
+            }
+            self.entries.push_back(DecompressedChunkCacheEntry {
+                key,
+                compressed: data.as_slice().into(),
+                uncompressed: Arc::clone(&uncompressed),
+            });
+        }
+        Ok(uncompressed)
+    }
+    fn position(&self, key: ContentKey, compressed: &[u8]) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|x| x.key == key && *x.compressed == *compressed)
+    }
+    /// Whether [`Self::get`] would return `chunk` without decompressing it.
+    pub fn contains(&self, chunk: &MaybeCompressedData) -> bool {
+        match chunk {
+            MaybeCompressedData::Compressed(data) => {
+                self.position(content_key(data), data).is_some()
+            }
+            _ => true,
+        }
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    /// Evicts the least recently used entries if there are more than `capacity`.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > capacity {
+            self.entries.pop_front();
+        }
+    }
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    #[inline]
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
@@ -1,14 +1,14 @@
 use std::{
     cmp::{max, min},
-    io::{self, Read, Seek, SeekFrom, Write},
+    io::{self, Cursor, Read, Seek, SeekFrom, Write},
     num::TryFromIntError,
 };
 
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 use thiserror::Error;
 
-use crate::misc::{VarInt, VarIntReader};
+use crate::misc::{VarIntError, VarIntReader, VarIntWriter};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
 #[repr(u8)]
@@ -27,12 +27,23 @@ pub enum DecompressionError {
     IncorrectUncompressedSize { declared: u32, actual: u64 },
     #[error("the declared block size ({declared}) doesn't match the actual one ({actual})")]
     IncorrectBlockSize { declared: u16, actual: u64 },
+    #[error("error in block {index} (at compressed offset {offset:#X})")]
+    Block {
+        index: u32,
+        offset: u64,
+        #[source]
+        source: Box<DecompressionError>,
+    },
+    #[error(transparent)]
+    VarInt(#[from] VarIntError),
     #[error(transparent)]
     Io(#[from] io::Error),
 }
 
 #[derive(Error, Debug)]
 pub enum CompressionError {
+    #[error(transparent)]
+    VarInt(#[from] VarIntError),
     #[error(transparent)]
     TryFromInt(#[from] TryFromIntError),
     #[error(transparent)]
@@ -47,50 +58,15 @@ where
     let uncompressed_size = src.read_varint()?;
     let num_blocks = src.read_varint()? + 1;
 
-    for _ in 0..num_blocks {
-        let block_size = src.read_u16::<LittleEndian>()?;
-        let block_start = src.stream_position()?;
-
-        'block: for _ in 0..256 {
-            let mut commands_byte = src.read_u8()?;
-            for _ in 0..4 {
-                match CompressionCommand::try_from(commands_byte & 0x03)
-                    .map_err(|err| DecompressionError::InvalidCompressionCommand(err.number))?
-                {
-                    CompressionCommand::EndBlock => break 'block,
-                    CompressionCommand::Copy => {
-                        let mut buf = [0u8];
-                        src.read_exact(&mut buf)?;
-                        dst.write_all(&buf)?;
-                    }
-                    CompressionCommand::Lz77 => {
-                        let mut buf = [0u8; 2];
-                        src.read_exact(&mut buf)?;
-                        dst.seek_relative(-(i64::from(buf[0]) | (i64::from(buf[1] & 0xF0) << 4)))?;
-                        let mut data_to_copy = vec![0u8; usize::from(buf[1] & 0x0F) + 2];
-                        dst.read_exact(&mut data_to_copy)?;
-                        dst.seek(SeekFrom::End(0))?;
-                        dst.write_all(&data_to_copy)?;
-                    }
-                    CompressionCommand::Rle => {
-                        let count = usize::from(src.read_u8()?) + 2;
-                        let data = src.read_u8()?;
-                        dst.write_all(&vec![data; count])?;
-                    }
-                }
-                commands_byte >>= 2;
+    for index in 0..num_blocks {
+        let offset = src.stream_position()?;
+        decompress_block(&mut src, &mut dst, strict).map_err(|source| {
+            DecompressionError::Block {
+                index,
+                offset,
+                source: Box::new(source),
             }
-        }
-
-        if strict {
-            let actual_block_size = src.stream_position()? - block_start;
-            if actual_block_size != block_size.into() {
-                return Err(DecompressionError::IncorrectBlockSize {
-                    declared: block_size,
-                    actual: actual_block_size,
-                });
-            }
-        }
+        })?;
     }
 
     if strict {
@@ -105,103 +81,493 @@ where
     Ok(())
 }
 
-pub fn compress<W>(src: &[u8], mut dst: W) -> Result<(), CompressionError>
+fn decompress_block<R, W>(mut src: R, mut dst: W, strict: bool) -> Result<(), DecompressionError>
 where
-    W: Write + Seek,
+    R: Read + Seek,
+    W: Read + Write + Seek,
 {
-    let uncompressed_size = src.len();
-    dst.write_all(&u32::try_from(uncompressed_size)?.encode_var())?;
-    let num_blocks = (uncompressed_size as f64 / 512.0).ceil() as u32;
-    dst.write_all(&(num_blocks - 1).encode_var())?;
-
-    for block_number in 0..num_blocks {
-        let uncompressed_block_position = usize::try_from(block_number)? * 512;
-        let uncompressed_block_size = min(uncompressed_size - uncompressed_block_position, 512);
-        let mut uncompressed_block_offset = 0usize;
-        let compressed_block_position = dst.stream_position()?;
-        dst.write_u16::<LittleEndian>(0x0000)?;
-        let mut last_command_number = -1i8;
-
-        while uncompressed_block_offset < uncompressed_block_size {
-            let commands_byte_position = dst.stream_position()?;
-            let mut commands_byte = 0u8;
-            dst.write_all(&[commands_byte])?;
-            for command_number in 0..4 {
-                if uncompressed_block_offset >= uncompressed_block_size {
-                    break;
+    let block_size = src.read_u16::<LittleEndian>()?;
+    let block_start = src.stream_position()?;
+
+    'block: for _ in 0..256 {
+        let mut commands_byte = src.read_u8()?;
+        for _ in 0..4 {
+            match CompressionCommand::try_from(commands_byte & 0x03)
+                .map_err(|err| DecompressionError::InvalidCompressionCommand(err.number))?
+            {
+                CompressionCommand::EndBlock => break 'block,
+                CompressionCommand::Copy => {
+                    let mut buf = [0u8];
+                    src.read_exact(&mut buf)?;
+                    dst.write_all(&buf)?;
+                }
+                CompressionCommand::Lz77 => {
+                    let mut buf = [0u8; 2];
+                    src.read_exact(&mut buf)?;
+                    dst.seek_relative(-(i64::from(buf[0]) | (i64::from(buf[1] & 0xF0) << 4)))?;
+                    let mut data_to_copy = vec![0u8; usize::from(buf[1] & 0x0F) + 2];
+                    dst.read_exact(&mut data_to_copy)?;
+                    dst.seek(SeekFrom::End(0))?;
+                    dst.write_all(&data_to_copy)?;
                 }
-                let current_uncompressed_position =
-                    uncompressed_block_position + uncompressed_block_offset;
-                let first_byte = src[current_uncompressed_position];
-
-                let mut lz77_best_length = 0u8;
-                let mut lz77_best_offset = 0u16;
-                for offset in (2..=min(current_uncompressed_position, 0xFFF) as u16).rev() {
-                    let mut current_length = 0u8;
-                    while current_length < 17
-                        && u16::from(current_length) < offset
-                        && uncompressed_block_offset + usize::from(current_length)
-                            < uncompressed_block_size
+                CompressionCommand::Rle => {
+                    let count = usize::from(src.read_u8()?) + 2;
+                    let data = src.read_u8()?;
+                    dst.write_all(&vec![data; count])?;
+                }
+            }
+            commands_byte >>= 2;
+        }
+    }
+
+    if strict {
+        let actual_block_size = src.stream_position()? - block_start;
+        if actual_block_size != block_size.into() {
+            return Err(DecompressionError::IncorrectBlockSize {
+                declared: block_size,
+                actual: actual_block_size,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The farthest back an LZ77 command can copy from.
+const LZ77_WINDOW_SIZE: usize = 0x1000;
+
+/// A [`Read`] adapter which decompresses the data from `src` one block at a time
+/// as it's read, rather than all at once like [`decompress`].
+#[derive(Debug)]
+pub struct Decompressor<R> {
+    src: R,
+    strict: bool,
+    uncompressed_size: u32,
+    num_blocks: u32,
+    next_block: u32,
+    /// The output of the last decompressed block, preceded by
+    /// as much of the earlier output as LZ77 commands can refer to.
+    window: Cursor<Vec<u8>>,
+    /// The position in `window` of the next byte to be read.
+    read_position: usize,
+    output_size: u64,
+}
+
+impl<R: Read + Seek> Decompressor<R> {
+    /// Reads the header of the compressed data, but no blocks yet.
+    pub fn new(mut src: R, strict: bool) -> Result<Self, DecompressionError> {
+        let uncompressed_size = src.read_varint()?;
+        let num_blocks = src.read_varint()? + 1;
+        Ok(Self {
+            src,
+            strict,
+            uncompressed_size,
+            num_blocks,
+            next_block: 0,
+            window: Cursor::new(Vec::new()),
+            read_position: 0,
+            output_size: 0,
+        })
+    }
+
+    /// The uncompressed size declared in the header,
+    /// which is only checked against the actual one if `strict`.
+    #[inline]
+    pub fn uncompressed_size(&self) -> u32 {
+        self.uncompressed_size
+    }
+    #[inline]
+    pub fn into_inner(self) -> R {
+        self.src
+    }
+
+    fn decompress_next_block(&mut self) -> Result<(), DecompressionError> {
+        let window = self.window.get_mut();
+        let excess = window.len().saturating_sub(LZ77_WINDOW_SIZE);
+        window.drain(..excess);
+        self.read_position -= excess;
+        let block_start = self.window.seek(SeekFrom::End(0))?;
+
+        let index = self.next_block;
+        let offset = self.src.stream_position()?;
+        decompress_block(&mut self.src, &mut self.window, self.strict).map_err(|source| {
+            DecompressionError::Block {
+                index,
+                offset,
+                source: Box::new(source),
+            }
+        })?;
+        self.next_block += 1;
+        self.output_size += self.window.position() - block_start;
+
+        if self.strict
+            && self.next_block == self.num_blocks
+            && self.output_size != self.uncompressed_size.into()
+        {
+            return Err(DecompressionError::IncorrectUncompressedSize {
+                declared: self.uncompressed_size,
+                actual: self.output_size,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for Decompressor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.read_position == self.window.get_ref().len() {
+            if self.next_block == self.num_blocks {
+                return Ok(0);
+            }
+            self.decompress_next_block().map_err(|error| match error {
+                DecompressionError::Io(error) => error,
+                error => io::Error::new(io::ErrorKind::InvalidData, error),
+            })?;
+        }
+        let available = &self.window.get_ref()[self.read_position..];
+        let len = min(buf.len(), available.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.read_position += len;
+        Ok(len)
+    }
+}
+
+/// Compresses `src` into `dst`.
+///
+/// Use a [`Compressor`] to compress many chunks in a row.
+#[inline]
+pub fn compress<W>(src: &[u8], dst: W) -> Result<(), CompressionError>
+where
+    W: Write + Seek,
+{
+    Compressor::new().compress(src, dst)
+}
+
+const COMPRESSION_BLOCK_SIZE: usize = 512;
+const LZ77_MAX_OFFSET: usize = 0xFFF;
+const LZ77_MAX_LENGTH: u8 = 17;
+const RLE_MAX_COUNT: u16 = 257;
+
+/// The state of [`compress`], which is kept between calls
+/// so that compressing many chunks doesn't allocate it every time.
+///
+/// Earlier positions are found through hash chains of the pairs of bytes starting at them,
+/// instead of trying every offset, but the output is the same as that of trying every offset.
+#[derive(Debug, Clone, Default)]
+pub struct Compressor {
+    /// The last position starting with each pair of bytes, plus one, or 0 if there's none.
+    head: Vec<u32>,
+    /// The previous position starting with the same pair of bytes as each position, plus one,
+    /// or 0 if there's none.
+    prev: Vec<u32>,
+    block: Vec<u8>,
+}
+
+impl Compressor {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn compress(&mut self, src: &[u8], mut dst: impl Write) -> Result<(), CompressionError> {
+        let uncompressed_size = src.len();
+        let num_blocks = u32::try_from(uncompressed_size.div_ceil(COMPRESSION_BLOCK_SIZE))?;
+        dst.write_varint(uncompressed_size.try_into()?)?;
+        dst.write_varint(num_blocks - 1)?;
+
+        self.head.clear();
+        self.head.resize(0x10000, 0);
+        self.prev.clear();
+        self.prev.resize(uncompressed_size, 0);
+        let mut num_inserted = 0;
+
+        for block_position in (0..uncompressed_size).step_by(COMPRESSION_BLOCK_SIZE) {
+            let block_end = min(block_position + COMPRESSION_BLOCK_SIZE, uncompressed_size);
+            let mut position = block_position;
+            self.block.clear();
+            let mut last_command_number = -1i8;
+
+            while position < block_end {
+                let commands_byte_position = self.block.len();
+                let mut commands_byte = 0u8;
+                self.block.push(commands_byte);
+                for command_number in 0..4 {
+                    if position >= block_end {
+                        break;
+                    }
+                    let first_byte = src[position];
+
+                    // Offsets start at 2, so every position up to 2 bytes back can be matched.
+                    while num_inserted + 2 <= position {
+                        self.insert(src, num_inserted);
+                        num_inserted += 1;
+                    }
+                    let (lz77_best_length, lz77_best_offset) =
+                        self.find_match(src, position, block_end);
+
+                    let mut rle_count = 1u16;
+                    while position + usize::from(rle_count) < block_end && rle_count < RLE_MAX_COUNT
                     {
-                        if src[current_uncompressed_position + usize::from(current_length)]
-                            != src[current_uncompressed_position - usize::from(offset)
-                                + usize::from(current_length)]
-                        {
+                        if src[position + usize::from(rle_count)] != first_byte {
                             break;
                         }
-                        current_length += 1;
-                    }
-                    if current_length > lz77_best_length {
-                        lz77_best_length = current_length;
-                        lz77_best_offset = offset;
+                        rle_count += 1;
                     }
-                }
 
-                let mut rle_count = 1u16;
-                while uncompressed_block_offset + usize::from(rle_count) < uncompressed_block_size
-                    && rle_count < 257
-                {
-                    if src[current_uncompressed_position + usize::from(rle_count)] != first_byte {
-                        break;
+                    let current_command: CompressionCommand;
+                    let best_length = max(lz77_best_length.into(), rle_count);
+                    if best_length <= 1 {
+                        current_command = CompressionCommand::Copy;
+                        self.block.push(first_byte);
+                    } else if u16::from(lz77_best_length) > rle_count {
+                        current_command = CompressionCommand::Lz77;
+                        self.block.extend([
+                            lz77_best_offset as u8,
+                            (lz77_best_length - 2) | (((lz77_best_offset & 0xF00) >> 4) as u8),
+                        ]);
+                    } else {
+                        current_command = CompressionCommand::Rle;
+                        self.block.extend([(rle_count - 2) as u8, first_byte]);
                     }
-                    rle_count += 1;
-                }
 
-                let current_command: CompressionCommand;
-                let best_length = max(lz77_best_length.into(), rle_count);
-                if best_length <= 1 {
-                    current_command = CompressionCommand::Copy;
-                    dst.write_all(&[first_byte])?;
-                } else if u16::from(lz77_best_length) > rle_count {
-                    current_command = CompressionCommand::Lz77;
-                    dst.write_all(&[
-                        lz77_best_offset as u8,
-                        (lz77_best_length - 2) | (((lz77_best_offset & 0xF00) >> 4) as u8),
-                    ])?;
-                } else {
-                    current_command = CompressionCommand::Rle;
-                    dst.write_all(&[(rle_count - 2) as u8, first_byte])?;
+                    commands_byte |= u8::from(current_command) << (command_number * 2);
+                    position += usize::from(best_length);
+                    last_command_number = command_number;
                 }
+                self.block[commands_byte_position] = commands_byte;
+            }
 
-                commands_byte |= u8::from(current_command) << (command_number * 2);
-                uncompressed_block_offset += usize::from(best_length);
-                last_command_number = command_number;
+            if last_command_number == 3 {
+                self.block.push(0);
             }
-            dst.seek(SeekFrom::Start(commands_byte_position))?;
-            dst.write_all(&[commands_byte])?;
-            dst.seek(SeekFrom::End(0))?;
+            dst.write_u16::<LittleEndian>(self.block.len().try_into()?)?;
+            dst.write_all(&self.block)?;
         }
 
-        if last_command_number == 3 {
-            dst.write_all(&[0u8])?;
+        Ok(())
+    }
+    /// Like [`Self::compress`], but returns the compressed data.
+    pub fn compress_to_vec(&mut self, src: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        let mut buf = Vec::new();
+        self.compress(src, &mut buf)?;
+        Ok(buf)
+    }
+
+    #[inline]
+    fn pair_at(src: &[u8], position: usize) -> Option<usize> {
+        Some(usize::from(*src.get(position)?) | usize::from(*src.get(position + 1)?) << 8)
+    }
+    fn insert(&mut self, src: &[u8], position: usize) {
+        if let Some(pair) = Self::pair_at(src, position) {
+            self.prev[position] = self.head[pair];
+            self.head[pair] = position as u32 + 1;
+        }
+    }
+    /// Returns the longest match of at least 2 bytes for `position`, preferring the largest
+    /// offset of equally long ones, or a length of 0 if there's none.
+    fn find_match(&self, src: &[u8], position: usize, block_end: usize) -> (u8, u16) {
+        let (mut best_length, mut best_offset) = (0u8, 0u16);
+        if position + 1 >= block_end {
+            return (best_length, best_offset);
+        }
+        let Some(pair) = Self::pair_at(src, position) else {
+            return (best_length, best_offset);
+        };
+        let max_length = min(usize::from(LZ77_MAX_LENGTH), block_end - position);
+        let mut candidate = self.head[pair];
+        while candidate != 0 {
+            let candidate_position = candidate as usize - 1;
+            let offset = position - candidate_position;
+            if offset > LZ77_MAX_OFFSET {
+                break;
+            }
+            let length = src[position..position + min(max_length, offset)]
+                .iter()
+                .zip(&src[candidate_position..])
+                .take_while(|(a, b)| a == b)
+                .count() as u8;
+            // The chain goes towards larger offsets.
+            if length >= best_length {
+                best_length = length;
+                best_offset = offset as u16;
+            }
+            candidate = self.prev[candidate_position];
         }
-        let compressed_block_end_position = dst.stream_position()?;
-        dst.seek(SeekFrom::Start(compressed_block_position))?;
-        dst.write_u16::<LittleEndian>(
-            (compressed_block_end_position - compressed_block_position - 2).try_into()?,
-        )?;
-        dst.seek(SeekFrom::End(0))?;
+        (best_length, best_offset)
     }
+}
 
-    Ok(())
+#[derive(Error, Debug)]
+pub enum BlzDecompressionError {
+    #[error("invalid BLZ footer")]
+    InvalidFooter,
+    #[error("the compressed data ended early")]
+    Truncated,
+    #[error("invalid back-reference at compressed offset {0:#X}")]
+    InvalidReference(usize),
+}
+
+const BLZ_FOOTER_SIZE: usize = 8;
+const BLZ_MIN_MATCH: usize = 3;
+const BLZ_MAX_MATCH: usize = 0x0F + BLZ_MIN_MATCH;
+const BLZ_MIN_DISPLACEMENT: usize = 3;
+const BLZ_MAX_DISPLACEMENT: usize = 0x0FFF + BLZ_MIN_DISPLACEMENT;
+
+/// Decompresses data compressed with the backwards LZ used for the ARM9 binary and overlays.
+///
+/// Data whose footer declares no size increase is returned as-is.
+pub fn blz_decompress(data: &[u8]) -> Result<Vec<u8>, BlzDecompressionError> {
+    let footer = data
+        .len()
+        .checked_sub(BLZ_FOOTER_SIZE)
+        .map(|x| &data[x..])
+        .ok_or(BlzDecompressionError::InvalidFooter)?;
+    let size_increase = LittleEndian::read_u32(&footer[4..]) as usize;
+    if size_increase == 0 {
+        return Ok(data.to_vec());
+    }
+    let header_len = usize::from(footer[3]);
+    let compressed_len = LittleEndian::read_u32(footer) as usize & 0x00FF_FFFF;
+    if header_len < BLZ_FOOTER_SIZE || compressed_len < header_len || compressed_len > data.len() {
+        return Err(BlzDecompressionError::InvalidFooter);
+    }
+
+    // Like the hardware, decompress in place, from the end towards the start.
+    let end = data.len() - compressed_len;
+    let mut buf = data.to_vec();
+    buf.resize(data.len() + size_increase, 0);
+    let mut inp = data.len() - header_len;
+    let mut out = buf.len();
+    let read = |buf: &[u8], inp: &mut usize| {
+        if *inp <= end {
+            return Err(BlzDecompressionError::Truncated);
+        }
+        *inp -= 1;
+        Ok(buf[*inp])
+    };
+    let mut flags = 0u8;
+    let mut remaining_flags = 0;
+    while out > end {
+        if remaining_flags == 0 {
+            flags = read(&buf, &mut inp)?;
+            remaining_flags = 8;
+        }
+        remaining_flags -= 1;
+        if flags & 0x80 != 0 {
+            let high = read(&buf, &mut inp)?;
+            let low = read(&buf, &mut inp)?;
+            let length = usize::from(high >> 4) + BLZ_MIN_MATCH;
+            let displacement =
+                (usize::from(high & 0x0F) << 8 | usize::from(low)) + BLZ_MIN_DISPLACEMENT;
+            if out + displacement > buf.len() {
+                return Err(BlzDecompressionError::InvalidReference(inp));
+            }
+            for _ in 0..length.min(out - end) {
+                out -= 1;
+                buf[out] = buf[out + displacement];
+            }
+        } else {
+            let byte = read(&buf, &mut inp)?;
+            out -= 1;
+            buf[out] = byte;
+        }
+        flags <<= 1;
+    }
+    Ok(buf)
+}
+
+/// Compresses data with the backwards LZ used for the ARM9 binary and overlays,
+/// such that it can be decompressed in place.
+///
+/// Returns `None` if the data can't be made smaller.
+pub fn blz_compress(data: &[u8]) -> Option<Vec<u8>> {
+    // Compress the reversed data forwards, and reverse the result at the end.
+    let reversed = data.iter().rev().copied().collect::<Vec<_>>();
+    let hash = |i: usize| {
+        (usize::from(reversed[i]) << 8
+            ^ usize::from(reversed[i + 1]) << 4
+            ^ usize::from(reversed[i + 2]))
+            & 0xFFFF
+    };
+    let mut head = vec![usize::MAX; 0x10000];
+    let mut previous = vec![usize::MAX; reversed.len()];
+
+    let mut output = Vec::new();
+    let mut flags_index = 0;
+    let mut num_tokens = 0;
+    // The positions in the input and output where compression stops.
+    let mut cut = (0, 0);
+    let mut i = 0;
+    while i < reversed.len() {
+        if num_tokens % 8 == 0 {
+            flags_index = output.len();
+            output.push(0);
+        }
+        let mut best = (0, 0);
+        if i + BLZ_MIN_MATCH <= reversed.len() {
+            let mut candidate = head[hash(i)];
+            while candidate != usize::MAX && i - candidate <= BLZ_MAX_DISPLACEMENT {
+                let displacement = i - candidate;
+                if displacement >= BLZ_MIN_DISPLACEMENT {
+                    let length = (0..BLZ_MAX_MATCH.min(reversed.len() - i))
+                        .take_while(|&j| reversed[candidate + j] == reversed[i + j])
+                        .count();
+                    if length > best.0 {
+                        best = (length, displacement);
+                        if length == BLZ_MAX_MATCH {
+                            break;
+                        }
+                    }
+                }
+                candidate = previous[candidate];
+            }
+        }
+
+        let start = i;
+        if best.0 >= BLZ_MIN_MATCH {
+            let (length, displacement) = (best.0, best.1 - BLZ_MIN_DISPLACEMENT);
+            output[flags_index] |= 0x80 >> (num_tokens % 8);
+            output.push(((length - BLZ_MIN_MATCH) << 4 | displacement >> 8) as u8);
+            output.push(displacement as u8);
+            i += length;
+        } else {
+            output.push(reversed[i]);
+            i += 1;
+        }
+        let hashed_end = i.min(reversed.len().saturating_sub(BLZ_MIN_MATCH - 1));
+        for (j, previous) in previous.iter_mut().enumerate().take(hashed_end).skip(start) {
+            let h = hash(j);
+            *previous = head[h];
+            head[h] = j;
+        }
+        num_tokens += 1;
+
+        // Decompressing in place is safe as long as the output never gets further
+        // ahead of the input than it is at the end, so stop where it's furthest ahead.
+        if i >= output.len() && i - output.len() >= cut.0 - cut.1 {
+            cut = (i, output.len());
+        }
+    }
+
+    let (consumed, compressed_len) = cut;
+    let uncompressed_len = data.len() - consumed;
+    let padding = (4 - (uncompressed_len + compressed_len) % 4) % 4;
+    let header_len = BLZ_FOOTER_SIZE + padding;
+    let size_increase = (consumed - compressed_len).checked_sub(header_len)?;
+    let total_compressed_len = u32::try_from(compressed_len + header_len)
+        .ok()
+        .filter(|&x| x <= 0x00FF_FFFF)?;
+    if size_increase == 0 {
+        return None;
+    }
+
+    let mut result = data[..uncompressed_len].to_vec();
+    result.extend(output[..compressed_len].iter().rev());
+    result.extend(std::iter::repeat_n(0xFF, padding));
+    result
+        .write_u32::<LittleEndian>(total_compressed_len | (header_len as u32) << 24)
+        .ok()?;
+    result
+        .write_u32::<LittleEndian>(size_increase.try_into().ok()?)
+        .ok()?;
+    Some(result)
 }
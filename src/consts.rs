@@ -22,3 +22,9 @@ pub const TREASURE_INFO_OFFSET_TABLE_LENGTH_ADDRESS: u64 = 0x4AA30;
 pub const TREASURE_INFO_OFFSET_TABLE_ADDRESS: u64 = TREASURE_INFO_OFFSET_TABLE_LENGTH_ADDRESS + 4;
 /// Overlay 3.
 pub const FIELD_MAP_CHUNK_TABLE_ADDRESS: u64 = 0x19FD0;
+
+pub const NUMBER_OF_FEVENT_CHUNKS_PER_MAP: usize = 3;
+/// Overlay 3.
+pub const FEVENT_OFFSET_TABLE_LENGTH_ADDRESS: u64 = 0xC8AC;
+/// Overlay 3.
+pub const FEVENT_OFFSET_TABLE_ADDRESS: u64 = FEVENT_OFFSET_TABLE_LENGTH_ADDRESS + 4;
@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+
+use thiserror::Error;
+
+use crate::{
+    project::{Project, ProjectError},
+    script::{CommandTable, DisassemblyError, Instruction, Script, ScriptMatch},
+};
+
+/// Answers which parts of a [`Project`] refer to what,
+/// such as which field maps use a tileset.
+///
+/// Each index is built from the project the first time it's queried,
+/// so it has to be [invalidated](Self::invalidate) after editing the project.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CrossRef {
+    /// Used to find the [`CommandInfo::map_operands`](crate::script::CommandInfo::map_operands)
+    /// of field event scripts.
+    pub command_table: CommandTable,
+    /// By chunk of [`FieldMaps::fmapdata_chunks`](crate::map::FieldMaps::fmapdata_chunks).
+    chunk_users: Option<BTreeMap<usize, Vec<usize>>>,
+    /// By field map.
+    warps: Option<BTreeMap<u32, Vec<ScriptMatch>>>,
+}
+
+#[derive(Error, Debug)]
+pub enum CrossRefError {
+    #[error("failed to disassemble the event script of field map {map}")]
+    Script {
+        map: usize,
+        #[source]
+        source: DisassemblyError,
+    },
+    #[error(transparent)]
+    Project(#[from] ProjectError),
+}
+
+fn lookup<K: Ord, V>(index: &BTreeMap<K, Vec<V>>, key: K) -> &[V] {
+    index.get(&key).map_or(&[], Vec::as_slice)
+}
+
+impl CrossRef {
+    pub fn new(command_table: CommandTable) -> Self {
+        Self {
+            command_table,
+            ..Default::default()
+        }
+    }
+
+    /// Discards all indexes, so that they're rebuilt from the project.
+    pub fn invalidate(&mut self) {
+        self.chunk_users = None;
+        self.warps = None;
+    }
+
+    /// The field maps which use the chunk `chunk` of
+    /// [`FieldMaps::fmapdata_chunks`](crate::map::FieldMaps::fmapdata_chunks),
+    /// as either a tileset or their map chunk.
+    pub fn maps_using_chunk(
+        &mut self,
+        project: &mut Project,
+        chunk: usize,
+    ) -> Result<&[usize], CrossRefError> {
+        if self.chunk_users.is_none() {
+            let mut index: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+            for (map_index, map) in project.field_maps()?.maps.iter().enumerate() {
+                let mut chunks: Vec<usize> =
+                    map.tileset_indexes.iter().flatten().copied().collect();
+                chunks.push(map.map_chunk_index);
+                chunks.sort_unstable();
+                chunks.dedup();
+                for chunk in chunks {
+                    index.entry(chunk).or_default().push(map_index);
+                }
+            }
+            self.chunk_users = Some(index);
+        }
+        Ok(lookup(self.chunk_users.as_ref().unwrap(), chunk))
+    }
+
+    /// The commands of field event scripts which refer to the field map `map`,
+    /// where [`ScriptMatch::script`] is the map that the script belongs to.
+    pub fn scripts_warping_to(
+        &mut self,
+        project: &mut Project,
+        map: u32,
+    ) -> Result<&[ScriptMatch], CrossRefError> {
+        if self.warps.is_none() {
+            let mut index: BTreeMap<u32, Vec<ScriptMatch>> = BTreeMap::new();
+            let field_events = project.field_events()?;
+            for map_index in 0..field_events.num_maps() {
+                let script = Script::disassemble_lenient(
+                    field_events.map_script(map_index).unwrap(),
+                    &self.command_table,
+                )
+                .map_err(|source| CrossRefError::Script {
+                    map: map_index,
+                    source,
+                })?;
+                for (instruction_index, instruction) in script.instructions.iter().enumerate() {
+                    let Instruction::Command(command) = instruction else {
+                        continue;
+                    };
+                    let Some(info) = self.command_table.get(command.opcode) else {
+                        continue;
+                    };
+                    for target in info
+                        .map_operands
+                        .iter()
+                        .filter_map(|&x| command.operands.get(x)?.as_unsigned())
+                    {
+                        let matches = index.entry(target).or_default();
+                        let script_match = ScriptMatch {
+                            script: map_index,
+                            instruction: instruction_index,
+                            opcode: command.opcode,
+                        };
+                        if matches.last() != Some(&script_match) {
+                            matches.push(script_match);
+                        }
+                    }
+                }
+            }
+            self.warps = Some(index);
+        }
+        Ok(lookup(self.warps.as_ref().unwrap(), map))
+    }
+}
@@ -0,0 +1,288 @@
+use byteorder::{ByteOrder, LittleEndian};
+use thiserror::Error;
+
+use crate::misc::DataWithOffsetTable;
+
+/// A position in 20.12 fixed-point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Vector3 {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl Vector3 {
+    pub const SIZE: usize = 12;
+    pub const ONE: i32 = 1 << 12;
+
+    pub fn from_bytes(data: &[u8]) -> Self {
+        Self {
+            x: LittleEndian::read_i32(&data[0..]),
+            y: LittleEndian::read_i32(&data[4..]),
+            z: LittleEndian::read_i32(&data[8..]),
+        }
+    }
+    pub fn to_bytes(&self, data: &mut [u8]) {
+        LittleEndian::write_i32(&mut data[0..], self.x);
+        LittleEndian::write_i32(&mut data[4..], self.y);
+        LittleEndian::write_i32(&mut data[8..], self.z);
+    }
+}
+
+/// A fixed-size element of a [`CutsceneTrack`].
+pub trait Keyframe: Sized {
+    const SIZE: usize;
+
+    fn from_bytes(data: &[u8]) -> Self;
+    fn to_bytes(&self, data: &mut [u8]);
+
+    /// The number of frames which the keyframe takes.
+    fn duration(&self) -> u16;
+}
+
+/// The state of the camera at the end of a keyframe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct CameraKeyframe {
+    /// How many frames the camera takes to get here from the previous keyframe.
+    pub duration: u16,
+    /// How the camera moves between the keyframes, with 0 being linear.
+    pub interpolation: u16,
+    pub position: Vector3,
+    /// The point which the camera looks at.
+    pub target: Vector3,
+}
+
+impl Keyframe for CameraKeyframe {
+    const SIZE: usize = 4 + Vector3::SIZE * 2;
+
+    fn from_bytes(data: &[u8]) -> Self {
+        Self {
+            duration: LittleEndian::read_u16(&data[0..]),
+            interpolation: LittleEndian::read_u16(&data[2..]),
+            position: Vector3::from_bytes(&data[4..]),
+            target: Vector3::from_bytes(&data[4 + Vector3::SIZE..]),
+        }
+    }
+    fn to_bytes(&self, data: &mut [u8]) {
+        LittleEndian::write_u16(&mut data[0..], self.duration);
+        LittleEndian::write_u16(&mut data[2..], self.interpolation);
+        self.position.to_bytes(&mut data[4..]);
+        self.target.to_bytes(&mut data[4 + Vector3::SIZE..]);
+    }
+    #[inline]
+    fn duration(&self) -> u16 {
+        self.duration
+    }
+}
+
+/// A point which an actor walks to along a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct PathPoint {
+    /// How many frames the actor takes to get here from the previous point.
+    pub duration: u16,
+    /// The animation which is played on the way.
+    pub animation: u16,
+    pub position: Vector3,
+}
+
+impl Keyframe for PathPoint {
+    const SIZE: usize = 4 + Vector3::SIZE;
+
+    fn from_bytes(data: &[u8]) -> Self {
+        Self {
+            duration: LittleEndian::read_u16(&data[0..]),
+            animation: LittleEndian::read_u16(&data[2..]),
+            position: Vector3::from_bytes(&data[4..]),
+        }
+    }
+    fn to_bytes(&self, data: &mut [u8]) {
+        LittleEndian::write_u16(&mut data[0..], self.duration);
+        LittleEndian::write_u16(&mut data[2..], self.animation);
+        self.position.to_bytes(&mut data[4..]);
+    }
+    #[inline]
+    fn duration(&self) -> u16 {
+        self.duration
+    }
+}
+
+/// A sequence of keyframes.
+///
+/// It's stored as a header of 4 `u16`s (the kind of track, the number of
+/// keyframes, `actor` and `flags`), followed by the keyframes
+/// and then data which is preserved as-is.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct CutsceneTrack<K> {
+    /// The actor which the track moves, or which the camera follows,
+    /// or [`Self::NO_ACTOR`].
+    pub actor: u16,
+    /// Bit 0 makes the track loop.
+    pub flags: u16,
+    pub keyframes: Vec<K>,
+    pub extra: Vec<u8>,
+}
+
+#[derive(Error, Debug)]
+pub enum CutsceneTrackDeserializationError {
+    #[error("the data is {actual} bytes long, but it should be at least {min} bytes long")]
+    TooShort { min: usize, actual: usize },
+}
+
+impl<K: Keyframe> CutsceneTrack<K> {
+    pub const NO_ACTOR: u16 = 0xFFFF;
+    pub const HEADER_SIZE: usize = 8;
+
+    #[inline]
+    pub fn looped(&self) -> bool {
+        self.flags & 1 != 0
+    }
+
+    /// The total number of frames which the track takes.
+    pub fn duration(&self) -> u32 {
+        self.keyframes.iter().map(|x| u32::from(x.duration())).sum()
+    }
+
+    fn from_bytes(data: &[u8]) -> Result<Self, CutsceneTrackDeserializationError> {
+        let too_short = |min| CutsceneTrackDeserializationError::TooShort {
+            min,
+            actual: data.len(),
+        };
+        if data.len() < Self::HEADER_SIZE {
+            return Err(too_short(Self::HEADER_SIZE));
+        }
+        let count = usize::from(LittleEndian::read_u16(&data[2..]));
+        let end = Self::HEADER_SIZE + count * K::SIZE;
+        if data.len() < end {
+            return Err(too_short(end));
+        }
+        Ok(Self {
+            actor: LittleEndian::read_u16(&data[4..]),
+            flags: LittleEndian::read_u16(&data[6..]),
+            keyframes: data[Self::HEADER_SIZE..end]
+                .chunks_exact(K::SIZE)
+                .map(K::from_bytes)
+                .collect(),
+            extra: data[end..].to_vec(),
+        })
+    }
+    fn to_bytes(&self, kind: u16) -> Result<Vec<u8>, CutsceneTrackSerializationError> {
+        let mut data = vec![0u8; Self::HEADER_SIZE + self.keyframes.len() * K::SIZE];
+        LittleEndian::write_u16(&mut data[0..], kind);
+        LittleEndian::write_u16(
+            &mut data[2..],
+            self.keyframes.len().try_into().map_err(|_| {
+                CutsceneTrackSerializationError::TooManyKeyframes(self.keyframes.len())
+            })?,
+        );
+        LittleEndian::write_u16(&mut data[4..], self.actor);
+        LittleEndian::write_u16(&mut data[6..], self.flags);
+        for (keyframe, chunk) in self
+            .keyframes
+            .iter()
+            .zip(data[Self::HEADER_SIZE..].chunks_exact_mut(K::SIZE))
+        {
+            keyframe.to_bytes(chunk);
+        }
+        data.extend_from_slice(&self.extra);
+        Ok(data)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum CutsceneTrackSerializationError {
+    #[error("a track can have at most 65535 keyframes, not {0}")]
+    TooManyKeyframes(usize),
+}
+
+/// A chunk of [`CutsceneData`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Track {
+    Camera(CutsceneTrack<CameraKeyframe>),
+    Path(CutsceneTrack<PathPoint>),
+    /// A track of another kind, which is preserved as-is.
+    Unknown(Vec<u8>),
+}
+
+impl Track {
+    pub const KIND_CAMERA: u16 = 0;
+    pub const KIND_PATH: u16 = 1;
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, CutsceneTrackDeserializationError> {
+        Ok(match data.get(..2).map(LittleEndian::read_u16) {
+            Some(Self::KIND_CAMERA) => Self::Camera(CutsceneTrack::from_bytes(data)?),
+            Some(Self::KIND_PATH) => Self::Path(CutsceneTrack::from_bytes(data)?),
+            _ => Self::Unknown(data.to_vec()),
+        })
+    }
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CutsceneTrackSerializationError> {
+        match self {
+            Self::Camera(track) => track.to_bytes(Self::KIND_CAMERA),
+            Self::Path(track) => track.to_bytes(Self::KIND_PATH),
+            Self::Unknown(data) => Ok(data.clone()),
+        }
+    }
+}
+
+/// The camera movement and paths of a cutscene, which event scripts refer to
+/// by the index of the track.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct CutsceneData {
+    pub tracks: Vec<Track>,
+    pub padding: Vec<u8>,
+}
+
+#[derive(Error, Debug)]
+pub enum CutsceneDataFromTableError {
+    #[error("failed to deserialize track {index}")]
+    Track {
+        index: usize,
+        #[source]
+        source: CutsceneTrackDeserializationError,
+    },
+}
+#[derive(Error, Debug)]
+pub enum CutsceneDataIntoTableError {
+    #[error("failed to serialize track {index}")]
+    Track {
+        index: usize,
+        #[source]
+        source: CutsceneTrackSerializationError,
+    },
+}
+
+impl TryFrom<DataWithOffsetTable> for CutsceneData {
+    type Error = CutsceneDataFromTableError;
+
+    fn try_from(value: DataWithOffsetTable) -> Result<Self, Self::Error> {
+        Ok(Self {
+            tracks: value
+                .chunks
+                .iter()
+                .enumerate()
+                .map(|(index, chunk)| {
+                    Track::from_bytes(chunk).map_err(|source| Self::Error::Track { index, source })
+                })
+                .collect::<Result<_, _>>()?,
+            padding: value.footer,
+        })
+    }
+}
+impl TryFrom<CutsceneData> for DataWithOffsetTable {
+    type Error = CutsceneDataIntoTableError;
+
+    fn try_from(value: CutsceneData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            chunks: value
+                .tracks
+                .iter()
+                .enumerate()
+                .map(|(index, track)| {
+                    track
+                        .to_bytes()
+                        .map_err(|source| Self::Error::Track { index, source })
+                })
+                .collect::<Result<_, _>>()?,
+            footer: value.padding,
+        })
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    fmt::{self, Display},
+    io::{Cursor, Read, Seek},
+    ops::Range,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::{
+    map::{FieldMapChunk, FieldMapChunkIntoTableError},
+    misc::{
+        DataWithOffsetTable, DataWithOffsetTableSerializationError, OffsetTableEntrySize,
+        VarIntReader,
+    },
+    CompressionCommand, DecompressionError,
+};
+
+/// A hexdump of some data, where ranges of it are labeled.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct AnnotatedDump {
+    pub data: Vec<u8>,
+    /// Sorted by start offset, and not overlapping.
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Annotation {
+    pub range: Range<usize>,
+    pub label: String,
+}
+
+impl AnnotatedDump {
+    pub const BYTES_PER_LINE: usize = 16;
+
+    pub fn new(data: Vec<u8>) -> Self {
+        Self {
+            data,
+            annotations: Vec::new(),
+        }
+    }
+
+    /// Appends an annotation, which must start after the end of the previous one.
+    pub fn annotate(&mut self, range: Range<usize>, label: impl Into<String>) {
+        debug_assert!(self
+            .annotations
+            .last()
+            .is_none_or(|x| x.range.end <= range.start));
+        self.annotations.push(Annotation {
+            range,
+            label: label.into(),
+        });
+    }
+
+    fn fmt_bytes(&self, f: &mut fmt::Formatter<'_>, range: Range<usize>) -> fmt::Result {
+        let data = &self.data[range.start.min(self.data.len())..range.end.min(self.data.len())];
+        for (i, line) in data.chunks(Self::BYTES_PER_LINE).enumerate() {
+            write!(f, "    {:08X} ", range.start + i * Self::BYTES_PER_LINE)?;
+            for byte in line {
+                write!(f, " {:02X}", byte)?;
+            }
+            write!(
+                f,
+                "{:width$}  |",
+                "",
+                width = (Self::BYTES_PER_LINE - line.len()) * 3
+            )?;
+            for &byte in line {
+                let c = char::from(byte);
+                write!(f, "{}", if c.is_ascii_graphic() { c } else { '.' })?;
+            }
+            writeln!(f, "|")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for AnnotatedDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut offset = 0;
+        for annotation in &self.annotations {
+            if annotation.range.start > offset {
+                writeln!(
+                    f,
+                    "{:08X}..{:08X} (unannotated)",
+                    offset, annotation.range.start
+                )?;
+                self.fmt_bytes(f, offset..annotation.range.start)?;
+            }
+            writeln!(
+                f,
+                "{:08X}..{:08X} {}",
+                annotation.range.start, annotation.range.end, annotation.label
+            )?;
+            self.fmt_bytes(f, annotation.range.clone())?;
+            offset = annotation.range.end;
+        }
+        if offset < self.data.len() {
+            writeln!(f, "{:08X}..{:08X} (unannotated)", offset, self.data.len())?;
+            self.fmt_bytes(f, offset..self.data.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Types which can be rendered as an [`AnnotatedDump`] of their serialized form.
+pub trait Dump {
+    type Error;
+
+    fn dump(&self) -> Result<AnnotatedDump, Self::Error>;
+}
+
+impl DataWithOffsetTable {
+    /// Like [`Dump::dump`], but with custom labels for the chunks.
+    pub fn dump_with_chunk_labels(
+        &self,
+        entry_size: OffsetTableEntrySize,
+        chunk_label: impl Fn(usize) -> String,
+    ) -> Result<AnnotatedDump, DataWithOffsetTableSerializationError> {
+        let mut data = Vec::new();
+        self.to_writer_with_entry_size(&mut data, entry_size, None, true)?;
+        let mut dump = AnnotatedDump::new(data);
+
+        let entry_size = entry_size.size();
+        let mut offset = 0;
+        for i in 0..=self.chunks.len() {
+            dump.annotate(offset..offset + entry_size, format!("offset {}", i));
+            offset += entry_size;
+        }
+        for (i, chunk) in self.chunks.iter().enumerate() {
+            if !chunk.is_empty() {
+                dump.annotate(offset..offset + chunk.len(), chunk_label(i));
+            }
+            offset += chunk.len();
+        }
+        if !self.footer.is_empty() {
+            dump.annotate(offset..offset + self.footer.len(), "footer");
+        }
+
+        Ok(dump)
+    }
+}
+
+impl Dump for DataWithOffsetTable {
+    type Error = DataWithOffsetTableSerializationError;
+
+    #[inline]
+    fn dump(&self) -> Result<AnnotatedDump, Self::Error> {
+        self.dump_with_chunk_labels(OffsetTableEntrySize::U32, |i| format!("chunk {}", i))
+    }
+}
+
+impl Dump for FieldMapChunk {
+    type Error = FieldMapChunkIntoTableError;
+
+    fn dump(&self) -> Result<AnnotatedDump, Self::Error> {
+        const LABELS: [&str; 17] = [
+            "tile layer 0",
+            "tile layer 1",
+            "tile layer 2",
+            "palette 0",
+            "palette 1",
+            "palette 2",
+            "properties",
+            "unk7",
+            "unk8",
+            "unk9",
+            "unk10",
+            "unk11",
+            "unk12",
+            "unk13",
+            "unk14",
+            "unk15",
+            "unk16",
+        ];
+
+        Ok(self
+            .to_offset_table()?
+            .dump_with_chunk_labels(OffsetTableEntrySize::U32, |i| {
+                format!("chunk {} ({})", i, LABELS[i])
+            })?)
+    }
+}
+
+/// Annotates the header, blocks and commands of compressed `data`
+/// without decompressing it.
+pub fn dump_compressed(data: &[u8]) -> Result<AnnotatedDump, DecompressionError> {
+    let mut dump = AnnotatedDump::new(data.to_vec());
+    let mut src = Cursor::new(data);
+    let position = |src: &Cursor<&[u8]>| src.position() as usize;
+
+    let uncompressed_size = src.read_varint()?;
+    dump.annotate(
+        0..position(&src),
+        format!("uncompressed size: {}", uncompressed_size),
+    );
+    let start = position(&src);
+    let num_blocks = src.read_varint()? + 1;
+    dump.annotate(
+        start..position(&src),
+        format!("number of blocks: {}", num_blocks),
+    );
+
+    for block in 0..num_blocks {
+        let start = position(&src);
+        let block_size = src.read_u16::<LittleEndian>()?;
+        dump.annotate(
+            start..position(&src),
+            format!("block {}: size {}", block, block_size),
+        );
+
+        'block: for _ in 0..256 {
+            let start = position(&src);
+            let mut commands_byte = src.read_u8()?;
+            dump.annotate(start..position(&src), "commands");
+            for _ in 0..4 {
+                let start = position(&src);
+                let label = match CompressionCommand::try_from(commands_byte & 0x03)
+                    .map_err(|err| DecompressionError::InvalidCompressionCommand(err.number))?
+                {
+                    CompressionCommand::EndBlock => break 'block,
+                    CompressionCommand::Copy => {
+                        format!("copy {:#04X}", src.read_u8()?)
+                    }
+                    CompressionCommand::Lz77 => {
+                        let mut buf = [0u8; 2];
+                        src.read_exact(&mut buf)?;
+                        format!(
+                            "LZ77: offset {}, length {}",
+                            u16::from(buf[0]) | (u16::from(buf[1] & 0xF0) << 4),
+                            (buf[1] & 0x0F) + 2
+                        )
+                    }
+                    CompressionCommand::Rle => {
+                        let count = usize::from(src.read_u8()?) + 2;
+                        format!("RLE: {:#04X} x {}", src.read_u8()?, count)
+                    }
+                };
+                dump.annotate(start..position(&src), label);
+                commands_byte >>= 2;
+            }
+        }
+    }
+
+    if src.stream_position()? < data.len() as u64 {
+        dump.annotate(position(&src)..data.len(), "trailing data");
+    }
+    Ok(dump)
+}
@@ -0,0 +1,270 @@
+use std::{
+    fmt::{self, Display},
+    io::{self, Cursor, Read, Seek, SeekFrom, Write},
+    num::TryFromIntError,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use thiserror::Error;
+
+#[cfg(feature = "fs")]
+use crate::misc::{ProjectPaths, SaveOptions};
+use crate::{
+    consts::{
+        FEVENT_OFFSET_TABLE_LENGTH_ADDRESS, NUMBER_OF_FEVENT_CHUNKS_PER_MAP,
+        STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT, STANDARD_FILE_ALIGNMENT,
+    },
+    rom::NdsRom,
+    utils::{necessary_padding_for, write_zeros},
+    vfs::Vfs,
+};
+#[cfg(feature = "fs")]
+use std::io::BufWriter;
+
+/// The event data of all field maps, stored in `FEvent.dat`
+/// with its offset table in overlay 3.
+///
+/// Every map has [`NUMBER_OF_FEVENT_CHUNKS_PER_MAP`] consecutive chunks,
+/// the first of which contains its event scripts.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct FieldEvents {
+    pub chunks: Vec<Vec<u8>>,
+    pub padding: Vec<u8>,
+}
+
+/// The files which [`FieldEvents`] is stored in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FieldEventsFile {
+    FEvent,
+    Overlay3,
+}
+
+impl Display for FieldEventsFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::FEvent => "FEvent.dat",
+            Self::Overlay3 => "overlay 3",
+        })
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FieldEventsFromFilesError {
+    #[error("failed to read {file}")]
+    File {
+        file: FieldEventsFile,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to read chunk {index} of {file} (at offset {offset:#X})")]
+    Chunk {
+        file: FieldEventsFile,
+        index: usize,
+        offset: u32,
+        #[source]
+        source: io::Error,
+    },
+    #[error(
+        "offset {index} ({offset:#X}) is smaller than the previous one ({previous_offset:#X})"
+    )]
+    DecreasingOffset {
+        index: usize,
+        offset: u32,
+        previous_offset: u32,
+    },
+    #[error(transparent)]
+    TryFromInt(#[from] TryFromIntError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+#[derive(Error, Debug)]
+pub enum FieldEventsToFilesError {
+    #[error("chunk {index} of FEvent.dat ends past the largest possible offset ({limit:#X})", limit = u32::MAX)]
+    OffsetOverflow { index: usize },
+    #[error(transparent)]
+    TryFromInt(#[from] TryFromIntError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl FieldEvents {
+    #[inline]
+    pub fn num_maps(&self) -> usize {
+        self.chunks.len() / NUMBER_OF_FEVENT_CHUNKS_PER_MAP
+    }
+
+    /// Returns the chunks of the field map `map_index`.
+    pub fn map_chunks(&self, map_index: usize) -> Option<&[Vec<u8>]> {
+        self.chunks
+            .chunks_exact(NUMBER_OF_FEVENT_CHUNKS_PER_MAP)
+            .nth(map_index)
+    }
+    pub fn map_chunks_mut(&mut self, map_index: usize) -> Option<&mut [Vec<u8>]> {
+        self.chunks
+            .chunks_exact_mut(NUMBER_OF_FEVENT_CHUNKS_PER_MAP)
+            .nth(map_index)
+    }
+
+    /// Returns the event script chunk of the field map `map_index`.
+    #[inline]
+    pub fn map_script(&self, map_index: usize) -> Option<&Vec<u8>> {
+        self.map_chunks(map_index)?.first()
+    }
+    #[inline]
+    pub fn map_script_mut(&mut self, map_index: usize) -> Option<&mut Vec<u8>> {
+        self.map_chunks_mut(map_index)?.first_mut()
+    }
+
+    pub fn from_files(
+        mut fevent: impl Read,
+        mut overlay3: impl Read + Seek,
+    ) -> Result<Self, FieldEventsFromFilesError> {
+        let in_file = |file| move |source| FieldEventsFromFilesError::File { file, source };
+
+        let offset_table = (|| {
+            overlay3.seek(SeekFrom::Start(FEVENT_OFFSET_TABLE_LENGTH_ADDRESS))?;
+            let length = overlay3.read_u32::<LittleEndian>()?;
+            let mut buf = vec![0; (length / 4).saturating_sub(1) as usize];
+            overlay3.read_u32_into::<LittleEndian>(&mut buf)?;
+            Ok(buf)
+        })()
+        .map_err(in_file(FieldEventsFile::Overlay3))?;
+
+        Ok(Self {
+            chunks: offset_table
+                .windows(2)
+                .enumerate()
+                .map(|(index, offset_pair)| {
+                    let (current_offset, next_offset) = (offset_pair[0], offset_pair[1]);
+                    let size = next_offset.checked_sub(current_offset).ok_or(
+                        FieldEventsFromFilesError::DecreasingOffset {
+                            index: index + 1,
+                            offset: next_offset,
+                            previous_offset: current_offset,
+                        },
+                    )?;
+                    let mut buf = vec![0u8; size.try_into()?];
+                    fevent.read_exact(&mut buf).map_err(|source| {
+                        FieldEventsFromFilesError::Chunk {
+                            file: FieldEventsFile::FEvent,
+                            index,
+                            offset: current_offset,
+                            source,
+                        }
+                    })?;
+                    Ok(buf)
+                })
+                .collect::<Result<_, FieldEventsFromFilesError>>()?,
+            padding: {
+                let mut buf: Vec<u8> = Vec::new();
+                fevent
+                    .read_to_end(&mut buf)
+                    .map_err(in_file(FieldEventsFile::FEvent))?;
+                buf
+            },
+        })
+    }
+
+    /// The offset table in overlay 3 is overwritten in place,
+    /// so it mustn't grow beyond its original number of chunks.
+    pub fn to_files(
+        &self,
+        mut fevent: impl Write,
+        mut overlay3: impl Write + Seek,
+        align_files: bool,
+    ) -> Result<(), FieldEventsToFilesError> {
+        overlay3.seek(SeekFrom::Start(FEVENT_OFFSET_TABLE_LENGTH_ADDRESS))?;
+        overlay3.write_u32::<LittleEndian>((u32::try_from(self.chunks.len())? + 2) * 4)?;
+        let mut current_offset = 0u32;
+        overlay3.write_u32::<LittleEndian>(current_offset)?;
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            let padding =
+                necessary_padding_for(chunk.len(), STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT);
+            current_offset = u32::try_from(chunk.len() + padding)
+                .ok()
+                .and_then(|size| current_offset.checked_add(size))
+                .ok_or(FieldEventsToFilesError::OffsetOverflow { index })?;
+            fevent.write_all(chunk)?;
+            write_zeros(&mut fevent, padding)?;
+            overlay3.write_u32::<LittleEndian>(current_offset)?;
+        }
+        if align_files {
+            write_zeros(
+                &mut fevent,
+                necessary_padding_for(current_offset.try_into()?, STANDARD_FILE_ALIGNMENT),
+            )?;
+        } else {
+            fevent.write_all(&self.padding)?;
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    #[cfg(feature = "fs")]
+    pub fn load_from(paths: &ProjectPaths) -> Result<Self, FieldEventsFromFilesError> {
+        Self::load_from_vfs(paths)
+    }
+    pub fn load_from_vfs(vfs: &(impl Vfs + ?Sized)) -> Result<Self, FieldEventsFromFilesError> {
+        Self::from_files(
+            &vfs.read_file("FEvent/FEvent.dat")?[..],
+            Cursor::new(vfs.read_overlay(3)?),
+        )
+    }
+    /// Reads the files straight out of a ROM, instead of an extracted project.
+    #[inline]
+    pub fn load_from_rom(rom: &NdsRom) -> Result<Self, FieldEventsFromFilesError> {
+        Self::load_from_vfs(rom)
+    }
+    /// Replaces the files in a ROM, which can then be [serialized](NdsRom::to_bytes).
+    pub fn save_to_rom(
+        &self,
+        rom: &mut NdsRom,
+        align_files: bool,
+    ) -> Result<(), FieldEventsToFilesError> {
+        let mut fevent = Vec::new();
+        let mut overlay3 = Cursor::new(rom.overlay(3)?.to_vec());
+        self.to_files(&mut fevent, &mut overlay3, align_files)?;
+        rom.replace_file("FEvent/FEvent.dat", fevent)?;
+        rom.replace_overlay(3, overlay3.into_inner())?;
+        Ok(())
+    }
+    #[cfg(feature = "fs")]
+    pub fn save_to(
+        &self,
+        paths: &ProjectPaths,
+        align_files: bool,
+        options: &SaveOptions,
+    ) -> Result<(), FieldEventsToFilesError> {
+        let (pending, [fevent], [overlay3]) = options.open_files(
+            [&paths.data_path("FEvent/FEvent.dat")],
+            [&paths.overlay_path(3)],
+        )?;
+        // The offset table is written one entry at a time.
+        let [mut fevent, mut overlay3] = [fevent, overlay3].map(BufWriter::new);
+        self.to_files(&mut fevent, &mut overlay3, align_files)?;
+        for mut file in [fevent, overlay3] {
+            file.flush()?;
+        }
+        pending.commit()?;
+        Ok(())
+    }
+
+    #[inline]
+    #[cfg(feature = "fs")]
+    pub fn load_from_filesystem_standard() -> Result<Self, FieldEventsFromFilesError> {
+        Self::load_from(&ProjectPaths::default())
+    }
+    #[inline]
+    #[cfg(feature = "fs")]
+    pub fn save_to_filesystem_standard(
+        &self,
+        align_files: bool,
+    ) -> Result<(), FieldEventsToFilesError> {
+        self.save_to(
+            &ProjectPaths::default(),
+            align_files,
+            &SaveOptions::default(),
+        )
+    }
+}
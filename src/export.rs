@@ -0,0 +1,404 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use grid::Grid;
+use rgb::Rgba;
+
+use crate::{
+    consts::{TILE_HEIGHT, TILE_WIDTH},
+    map::{
+        palette_index, FieldMapChunk, FieldMapRenderError, FieldMaps, FieldMapsRenderError,
+        PixelSize, Tileset,
+    },
+    misc::PaletteLut,
+    patch::crc32,
+};
+
+/// A field map prepared for game engines and web viewers,
+/// which is written as JSON by [`Self::to_json`].
+///
+/// Unlike the text representation of [`FieldMapChunk`], this is lossy:
+/// it only contains what's needed to draw the map, with the palettes
+/// already applied to the tilesets. Collision isn't included yet,
+/// since its format hasn't been decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapExport {
+    /// In tiles.
+    pub width: usize,
+    /// In tiles.
+    pub height: usize,
+    pub tilesets: Vec<ExportTileset>,
+    /// From bottom to top.
+    pub layers: Vec<ExportLayer>,
+}
+
+/// The distinct tiles of a layer, rendered into a single image
+/// with [`Self::columns`] tiles per row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportTileset {
+    pub image: Grid<Rgba<u8>>,
+    pub columns: usize,
+    pub tile_count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportLayer {
+    /// The index into [`FieldMapChunk::tile_layers`].
+    pub source_layer: usize,
+    /// The index into [`MapExport::tilesets`].
+    pub tileset: usize,
+    /// Row by row, with `None` for tiles that aren't in the tileset.
+    pub tiles: Vec<Option<ExportTile>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExportTile {
+    /// The index into the [`ExportTileset`] of the layer.
+    pub id: usize,
+    pub flipped_horizontally: bool,
+    pub flipped_vertically: bool,
+}
+
+impl MapExport {
+    /// Layers without a tileset or palette are left out.
+    pub fn new(
+        map_chunk: &FieldMapChunk,
+        tilesets: [Option<&Tileset>; 3],
+        columns: usize,
+    ) -> Result<Self, FieldMapRenderError> {
+        let luts = map_chunk
+            .palettes
+            .each_ref()
+            .map(|x| x.as_ref().map(PaletteLut::from));
+        Self::with_luts(
+            map_chunk,
+            tilesets,
+            luts.each_ref().map(Option::as_ref),
+            columns,
+        )
+    }
+    /// Like [`Self::new`], but with the colors of each layer taken from `luts`
+    /// instead of the palettes of `map_chunk`, like [`FieldMapChunk::render_with_luts`].
+    pub fn with_luts(
+        map_chunk: &FieldMapChunk,
+        tilesets: [Option<&Tileset>; 3],
+        luts: [Option<&PaletteLut>; 3],
+        columns: usize,
+    ) -> Result<Self, FieldMapRenderError> {
+        let columns = columns.max(1);
+        let pixel_sizes = map_chunk
+            .properties
+            .tilesets_properties
+            .tileset_pixel_sizes();
+        let mut export = Self {
+            width: map_chunk.properties.width.into(),
+            height: map_chunk.properties.height.into(),
+            tilesets: Vec::new(),
+            layers: Vec::new(),
+        };
+        for layer in (0..3).rev() {
+            let (Some(tile_layer), Some(lut), Some(tileset)) =
+                (&map_chunk.tile_layers[layer], luts[layer], tilesets[layer])
+            else {
+                continue;
+            };
+
+            // Nibble tiles are rendered once for each palette offset they're used with.
+            let mut ids: BTreeMap<(u16, u8), usize> = BTreeMap::new();
+            let mut sources = Vec::new();
+            let tiles = tile_layer
+                .iter()
+                .map(|tile| {
+                    let tile_id = tile.tileset_tile_id();
+                    tileset.0.get(usize::from(tile_id))?;
+                    let palette_offset = match pixel_sizes[layer] {
+                        PixelSize::Nibble => tile.palette_offset(),
+                        PixelSize::Byte => 0,
+                    };
+                    let id = *ids.entry((tile_id, palette_offset)).or_insert_with(|| {
+                        sources.push((tile_id, palette_offset));
+                        sources.len() - 1
+                    });
+                    Some(ExportTile {
+                        id,
+                        flipped_horizontally: tile.flipped_horizontally(),
+                        flipped_vertically: tile.flipped_vertically(),
+                    })
+                })
+                .collect();
+
+            let mut image = Grid::init(
+                sources.len().div_ceil(columns) * TILE_HEIGHT,
+                columns * TILE_WIDTH,
+                Rgba::new(0, 0, 0, 0),
+            );
+            for (id, &(tile_id, palette_offset)) in sources.iter().enumerate() {
+                let tileset_tile = &tileset.0[usize::from(tile_id)];
+                for (offset, &pixel) in tileset_tile.0.iter().enumerate() {
+                    let Some(index) = palette_index(pixel, palette_offset, pixel_sizes[layer])
+                    else {
+                        continue;
+                    };
+                    let Some(color) = lut.get(index) else {
+                        return Err(FieldMapRenderError::ColorNotInPalette { layer, index });
+                    };
+                    image[(
+                        id / columns * TILE_HEIGHT + offset / TILE_WIDTH,
+                        id % columns * TILE_WIDTH + offset % TILE_WIDTH,
+                    )] = color;
+                }
+            }
+
+            export.layers.push(ExportLayer {
+                source_layer: layer,
+                tileset: export.tilesets.len(),
+                tiles,
+            });
+            export.tilesets.push(ExportTileset {
+                image,
+                columns,
+                tile_count: sources.len(),
+            });
+        }
+        Ok(export)
+    }
+
+    /// Writes the map as JSON, where `tileset_image` returns the reference
+    /// to the image of each tileset, such as a file name:
+    ///
+    /// ```json
+    /// {
+    ///   "format": "mnllib-map",
+    ///   "version": 1,
+    ///   "tile_width": 8,
+    ///   "tile_height": 8,
+    ///   "width": 2,
+    ///   "height": 1,
+    ///   "tilesets": [
+    ///     {"image": "tileset0.png", "columns": 16, "tile_count": 1}
+    ///   ],
+    ///   "layers": [
+    ///     {"source_layer": 0, "tileset": 0, "tiles": [
+    ///       {"id": 0, "flip_x": false, "flip_y": true}, null
+    ///     ]}
+    ///   ]
+    /// }
+    /// ```
+    ///
+    /// Layers are listed from bottom to top, and their tiles row by row.
+    /// Tiles which are `null` aren't drawn.
+    pub fn to_json(&self, mut tileset_image: impl FnMut(usize) -> String) -> String {
+        let mut out = String::new();
+        out.push_str("{\n  \"format\": \"mnllib-map\",\n  \"version\": 1,\n");
+        writeln!(out, "  \"tile_width\": {TILE_WIDTH},").unwrap();
+        writeln!(out, "  \"tile_height\": {TILE_HEIGHT},").unwrap();
+        writeln!(out, "  \"width\": {},", self.width).unwrap();
+        writeln!(out, "  \"height\": {},", self.height).unwrap();
+
+        out.push_str("  \"tilesets\": [");
+        for (index, tileset) in self.tilesets.iter().enumerate() {
+            out.push_str(if index == 0 { "\n" } else { ",\n" });
+            write!(
+                out,
+                "    {{\"image\": {}, \"columns\": {}, \"tile_count\": {}}}",
+                json_string(&tileset_image(index)),
+                tileset.columns,
+                tileset.tile_count
+            )
+            .unwrap();
+        }
+        out.push_str(if self.tilesets.is_empty() {
+            "],\n"
+        } else {
+            "\n  ],\n"
+        });
+
+        out.push_str("  \"layers\": [");
+        for (index, layer) in self.layers.iter().enumerate() {
+            out.push_str(if index == 0 { "\n" } else { ",\n" });
+            write!(
+                out,
+                "    {{\"source_layer\": {}, \"tileset\": {}, \"tiles\": [",
+                layer.source_layer, layer.tileset
+            )
+            .unwrap();
+            // One row per line.
+            for (tile_index, tile) in layer.tiles.iter().enumerate() {
+                out.push_str(match tile_index {
+                    0 => "\n      ",
+                    _ if tile_index.is_multiple_of(self.width.max(1)) => ",\n      ",
+                    _ => ", ",
+                });
+                match tile {
+                    Some(tile) => write!(
+                        out,
+                        "{{\"id\": {}, \"flip_x\": {}, \"flip_y\": {}}}",
+                        tile.id, tile.flipped_horizontally, tile.flipped_vertically
+                    )
+                    .unwrap(),
+                    None => out.push_str("null"),
+                }
+            }
+            out.push_str(if layer.tiles.is_empty() {
+                "]}"
+            } else {
+                "\n    ]}"
+            });
+        }
+        out.push_str(if self.layers.is_empty() {
+            "]\n}\n"
+        } else {
+            "\n  ]\n}\n"
+        });
+        out
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c < ' ' => write!(out, "\\u{:04x}", u32::from(c)).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// What a rendered field map image shows, so that it can be traced back to its source.
+///
+/// `map_image_to_png`, which needs the `png` feature, embeds it in `tEXt` chunks
+/// with keywords starting with `mnllib:`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MapImageMetadata {
+    pub map_index: usize,
+    /// Which tile layers were drawn.
+    pub layers: [bool; 3],
+    pub mnllib_version: String,
+    /// Of the uncompressed map chunk.
+    pub map_chunk_crc32: u32,
+    /// Of the uncompressed tileset chunks.
+    pub tileset_crc32s: [Option<u32>; 3],
+}
+
+impl MapImageMetadata {
+    const KEYWORD_PREFIX: &'static str = "mnllib:";
+
+    /// Keywords and text, for PNG `tEXt` chunks. Missing tilesets are written as `-`.
+    pub fn to_text(&self) -> Vec<(String, String)> {
+        [
+            ("map_index", self.map_index.to_string()),
+            (
+                "layers",
+                self.layers.map(|x| u8::from(x).to_string()).join(","),
+            ),
+            ("version", self.mnllib_version.clone()),
+            ("map_chunk_crc32", format!("{:08X}", self.map_chunk_crc32)),
+            (
+                "tileset_crc32s",
+                self.tileset_crc32s
+                    .map(|x| x.map_or_else(|| "-".to_owned(), |x| format!("{x:08X}")))
+                    .join(","),
+            ),
+        ]
+        .into_iter()
+        .map(|(keyword, text)| (format!("{}{keyword}", Self::KEYWORD_PREFIX), text))
+        .collect()
+    }
+
+    /// The inverse of [`Self::to_text`], ignoring other keywords.
+    /// Returns `None` if any field is missing or invalid.
+    pub fn from_text(text: &[(String, String)]) -> Option<Self> {
+        let field = |name: &str| {
+            text.iter()
+                .find(|(keyword, _)| keyword.strip_prefix(Self::KEYWORD_PREFIX) == Some(name))
+                .map(|(_, text)| text.as_str())
+        };
+        let list = |name: &str| -> Option<[&str; 3]> {
+            field(name)?.split(',').collect::<Vec<_>>().try_into().ok()
+        };
+        let mut layers = [false; 3];
+        for (layer, text) in layers.iter_mut().zip(list("layers")?) {
+            *layer = match text {
+                "0" => false,
+                "1" => true,
+                _ => return None,
+            };
+        }
+        let mut tileset_crc32s = [None; 3];
+        for (crc, text) in tileset_crc32s.iter_mut().zip(list("tileset_crc32s")?) {
+            if text != "-" {
+                *crc = Some(u32::from_str_radix(text, 16).ok()?);
+            }
+        }
+        Some(Self {
+            map_index: field("map_index")?.parse().ok()?,
+            layers,
+            mnllib_version: field("version")?.to_owned(),
+            map_chunk_crc32: u32::from_str_radix(field("map_chunk_crc32")?, 16).ok()?,
+            tileset_crc32s,
+        })
+    }
+}
+
+/// Renders map `map_index` like [`FieldMaps::render_map`],
+/// but only with the tile layers which are `true` in `layers`.
+pub fn render_map_layers(
+    field_maps: &FieldMaps,
+    map_index: usize,
+    layers: [bool; 3],
+) -> Result<(Grid<Rgba<u8>>, MapImageMetadata), FieldMapsRenderError> {
+    let (map_chunk, mut tilesets) = field_maps.decode_map(map_index)?;
+    for (tileset, enabled) in tilesets.iter_mut().zip(layers) {
+        if !enabled {
+            *tileset = None;
+        }
+    }
+    let image = map_chunk.render(tilesets.each_ref().map(Option::as_ref))?;
+
+    let map = &field_maps.maps[map_index];
+    let chunk_crc32 = |index: usize| {
+        field_maps.fmapdata_chunks[index]
+            .to_uncompressed(true)
+            .map(|x| crc32(&x))
+            .map_err(|source| FieldMapsRenderError::ChunkDecompression { index, source })
+    };
+    let mut tileset_crc32s = [None; 3];
+    for (crc, index) in tileset_crc32s.iter_mut().zip(map.tileset_indexes) {
+        if let Some(index) = index {
+            *crc = Some(chunk_crc32(index)?);
+        }
+    }
+    Ok((
+        image,
+        MapImageMetadata {
+            map_index,
+            layers,
+            mnllib_version: env!("CARGO_PKG_VERSION").to_owned(),
+            map_chunk_crc32: chunk_crc32(map.map_chunk_index)?,
+            tileset_crc32s,
+        },
+    ))
+}
+
+/// Encodes a rendered map as a PNG file with `metadata` embedded.
+#[cfg(feature = "png")]
+pub fn map_image_to_png(
+    image: &Grid<Rgba<u8>>,
+    metadata: &MapImageMetadata,
+    out: impl std::io::Write,
+) -> Result<(), png::EncodingError> {
+    crate::sprites::image_to_png_with_text(image, &metadata.to_text(), out)
+}
+
+/// Reads the metadata embedded by [`map_image_to_png`],
+/// or `None` if the PNG file doesn't have it.
+#[cfg(feature = "png")]
+pub fn map_image_metadata_from_png(
+    inp: impl std::io::Read,
+) -> Result<Option<MapImageMetadata>, png::DecodingError> {
+    Ok(MapImageMetadata::from_text(&crate::sprites::png_text(inp)?))
+}
@@ -0,0 +1,197 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+};
+
+use derive_more::derive::{From, Into};
+#[cfg(feature = "toml")]
+use thiserror::Error;
+
+use crate::script::{CommandTable, Instruction, Script};
+
+/// Identifies a flag, as given to the commands of event scripts
+/// which test, set or clear it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, From, Into)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct FlagId(pub u16);
+
+impl Display for FlagId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "flag_{:#06X}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum FlagKind {
+    #[default]
+    Unknown,
+    /// Story progress.
+    Story,
+    /// Whether a treasure has been collected.
+    Treasure,
+    /// Anything else, such as whether a tutorial has been shown.
+    Misc,
+}
+
+/// What's known about a flag.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlagInfo {
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub name: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub kind: FlagKind,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub description: Option<String>,
+}
+
+/// The known meanings of flags.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FlagDatabase(pub BTreeMap<FlagId, FlagInfo>);
+
+#[cfg(feature = "toml")]
+#[derive(Error, Debug)]
+pub enum FlagDatabaseFromTomlError {
+    #[error("invalid flag ID `{0}`")]
+    InvalidFlagId(String),
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+}
+
+#[cfg(feature = "toml")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct FlagDatabaseToml {
+    flags: BTreeMap<String, FlagInfo>,
+}
+
+impl FlagDatabase {
+    /// Parses flags from TOML like this,
+    /// where the flag IDs are hexadecimal (with a `0x` prefix) or decimal:
+    ///
+    /// ```toml
+    /// [flags.0x0010]
+    /// name = "met_starlow"
+    /// kind = "story"
+    /// ```
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> Result<Self, FlagDatabaseFromTomlError> {
+        let file: FlagDatabaseToml = toml::from_str(toml)?;
+        Ok(Self(
+            file.flags
+                .into_iter()
+                .map(|(flag, info)| {
+                    let parsed = match flag.strip_prefix("0x") {
+                        Some(hex) => u16::from_str_radix(hex, 16),
+                        None => flag.parse(),
+                    };
+                    Ok((
+                        FlagId(parsed.map_err(|_| FlagDatabaseFromTomlError::InvalidFlagId(flag))?),
+                        info,
+                    ))
+                })
+                .collect::<Result<_, FlagDatabaseFromTomlError>>()?,
+        ))
+    }
+    /// Serializes the database in the format accepted by [`Self::from_toml`].
+    #[cfg(feature = "toml")]
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(&FlagDatabaseToml {
+            flags: self
+                .0
+                .iter()
+                .map(|(flag, info)| (format!("0x{:04X}", flag.0), info.clone()))
+                .collect(),
+        })
+    }
+
+    pub fn insert(&mut self, flag: FlagId, info: FlagInfo) {
+        self.0.insert(flag, info);
+    }
+    pub fn get(&self, flag: FlagId) -> Option<&FlagInfo> {
+        self.0.get(&flag)
+    }
+    /// Returns the flag's name, if it's known.
+    pub fn name(&self, flag: FlagId) -> Option<&str> {
+        self.get(flag)?.name.as_deref()
+    }
+    /// Looks up a flag by its name.
+    pub fn find(&self, name: &str) -> Option<FlagId> {
+        self.0
+            .iter()
+            .find(|(_, info)| info.name.as_deref() == Some(name))
+            .map(|(&flag, _)| flag)
+    }
+}
+
+/// Something which reads or writes a flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FlagReference {
+    /// A command with a flag operand, per [`CommandInfo::flag_operands`](crate::script::CommandInfo::flag_operands).
+    Script {
+        /// Supplied by the caller, e.g. the map index of an event script.
+        script: usize,
+        instruction: usize,
+        opcode: u16,
+    },
+}
+
+/// All known references to each flag, for answering questions like
+/// "which scripts set this flag?"
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FlagReferences(pub BTreeMap<FlagId, Vec<FlagReference>>);
+
+impl FlagReferences {
+    fn add(&mut self, flag: FlagId, reference: FlagReference) {
+        self.0.entry(flag).or_default().push(reference);
+    }
+
+    /// Adds the flags given to the commands of `script`
+    /// in the operands listed by their [`CommandInfo::flag_operands`](crate::script::CommandInfo::flag_operands),
+    /// with `script_index` identifying the script in the references.
+    pub fn add_script(&mut self, script_index: usize, script: &Script, table: &CommandTable) {
+        for (index, instruction) in script.instructions.iter().enumerate() {
+            let Instruction::Command(command) = instruction else {
+                continue;
+            };
+            let Some(info) = table.get(command.opcode) else {
+                continue;
+            };
+            for &operand_index in &info.flag_operands {
+                let flag = command
+                    .operands
+                    .get(operand_index)
+                    .and_then(|x| x.as_unsigned())
+                    .and_then(|x| x.try_into().ok());
+                if let Some(flag) = flag {
+                    self.add(
+                        FlagId(flag),
+                        FlagReference::Script {
+                            script: script_index,
+                            instruction: index,
+                            opcode: command.opcode,
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, flag: FlagId) -> &[FlagReference] {
+        self.0.get(&flag).map_or(&[], Vec::as_slice)
+    }
+}
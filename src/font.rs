@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+
+use grid::Grid;
+use rgb::Rgba;
+use thiserror::Error;
+
+use crate::{
+    consts::{TILE_AREA, TILE_HEIGHT, TILE_WIDTH},
+    map::{Tileset, TilesetTile},
+    misc::{Palette, PaletteLut},
+    text::{CharacterTable, GlyphWidths, CONTROL_CODE_PREFIX, MESSAGE_TERMINATOR},
+};
+
+/// A glyph of a [`Font`], whose pixels are palette indexes,
+/// with 0 being transparent.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Glyph {
+    /// How far the next glyph is drawn to the right, in pixels.
+    pub width: u8,
+    pub pixels: Grid<u8>,
+}
+
+/// A font whose glyphs are indexed by the single-byte character codes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Font {
+    /// The size of every glyph's cell, in tiles.
+    pub cell_size: (usize, usize),
+    pub glyphs: Vec<Glyph>,
+}
+
+#[derive(Error, Debug)]
+pub enum FontFromTilesetError {
+    #[error("the glyph cells ({cell_size:?} tiles) are empty")]
+    EmptyCell { cell_size: (usize, usize) },
+    #[error(
+        "the number of tiles ({tiles}) isn't a multiple of the tiles per glyph ({tiles_per_glyph})"
+    )]
+    IncompleteGlyph {
+        tiles: usize,
+        tiles_per_glyph: usize,
+    },
+    #[error("the number of glyphs ({glyphs}) doesn't match the number of widths ({widths})")]
+    WidthCountMismatch { glyphs: usize, widths: usize },
+}
+
+#[derive(Error, Debug)]
+pub enum AddGlyphError {
+    #[error("the font can't have more than {max} glyphs", max = Font::MAX_GLYPHS)]
+    TooManyGlyphs,
+    #[error("glyph {index} is {actual:?} pixels large, but the cells are {expected:?}")]
+    WrongSize {
+        index: usize,
+        expected: (usize, usize),
+        actual: (usize, usize),
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum RenderError {
+    #[error("there's no glyph for the code {code:#04X} at offset {offset:#X}")]
+    MissingGlyph { offset: usize, code: u8 },
+    #[error("the palette has no color {index} (used at offset {offset:#X})")]
+    ColorNotInPalette { offset: usize, index: usize },
+    #[error("the line break code is empty")]
+    EmptyLineBreak,
+}
+
+impl Font {
+    /// Every single-byte code except [`CONTROL_CODE_PREFIX`] can have a glyph.
+    pub const MAX_GLYPHS: usize = CONTROL_CODE_PREFIX as usize;
+
+    /// The glyphs are stored one after another in `tileset`,
+    /// each one being `cell_size.0 * cell_size.1` tiles in row-major order,
+    /// and their widths are stored in `widths`.
+    pub fn from_tileset(
+        tileset: &Tileset,
+        widths: &[u8],
+        cell_size: (usize, usize),
+    ) -> Result<Self, FontFromTilesetError> {
+        let tiles_per_glyph = cell_size.0 * cell_size.1;
+        if tiles_per_glyph == 0 {
+            return Err(FontFromTilesetError::EmptyCell { cell_size });
+        }
+        if !tileset.0.len().is_multiple_of(tiles_per_glyph) {
+            return Err(FontFromTilesetError::IncompleteGlyph {
+                tiles: tileset.0.len(),
+                tiles_per_glyph,
+            });
+        }
+        let num_glyphs = tileset.0.len() / tiles_per_glyph;
+        if num_glyphs != widths.len() {
+            return Err(FontFromTilesetError::WidthCountMismatch {
+                glyphs: num_glyphs,
+                widths: widths.len(),
+            });
+        }
+
+        Ok(Self {
+            cell_size,
+            glyphs: tileset
+                .0
+                .chunks_exact(tiles_per_glyph)
+                .zip(widths)
+                .map(|(tiles, &width)| {
+                    let mut pixels = Grid::new(cell_size.1 * TILE_HEIGHT, cell_size.0 * TILE_WIDTH);
+                    for (i, tile) in tiles.iter().enumerate() {
+                        let (tile_x, tile_y) = (i % cell_size.0, i / cell_size.0);
+                        for (j, &pixel) in tile.0.iter().enumerate() {
+                            pixels[(
+                                tile_y * TILE_HEIGHT + j / TILE_WIDTH,
+                                tile_x * TILE_WIDTH + j % TILE_WIDTH,
+                            )] = pixel;
+                        }
+                    }
+                    Glyph { width, pixels }
+                })
+                .collect(),
+        })
+    }
+
+    /// The inverse of [`Self::from_tileset`].
+    /// Glyph pixels outside of the cell are ignored.
+    pub fn to_tileset(&self) -> (Tileset, Vec<u8>) {
+        let mut tiles = Vec::with_capacity(self.glyphs.len() * self.cell_size.0 * self.cell_size.1);
+        for glyph in &self.glyphs {
+            for tile_y in 0..self.cell_size.1 {
+                for tile_x in 0..self.cell_size.0 {
+                    let mut tile = [0u8; TILE_AREA];
+                    for (j, pixel) in tile.iter_mut().enumerate() {
+                        *pixel = glyph
+                            .pixels
+                            .get(
+                                tile_y * TILE_HEIGHT + j / TILE_WIDTH,
+                                tile_x * TILE_WIDTH + j % TILE_WIDTH,
+                            )
+                            .copied()
+                            .unwrap_or_default();
+                    }
+                    tiles.push(TilesetTile(tile));
+                }
+            }
+        }
+        (
+            Tileset(tiles),
+            self.glyphs.iter().map(|x| x.width).collect(),
+        )
+    }
+
+    #[inline]
+    pub fn cell_width(&self) -> usize {
+        self.cell_size.0 * TILE_WIDTH
+    }
+    #[inline]
+    pub fn cell_height(&self) -> usize {
+        self.cell_size.1 * TILE_HEIGHT
+    }
+
+    #[inline]
+    pub fn glyph(&self, code: u8) -> Option<&Glyph> {
+        self.glyphs.get(usize::from(code))
+    }
+
+    /// Appends `glyphs` to the font and maps their codes to the accompanying text
+    /// in `table`, returning the code of the first one.
+    ///
+    /// Nothing is changed if any of the glyphs can't be added.
+    pub fn add_glyphs<S: Into<String>>(
+        &mut self,
+        glyphs: impl IntoIterator<Item = (Glyph, S)>,
+        table: &mut CharacterTable,
+    ) -> Result<u8, AddGlyphError> {
+        let glyphs: Vec<_> = glyphs.into_iter().collect();
+        if self.glyphs.len() + glyphs.len() > Self::MAX_GLYPHS {
+            return Err(AddGlyphError::TooManyGlyphs);
+        }
+        let expected = (self.cell_width(), self.cell_height());
+        for (i, (glyph, _)) in glyphs.iter().enumerate() {
+            let actual = (glyph.pixels.cols(), glyph.pixels.rows());
+            if actual != expected {
+                return Err(AddGlyphError::WrongSize {
+                    index: i,
+                    expected,
+                    actual,
+                });
+            }
+        }
+
+        // The check above guarantees that the codes fit into a `u8`.
+        let first_code = self.glyphs.len() as u8;
+        for (glyph, text) in glyphs {
+            table.insert([self.glyphs.len() as u8], text);
+            self.glyphs.push(glyph);
+        }
+        Ok(first_code)
+    }
+
+    /// Renders the encoded message `data` the way the game draws it into a textbox,
+    /// stopping at [`MESSAGE_TERMINATOR`].
+    ///
+    /// Pixels without a glyph are transparent.
+    pub fn render(
+        &self,
+        data: &[u8],
+        palette: &Palette,
+        options: &RenderOptions,
+    ) -> Result<Grid<Rgba<u8>>, RenderError> {
+        self.render_with_lut(data, &PaletteLut::from(palette), options)
+    }
+    /// Like [`Self::render`], but with a [`PaletteLut`] made beforehand,
+    /// so that rendering many messages doesn't convert the palette every time.
+    pub fn render_with_lut(
+        &self,
+        data: &[u8],
+        lut: &PaletteLut,
+        options: &RenderOptions,
+    ) -> Result<Grid<Rgba<u8>>, RenderError> {
+        if options.line_break.is_empty() {
+            return Err(RenderError::EmptyLineBreak);
+        }
+        // (x, line, offset in `data`, code, palette offset)
+        let mut placements: Vec<(usize, usize, usize, u8, usize)> = Vec::new();
+        let (mut x, mut line, mut width) = (0usize, 0usize, 0usize);
+        let mut palette_offset = options.default_palette_offset;
+        let mut offset = 0;
+        while offset < data.len() {
+            let rest = &data[offset..];
+            if rest.starts_with(&MESSAGE_TERMINATOR) {
+                break;
+            }
+            if rest.starts_with(&options.line_break) {
+                (x, line) = (0, line + 1);
+                offset += options.line_break.len();
+                continue;
+            }
+            if rest[0] == CONTROL_CODE_PREFIX {
+                let Some(&command) = rest.get(1) else {
+                    break;
+                };
+                if let Some(&new_palette_offset) = options.color_codes.get(&command) {
+                    palette_offset = new_palette_offset;
+                }
+                offset += 2 + options
+                    .control_code_arguments
+                    .get(&command)
+                    .copied()
+                    .unwrap_or_default();
+                continue;
+            }
+
+            let glyph = self.glyph(rest[0]).ok_or(RenderError::MissingGlyph {
+                offset,
+                code: rest[0],
+            })?;
+            placements.push((x, line, offset, rest[0], palette_offset));
+            x += usize::from(glyph.width);
+            width = width.max(x);
+            offset += 1;
+        }
+
+        let line_height = options.line_height.unwrap_or(self.cell_height());
+        let mut image = Grid::init(
+            (line + 1) * line_height,
+            options.width.unwrap_or(width),
+            Rgba::new(0, 0, 0, 0),
+        );
+        for (x, line, offset, code, palette_offset) in placements {
+            let glyph = self.glyph(code).unwrap();
+            for ((row, col), &pixel) in glyph.pixels.indexed_iter() {
+                if pixel == 0 {
+                    continue;
+                }
+                let index = palette_offset + usize::from(pixel);
+                let Some(color) = lut.get(index) else {
+                    return Err(RenderError::ColorNotInPalette { offset, index });
+                };
+                if let Some(target) = image.get_mut(line * line_height + row, x + col) {
+                    *target = color;
+                }
+            }
+        }
+        Ok(image)
+    }
+}
+
+impl GlyphWidths for Font {
+    /// Codes which aren't a single byte, such as control codes, have no width.
+    fn glyph_width(&self, code: &[u8]) -> u32 {
+        match code {
+            &[code] => self.glyph(code).map_or(0, |x| x.width.into()),
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// The width of the image, or `None` to fit the widest line.
+    pub width: Option<usize>,
+    /// The distance between lines, or `None` to use the cell height.
+    pub line_height: Option<usize>,
+    pub line_break: Vec<u8>,
+    /// The number of argument bytes of each control code, by its second byte.
+    /// Other control codes have no arguments.
+    pub control_code_arguments: HashMap<u8, usize>,
+    /// The palette offset which each color control code switches to, by its second byte.
+    pub color_codes: HashMap<u8, usize>,
+    pub default_palette_offset: usize,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            width: None,
+            line_height: None,
+            line_break: vec![CONTROL_CODE_PREFIX, 0x00],
+            control_code_arguments: HashMap::new(),
+            color_codes: HashMap::new(),
+            default_palette_offset: 0,
+        }
+    }
+}
@@ -0,0 +1,605 @@
+//! [Kaitai Struct](https://kaitai.io) definitions (`.ksy`) of the formats which the crate parses,
+//! e.g. for inspecting files in the Kaitai Web IDE.
+//!
+//! They're generated from the same [`StructLayout`]s and constants as the parsers,
+//! so they describe exactly what the crate reads.
+
+use std::fmt::{self, Display, Write};
+
+use crate::{
+    layout::{Endianness, FieldType, HasLayout, StructLayout, Trailing, LAYOUTS},
+    map::FieldMapProperties,
+    misc::{OffsetTableEntrySize, VARINT_MAX_EXTRA_BYTES},
+    CompressionCommand,
+};
+
+/// The subset of YAML which `.ksy` files are made of.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Yaml {
+    Scalar(String),
+    List(Vec<Yaml>),
+    Map(Vec<(String, Yaml)>),
+}
+
+fn scalar(value: impl ToString) -> Yaml {
+    Yaml::Scalar(value.to_string())
+}
+fn map<const N: usize>(entries: [(&str, Yaml); N]) -> Yaml {
+    Yaml::Map(entries.map(|(k, v)| (k.to_owned(), v)).into())
+}
+
+impl Yaml {
+    /// Quotes `value` if it would otherwise not be read back as the same string.
+    fn fmt_scalar(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+        let needs_quotes = value.is_empty()
+            || value.starts_with(|x: char| "-?:,[]{}#&*!|>'\"%@` ".contains(x))
+            || value.ends_with([' ', ':'])
+            || value.contains(": ")
+            || value.contains(" #");
+        if needs_quotes {
+            write!(f, "'{}'", value.replace('\'', "''"))
+        } else {
+            f.write_str(value)
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        match self {
+            Self::Scalar(value) => {
+                Self::fmt_scalar(f, value)?;
+                f.write_char('\n')
+            }
+            Self::List(items) => {
+                for item in items {
+                    write!(f, "{:indent$}- ", "")?;
+                    match item {
+                        // The first entry goes on the same line as the `-`.
+                        Self::Map(entries) if !entries.is_empty() => {
+                            Self::fmt_entry(f, &entries[0], indent + 2)?;
+                            for entry in &entries[1..] {
+                                write!(f, "{:width$}", "", width = indent + 2)?;
+                                Self::fmt_entry(f, entry, indent + 2)?;
+                            }
+                        }
+                        item => item.fmt_indented(f, indent + 2)?,
+                    }
+                }
+                Ok(())
+            }
+            Self::Map(entries) => {
+                for entry in entries {
+                    write!(f, "{:indent$}", "")?;
+                    Self::fmt_entry(f, entry, indent)?;
+                }
+                Ok(())
+            }
+        }
+    }
+    fn fmt_entry(
+        f: &mut fmt::Formatter<'_>,
+        (key, value): &(String, Yaml),
+        indent: usize,
+    ) -> fmt::Result {
+        Self::fmt_scalar(f, key)?;
+        f.write_char(':')?;
+        match value {
+            Self::Scalar(_) => {
+                f.write_char(' ')?;
+                value.fmt_indented(f, indent)
+            }
+            Self::List(items) if items.is_empty() => f.write_str(" []\n"),
+            Self::Map(entries) if entries.is_empty() => f.write_str(" {}\n"),
+            _ => {
+                f.write_char('\n')?;
+                value.fmt_indented(f, indent + 2)
+            }
+        }
+    }
+}
+
+impl Display for Yaml {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+/// E.g. `FieldMapProperties` to `field_map_properties`, as Kaitai Struct requires.
+fn snake_case(name: &str) -> String {
+    let mut result = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i != 0 {
+                result.push('_');
+            }
+            result.push(c.to_ascii_lowercase());
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+fn attribute<const N: usize>(id: &str, keys: [(&str, Yaml); N]) -> Yaml {
+    let mut entries = vec![("id".to_owned(), scalar(id))];
+    entries.extend(keys.map(|(k, v)| (k.to_owned(), v)));
+    Yaml::Map(entries)
+}
+
+/// A whole `.ksy` file, whose top-level type is made of `body` and which uses `types`.
+fn ksy(
+    id: &str,
+    endianness: Endianness,
+    doc: &str,
+    body: Yaml,
+    types: Vec<(String, Yaml)>,
+) -> String {
+    let Yaml::Map(mut entries) = map([
+        (
+            "meta",
+            map([
+                ("id", scalar(id)),
+                (
+                    "endian",
+                    scalar(match endianness {
+                        Endianness::Little => "le",
+                        Endianness::Big => "be",
+                    }),
+                ),
+                ("bit-endian", scalar("le")),
+            ]),
+        ),
+        ("doc", scalar(doc)),
+    ]) else {
+        unreachable!()
+    };
+    if let Yaml::Map(body) = body {
+        entries.extend(body);
+    }
+    if !types.is_empty() {
+        entries.push(("types".to_owned(), Yaml::Map(types)));
+    }
+    Yaml::Map(entries).to_string()
+}
+
+fn field_type(field_type: FieldType) -> (&'static str, Yaml) {
+    match field_type {
+        FieldType::U8 => ("type", scalar("u1")),
+        FieldType::U16 => ("type", scalar("u2")),
+        FieldType::U32 => ("type", scalar("u4")),
+        FieldType::I8 => ("type", scalar("s1")),
+        FieldType::I16 => ("type", scalar("s2")),
+        FieldType::I32 => ("type", scalar("s4")),
+        FieldType::Bytes(size) => ("size", scalar(size)),
+    }
+}
+
+/// The type described by `layout`, along with the types it uses.
+///
+/// Bytes between fields which aren't described are named after their offset.
+fn layout_types(layout: &StructLayout) -> Vec<(String, Yaml)> {
+    let mut seq = Vec::new();
+    let mut offset = 0;
+    for field in layout.fields {
+        if field.offset > offset {
+            seq.push(attribute(
+                &format!("unk_{offset:#04x}"),
+                [("size", scalar(field.offset - offset))],
+            ));
+        }
+        seq.push(attribute(field.name, [field_type(field.field_type)]));
+        offset = field.offset + field.field_type.size();
+    }
+    if layout.size > offset {
+        seq.push(attribute(
+            &format!("unk_{offset:#04x}"),
+            [("size", scalar(layout.size - offset))],
+        ));
+    }
+
+    let mut types = Vec::new();
+    match layout.trailing {
+        Trailing::Nothing => {}
+        Trailing::Extra => seq.push(attribute("extra", [("size-eos", scalar(true))])),
+        Trailing::Repeated {
+            name,
+            element,
+            count_field,
+        } => {
+            let element_type = snake_case(element.name);
+            seq.push(match count_field {
+                Some(count_field) => attribute(
+                    name,
+                    [
+                        ("type", scalar(&element_type)),
+                        ("repeat", scalar("expr")),
+                        ("repeat-expr", scalar(count_field)),
+                    ],
+                ),
+                None => attribute(
+                    name,
+                    [("type", scalar(&element_type)), ("repeat", scalar("eos"))],
+                ),
+            });
+            if count_field.is_some() {
+                seq.push(attribute("extra", [("size-eos", scalar(true))]));
+            }
+            types.extend(layout_types(element));
+        }
+    }
+    types.insert(
+        0,
+        (
+            snake_case(layout.name),
+            map([
+                ("doc", scalar(format!("`{}`", layout.name))),
+                ("seq", Yaml::List(seq)),
+            ]),
+        ),
+    );
+    types
+}
+
+/// A single entry of a table of records described by `layout`.
+pub fn record_ksy(layout: &StructLayout) -> String {
+    let mut types = layout_types(layout);
+    let (id, Yaml::Map(mut body)) = types.remove(0) else {
+        unreachable!()
+    };
+    // The top-level type has the documentation in `meta` instead.
+    body.retain(|(k, _)| k != "doc");
+    let body = Yaml::Map(body);
+    ksy(
+        &id,
+        layout.endianness,
+        &format!("`{}`", layout.name),
+        body,
+        types,
+    )
+}
+
+fn varint_type() -> (String, Yaml) {
+    let value = (0..VARINT_MAX_EXTRA_BYTES)
+        .map(|i| format!(" | (extra.size > {i} ? extra[{i}] << {} : 0)", (i + 1) * 6))
+        .collect::<String>();
+    (
+        "varint".to_owned(),
+        map([
+            (
+                "doc",
+                scalar("The top 2 bits of the first byte are the number of extra bytes, each of which is shifted by 6 bits more than the previous one."),
+            ),
+            (
+                "seq",
+                Yaml::List(vec![
+                    attribute("first", [("type", scalar("u1"))]),
+                    attribute(
+                        "extra",
+                        [
+                            ("type", scalar("u1")),
+                            ("repeat", scalar("expr")),
+                            ("repeat-expr", scalar("first >> 6")),
+                        ],
+                    ),
+                ]),
+            ),
+            (
+                "instances",
+                map([(
+                    "value",
+                    map([("value", scalar(format!("(first & 0x3f){value}")))]),
+                )]),
+            ),
+        ]),
+    )
+}
+
+fn compression_types() -> Vec<(String, Yaml)> {
+    let command = |command: CompressionCommand| u8::from(command).to_string();
+    let commands: Vec<Yaml> = (0..4)
+        .map(|i| {
+            attribute(
+                &format!("command{i}"),
+                [(
+                    "type",
+                    map([
+                        ("switch-on", scalar(format!("(commands >> {}) & 3", i * 2))),
+                        (
+                            "cases",
+                            Yaml::Map(vec![
+                                (command(CompressionCommand::Copy), scalar("copy")),
+                                (command(CompressionCommand::Lz77), scalar("lz77")),
+                                (command(CompressionCommand::Rle), scalar("rle")),
+                            ]),
+                        ),
+                    ]),
+                )],
+            )
+        })
+        .collect();
+    vec![
+        varint_type(),
+        (
+            "block".to_owned(),
+            map([(
+                "seq",
+                Yaml::List(vec![
+                    attribute("size", [("type", scalar("u2"))]),
+                    attribute(
+                        "command_groups",
+                        [
+                            ("size", scalar("size")),
+                            ("type", scalar("command_group")),
+                            ("repeat", scalar("eos")),
+                        ],
+                    ),
+                ]),
+            )]),
+        ),
+        (
+            "command_group".to_owned(),
+            map([
+                (
+                    "doc",
+                    scalar(
+                        "The lowest 2 bits of `commands` are the first command; 0 ends the block.",
+                    ),
+                ),
+                (
+                    "seq",
+                    Yaml::List(
+                        [attribute("commands", [("type", scalar("u1"))])]
+                            .into_iter()
+                            .chain(commands)
+                            .collect(),
+                    ),
+                ),
+            ]),
+        ),
+        (
+            "copy".to_owned(),
+            map([(
+                "seq",
+                Yaml::List(vec![attribute("value", [("type", scalar("u1"))])]),
+            )]),
+        ),
+        (
+            "lz77".to_owned(),
+            map([
+                (
+                    "doc",
+                    scalar("Copies `length` bytes from `distance` bytes back in the output."),
+                ),
+                (
+                    "seq",
+                    Yaml::List(vec![
+                        attribute("low", [("type", scalar("u1"))]),
+                        attribute("high", [("type", scalar("u1"))]),
+                    ]),
+                ),
+                (
+                    "instances",
+                    map([
+                        (
+                            "distance",
+                            map([("value", scalar("low | ((high & 0xf0) << 4)"))]),
+                        ),
+                        ("length", map([("value", scalar("(high & 0x0f) + 2"))])),
+                    ]),
+                ),
+            ]),
+        ),
+        (
+            "rle".to_owned(),
+            map([
+                (
+                    "seq",
+                    Yaml::List(vec![
+                        attribute("count_minus_2", [("type", scalar("u1"))]),
+                        attribute("value", [("type", scalar("u1"))]),
+                    ]),
+                ),
+                (
+                    "instances",
+                    map([("count", map([("value", scalar("count_minus_2 + 2"))]))]),
+                ),
+            ]),
+        ),
+    ]
+}
+
+/// The container read by [`decompress`](crate::decompress).
+pub fn compressed_data_ksy() -> String {
+    ksy(
+        "compressed_data",
+        Endianness::Little,
+        "Data compressed with `mnllib::compress`.",
+        compressed_data_type(),
+        compression_types(),
+    )
+}
+fn compressed_data_type() -> Yaml {
+    map([(
+        "seq",
+        Yaml::List(vec![
+            attribute("uncompressed_size", [("type", scalar("varint"))]),
+            attribute("num_blocks_minus_1", [("type", scalar("varint"))]),
+            attribute(
+                "blocks",
+                [
+                    ("type", scalar("block")),
+                    ("repeat", scalar("expr")),
+                    ("repeat-expr", scalar("num_blocks_minus_1.value + 1")),
+                ],
+            ),
+        ]),
+    )])
+}
+
+/// A [`DataWithOffsetTable`](crate::misc::DataWithOffsetTable), with its chunks typed
+/// by `chunk_type` if given, an expression of `_index` giving a type name or a switch.
+fn data_with_offset_table_type(entry_size: OffsetTableEntrySize, chunk_type: Option<Yaml>) -> Yaml {
+    let size = entry_size.size();
+    let mut chunk = vec![
+        ("id", scalar("chunks")),
+        ("size", scalar("offsets[_index + 1] - offsets[_index]")),
+    ];
+    if let Some(chunk_type) = chunk_type {
+        chunk.push(("type", chunk_type));
+    }
+    chunk.extend([
+        ("repeat", scalar("expr")),
+        ("repeat-expr", scalar("offsets.size - 1")),
+    ]);
+    map([
+        (
+            "seq",
+            Yaml::List(vec![
+                attribute(
+                    "offsets",
+                    [
+                        ("type", scalar(format!("u{size}"))),
+                        ("repeat", scalar("expr")),
+                        ("repeat-expr", scalar(format!("first_offset / {size}"))),
+                    ],
+                ),
+                attribute(
+                    "padding",
+                    [("size", scalar(format!("first_offset % {size}")))],
+                ),
+                Yaml::Map(chunk.into_iter().map(|(k, v)| (k.to_owned(), v)).collect()),
+                attribute("footer", [("size-eos", scalar(true))]),
+            ]),
+        ),
+        (
+            "instances",
+            map([(
+                "first_offset",
+                map([("pos", scalar(0)), ("type", scalar(format!("u{size}")))]),
+            )]),
+        ),
+    ])
+}
+
+/// A [`DataWithOffsetTable`](crate::misc::DataWithOffsetTable) with untyped chunks.
+pub fn data_with_offset_table_ksy(entry_size: OffsetTableEntrySize) -> String {
+    let id = match entry_size {
+        OffsetTableEntrySize::U16 => "data_with_offset_table_16",
+        OffsetTableEntrySize::U32 => "data_with_offset_table",
+    };
+    ksy(
+        id,
+        Endianness::Little,
+        "Chunks preceded by a table of their offsets, the first of which is also the size of the table.",
+        data_with_offset_table_type(entry_size, None),
+        Vec::new(),
+    )
+}
+
+/// A decompressed [`FieldMapChunk`](crate::map::FieldMapChunk).
+pub fn field_map_chunk_ksy() -> String {
+    // Empty chunks are left untyped, as they stand for missing tile layers and such.
+    let chunk_type = map([
+        (
+            "switch-on",
+            scalar("offsets[_index + 1] == offsets[_index] ? -1 : _index"),
+        ),
+        (
+            "cases",
+            Yaml::Map(
+                (0..3)
+                    .map(|i| (i.to_string(), scalar("tile_layer")))
+                    .chain((3..6).map(|i| (i.to_string(), scalar("palette"))))
+                    .chain([
+                        (
+                            "6".to_owned(),
+                            scalar(snake_case(FieldMapProperties::LAYOUT.name)),
+                        ),
+                        ("9".to_owned(), scalar("data_with_offset_table")),
+                        ("10".to_owned(), scalar("data_with_offset_table")),
+                    ])
+                    .collect(),
+            ),
+        ),
+    ]);
+    let mut types = vec![
+        (
+            "tile_layer".to_owned(),
+            map([(
+                "seq",
+                Yaml::List(vec![attribute(
+                    "tiles",
+                    [("type", scalar("tile")), ("repeat", scalar("eos"))],
+                )]),
+            )]),
+        ),
+        (
+            "tile".to_owned(),
+            map([(
+                "seq",
+                Yaml::List(vec![
+                    attribute("tileset_tile_id", [("type", scalar("b10"))]),
+                    attribute("flipped_horizontally", [("type", scalar("b1"))]),
+                    attribute("flipped_vertically", [("type", scalar("b1"))]),
+                    attribute("palette_offset", [("type", scalar("b4"))]),
+                ]),
+            )]),
+        ),
+        (
+            "palette".to_owned(),
+            map([(
+                "seq",
+                Yaml::List(vec![attribute(
+                    "colors",
+                    [("type", scalar("rgb555")), ("repeat", scalar("eos"))],
+                )]),
+            )]),
+        ),
+        (
+            "rgb555".to_owned(),
+            map([(
+                "seq",
+                Yaml::List(vec![
+                    attribute("r", [("type", scalar("b5"))]),
+                    attribute("g", [("type", scalar("b5"))]),
+                    attribute("b", [("type", scalar("b5"))]),
+                    attribute("unused", [("type", scalar("b1"))]),
+                ]),
+            )]),
+        ),
+        (
+            "data_with_offset_table".to_owned(),
+            data_with_offset_table_type(OffsetTableEntrySize::U32, None),
+        ),
+    ];
+    types.extend(layout_types(&FieldMapProperties::LAYOUT));
+    ksy(
+        "field_map_chunk",
+        Endianness::Little,
+        "A decompressed chunk of `FMapData.dat` holding a field map.",
+        data_with_offset_table_type(OffsetTableEntrySize::U32, Some(chunk_type)),
+        types,
+    )
+}
+
+/// All of the definitions, by the ID of their top-level type,
+/// which is also the stem of their filename.
+pub fn all_ksy() -> Vec<(String, String)> {
+    let mut definitions = vec![
+        ("compressed_data".to_owned(), compressed_data_ksy()),
+        (
+            "data_with_offset_table".to_owned(),
+            data_with_offset_table_ksy(OffsetTableEntrySize::U32),
+        ),
+        (
+            "data_with_offset_table_16".to_owned(),
+            data_with_offset_table_ksy(OffsetTableEntrySize::U16),
+        ),
+        ("field_map_chunk".to_owned(), field_map_chunk_ksy()),
+    ];
+    definitions.extend(
+        LAYOUTS
+            .iter()
+            .map(|layout| (snake_case(layout.name), record_ksy(layout))),
+    );
+    definitions
+}
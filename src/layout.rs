@@ -0,0 +1,169 @@
+//! The known binary structure of the fixed-size records, as data,
+//! e.g. for generating documentation or hex editor templates.
+//!
+//! Each layout is defined next to the parser of its type,
+//! which takes its size from the layout.
+
+use std::fmt::{self, Display};
+
+use crate::{map::FieldMapProperties, rom::OverlayEntry};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum FieldType {
+    U8,
+    U16,
+    U32,
+    I8,
+    I16,
+    I32,
+    /// Bytes which are kept as-is, or a bitfield.
+    Bytes(usize),
+}
+
+impl FieldType {
+    pub const fn size(self) -> usize {
+        match self {
+            Self::U8 | Self::I8 => 1,
+            Self::U16 | Self::I16 => 2,
+            Self::U32 | Self::I32 => 4,
+            Self::Bytes(size) => size,
+        }
+    }
+}
+
+impl Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::U8 => f.write_str("u8"),
+            Self::U16 => f.write_str("u16"),
+            Self::U32 => f.write_str("u32"),
+            Self::I8 => f.write_str("i8"),
+            Self::I16 => f.write_str("i16"),
+            Self::I32 => f.write_str("i32"),
+            Self::Bytes(size) => write!(f, "[u8; {size}]"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FieldLayout {
+    /// The name of the field of the Rust type.
+    pub name: &'static str,
+    pub offset: usize,
+    pub field_type: FieldType,
+}
+
+impl FieldLayout {
+    pub const fn new(name: &'static str, offset: usize, field_type: FieldType) -> Self {
+        Self {
+            name,
+            offset,
+            field_type,
+        }
+    }
+}
+
+/// What follows the fields in an entry larger than [`StructLayout::size`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(tag = "kind", rename_all = "snake_case")
+)]
+pub enum Trailing {
+    Nothing,
+    /// Unknown bytes, which are preserved as-is in the field `extra`.
+    Extra,
+    /// Consecutive elements, followed by unknown bytes in the field `extra`.
+    Repeated {
+        name: &'static str,
+        element: &'static StructLayout,
+        /// The field holding the number of elements,
+        /// or `None` if the elements fill the entry.
+        count_field: Option<&'static str>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StructLayout {
+    /// The name of the Rust type.
+    pub name: &'static str,
+    /// The size of the fields, which is also the smallest size of an entry.
+    pub size: usize,
+    pub endianness: Endianness,
+    /// By offset.
+    pub fields: &'static [FieldLayout],
+    pub trailing: Trailing,
+}
+
+impl StructLayout {
+    pub fn field(&self, name: &str) -> Option<&'static FieldLayout> {
+        self.fields.iter().find(|x| x.name == name)
+    }
+}
+
+/// Writes a C-like definition, with the offset of each field.
+impl Display for StructLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let endianness = match self.endianness {
+            Endianness::Little => "little-endian",
+            Endianness::Big => "big-endian",
+        };
+        writeln!(
+            f,
+            "struct {} {{ // {:#X} bytes, {endianness}",
+            self.name, self.size
+        )?;
+        for field in self.fields {
+            writeln!(
+                f,
+                "    /* {:#04X} */ {}: {},",
+                field.offset, field.name, field.field_type
+            )?;
+        }
+        match self.trailing {
+            Trailing::Nothing => {}
+            Trailing::Extra => writeln!(f, "    /* {:#04X} */ extra: [u8],", self.size)?,
+            Trailing::Repeated {
+                name,
+                element,
+                count_field,
+            } => {
+                let count = count_field.unwrap_or("..");
+                writeln!(
+                    f,
+                    "    /* {:#04X} */ {name}: [{}; {count}],",
+                    self.size, element.name
+                )?;
+                writeln!(f, "    extra: [u8],")?;
+            }
+        }
+        f.write_str("}")
+    }
+}
+
+/// Types whose binary structure is described by a [`StructLayout`].
+pub trait HasLayout {
+    const LAYOUT: StructLayout;
+}
+
+/// The layouts of all types which implement [`HasLayout`].
+pub const LAYOUTS: &[&StructLayout] = &[&FieldMapProperties::LAYOUT, &OverlayEntry::LAYOUT];
@@ -1,7 +1,43 @@
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+#[cfg(feature = "aseprite")]
+pub mod aseprite;
+pub mod audio;
+pub mod battle;
+pub mod cache;
 pub mod compression;
 pub mod consts;
+#[cfg(feature = "fs")]
+pub mod crossref;
+pub mod cutscene;
+pub mod dump;
+pub mod event;
+pub mod export;
+pub mod flags;
+pub mod font;
+pub mod kaitai;
+pub mod layout;
+pub mod localization;
 pub mod map;
+pub mod minigame;
 pub mod misc;
+#[cfg(feature = "fs")]
+pub mod modpack;
+pub mod objects;
+pub mod offset_table;
+pub mod patch;
+pub mod profile;
+#[cfg(feature = "fs")]
+pub mod project;
+pub mod rom;
+pub mod script;
+pub mod sprites;
+pub mod symbols;
+pub mod text;
 pub mod utils;
+pub mod vfs;
+pub mod vram;
+#[cfg(feature = "zip")]
+pub mod zip;
 
 pub use compression::*;
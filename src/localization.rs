@@ -0,0 +1,252 @@
+use std::collections::BTreeSet;
+
+use thiserror::Error;
+
+use crate::text::{
+    MessageArchive, MessageList, MessageListSet, CONTROL_CODE_PREFIX, MESSAGE_TERMINATOR,
+};
+
+/// How messages of different languages are compared.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LocalizationOptions {
+    /// The second bytes of control codes which may differ between languages,
+    /// such as line breaks.
+    pub ignored_control_codes: BTreeSet<u8>,
+}
+
+impl Default for LocalizationOptions {
+    fn default() -> Self {
+        Self {
+            ignored_control_codes: BTreeSet::from([0x00]),
+        }
+    }
+}
+
+impl LocalizationOptions {
+    /// The control codes of `message` which aren't ignored, in order.
+    pub fn control_codes(&self, message: &[u8]) -> Vec<u8> {
+        split_control_codes(message)
+            .into_iter()
+            .filter_map(|x| match x {
+                Segment::Control(code) if !self.ignored_control_codes.contains(&code) => Some(code),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Segment {
+    Text,
+    Control(u8),
+}
+
+fn split_control_codes(message: &[u8]) -> Vec<Segment> {
+    let mut result = Vec::new();
+    let mut bytes = message.iter().copied();
+    while let Some(byte) = bytes.next() {
+        result.push(match byte {
+            CONTROL_CODE_PREFIX => bytes.next().map_or(Segment::Text, Segment::Control),
+            _ => Segment::Text,
+        });
+    }
+    result
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageDifference {
+    /// The message is empty or doesn't exist in the target language.
+    Missing,
+    /// The message only exists in the target language.
+    Extra,
+    /// The message is the same in both languages, so it probably wasn't translated.
+    Identical,
+    /// The control codes of the message differ between the languages.
+    ControlCodesDiffer,
+}
+
+/// A difference between a message in two languages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MessageDiff {
+    pub message: usize,
+    pub difference: MessageDifference,
+}
+
+#[derive(Error, Debug)]
+pub enum LocalizationError {
+    #[error("message list {0} doesn't exist or isn't deserialized")]
+    MissingList(usize),
+}
+
+/// Aligns the messages of `target` with the ones of `reference` by index
+/// and reports how they differ.
+pub fn diff_lists(
+    reference: &MessageList,
+    target: &MessageList,
+    options: &LocalizationOptions,
+) -> Vec<MessageDiff> {
+    let is_empty = |x: Option<&Vec<u8>>| x.is_none_or(|x| x.is_empty());
+    (0..reference.messages.len().max(target.messages.len()))
+        .filter_map(|message| {
+            let (a, b) = (
+                reference.messages.get(message),
+                target.messages.get(message),
+            );
+            let difference = match (is_empty(a), is_empty(b)) {
+                (true, true) => return None,
+                (false, true) => MessageDifference::Missing,
+                (true, false) => MessageDifference::Extra,
+                (false, false) => {
+                    let (a, b) = (a.unwrap(), b.unwrap());
+                    if a == b {
+                        MessageDifference::Identical
+                    } else if options.control_codes(a) != options.control_codes(b) {
+                        MessageDifference::ControlCodesDiffer
+                    } else {
+                        return None;
+                    }
+                }
+            };
+            Some(MessageDiff {
+                message,
+                difference,
+            })
+        })
+        .collect()
+}
+
+fn deserialized_list(set: &MessageListSet, list: usize) -> Result<&MessageList, LocalizationError> {
+    set.lists
+        .get(list)
+        .and_then(|x| x.as_deserialized())
+        .ok_or(LocalizationError::MissingList(list))
+}
+
+/// [`diff_lists`] for two languages of a set, such as the lists of an `mfset_*.dat` file.
+pub fn diff_languages(
+    set: &MessageListSet,
+    reference: usize,
+    target: usize,
+    options: &LocalizationOptions,
+) -> Result<Vec<MessageDiff>, LocalizationError> {
+    Ok(diff_lists(
+        deserialized_list(set, reference)?,
+        deserialized_list(set, target)?,
+        options,
+    ))
+}
+
+/// [`diff_languages`] for every set of `archive`, along with the index of the set.
+///
+/// Sets without both languages are skipped.
+pub fn diff_archive_languages(
+    archive: &MessageArchive,
+    reference: usize,
+    target: usize,
+    options: &LocalizationOptions,
+) -> Vec<(usize, MessageDiff)> {
+    archive
+        .sets
+        .iter()
+        .enumerate()
+        .filter_map(|(index, set)| {
+            Some((
+                index,
+                diff_languages(set.as_ref()?, reference, target, options).ok()?,
+            ))
+        })
+        .flat_map(|(index, diffs)| diffs.into_iter().map(move |x| (index, x)))
+        .collect()
+}
+
+/// Why a translated message wasn't merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MergeConflict {
+    /// The control codes of the translation differ from the ones of the original message,
+    /// which has control codes inside of its text that can't be placed automatically.
+    ControlCodesDiffer { message: usize },
+}
+
+/// Merges the non-empty messages of `translated` into `target`.
+///
+/// Translations with the same control codes as the original message replace it.
+/// Translations without any control codes are wrapped in the control codes
+/// at the start and the end of the original message, including the [`MESSAGE_TERMINATOR`].
+/// Other translations aren't merged, but returned as conflicts.
+pub fn merge_translations(
+    target: &mut MessageList,
+    translated: &MessageList,
+    options: &LocalizationOptions,
+) -> Vec<MergeConflict> {
+    let mut conflicts = Vec::new();
+    for (message, translation) in translated.messages.iter().enumerate() {
+        if translation.is_empty() {
+            continue;
+        }
+        if target.messages.len() <= message {
+            target.messages.resize(message + 1, Vec::new());
+        }
+        let original = &mut target.messages[message];
+        let plain = translation
+            .strip_suffix(&MESSAGE_TERMINATOR)
+            .unwrap_or(translation);
+        if original.is_empty()
+            || options.control_codes(translation) == options.control_codes(original)
+        {
+            *original = translation.clone();
+        } else if let Some(merged) = options
+            .control_codes(plain)
+            .is_empty()
+            .then(|| wrap_like(original, plain, options))
+            .flatten()
+        {
+            *original = merged;
+        } else {
+            conflicts.push(MergeConflict::ControlCodesDiffer { message });
+        }
+    }
+    conflicts
+}
+
+/// Surrounds `text` with the leading and trailing control codes of `original`,
+/// unless `original` has control codes between its text too.
+fn wrap_like(original: &[u8], text: &[u8], options: &LocalizationOptions) -> Option<Vec<u8>> {
+    let (body, terminator) = match original.strip_suffix(&MESSAGE_TERMINATOR) {
+        Some(body) => (body, &MESSAGE_TERMINATOR[..]),
+        None => (original, &[][..]),
+    };
+    let segments = split_control_codes(body);
+    let leading = segments
+        .iter()
+        .take_while(|x| matches!(x, Segment::Control(_)))
+        .count();
+    let trailing = segments[leading..]
+        .iter()
+        .rev()
+        .take_while(|x| matches!(x, Segment::Control(_)))
+        .count();
+    let inner_codes = segments[leading..segments.len() - trailing].iter().any(
+        |x| matches!(x, Segment::Control(code) if !options.ignored_control_codes.contains(code)),
+    );
+    if inner_codes {
+        return None;
+    }
+    let codes = |segments: &[Segment]| -> Vec<u8> {
+        segments
+            .iter()
+            .flat_map(|x| match *x {
+                Segment::Control(code) => [CONTROL_CODE_PREFIX, code],
+                Segment::Text => unreachable!(),
+            })
+            .collect()
+    };
+    Some(
+        [
+            codes(&segments[..leading]),
+            text.to_vec(),
+            codes(&segments[segments.len() - trailing..]),
+            terminator.to_vec(),
+        ]
+        .concat(),
+    )
+}
@@ -1,5 +1,5 @@
 use std::{
-    fs::File,
+    fmt::{self, Display},
     io::{self, Cursor, Read, Seek, SeekFrom, Write},
     num::TryFromIntError,
 };
@@ -14,28 +14,47 @@ use num_enum::{IntoPrimitive, TryFromPrimitive};
 use rgb::Rgba;
 use thiserror::Error;
 
+#[cfg(feature = "fs")]
+use crate::misc::{ProjectPaths, SaveOptions};
 use crate::{
     compress,
     consts::{
-        BATTLE_MAP_WIDTH, BATTLE_TILESET_PIXEL_SIZE, FIELD_MAP_CHUNK_TABLE_ADDRESS,
-        FMAPDATA_OFFSET_TABLE_LENGTH_ADDRESS, NUMBER_OF_FIELD_MAPS,
-        STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT, STANDARD_FILE_ALIGNMENT, TILE_AREA,
-        TREASURE_INFO_OFFSET_TABLE_LENGTH_ADDRESS,
+        BATTLE_MAP_HEIGHT, BATTLE_MAP_WIDTH, BATTLE_TILESET_PIXEL_SIZE,
+        FIELD_MAP_CHUNK_TABLE_ADDRESS, FMAPDATA_OFFSET_TABLE_LENGTH_ADDRESS, NUMBER_OF_FIELD_MAPS,
+        STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT, STANDARD_FILE_ALIGNMENT, TILE_AREA, TILE_HEIGHT,
+        TILE_WIDTH, TREASURE_INFO_OFFSET_TABLE_LENGTH_ADDRESS,
     },
     decompress,
+    layout::{Endianness, FieldLayout, FieldType, HasLayout, StructLayout, Trailing},
     misc::{
-        filesystem_standard_data_path, filesystem_standard_overlay_path, DataWithOffsetTable,
+        ChunksCompressionError, CompressionCache, DataWithOffsetTable,
         DataWithOffsetTableDeserializationError, DataWithOffsetTableSerializationError,
-        MaybeCompressedData, MaybeSerialized, Palette, PaletteDeserializationError, Rgb555,
+        DecompressedChunkCache, MaybeCompressedData, MaybeSerialized, PackedChunks, Palette,
+        PaletteDeserializationError, PaletteLut, Rgb555, SalvageReport,
     },
+    offset_table::salvage_chunks,
+    rom::NdsRom,
     utils::{
-        empty_if_none, necessary_padding_for, none_if_empty, option_to_u32_or_max_try_into,
-        u32_or_max_to_option_try_into, AlignToElements,
+        necessary_padding_for, none_if_empty, option_to_u32_or_max_try_into,
+        u32_or_max_to_option_try_into, write_zeros, AlignToElements, ByteCounter, IndexRemap,
     },
+    vfs::Vfs,
     CompressionError, DecompressionError,
 };
+#[cfg(feature = "fs")]
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, TryFromPrimitive, IntoPrimitive)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, TryFromPrimitive, IntoPrimitive,
+)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 #[repr(u8)]
 pub enum PixelSize {
     Nibble = 0,
@@ -43,6 +62,15 @@ pub enum PixelSize {
 }
 
 impl PixelSize {
+    /// The size of a serialized [`TilesetTile`].
+    #[inline]
+    pub const fn tile_size(self) -> usize {
+        match self {
+            Self::Nibble => TILE_AREA / 2,
+            Self::Byte => TILE_AREA,
+        }
+    }
+
     // For `bitfield_struct`.
     const fn from_bits(value: u8) -> Self {
         match value {
@@ -66,6 +94,7 @@ impl PixelSize {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct TilesetTile(pub [u8; TILE_AREA]);
 
 #[derive(Error, Debug)]
@@ -77,6 +106,8 @@ pub enum TilesetTileDeserializationError {
 pub enum TilesetTileSerializationError {
     #[error("a pixel's value is too large to fit in {pixel_size:?}")]
     PixelValueTooLarge { pixel_size: PixelSize },
+    #[error(transparent)]
+    Io(#[from] io::Error),
 }
 #[derive(Error, Debug)]
 pub enum TilesetTileFromColorsError {
@@ -108,21 +139,31 @@ impl TilesetTile {
         &self,
         pixel_size: PixelSize,
     ) -> Result<Vec<u8>, TilesetTileSerializationError> {
-        Ok(match pixel_size {
-            PixelSize::Nibble => self
-                .0
-                .chunks_exact(2)
-                .map(|pixels| {
+        let mut buf = Vec::with_capacity(pixel_size.tile_size());
+        self.write_to(&mut buf, pixel_size)?;
+        Ok(buf)
+    }
+    pub fn write_to(
+        &self,
+        mut out: impl Write,
+        pixel_size: PixelSize,
+    ) -> Result<(), TilesetTileSerializationError> {
+        match pixel_size {
+            PixelSize::Nibble => {
+                let mut buf = [0u8; TILE_AREA / 2];
+                for (byte, pixels) in buf.iter_mut().zip(self.0.chunks_exact(2)) {
                     if pixels[0] > 0x0F || pixels[1] > 0x0F {
                         return Err(TilesetTileSerializationError::PixelValueTooLarge {
                             pixel_size,
                         });
                     }
-                    Ok(pixels[0] | (pixels[1] << 4))
-                })
-                .collect::<Result<Vec<_>, _>>()?,
-            PixelSize::Byte => self.0.to_vec(),
-        })
+                    *byte = pixels[0] | (pixels[1] << 4);
+                }
+                out.write_all(&buf)?;
+            }
+            PixelSize::Byte => out.write_all(&self.0)?,
+        }
+        Ok(())
     }
 
     #[inline]
@@ -167,6 +208,8 @@ impl TilesetTile {
                             .iter()
                             .skip(1)
                             .position(|x| x == color)
+                            // Index 0 is transparent, so it was skipped.
+                            .map(|x| x + 1)
                             .ok_or(TilesetTileFromColorsError::ColorNotInPalette)?
                             .try_into()?
                     } else {
@@ -195,7 +238,51 @@ impl TilesetTile {
     }
 }
 
+/// Serialized as one hexadecimal digit per pixel,
+/// or two if any pixel doesn't fit into one.
+#[cfg(feature = "serde")]
+impl serde::Serialize for TilesetTile {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let text: String = if self.0.iter().all(|&x| x < 0x10) {
+            self.0.iter().map(|x| format!("{x:X}")).collect()
+        } else {
+            self.0.iter().map(|x| format!("{x:02X}")).collect()
+        };
+        serializer.serialize_str(&text)
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TilesetTile {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        let digits_per_pixel = text.len() / TILE_AREA;
+        if !text.is_ascii() || text.len() % TILE_AREA != 0 || !(1..=2).contains(&digits_per_pixel) {
+            return Err(serde::de::Error::custom(format!(
+                "a tile must have {} or {} hexadecimal digits",
+                TILE_AREA,
+                TILE_AREA * 2
+            )));
+        }
+        text.as_bytes()
+            .chunks_exact(digits_per_pixel)
+            .map(|x| {
+                std::str::from_utf8(x)
+                    .ok()
+                    .and_then(|x| u8::from_str_radix(x, 16).ok())
+                    .ok_or_else(|| serde::de::Error::custom("invalid hexadecimal digit"))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(|x| Self(x.try_into().unwrap()))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct Tileset(pub Vec<TilesetTile>);
 
 impl Tileset {
@@ -204,12 +291,9 @@ impl Tileset {
         pixel_size: PixelSize,
     ) -> Result<Self, TilesetTileDeserializationError> {
         Ok(Self(
-            data.chunks(match pixel_size {
-                PixelSize::Nibble => TILE_AREA / 2,
-                PixelSize::Byte => TILE_AREA,
-            })
-            .map(|d| TilesetTile::from_bytes(d, pixel_size))
-            .collect::<Result<Vec<_>, _>>()?,
+            data.chunks(pixel_size.tile_size())
+                .map(|d| TilesetTile::from_bytes(d, pixel_size))
+                .collect::<Result<Vec<_>, _>>()?,
         ))
     }
 
@@ -217,14 +301,170 @@ impl Tileset {
         &self,
         pixel_size: PixelSize,
     ) -> Result<Vec<u8>, TilesetTileSerializationError> {
-        self.0
-            .iter()
-            .map(|x| x.to_bytes(pixel_size))
-            .flatten_ok()
-            .collect()
+        let mut buf = Vec::with_capacity(self.0.len() * pixel_size.tile_size());
+        self.write_to(&mut buf, pixel_size)?;
+        Ok(buf)
+    }
+    pub fn write_to(
+        &self,
+        mut out: impl Write,
+        pixel_size: PixelSize,
+    ) -> Result<(), TilesetTileSerializationError> {
+        for tile in &self.0 {
+            tile.write_to(&mut out, pixel_size)?;
+        }
+        Ok(())
+    }
+
+    /// Splits `image` into tiles in row-major order, using [`TilesetTile::from_rgba8888`].
+    ///
+    /// Tiles at the right and bottom edges are padded with transparent pixels.
+    pub fn from_rgba8888_image(
+        image: &Grid<Rgba<u8>>,
+        palette: &Palette,
+    ) -> Result<Self, TilesetTileFromColorsError> {
+        let transparent = Rgba::new(0, 0, 0, 0);
+        let mut tiles = Vec::new();
+        for tile_y in (0..image.rows()).step_by(TILE_HEIGHT) {
+            for tile_x in (0..image.cols()).step_by(TILE_WIDTH) {
+                let colors = std::array::from_fn(|i| {
+                    *image
+                        .get(tile_y + i / TILE_WIDTH, tile_x + i % TILE_WIDTH)
+                        .unwrap_or(&transparent)
+                });
+                tiles.push(TilesetTile::from_rgba8888(&colors, palette)?);
+            }
+        }
+        Ok(Self(tiles))
+    }
+
+    /// Splits an image of palette indices, such as an
+    /// [`IndexedImage`](crate::sprites::IndexedImage), into tiles in row-major order,
+    /// keeping the indices instead of matching colors.
+    ///
+    /// With [`PixelSize::Nibble`], the palette is made of rows of 16 colors, the first
+    /// of which is transparent. Each tile may use only one row, and its pixels are
+    /// relative to it. Index 0 counts as transparent in any row.
+    /// Returns the palette row of every tile, which is always 0 with [`PixelSize::Byte`].
+    ///
+    /// Tiles at the right and bottom edges are padded with index 0.
+    pub fn from_indexed_image(
+        pixels: &Grid<u8>,
+        pixel_size: PixelSize,
+    ) -> Result<(Self, Vec<u8>), TilesetFromIndexedImageError> {
+        let mut tiles = Vec::new();
+        let mut palette_rows = Vec::new();
+        for tile_y in (0..pixels.rows()).step_by(TILE_HEIGHT) {
+            for tile_x in (0..pixels.cols()).step_by(TILE_WIDTH) {
+                let mut tile: [u8; TILE_AREA] = std::array::from_fn(|i| {
+                    *pixels
+                        .get(tile_y + i / TILE_WIDTH, tile_x + i % TILE_WIDTH)
+                        .unwrap_or(&0)
+                });
+                let palette_row = match pixel_size {
+                    PixelSize::Nibble => {
+                        let rows: Vec<u8> = tile
+                            .iter()
+                            .filter(|&&x| x != 0)
+                            .map(|x| x >> 4)
+                            .unique()
+                            .collect();
+                        if rows.len() > 1 {
+                            return Err(TilesetFromIndexedImageError::MultiplePaletteRows {
+                                tile: tiles.len(),
+                            });
+                        }
+                        for pixel in &mut tile {
+                            *pixel &= 0x0F;
+                        }
+                        rows.first().copied().unwrap_or(0)
+                    }
+                    PixelSize::Byte => 0,
+                };
+                tiles.push(TilesetTile(tile));
+                palette_rows.push(palette_row);
+            }
+        }
+        Ok((Self(tiles), palette_rows))
+    }
+}
+
+/// A [`Tileset`] which reads its tiles straight from their serialized bytes
+/// instead of copying them, for read-only access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TilesetRef<'a> {
+    data: &'a [u8],
+    pixel_size: PixelSize,
+}
+
+impl<'a> TilesetRef<'a> {
+    /// Fails under the same conditions as [`Tileset::from_bytes`].
+    pub fn new(
+        data: &'a [u8],
+        pixel_size: PixelSize,
+    ) -> Result<Self, TilesetTileDeserializationError> {
+        if !data.len().is_multiple_of(pixel_size.tile_size()) {
+            return Err(TilesetTileDeserializationError::InvalidInputLength);
+        }
+        Ok(Self { data, pixel_size })
+    }
+
+    #[inline]
+    pub fn pixel_size(&self) -> PixelSize {
+        self.pixel_size
+    }
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.data.len() / self.pixel_size.tile_size()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// The serialized bytes of tile `index`.
+    #[inline]
+    pub fn tile_bytes(&self, index: usize) -> Option<&'a [u8]> {
+        let tile_size = self.pixel_size.tile_size();
+        self.data.get(index * tile_size..(index + 1) * tile_size)
+    }
+    /// The pixel at `pixel` in row-major order of tile `index`,
+    /// without decoding the rest of the tile.
+    pub fn pixel(&self, index: usize, pixel: usize) -> Option<u8> {
+        if pixel >= TILE_AREA {
+            return None;
+        }
+        let data = self.tile_bytes(index)?;
+        Some(match self.pixel_size {
+            PixelSize::Nibble => (data[pixel / 2] >> (pixel % 2 * 4)) & 0x0F,
+            PixelSize::Byte => data[pixel],
+        })
+    }
+    /// Decodes tile `index`, without allocating.
+    pub fn tile(&self, index: usize) -> Option<TilesetTile> {
+        let data = self.tile_bytes(index)?;
+        Some(TilesetTile(match self.pixel_size {
+            PixelSize::Nibble => std::array::from_fn(|i| (data[i / 2] >> (i % 2 * 4)) & 0x0F),
+            PixelSize::Byte => data.try_into().unwrap(),
+        }))
+    }
+    pub fn iter(&self) -> impl Iterator<Item = TilesetTile> + 'a {
+        let tileset = *self;
+        (0..self.len()).map(move |i| tileset.tile(i).unwrap())
     }
+
+    pub fn to_tileset(&self) -> Tileset {
+        Tileset(self.iter().collect())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum TilesetFromIndexedImageError {
+    #[error("tile {tile} uses colors of more than one palette row")]
+    MultiplePaletteRows { tile: usize },
 }
 
+/// [`Tile::new`] and [`Default`] give tileset tile 0, unflipped and with palette offset 0.
 #[bitfield(u16, repr = le16, from = le16::from_ne, into = le16::to_ne)]
 #[derive(PartialEq, Eq, Hash)]
 pub struct Tile {
@@ -236,46 +476,242 @@ pub struct Tile {
     pub palette_offset: u8,
 }
 
+impl Tile {
+    /// Flips the tile horizontally and/or vertically,
+    /// on top of any flipping it already has.
+    #[inline]
+    pub fn apply_flip(self, horizontally: bool, vertically: bool) -> Self {
+        self.with_flipped_horizontally(self.flipped_horizontally() ^ horizontally)
+            .with_flipped_vertically(self.flipped_vertically() ^ vertically)
+    }
+
+    /// Replaces the tileset tile ID with `f` of it, keeping the flipping and palette offset,
+    /// e.g. to renumber the tiles after deduplicating a tileset.
+    ///
+    /// Panics if the new ID doesn't fit in 10 bits.
+    #[inline]
+    pub fn with_remapped_tileset_id(self, f: impl FnOnce(u16) -> u16) -> Self {
+        self.with_tileset_tile_id(f(self.tileset_tile_id()))
+    }
+
+    /// Whether the tile shows tileset tile `blank_id`,
+    /// regardless of its flipping and palette offset.
+    #[inline]
+    pub fn is_blank(self, blank_id: u16) -> bool {
+        self.tileset_tile_id() == blank_id
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Default, From, Into, Deref, DerefMut)]
 pub struct TileLayer(pub Grid<Tile>);
 
+#[derive(Error, Debug)]
+pub enum TileLayerDeserializationError {
+    #[error(
+        "the input is {actual} bytes long, which isn't a multiple of the size of a row ({expected_multiple_of})"
+    )]
+    InvalidInputLength {
+        expected_multiple_of: usize,
+        actual: usize,
+    },
+}
+
+/// Checks that `data` consists of whole rows of `width` tiles.
+fn check_tile_layer_len(data: &[u8], width: usize) -> Result<(), TileLayerDeserializationError> {
+    let row_size = width * 2;
+    if !data.len().is_multiple_of(row_size) {
+        return Err(TileLayerDeserializationError::InvalidInputLength {
+            expected_multiple_of: row_size,
+            actual: data.len(),
+        });
+    }
+    Ok(())
+}
+
 impl TileLayer {
-    pub fn from_bytes(data: &[u8], width: usize) -> Self {
-        Self(Grid::from_vec(
+    /// `data` must consist of whole rows of `width` tiles.
+    pub fn from_bytes(data: &[u8], width: usize) -> Result<Self, TileLayerDeserializationError> {
+        check_tile_layer_len(data, width)?;
+        Ok(Self(Grid::from_vec(
             // UNSTABLE: Use `slice::array_chunks`.
             data.chunks_exact(2)
                 .map(|d| le16::from_le_bytes(d.try_into().unwrap()).into())
                 .collect(),
             width,
-        ))
+        )))
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
-        self.0
-            .iter()
-            .flat_map(|x| x.into_bits().to_le_bytes())
-            .collect()
+        let mut buf = Vec::with_capacity(self.0.rows() * self.0.cols() * 2);
+        self.write_to(&mut buf).unwrap();
+        buf
+    }
+    /// Each tile is written separately, so `out` should be buffered.
+    pub fn write_to(&self, mut out: impl Write) -> io::Result<()> {
+        for tile in self.0.iter() {
+            out.write_all(&tile.into_bits().to_le_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// A [`TileLayer`] which reads its tiles straight from their serialized bytes
+/// instead of copying them, for read-only access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TileLayerRef<'a> {
+    data: &'a [u8],
+    width: usize,
+}
+
+impl<'a> TileLayerRef<'a> {
+    /// `data` must consist of whole rows of `width` tiles.
+    pub fn new(data: &'a [u8], width: usize) -> Result<Self, TileLayerDeserializationError> {
+        check_tile_layer_len(data, width)?;
+        Ok(Self { data, width })
+    }
+
+    #[inline]
+    pub fn cols(&self) -> usize {
+        self.width
+    }
+    #[inline]
+    pub fn rows(&self) -> usize {
+        self.data.len().checked_div(self.width * 2).unwrap_or(0)
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> Option<Tile> {
+        if col >= self.width {
+            return None;
+        }
+        let offset = (row * self.width + col) * 2;
+        let data = self.data.get(offset..offset + 2)?;
+        Some(le16::from_le_bytes([data[0], data[1]]).into())
+    }
+    /// The tiles in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = Tile> + 'a {
+        // UNSTABLE: Use `slice::array_chunks`.
+        self.data
+            .chunks_exact(2)
+            .map(|x| le16::from_le_bytes([x[0], x[1]]).into())
+    }
+
+    pub fn to_tile_layer(&self) -> TileLayer {
+        TileLayer(Grid::from_vec(self.iter().collect(), self.width))
+    }
+}
+
+/// Formatted as e.g. `03Ah-2`: the tileset tile ID, `h` if flipped horizontally,
+/// `v` if flipped vertically (`-` otherwise), and the palette offset, in hexadecimal.
+impl Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:03X}{}{}{:X}",
+            self.tileset_tile_id(),
+            if self.flipped_horizontally() {
+                'h'
+            } else {
+                '-'
+            },
+            if self.flipped_vertically() { 'v' } else { '-' },
+            self.palette_offset()
+        )
+    }
+}
+impl std::str::FromStr for Tile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid tile `{s}`");
+        if s.len() != 6 || !s.is_ascii() {
+            return Err(invalid());
+        }
+        let flag = |index: usize, set: u8| match s.as_bytes()[index] {
+            b'-' => Ok(false),
+            x if x == set => Ok(true),
+            _ => Err(invalid()),
+        };
+        Ok(Self::new()
+            .with_tileset_tile_id_checked(u16::from_str_radix(&s[..3], 16).map_err(|_| invalid())?)
+            .map_err(|_| invalid())?
+            .with_flipped_horizontally(flag(3, b'h')?)
+            .with_flipped_vertically(flag(4, b'v')?)
+            .with_palette_offset(u8::from_str_radix(&s[5..], 16).map_err(|_| invalid())?))
+    }
+}
+
+/// Serialized as a list of rows, with the [`Tile`]s of each separated by spaces.
+#[cfg(feature = "serde")]
+impl serde::Serialize for TileLayer {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter_rows().map(|mut x| x.join(" ")))
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TileLayer {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let rows = Vec::<String>::deserialize(deserializer)?;
+        let mut tiles = Vec::new();
+        let mut width = None;
+        for row in &rows {
+            let row_start = tiles.len();
+            for tile in row.split_whitespace() {
+                tiles.push(tile.parse().map_err(serde::de::Error::custom)?);
+            }
+            if *width.get_or_insert(tiles.len() - row_start) != tiles.len() - row_start {
+                return Err(serde::de::Error::custom(
+                    "all rows must have the same number of tiles",
+                ));
+            }
+        }
+        Ok(Self(Grid::from_vec(tiles, width.unwrap_or(0))))
     }
 }
 
+/// [`TilesetsProperties::new`] and [`Default`] give the most common properties
+/// in the game, with 8-bit pixels in all tilesets.
 #[bitfield(u8)]
 #[derive(PartialEq, Eq, Hash)]
 pub struct TilesetsProperties {
-    #[bits(3, from = PixelSize::array3_from_bits, into = PixelSize::array3_into_bits)]
+    #[bits(
+        3,
+        default = [PixelSize::Byte; 3],
+        from = PixelSize::array3_from_bits,
+        into = PixelSize::array3_into_bits
+    )]
     pub tileset_pixel_sizes: [PixelSize; 3],
-    #[bits(5)]
+    #[bits(5, default = 0x1E)]
     pub unk: u8,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FieldMapProperties {
     pub width: u16,
     pub height: u16,
     pub unk_0x04: u8,
+    #[cfg_attr(feature = "serde", serde(with = "tilesets_properties"))]
     pub tilesets_properties: TilesetsProperties,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_bytes"))]
     pub unk_0x06: [u8; 6],
 }
 
+impl HasLayout for FieldMapProperties {
+    const LAYOUT: StructLayout = StructLayout {
+        name: "FieldMapProperties",
+        size: 12,
+        endianness: Endianness::Little,
+        fields: &[
+            FieldLayout::new("width", 0, FieldType::U16),
+            FieldLayout::new("height", 2, FieldType::U16),
+            FieldLayout::new("unk_0x04", 4, FieldType::U8),
+            FieldLayout::new("tilesets_properties", 5, FieldType::Bytes(1)),
+            FieldLayout::new("unk_0x06", 6, FieldType::Bytes(6)),
+        ],
+        trailing: Trailing::Nothing,
+    };
+}
+
 impl FieldMapProperties {
     pub fn from_reader(mut inp: impl Read) -> io::Result<Self> {
         Ok(Self {
@@ -301,22 +737,57 @@ impl FieldMapProperties {
         Ok(())
     }
 }
+/// The most common properties in the game, but with a size of 0,
+/// so `width` and `height` should be set.
+impl Default for FieldMapProperties {
+    fn default() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            unk_0x04: 0xFF,
+            tilesets_properties: TilesetsProperties::default(),
+            unk_0x06: [0; 6],
+        }
+    }
+}
 
+/// With the `serde` feature, this has a text representation meant for version control,
+/// where missing tile layers and palettes are empty.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FieldMapChunk {
+    #[cfg_attr(feature = "serde", serde(with = "empty_if_none_array"))]
     pub tile_layers: [Option<TileLayer>; 3],
+    #[cfg_attr(feature = "serde", serde(with = "empty_if_none_array"))]
     pub palettes: [Option<Palette>; 3],
     pub properties: FieldMapProperties,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_bytes"))]
     pub unk7: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_bytes"))]
     pub unk8: Vec<u8>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
     pub unk9: Option<DataWithOffsetTable>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
     pub unk10: Option<DataWithOffsetTable>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_bytes"))]
     pub unk11: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_bytes"))]
     pub unk12: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_bytes"))]
     pub unk13: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_bytes"))]
     pub unk14: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_bytes"))]
     pub unk15: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_bytes"))]
     pub unk16: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_bytes"))]
     pub padding: Vec<u8>,
 }
 
@@ -324,10 +795,26 @@ pub struct FieldMapChunk {
 pub enum FieldMapChunkFromTableError {
     #[error("the input must have exactly 17 chunks, not {0}")]
     InvalidNumberOfChunks(usize),
-    #[error(transparent)]
-    DataWithOffsetTableDeserialization(#[from] DataWithOffsetTableDeserializationError),
-    #[error(transparent)]
-    PaletteDeserialization(#[from] PaletteDeserializationError),
+    #[error("failed to deserialize the offset table in chunk {index}")]
+    DataWithOffsetTableDeserialization {
+        index: usize,
+        #[source]
+        source: DataWithOffsetTableDeserializationError,
+    },
+    #[error("failed to deserialize the palette in chunk {index}")]
+    PaletteDeserialization {
+        index: usize,
+        #[source]
+        source: PaletteDeserializationError,
+    },
+    #[error("failed to deserialize the tile layer in chunk {index}")]
+    TileLayerDeserialization {
+        index: usize,
+        #[source]
+        source: TileLayerDeserializationError,
+    },
+    #[error("failed to deserialize the properties in chunk 6")]
+    PropertiesDeserialization(#[source] io::Error),
     #[error(transparent)]
     Io(#[from] io::Error),
 }
@@ -348,7 +835,8 @@ impl TryFrom<DataWithOffsetTable> for FieldMapChunk {
             return Err(Self::Error::InvalidNumberOfChunks(chunks_len));
         }
 
-        let properties = FieldMapProperties::from_reader(&value.chunks[6][..])?;
+        let properties = FieldMapProperties::from_reader(&value.chunks[6][..])
+            .map_err(Self::Error::PropertiesDeserialization)?;
         Ok(Self {
             unk16: value.chunks.pop().unwrap(),
             unk15: value.chunks.pop().unwrap(),
@@ -358,25 +846,44 @@ impl TryFrom<DataWithOffsetTable> for FieldMapChunk {
             unk11: value.chunks.pop().unwrap(),
             unk10: none_if_empty(value.chunks.pop().unwrap())
                 .map(|x| DataWithOffsetTable::from_reader(&x[..]))
-                .transpose()?,
+                .transpose()
+                .map_err(|source| Self::Error::DataWithOffsetTableDeserialization {
+                    index: 10,
+                    source,
+                })?,
             unk9: none_if_empty(value.chunks.pop().unwrap())
                 .map(|x| DataWithOffsetTable::from_reader(&x[..]))
-                .transpose()?,
+                .transpose()
+                .map_err(|source| Self::Error::DataWithOffsetTableDeserialization {
+                    index: 9,
+                    source,
+                })?,
             unk8: value.chunks.pop().unwrap(),
             unk7: value.chunks.pop().unwrap(),
             // UNSABLE: Use `array::try_map`.
             palettes: value.chunks[3..=5]
                 .iter()
-                .map(|x| none_if_empty(x).map(|x| Palette::from_bytes(x)).transpose())
+                .zip(3..)
+                .map(|(x, index)| {
+                    none_if_empty(x)
+                        .map(|x| Palette::from_bytes(x))
+                        .transpose()
+                        .map_err(|source| Self::Error::PaletteDeserialization { index, source })
+                })
                 .collect::<Result<Vec<_>, _>>()?
                 .try_into()
                 .unwrap(),
             tile_layers: value.chunks[0..=2]
                 .iter()
-                .map(|x| {
-                    none_if_empty(x).map(|x| TileLayer::from_bytes(x, properties.width.into()))
+                .enumerate()
+                .map(|(index, x)| {
+                    none_if_empty(x)
+                        .map(|x| TileLayer::from_bytes(x, properties.width.into()))
+                        .transpose()
+                        .map_err(|source| Self::Error::TileLayerDeserialization { index, source })
                 })
-                .collect_array()
+                .collect::<Result<Vec<_>, _>>()?
+                .try_into()
                 .unwrap(),
             properties,
             padding: value.footer,
@@ -386,51 +893,298 @@ impl TryFrom<DataWithOffsetTable> for FieldMapChunk {
 impl TryFrom<FieldMapChunk> for DataWithOffsetTable {
     type Error = FieldMapChunkIntoTableError;
 
+    #[inline]
     fn try_from(value: FieldMapChunk) -> Result<Self, Self::Error> {
-        Ok(Self {
-            chunks: value
-                .tile_layers
-                .iter()
-                .map(|x| empty_if_none(x.as_ref().map(|x| x.to_bytes())))
-                .chain(
-                    value
-                        .palettes
-                        .iter()
-                        .map(|x| empty_if_none(x.as_ref().map(|x| x.to_bytes()))),
-                )
-                .chain([
-                    {
-                        let mut buf = Vec::new();
-                        value.properties.to_writer(&mut buf)?;
-                        buf
-                    },
-                    value.unk7,
-                    value.unk8,
-                    {
-                        let mut buf = Vec::new();
-                        if let Some(mut value) = value.unk9 {
-                            value.to_writer(&mut buf, None, true)?;
-                        }
-                        buf
-                    },
-                    {
-                        let mut buf = Vec::new();
-                        if let Some(mut value) = value.unk10 {
-                            value.to_writer(&mut buf, None, true)?;
-                        }
-                        buf
-                    },
-                    value.unk11,
-                    value.unk12,
-                    value.unk13,
-                    value.unk14,
-                    value.unk15,
-                    value.unk16,
-                ])
-                .collect(),
-            footer: value.padding,
+        value.to_offset_table()
+    }
+}
+
+#[cfg(feature = "toml")]
+impl FieldMapChunk {
+    pub fn from_toml(toml: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(toml)
+    }
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string_pretty(self)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FieldMapRenderError {
+    #[error("tile layer {layer} uses color {index}, which isn't in its palette")]
+    ColorNotInPalette { layer: usize, index: usize },
+}
+
+/// The index into the palette of a pixel of a [`Tile`],
+/// or `None` if it's transparent.
+pub(crate) fn palette_index(pixel: u8, palette_offset: u8, pixel_size: PixelSize) -> Option<usize> {
+    if pixel == 0 {
+        return None;
+    }
+    Some(match pixel_size {
+        PixelSize::Nibble => usize::from(palette_offset) * 16 + usize::from(pixel),
+        PixelSize::Byte => usize::from(pixel),
+    })
+}
+
+/// An inconsistency found by [`FieldMapChunk::validate`].
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FieldMapChunkProblem {
+    /// The tile layer isn't [`FieldMapProperties::width`] by
+    /// [`FieldMapProperties::height`] tiles.
+    #[error(
+        "tile layer {layer} is {cols}x{rows} tiles, but the map is {expected_cols}x{expected_rows}"
+    )]
+    WrongLayerSize {
+        layer: usize,
+        cols: usize,
+        rows: usize,
+        expected_cols: usize,
+        expected_rows: usize,
+    },
+    /// There's a tile layer, but no palette to draw it with.
+    #[error("tile layer {layer} has no palette")]
+    MissingPalette { layer: usize },
+    /// The palette has more colors than the hardware can use.
+    #[error("palette {layer} has {len} colors, but at most {max} can be used", max = MAX_PALETTE_COLORS)]
+    PaletteTooLarge { layer: usize, len: usize },
+    /// The tile at (`row`, `col`) refers to a tile past the end of its tileset.
+    #[error("tile ({row}, {col}) of layer {layer} uses tileset tile {tileset_tile_id}, but the tileset has only {tileset_len}")]
+    TileNotInTileset {
+        layer: usize,
+        row: usize,
+        col: usize,
+        tileset_tile_id: u16,
+        tileset_len: usize,
+    },
+    /// The tile at (`row`, `col`) uses color `index`, which is past the end of the palette,
+    /// given the layer's [`PixelSize`] and the tile's palette offset.
+    /// Only the largest such color of each tile is reported.
+    #[error("tile ({row}, {col}) of layer {layer} uses color {index}, which isn't in its palette")]
+    ColorNotInPalette {
+        layer: usize,
+        row: usize,
+        col: usize,
+        index: usize,
+    },
+}
+
+/// 16 palette rows of 16 colors, or 256 colors for [`PixelSize::Byte`].
+const MAX_PALETTE_COLORS: usize = 256;
+
+impl FieldMapChunk {
+    /// Like converting into a [`DataWithOffsetTable`], but without consuming `self`.
+    pub fn to_offset_table(&self) -> Result<DataWithOffsetTable, FieldMapChunkIntoTableError> {
+        let mut chunks = Vec::with_capacity(17);
+        for tile_layer in &self.tile_layers {
+            let mut buf = Vec::new();
+            if let Some(tile_layer) = tile_layer {
+                tile_layer.write_to(&mut buf)?;
+            }
+            chunks.push(buf);
+        }
+        for palette in &self.palettes {
+            let mut buf = Vec::new();
+            if let Some(palette) = palette {
+                palette.write_to(&mut buf)?;
+            }
+            chunks.push(buf);
+        }
+        chunks.extend([
+            {
+                let mut buf = Vec::new();
+                self.properties.to_writer(&mut buf)?;
+                buf
+            },
+            self.unk7.clone(),
+            self.unk8.clone(),
+            {
+                let mut buf = Vec::new();
+                if let Some(value) = &self.unk9 {
+                    value.to_writer(&mut buf, None, true)?;
+                }
+                buf
+            },
+            {
+                let mut buf = Vec::new();
+                if let Some(value) = &self.unk10 {
+                    value.to_writer(&mut buf, None, true)?;
+                }
+                buf
+            },
+            self.unk11.clone(),
+            self.unk12.clone(),
+            self.unk13.clone(),
+            self.unk14.clone(),
+            self.unk15.clone(),
+            self.unk16.clone(),
+        ]);
+        Ok(DataWithOffsetTable {
+            chunks,
+            footer: self.padding.clone(),
         })
     }
+    /// Writes the chunk the way it's stored in FMapData, before compression.
+    pub fn to_writer(&self, out: impl Write) -> Result<(), FieldMapChunkIntoTableError> {
+        self.to_offset_table()?.to_writer(
+            out,
+            Some(STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT),
+            true,
+        )?;
+        Ok(())
+    }
+
+    /// Checks that the tile layers, palettes and `tilesets` fit together,
+    /// returning every problem found, in order of layer.
+    ///
+    /// Tiles of layers whose tileset is `None` aren't checked against it.
+    /// A few maps of the game itself have tile layers of the wrong height,
+    /// no palette or tiles past the end of their tileset,
+    /// so not every problem prevents the map from working.
+    pub fn validate(&self, tilesets: [Option<&Tileset>; 3]) -> Vec<FieldMapChunkProblem> {
+        let mut problems = Vec::new();
+        let (expected_cols, expected_rows) = (
+            usize::from(self.properties.width),
+            usize::from(self.properties.height),
+        );
+        let pixel_sizes = self.properties.tilesets_properties.tileset_pixel_sizes();
+        for layer in 0..3 {
+            let palette = self.palettes[layer].as_ref();
+            if let Some(palette) = palette {
+                if palette.0.len() > MAX_PALETTE_COLORS {
+                    problems.push(FieldMapChunkProblem::PaletteTooLarge {
+                        layer,
+                        len: palette.0.len(),
+                    });
+                }
+            }
+            let Some(tile_layer) = &self.tile_layers[layer] else {
+                continue;
+            };
+            if (tile_layer.cols(), tile_layer.rows()) != (expected_cols, expected_rows) {
+                problems.push(FieldMapChunkProblem::WrongLayerSize {
+                    layer,
+                    cols: tile_layer.cols(),
+                    rows: tile_layer.rows(),
+                    expected_cols,
+                    expected_rows,
+                });
+            }
+            if palette.is_none() {
+                problems.push(FieldMapChunkProblem::MissingPalette { layer });
+            }
+            let Some(tileset) = tilesets[layer] else {
+                continue;
+            };
+            for ((row, col), tile) in tile_layer.indexed_iter() {
+                let Some(tileset_tile) = tileset.0.get(usize::from(tile.tileset_tile_id())) else {
+                    problems.push(FieldMapChunkProblem::TileNotInTileset {
+                        layer,
+                        row,
+                        col,
+                        tileset_tile_id: tile.tileset_tile_id(),
+                        tileset_len: tileset.0.len(),
+                    });
+                    continue;
+                };
+                let Some(palette) = palette else {
+                    continue;
+                };
+                let max_index = tileset_tile
+                    .0
+                    .iter()
+                    .filter_map(|&pixel| {
+                        palette_index(pixel, tile.palette_offset(), pixel_sizes[layer])
+                    })
+                    .max();
+                if let Some(index) = max_index.filter(|&x| x >= palette.0.len()) {
+                    problems.push(FieldMapChunkProblem::ColorNotInPalette {
+                        layer,
+                        row,
+                        col,
+                        index,
+                    });
+                }
+            }
+        }
+        problems
+    }
+
+    /// Renders the tile layers, drawing each one with the tileset
+    /// and palette of the same index. The first layer is on top.
+    ///
+    /// Layers without a tileset or palette are skipped,
+    /// and so are tiles which aren't in the tileset.
+    pub fn render(
+        &self,
+        tilesets: [Option<&Tileset>; 3],
+    ) -> Result<Grid<Rgba<u8>>, FieldMapRenderError> {
+        let luts = self
+            .palettes
+            .each_ref()
+            .map(|x| x.as_ref().map(PaletteLut::from));
+        self.render_with_luts(tilesets, luts.each_ref().map(Option::as_ref))
+    }
+    /// Like [`Self::render`], but with the colors of each layer taken from `luts`
+    /// instead of [`Self::palettes`], so that they aren't converted on every call.
+    pub fn render_with_luts(
+        &self,
+        tilesets: [Option<&Tileset>; 3],
+        luts: [Option<&PaletteLut>; 3],
+    ) -> Result<Grid<Rgba<u8>>, FieldMapRenderError> {
+        let mut image = Grid::init(
+            usize::from(self.properties.height) * TILE_HEIGHT,
+            usize::from(self.properties.width) * TILE_WIDTH,
+            Rgba::new(0, 0, 0, 0),
+        );
+        let pixel_sizes = self.properties.tilesets_properties.tileset_pixel_sizes();
+        for layer in (0..3).rev() {
+            let (Some(tile_layer), Some(lut), Some(tileset)) =
+                (&self.tile_layers[layer], luts[layer], tilesets[layer])
+            else {
+                continue;
+            };
+            for ((row, col), tile) in tile_layer.indexed_iter() {
+                // Some maps refer to tiles past the end of their tileset,
+                // which the game reads out of whatever is in VRAM.
+                let Some(tileset_tile) = tileset.0.get(usize::from(tile.tileset_tile_id())) else {
+                    continue;
+                };
+                for y in 0..TILE_HEIGHT {
+                    for x in 0..TILE_WIDTH {
+                        let (source_x, source_y) = (
+                            if tile.flipped_horizontally() {
+                                TILE_WIDTH - 1 - x
+                            } else {
+                                x
+                            },
+                            if tile.flipped_vertically() {
+                                TILE_HEIGHT - 1 - y
+                            } else {
+                                y
+                            },
+                        );
+                        let Some(index) = palette_index(
+                            tileset_tile.0[source_y * TILE_WIDTH + source_x],
+                            tile.palette_offset(),
+                            pixel_sizes[layer],
+                        ) else {
+                            continue;
+                        };
+                        let Some(color) = lut.get(index) else {
+                            return Err(FieldMapRenderError::ColorNotInPalette { layer, index });
+                        };
+                        if let Some(target) =
+                            image.get_mut(row * TILE_HEIGHT + y, col * TILE_WIDTH + x)
+                        {
+                            *target = color;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(image)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -449,197 +1203,947 @@ pub struct FieldMaps {
     pub maps: Vec<FieldMap>,
 }
 
-#[derive(Error, Debug)]
-pub enum FieldMapsFromFilesError {
-    #[error(transparent)]
-    TryFromInt(#[from] TryFromIntError),
-    #[error(transparent)]
-    Io(#[from] io::Error),
-}
-#[derive(Error, Debug)]
-pub enum FieldMapsToFilesError {
-    #[error("`self.maps` must contain exactly {expected} elements, not {0}", expected = NUMBER_OF_FIELD_MAPS)]
-    IncorrectNumberOfMaps(usize),
-    #[error(transparent)]
-    Compression(#[from] CompressionError),
-    #[error(transparent)]
-    TryFromInt(#[from] TryFromIntError),
-    #[error(transparent)]
-    Io(#[from] io::Error),
+/// The offset tables and the [`FieldMap`]s of [`FieldMaps`], without any chunks,
+/// for reading single chunks out of `FMapData.dat` and `TreasureInfo.dat` when they're
+/// needed instead of holding all of them in memory.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FieldMapsIndex {
+    /// One more than the number of chunks, the last being the end of the last chunk.
+    pub fmapdata_offsets: Vec<u32>,
+    /// One more than the number of chunks, the last being the end of the last chunk.
+    pub treasure_info_offsets: Vec<u32>,
+    pub maps: Vec<FieldMap>,
 }
 
-impl FieldMaps {
-    pub fn from_files(
-        mut fmapdata: impl Read,
-        mut treasure_info: impl Read,
+impl FieldMapsIndex {
+    pub fn from_overlays(
         mut overlay3: impl Read + Seek,
         mut overlay4: impl Read + Seek,
     ) -> Result<Self, FieldMapsFromFilesError> {
-        overlay3.seek(SeekFrom::Start(FMAPDATA_OFFSET_TABLE_LENGTH_ADDRESS))?;
-        let mut fmapdata_offset_table =
-            vec![0; (usize::try_from(overlay3.read_u32::<LittleEndian>()?)? / 4) - 1];
-        overlay3.read_u32_into::<LittleEndian>(&mut fmapdata_offset_table)?;
-        overlay4.seek(SeekFrom::Start(TREASURE_INFO_OFFSET_TABLE_LENGTH_ADDRESS))?;
-        let mut treasure_info_offset_table =
-            vec![0; (usize::try_from(overlay4.read_u32::<LittleEndian>()?)? / 4) - 1];
-        overlay4.read_u32_into::<LittleEndian>(&mut treasure_info_offset_table)?;
-        overlay3.seek(SeekFrom::Start(FIELD_MAP_CHUNK_TABLE_ADDRESS))?;
+        let in_file = |file| move |source| FieldMapsFromFilesError::File { file, source };
+
+        let fmapdata_offsets = (|| {
+            overlay3.seek(SeekFrom::Start(FMAPDATA_OFFSET_TABLE_LENGTH_ADDRESS))?;
+            let length = overlay3.read_u32::<LittleEndian>()?;
+            let mut buf = vec![0; (length / 4).saturating_sub(1) as usize];
+            overlay3.read_u32_into::<LittleEndian>(&mut buf)?;
+            Ok(buf)
+        })()
+        .map_err(in_file(FieldMapsFile::Overlay3))?;
+        let treasure_info_offsets = (|| {
+            overlay4.seek(SeekFrom::Start(TREASURE_INFO_OFFSET_TABLE_LENGTH_ADDRESS))?;
+            let length = overlay4.read_u32::<LittleEndian>()?;
+            let mut buf = vec![0; (length / 4).saturating_sub(1) as usize];
+            overlay4.read_u32_into::<LittleEndian>(&mut buf)?;
+            Ok(buf)
+        })()
+        .map_err(in_file(FieldMapsFile::Overlay4))?;
         let mut chunk_table = [0; NUMBER_OF_FIELD_MAPS * 5];
-        overlay3.read_u32_into::<LittleEndian>(&mut chunk_table)?;
+        (|| {
+            overlay3.seek(SeekFrom::Start(FIELD_MAP_CHUNK_TABLE_ADDRESS))?;
+            overlay3.read_u32_into::<LittleEndian>(&mut chunk_table)
+        })()
+        .map_err(in_file(FieldMapsFile::Overlay3))?;
 
         Ok(Self {
-            fmapdata_chunks: fmapdata_offset_table
-                .windows(2)
-                .map(|offset_pair| -> Result<_, FieldMapsFromFilesError> {
-                    let (current_offset, next_offset) = (offset_pair[0], offset_pair[1]);
-                    let mut buf = vec![0u8; (next_offset - current_offset).try_into()?];
-                    fmapdata.read_exact(&mut buf)?;
-                    Ok(MaybeCompressedData::Compressed(buf))
-                })
-                .collect::<Result<Vec<_>, _>>()?,
-            fmapdata_padding: {
-                let mut buf: Vec<u8> = Vec::new();
-                fmapdata.read_to_end(&mut buf)?;
-                buf
-            },
-            treasure_data: treasure_info_offset_table
-                .windows(2)
-                .map(|offset_pair| -> Result<_, FieldMapsFromFilesError> {
-                    let (current_offset, next_offset) = (offset_pair[0], offset_pair[1]);
-                    let mut buf = vec![0u8; (next_offset - current_offset).try_into()?];
-                    treasure_info.read_exact(&mut buf)?;
-                    Ok(buf)
-                })
-                .collect::<Result<Vec<_>, _>>()?,
-            treasure_info_padding: {
-                let mut buf: Vec<u8> = Vec::new();
-                treasure_info.read_to_end(&mut buf)?;
-                buf
-            },
+            fmapdata_offsets,
+            treasure_info_offsets,
             maps: chunk_table
                 .chunks_exact(5)
-                .map(|map| -> Result<_, FieldMapsFromFilesError> {
-                    Ok(FieldMap {
-                        tileset_indexes: [
-                            u32_or_max_to_option_try_into(map[0])?,
-                            u32_or_max_to_option_try_into(map[1])?,
-                            u32_or_max_to_option_try_into(map[2])?,
-                        ],
-                        map_chunk_index: map[3].try_into()?,
-                        treasure_data_index: u32_or_max_to_option_try_into(map[4])?,
-                    })
+                .enumerate()
+                .map(|(map_index, map)| {
+                    (|| {
+                        Ok(FieldMap {
+                            tileset_indexes: [
+                                u32_or_max_to_option_try_into(map[0])?,
+                                u32_or_max_to_option_try_into(map[1])?,
+                                u32_or_max_to_option_try_into(map[2])?,
+                            ],
+                            map_chunk_index: map[3].try_into()?,
+                            treasure_data_index: u32_or_max_to_option_try_into(map[4])?,
+                        })
+                    })()
+                    .map_err(|source| FieldMapsFromFilesError::MapEntry { map_index, source })
                 })
                 .collect::<Result<Vec<_>, _>>()?,
         })
     }
+    /// Reads only the overlays.
+    #[cfg(feature = "fs")]
+    pub fn load_from(paths: &ProjectPaths) -> Result<Self, FieldMapsFromFilesError> {
+        let in_file = |file| move |source| FieldMapsFromFilesError::File { file, source };
+        Self::from_overlays(
+            Cursor::new(
+                paths
+                    .read_overlay(3)
+                    .map_err(in_file(FieldMapsFile::Overlay3))?,
+            ),
+            Cursor::new(
+                paths
+                    .read_overlay(4)
+                    .map_err(in_file(FieldMapsFile::Overlay4))?,
+            ),
+        )
+    }
 
-    pub fn to_files(
+    #[inline]
+    pub fn num_fmapdata_chunks(&self) -> usize {
+        self.fmapdata_offsets.len().saturating_sub(1)
+    }
+    #[inline]
+    pub fn num_treasure_data(&self) -> usize {
+        self.treasure_info_offsets.len().saturating_sub(1)
+    }
+
+    /// Seeks to and reads chunk `index` of `fmapdata`, which is like
+    /// [`FieldMaps::fmapdata_chunks`], including its padding.
+    pub fn read_fmapdata_chunk(
         &self,
-        mut fmapdata: impl Write,
+        fmapdata: impl Read + Seek,
+        index: usize,
+    ) -> Result<MaybeCompressedData, FieldMapsFromFilesError> {
+        Self::read_chunk(
+            fmapdata,
+            FieldMapsFile::FMapData,
+            &self.fmapdata_offsets,
+            index,
+        )
+        .map(MaybeCompressedData::Compressed)
+    }
+    /// Seeks to and reads entry `index` of `treasure_info`, which is like
+    /// [`FieldMaps::treasure_data`].
+    pub fn read_treasure_data(
+        &self,
+        treasure_info: impl Read + Seek,
+        index: usize,
+    ) -> Result<Vec<u8>, FieldMapsFromFilesError> {
+        Self::read_chunk(
+            treasure_info,
+            FieldMapsFile::TreasureInfo,
+            &self.treasure_info_offsets,
+            index,
+        )
+    }
+    /// Reads all chunks of `fmapdata` into a single buffer,
+    /// each still compressed and including its padding.
+    pub fn read_fmapdata_packed(
+        &self,
+        fmapdata: impl Read + Seek,
+    ) -> Result<PackedChunks, FieldMapsFromFilesError> {
+        Self::read_packed(fmapdata, FieldMapsFile::FMapData, &self.fmapdata_offsets)
+    }
+    /// Reads all entries of `treasure_info` into a single buffer.
+    pub fn read_treasure_data_packed(
+        &self,
+        treasure_info: impl Read + Seek,
+    ) -> Result<PackedChunks, FieldMapsFromFilesError> {
+        Self::read_packed(
+            treasure_info,
+            FieldMapsFile::TreasureInfo,
+            &self.treasure_info_offsets,
+        )
+    }
+    fn read_packed(
+        mut inp: impl Read + Seek,
+        file: FieldMapsFile,
+        offset_table: &[u32],
+    ) -> Result<PackedChunks, FieldMapsFromFilesError> {
+        let sizes = offset_table
+            .iter()
+            .tuple_windows()
+            .map(|(&offset, &next_offset)| usize::try_from(next_offset.saturating_sub(offset)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut chunks = PackedChunks::with_capacity(sizes.len(), sizes.iter().sum());
+        for (index, (&offset, size)) in offset_table.iter().zip(sizes).enumerate() {
+            inp.seek(SeekFrom::Start(offset.into()))
+                .and_then(|_| chunks.push_from_reader(&mut inp, size))
+                .map_err(|source| FieldMapsFromFilesError::Chunk {
+                    file,
+                    index,
+                    offset,
+                    source,
+                })?;
+        }
+        Ok(chunks)
+    }
+    fn read_chunk(
+        mut inp: impl Read + Seek,
+        file: FieldMapsFile,
+        offset_table: &[u32],
+        index: usize,
+    ) -> Result<Vec<u8>, FieldMapsFromFilesError> {
+        let (Some(&offset), Some(&next_offset)) =
+            (offset_table.get(index), offset_table.get(index + 1))
+        else {
+            return Err(FieldMapsFromFilesError::NoSuchChunk { file, index });
+        };
+        let mut buf = vec![0u8; next_offset.saturating_sub(offset).try_into()?];
+        inp.seek(SeekFrom::Start(offset.into()))
+            .and_then(|_| inp.read_exact(&mut buf))
+            .map_err(|source| FieldMapsFromFilesError::Chunk {
+                file,
+                index,
+                offset,
+                source,
+            })?;
+        Ok(buf)
+    }
+}
+
+/// The files which [`FieldMaps`] is stored in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FieldMapsFile {
+    FMapData,
+    TreasureInfo,
+    Overlay3,
+    Overlay4,
+}
+
+impl Display for FieldMapsFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::FMapData => "FMapData.dat",
+            Self::TreasureInfo => "TreasureInfo.dat",
+            Self::Overlay3 => "overlay 3",
+            Self::Overlay4 => "overlay 4",
+        })
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FieldMapsFromFilesError {
+    #[error("failed to read {file}")]
+    File {
+        file: FieldMapsFile,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to read chunk {index} of {file} (at offset {offset:#X})")]
+    Chunk {
+        file: FieldMapsFile,
+        index: usize,
+        offset: u32,
+        #[source]
+        source: io::Error,
+    },
+    #[error("chunk {index} of {file} doesn't exist")]
+    NoSuchChunk { file: FieldMapsFile, index: usize },
+    #[error(
+        "offset {index} of {file} ({offset:#X}) is smaller than the previous one ({previous_offset:#X})"
+    )]
+    DecreasingOffset {
+        file: FieldMapsFile,
+        index: usize,
+        offset: u32,
+        previous_offset: u32,
+    },
+    #[error("the entry of map {map_index} in overlay 3 is invalid")]
+    MapEntry {
+        map_index: usize,
+        #[source]
+        source: TryFromIntError,
+    },
+    #[error(transparent)]
+    TryFromInt(#[from] TryFromIntError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+/// The sizes in bytes of what [`FieldMaps::to_files`] would write,
+/// as returned by [`FieldMaps::computed_sizes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FieldMapsSizes {
+    pub fmapdata: usize,
+    pub treasure_info: usize,
+    /// The length stored in overlay 3 for the offset table of FMapData,
+    /// which includes the length itself.
+    pub fmapdata_offset_table: u32,
+    /// The length stored in overlay 4 for the offset table of TreasureInfo.dat,
+    /// which includes the length itself.
+    pub treasure_info_offset_table: u32,
+}
+
+/// What had to be worked around in each file by [`FieldMaps::from_files_lenient`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldMapsSalvageReport {
+    pub fmapdata: SalvageReport,
+    pub treasure_info: SalvageReport,
+}
+
+impl FieldMapsSalvageReport {
+    /// Whether nothing had to be worked around in any file.
+    #[inline]
+    pub fn is_clean(&self) -> bool {
+        self.fmapdata.is_clean() && self.treasure_info.is_clean()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FieldMapsToFilesError {
+    #[error("`self.maps` must contain exactly {expected} elements, not {0}", expected = NUMBER_OF_FIELD_MAPS)]
+    IncorrectNumberOfMaps(usize),
+    #[error("failed to write {file}")]
+    File {
+        file: FieldMapsFile,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to compress chunk {index} of {file}", file = FieldMapsFile::FMapData)]
+    ChunkCompression {
+        index: usize,
+        #[source]
+        source: CompressionError,
+    },
+    #[error("the indexes of map {map_index} don't fit into overlay 3")]
+    MapEntry {
+        map_index: usize,
+        #[source]
+        source: TryFromIntError,
+    },
+    #[error("chunk {index} of {file} ends past the largest possible offset ({limit:#X})", limit = u32::MAX)]
+    OffsetOverflow { file: FieldMapsFile, index: usize },
+    #[error(transparent)]
+    TryFromInt(#[from] TryFromIntError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+#[derive(Error, Debug)]
+pub enum FieldMapsRenderError {
+    #[error("field map {0} doesn't exist")]
+    NoSuchMap(usize),
+    #[error("chunk {0} of FMapData doesn't exist")]
+    NoSuchChunk(usize),
+    #[error("failed to decompress chunk {index} of FMapData")]
+    ChunkDecompression {
+        index: usize,
+        #[source]
+        source: DecompressionError,
+    },
+    #[error("failed to deserialize the tileset in chunk {index} of FMapData")]
+    TilesetDeserialization {
+        index: usize,
+        #[source]
+        source: TilesetTileDeserializationError,
+    },
+    #[error(transparent)]
+    DataWithOffsetTableDeserialization(#[from] DataWithOffsetTableDeserializationError),
+    #[error(transparent)]
+    FieldMapChunkFromTable(#[from] FieldMapChunkFromTableError),
+    #[error(transparent)]
+    Render(#[from] FieldMapRenderError),
+}
+#[derive(Error, Debug)]
+pub enum FieldMapsRemoveError {
+    #[error("the chunk is still referenced by map {map_index}")]
+    ChunkInUse { map_index: usize },
+}
+
+impl FieldMap {
+    fn remap_fmapdata_indexes(&mut self, remap: IndexRemap) {
+        for tileset_index in self.tileset_indexes.iter_mut().flatten() {
+            *tileset_index = remap.apply(*tileset_index).unwrap();
+        }
+        self.map_chunk_index = remap.apply(self.map_chunk_index).unwrap();
+    }
+}
+
+impl FieldMaps {
+    /// Inserts `chunk` into `self.fmapdata_chunks` at `index`,
+    /// updating the references to the chunks after it in `self.maps`.
+    pub fn insert_fmapdata_chunk(
+        &mut self,
+        index: usize,
+        chunk: MaybeCompressedData,
+    ) -> IndexRemap {
+        self.fmapdata_chunks.insert(index, chunk);
+        let remap = IndexRemap::Inserted(index);
+        for map in &mut self.maps {
+            map.remap_fmapdata_indexes(remap);
+        }
+        remap
+    }
+    /// Removes the chunk at `index` from `self.fmapdata_chunks`,
+    /// updating the references to the chunks after it in `self.maps`.
+    ///
+    /// Fails without modifying anything if any map still references the chunk.
+    pub fn remove_fmapdata_chunk(
+        &mut self,
+        index: usize,
+    ) -> Result<(MaybeCompressedData, IndexRemap), FieldMapsRemoveError> {
+        if let Some(map_index) = self.maps.iter().position(|map| {
+            map.map_chunk_index == index || map.tileset_indexes.contains(&Some(index))
+        }) {
+            return Err(FieldMapsRemoveError::ChunkInUse { map_index });
+        }
+        let chunk = self.fmapdata_chunks.remove(index);
+        let remap = IndexRemap::Removed(index);
+        for map in &mut self.maps {
+            map.remap_fmapdata_indexes(remap);
+        }
+        Ok((chunk, remap))
+    }
+
+    /// Inserts `data` into `self.treasure_data` at `index`,
+    /// updating the references to the entries after it in `self.maps`.
+    pub fn insert_treasure_data(&mut self, index: usize, data: Vec<u8>) -> IndexRemap {
+        self.treasure_data.insert(index, data);
+        let remap = IndexRemap::Inserted(index);
+        for treasure_data_index in self
+            .maps
+            .iter_mut()
+            .flat_map(|x| &mut x.treasure_data_index)
+        {
+            *treasure_data_index = remap.apply(*treasure_data_index).unwrap();
+        }
+        remap
+    }
+    /// Removes the entry at `index` from `self.treasure_data`,
+    /// updating the references to the entries after it in `self.maps`.
+    ///
+    /// Fails without modifying anything if any map still references the entry.
+    pub fn remove_treasure_data(
+        &mut self,
+        index: usize,
+    ) -> Result<(Vec<u8>, IndexRemap), FieldMapsRemoveError> {
+        if let Some(map_index) = self
+            .maps
+            .iter()
+            .position(|map| map.treasure_data_index == Some(index))
+        {
+            return Err(FieldMapsRemoveError::ChunkInUse { map_index });
+        }
+        let data = self.treasure_data.remove(index);
+        let remap = IndexRemap::Removed(index);
+        for treasure_data_index in self
+            .maps
+            .iter_mut()
+            .flat_map(|x| &mut x.treasure_data_index)
+        {
+            *treasure_data_index = remap.apply(*treasure_data_index).unwrap();
+        }
+        Ok((data, remap))
+    }
+
+    /// Decompresses and deserializes the map chunk and tilesets of `map_index`.
+    #[inline]
+    pub fn decode_map(
+        &self,
+        map_index: usize,
+    ) -> Result<(FieldMapChunk, [Option<Tileset>; 3]), FieldMapsRenderError> {
+        self.decode_map_with(map_index, |chunk| chunk.to_uncompressed(true))
+    }
+    /// Like [`Self::decode_map`], but the chunks are decompressed through `cache`,
+    /// so that revisiting a map doesn't decompress its chunks again.
+    #[inline]
+    pub fn decode_map_cached(
+        &self,
+        map_index: usize,
+        cache: &mut DecompressedChunkCache,
+    ) -> Result<(FieldMapChunk, [Option<Tileset>; 3]), FieldMapsRenderError> {
+        self.decode_map_with(map_index, |chunk| cache.get(chunk, true))
+    }
+    fn decode_map_with<'a, D: std::ops::Deref<Target = [u8]>>(
+        &'a self,
+        map_index: usize,
+        mut decompress: impl FnMut(&'a MaybeCompressedData) -> Result<D, DecompressionError>,
+    ) -> Result<(FieldMapChunk, [Option<Tileset>; 3]), FieldMapsRenderError> {
+        let map = self
+            .maps
+            .get(map_index)
+            .ok_or(FieldMapsRenderError::NoSuchMap(map_index))?;
+        let mut chunk_data = |index: usize| {
+            decompress(
+                self.fmapdata_chunks
+                    .get(index)
+                    .ok_or(FieldMapsRenderError::NoSuchChunk(index))?,
+            )
+            .map_err(|source| FieldMapsRenderError::ChunkDecompression { index, source })
+        };
+        let map_chunk = FieldMapChunk::try_from(DataWithOffsetTable::from_reader(
+            &chunk_data(map.map_chunk_index)?[..],
+        )?)?;
+        let pixel_sizes = map_chunk
+            .properties
+            .tilesets_properties
+            .tileset_pixel_sizes();
+        let mut tilesets: [Option<Tileset>; 3] = Default::default();
+        for (layer, tileset_index) in map.tileset_indexes.iter().enumerate() {
+            let Some(index) = *tileset_index else {
+                continue;
+            };
+            tilesets[layer] = Some(
+                Tileset::from_bytes(&chunk_data(index)?, pixel_sizes[layer]).map_err(|source| {
+                    FieldMapsRenderError::TilesetDeserialization { index, source }
+                })?,
+            );
+        }
+        Ok((map_chunk, tilesets))
+    }
+    /// [Decodes](Self::decode_map) the map `map_index`
+    /// and [renders](FieldMapChunk::render) it.
+    pub fn render_map(&self, map_index: usize) -> Result<Grid<Rgba<u8>>, FieldMapsRenderError> {
+        let (map_chunk, tilesets) = self.decode_map(map_index)?;
+        Ok(map_chunk.render(tilesets.each_ref().map(Option::as_ref))?)
+    }
+    /// Like [`Self::render_map`], but with [`Self::decode_map_cached`].
+    pub fn render_map_cached(
+        &self,
+        map_index: usize,
+        cache: &mut DecompressedChunkCache,
+    ) -> Result<Grid<Rgba<u8>>, FieldMapsRenderError> {
+        let (map_chunk, tilesets) = self.decode_map_cached(map_index, cache)?;
+        Ok(map_chunk.render(tilesets.each_ref().map(Option::as_ref))?)
+    }
+
+    #[inline]
+    pub fn from_files(
+        fmapdata: impl Read,
+        treasure_info: impl Read,
+        overlay3: impl Read + Seek,
+        overlay4: impl Read + Seek,
+    ) -> Result<Self, FieldMapsFromFilesError> {
+        Self::read_files(fmapdata, treasure_info, overlay3, overlay4, None)
+    }
+    /// Like [`Self::from_files`], but recovers as much as possible
+    /// from truncated or partially corrupted FMapData and TreasureInfo.dat,
+    /// the same way as [`DataWithOffsetTable::from_reader_lenient`].
+    /// The overlays still have to be intact.
+    pub fn from_files_lenient(
+        fmapdata: impl Read,
+        treasure_info: impl Read,
+        overlay3: impl Read + Seek,
+        overlay4: impl Read + Seek,
+    ) -> Result<(Self, FieldMapsSalvageReport), FieldMapsFromFilesError> {
+        let mut report = FieldMapsSalvageReport::default();
+        let field_maps = Self::read_files(
+            fmapdata,
+            treasure_info,
+            overlay3,
+            overlay4,
+            Some(&mut report),
+        )?;
+        Ok((field_maps, report))
+    }
+    fn read_files(
+        fmapdata: impl Read,
+        treasure_info: impl Read,
+        overlay3: impl Read + Seek,
+        overlay4: impl Read + Seek,
+        mut report: Option<&mut FieldMapsSalvageReport>,
+    ) -> Result<Self, FieldMapsFromFilesError> {
+        let index = FieldMapsIndex::from_overlays(overlay3, overlay4)?;
+        let (fmapdata_chunks, fmapdata_padding) = Self::read_chunks(
+            fmapdata,
+            FieldMapsFile::FMapData,
+            &index.fmapdata_offsets,
+            report.as_deref_mut().map(|x| &mut x.fmapdata),
+        )?;
+        let (treasure_data, treasure_info_padding) = Self::read_chunks(
+            treasure_info,
+            FieldMapsFile::TreasureInfo,
+            &index.treasure_info_offsets,
+            report.map(|x| &mut x.treasure_info),
+        )?;
+
+        Ok(Self {
+            fmapdata_chunks: fmapdata_chunks
+                .into_iter()
+                .map(MaybeCompressedData::Compressed)
+                .collect(),
+            fmapdata_padding,
+            treasure_data,
+            treasure_info_padding,
+            maps: index.maps,
+        })
+    }
+
+    /// Returns the chunks and the padding after them.
+    fn read_chunks(
+        mut inp: impl Read,
+        file: FieldMapsFile,
+        offset_table: &[u32],
+        report: Option<&mut SalvageReport>,
+    ) -> Result<(Vec<Vec<u8>>, Vec<u8>), FieldMapsFromFilesError> {
+        let in_file = |source| FieldMapsFromFilesError::File { file, source };
+
+        if let Some(report) = report {
+            let mut data: Vec<u8> = Vec::new();
+            inp.read_to_end(&mut data).map_err(in_file)?;
+            return Ok(salvage_chunks(
+                &data,
+                offset_table.first().copied().unwrap_or(0),
+                offset_table,
+                report,
+            ));
+        }
+        let chunks = offset_table
+            .windows(2)
+            .enumerate()
+            .map(|(index, offset_pair)| {
+                let (current_offset, next_offset) = (offset_pair[0], offset_pair[1]);
+                let size = next_offset.checked_sub(current_offset).ok_or(
+                    FieldMapsFromFilesError::DecreasingOffset {
+                        file,
+                        index: index + 1,
+                        offset: next_offset,
+                        previous_offset: current_offset,
+                    },
+                )?;
+                let mut buf = vec![0u8; size.try_into()?];
+                inp.read_exact(&mut buf)
+                    .map_err(|source| FieldMapsFromFilesError::Chunk {
+                        file,
+                        index,
+                        offset: current_offset,
+                        source,
+                    })?;
+                Ok(buf)
+            })
+            .collect::<Result<_, FieldMapsFromFilesError>>()?;
+        let mut padding: Vec<u8> = Vec::new();
+        inp.read_to_end(&mut padding).map_err(in_file)?;
+        Ok((chunks, padding))
+    }
+
+    #[inline]
+    pub fn to_files(
+        &self,
+        fmapdata: impl Write,
+        treasure_info: impl Write,
+        overlay3: impl Write + Seek,
+        overlay4: impl Write + Seek,
+        align_files: bool,
+    ) -> Result<(), FieldMapsToFilesError> {
+        self.to_files_with_cache(
+            fmapdata,
+            treasure_info,
+            overlay3,
+            overlay4,
+            align_files,
+            None,
+        )
+    }
+    /// Like [`Self::to_files`], but the chunks which have to be compressed
+    /// are looked up in `cache` first.
+    pub fn to_files_with_cache(
+        &self,
+        mut fmapdata: impl Write,
         mut treasure_info: impl Write,
         mut overlay3: impl Write + Seek,
         mut overlay4: impl Write + Seek,
         align_files: bool,
+        cache: Option<&CompressionCache>,
     ) -> Result<(), FieldMapsToFilesError> {
         let maps_len = self.maps.len();
         if maps_len != NUMBER_OF_FIELD_MAPS {
             return Err(FieldMapsToFilesError::IncorrectNumberOfMaps(maps_len));
         }
 
-        overlay3.seek(SeekFrom::Start(FMAPDATA_OFFSET_TABLE_LENGTH_ADDRESS))?;
-        overlay3.write_u32::<LittleEndian>((u32::try_from(self.fmapdata_chunks.len())? + 2) * 4)?;
-        let mut current_fmapdata_offset = 0;
-        overlay3.write_u32::<LittleEndian>(current_fmapdata_offset)?;
-        for chunk in &self.fmapdata_chunks {
-            let data = chunk.to_compressed()?;
-            fmapdata.write_all(&data)?;
-            let padding =
-                necessary_padding_for(data.len(), STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT);
-            fmapdata.write_all(&vec![0u8; padding])?;
-            current_fmapdata_offset += u32::try_from(data.len() + padding)?;
-            overlay3.write_u32::<LittleEndian>(current_fmapdata_offset)?;
-        }
-        if align_files {
-            fmapdata.write_all(&vec![
-                0u8;
-                necessary_padding_for(
-                    current_fmapdata_offset.try_into()?,
-                    STANDARD_FILE_ALIGNMENT
-                )
-            ])?;
-        } else {
-            fmapdata.write_all(&self.fmapdata_padding)?;
-        }
-        overlay4.seek(SeekFrom::Start(TREASURE_INFO_OFFSET_TABLE_LENGTH_ADDRESS))?;
-        overlay4.write_u32::<LittleEndian>((u32::try_from(self.treasure_data.len())? + 2) * 4)?;
-        let mut current_treasure_info_offset = 0;
-        overlay4.write_u32::<LittleEndian>(current_treasure_info_offset)?;
-        for chunk in &self.treasure_data {
-            treasure_info.write_all(chunk)?;
+        let fmapdata_chunks =
+            MaybeCompressedData::to_compressed_parallel(&self.fmapdata_chunks, cache).map_err(
+                |ChunksCompressionError::Chunk { index, source }| {
+                    FieldMapsToFilesError::ChunkCompression { index, source }
+                },
+            )?;
+        let fmapdata_offsets = Self::write_chunks(
+            &mut fmapdata,
+            FieldMapsFile::FMapData,
+            fmapdata_chunks.iter().map(|x| &x[..]),
+            align_files,
+            &self.fmapdata_padding,
+        )?;
+        Self::write_offset_table(
+            &mut overlay3,
+            FieldMapsFile::Overlay3,
+            FMAPDATA_OFFSET_TABLE_LENGTH_ADDRESS,
+            &fmapdata_offsets,
+        )?;
+        let treasure_info_offsets = Self::write_chunks(
+            &mut treasure_info,
+            FieldMapsFile::TreasureInfo,
+            self.treasure_data.iter().map(|x| &x[..]),
+            align_files,
+            &self.treasure_info_padding,
+        )?;
+        Self::write_offset_table(
+            &mut overlay4,
+            FieldMapsFile::Overlay4,
+            TREASURE_INFO_OFFSET_TABLE_LENGTH_ADDRESS,
+            &treasure_info_offsets,
+        )?;
+
+        let in_overlay3 = |source| FieldMapsToFilesError::File {
+            file: FieldMapsFile::Overlay3,
+            source,
+        };
+        overlay3
+            .seek(SeekFrom::Start(FIELD_MAP_CHUNK_TABLE_ADDRESS))
+            .map_err(in_overlay3)?;
+        for (map_index, map) in self.maps.iter().enumerate() {
+            let entry = (|| {
+                Ok([
+                    option_to_u32_or_max_try_into(map.tileset_indexes[0])?,
+                    option_to_u32_or_max_try_into(map.tileset_indexes[1])?,
+                    option_to_u32_or_max_try_into(map.tileset_indexes[2])?,
+                    map.map_chunk_index.try_into()?,
+                    option_to_u32_or_max_try_into(map.treasure_data_index)?,
+                ])
+            })()
+            .map_err(|source| FieldMapsToFilesError::MapEntry { map_index, source })?;
+            for value in entry {
+                overlay3
+                    .write_u32::<LittleEndian>(value)
+                    .map_err(in_overlay3)?;
+            }
+        }
+
+        Ok(())
+    }
+    /// Writes the chunks to `out`, each padded, and returns their offsets
+    /// followed by the end offset.
+    fn write_chunks<'c>(
+        mut out: impl Write,
+        file: FieldMapsFile,
+        chunks: impl IntoIterator<Item = &'c [u8]>,
+        align_file: bool,
+        file_padding: &[u8],
+    ) -> Result<Vec<u32>, FieldMapsToFilesError> {
+        let in_file = |source| FieldMapsToFilesError::File { file, source };
+        let mut current_offset = 0u32;
+        let mut offsets = vec![current_offset];
+        for (index, chunk) in chunks.into_iter().enumerate() {
             let padding =
                 necessary_padding_for(chunk.len(), STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT);
-            fmapdata.write_all(&vec![0u8; padding])?;
-            current_treasure_info_offset += u32::try_from(chunk.len() + padding)?;
-            overlay4.write_u32::<LittleEndian>(current_treasure_info_offset)?;
-        }
-        if align_files {
-            treasure_info.write_all(&vec![
-                0u8;
-                necessary_padding_for(
-                    current_treasure_info_offset.try_into()?,
-                    STANDARD_FILE_ALIGNMENT
-                )
-            ])?;
+            current_offset = u32::try_from(chunk.len() + padding)
+                .ok()
+                .and_then(|size| current_offset.checked_add(size))
+                .ok_or(FieldMapsToFilesError::OffsetOverflow { file, index })?;
+            out.write_all(chunk).map_err(in_file)?;
+            write_zeros(&mut out, padding).map_err(in_file)?;
+            offsets.push(current_offset);
+        }
+        if align_file {
+            write_zeros(
+                &mut out,
+                necessary_padding_for(current_offset.try_into()?, STANDARD_FILE_ALIGNMENT),
+            )
+            .map_err(in_file)?;
         } else {
-            treasure_info.write_all(&self.treasure_info_padding)?;
+            out.write_all(file_padding).map_err(in_file)?;
+        }
+        Ok(offsets)
+    }
+    /// Computes the sizes of the files [`Self::to_files_with_cache`] would write,
+    /// failing the same way it would, without writing anything.
+    ///
+    /// The chunks of FMapData still have to be compressed for this,
+    /// which `cache` can speed up.
+    pub fn computed_sizes(
+        &self,
+        align_files: bool,
+        cache: Option<&CompressionCache>,
+    ) -> Result<FieldMapsSizes, FieldMapsToFilesError> {
+        let maps_len = self.maps.len();
+        if maps_len != NUMBER_OF_FIELD_MAPS {
+            return Err(FieldMapsToFilesError::IncorrectNumberOfMaps(maps_len));
         }
 
-        overlay3.seek(SeekFrom::Start(FIELD_MAP_CHUNK_TABLE_ADDRESS))?;
-        for map in &self.maps {
-            for tileset_index in map.tileset_indexes {
-                overlay3
-                    .write_u32::<LittleEndian>(option_to_u32_or_max_try_into(tileset_index)?)?;
+        let fmapdata_chunks =
+            MaybeCompressedData::to_compressed_parallel(&self.fmapdata_chunks, cache).map_err(
+                |ChunksCompressionError::Chunk { index, source }| {
+                    FieldMapsToFilesError::ChunkCompression { index, source }
+                },
+            )?;
+        let mut fmapdata = ByteCounter::default();
+        let fmapdata_offsets = Self::write_chunks(
+            &mut fmapdata,
+            FieldMapsFile::FMapData,
+            fmapdata_chunks.iter().map(|x| &x[..]),
+            align_files,
+            &self.fmapdata_padding,
+        )?;
+        let mut treasure_info = ByteCounter::default();
+        let treasure_info_offsets = Self::write_chunks(
+            &mut treasure_info,
+            FieldMapsFile::TreasureInfo,
+            self.treasure_data.iter().map(|x| &x[..]),
+            align_files,
+            &self.treasure_info_padding,
+        )?;
+        Ok(FieldMapsSizes {
+            fmapdata: fmapdata.0,
+            treasure_info: treasure_info.0,
+            fmapdata_offset_table: Self::offset_table_length(&fmapdata_offsets)?,
+            treasure_info_offset_table: Self::offset_table_length(&treasure_info_offsets)?,
+        })
+    }
+    fn offset_table_length(offsets: &[u32]) -> Result<u32, TryFromIntError> {
+        u32::try_from((offsets.len() + 1) * 4)
+    }
+    fn write_offset_table(
+        mut overlay: impl Write + Seek,
+        file: FieldMapsFile,
+        length_address: u64,
+        offsets: &[u32],
+    ) -> Result<(), FieldMapsToFilesError> {
+        let length = Self::offset_table_length(offsets)?;
+        (|| {
+            overlay.seek(SeekFrom::Start(length_address))?;
+            overlay.write_u32::<LittleEndian>(length)?;
+            for &offset in offsets {
+                overlay.write_u32::<LittleEndian>(offset)?;
             }
-            overlay3.write_u32::<LittleEndian>(map.map_chunk_index.try_into()?)?;
-            overlay3.write_u32::<LittleEndian>(option_to_u32_or_max_try_into(
-                map.treasure_data_index,
-            )?)?;
-        }
+            Ok(())
+        })()
+        .map_err(|source| FieldMapsToFilesError::File { file, source })
+    }
 
+    /// Streams the chunks out of the files, instead of reading them whole first
+    /// like [`Self::load_from_vfs`] does.
+    #[cfg(feature = "fs")]
+    pub fn load_from(paths: &ProjectPaths) -> Result<Self, FieldMapsFromFilesError> {
+        let in_file = |file| move |source| FieldMapsFromFilesError::File { file, source };
+        Self::from_files(
+            BufReader::new(
+                File::open(paths.data_path("FMap/FMapData.dat"))
+                    .map_err(in_file(FieldMapsFile::FMapData))?,
+            ),
+            BufReader::new(
+                File::open(paths.data_path("Treasure/TreasureInfo.dat"))
+                    .map_err(in_file(FieldMapsFile::TreasureInfo))?,
+            ),
+            Cursor::new(
+                paths
+                    .read_overlay(3)
+                    .map_err(in_file(FieldMapsFile::Overlay3))?,
+            ),
+            Cursor::new(
+                paths
+                    .read_overlay(4)
+                    .map_err(in_file(FieldMapsFile::Overlay4))?,
+            ),
+        )
+    }
+    pub fn load_from_vfs(vfs: &(impl Vfs + ?Sized)) -> Result<Self, FieldMapsFromFilesError> {
+        let in_file = |file| move |source| FieldMapsFromFilesError::File { file, source };
+        Self::from_files(
+            &vfs.read_file("FMap/FMapData.dat")
+                .map_err(in_file(FieldMapsFile::FMapData))?[..],
+            &vfs.read_file("Treasure/TreasureInfo.dat")
+                .map_err(in_file(FieldMapsFile::TreasureInfo))?[..],
+            Cursor::new(
+                vfs.read_overlay(3)
+                    .map_err(in_file(FieldMapsFile::Overlay3))?,
+            ),
+            Cursor::new(
+                vfs.read_overlay(4)
+                    .map_err(in_file(FieldMapsFile::Overlay4))?,
+            ),
+        )
+    }
+    /// Reads the files straight out of a ROM, instead of an extracted project.
+    #[inline]
+    pub fn load_from_rom(rom: &NdsRom) -> Result<Self, FieldMapsFromFilesError> {
+        Self::load_from_vfs(rom)
+    }
+    /// Replaces the files in a ROM, which can then be [serialized](NdsRom::to_bytes).
+    pub fn save_to_rom(
+        &self,
+        rom: &mut NdsRom,
+        align_files: bool,
+    ) -> Result<(), FieldMapsToFilesError> {
+        let mut fmapdata = Vec::new();
+        let mut treasure_info = Vec::new();
+        let mut overlay3 = Cursor::new(rom.overlay(3)?.to_vec());
+        let mut overlay4 = Cursor::new(rom.overlay(4)?.to_vec());
+        self.to_files(
+            &mut fmapdata,
+            &mut treasure_info,
+            &mut overlay3,
+            &mut overlay4,
+            align_files,
+        )?;
+        rom.replace_file("FMap/FMapData.dat", fmapdata)?;
+        rom.replace_file("Treasure/TreasureInfo.dat", treasure_info)?;
+        rom.replace_overlay(3, overlay3.into_inner())?;
+        rom.replace_overlay(4, overlay4.into_inner())?;
+        Ok(())
+    }
+    #[cfg(feature = "fs")]
+    pub fn save_to(
+        &self,
+        paths: &ProjectPaths,
+        align_files: bool,
+        options: &SaveOptions,
+    ) -> Result<(), FieldMapsToFilesError> {
+        let (pending, [fmapdata, treasure_info], [overlay3, overlay4]) = options.open_files(
+            [
+                &paths.data_path("FMap/FMapData.dat"),
+                &paths.data_path("Treasure/TreasureInfo.dat"),
+            ],
+            [&paths.overlay_path(3), &paths.overlay_path(4)],
+        )?;
+        // The offset tables are written one entry at a time.
+        let [mut fmapdata, mut treasure_info, mut overlay3, mut overlay4] =
+            [fmapdata, treasure_info, overlay3, overlay4].map(BufWriter::new);
+        self.to_files(
+            &mut fmapdata,
+            &mut treasure_info,
+            &mut overlay3,
+            &mut overlay4,
+            align_files,
+        )?;
+        for (mut out, file) in [fmapdata, treasure_info, overlay3, overlay4]
+            .into_iter()
+            .zip([
+                FieldMapsFile::FMapData,
+                FieldMapsFile::TreasureInfo,
+                FieldMapsFile::Overlay3,
+                FieldMapsFile::Overlay4,
+            ])
+        {
+            out.flush()
+                .map_err(|source| FieldMapsToFilesError::File { file, source })?;
+        }
+        pending.commit()?;
         Ok(())
     }
 
+    #[inline]
+    #[cfg(feature = "fs")]
     pub fn load_from_filesystem_standard() -> Result<Self, FieldMapsFromFilesError> {
-        Self::from_files(
-            File::open(filesystem_standard_data_path("FMap/FMapData.dat"))?,
-            File::open(filesystem_standard_data_path("Treasure/TreasureInfo.dat"))?,
-            File::open(filesystem_standard_overlay_path(3))?,
-            File::open(filesystem_standard_overlay_path(4))?,
-        )
+        Self::load_from(&ProjectPaths::default())
     }
+    #[inline]
+    #[cfg(feature = "fs")]
     pub fn save_to_filesystem_standard(
         &self,
         align_files: bool,
     ) -> Result<(), FieldMapsToFilesError> {
-        self.to_files(
-            File::open(filesystem_standard_data_path("FMap/FMapData.dat"))?,
-            File::open(filesystem_standard_data_path("Treasure/TreasureInfo.dat"))?,
-            File::open(filesystem_standard_overlay_path(3))?,
-            File::open(filesystem_standard_overlay_path(4))?,
+        self.save_to(
+            &ProjectPaths::default(),
             align_files,
+            &SaveOptions::default(),
         )
     }
 }
 
+/// With the `serde` feature, this has a text representation meant for version control,
+/// which always contains the deserialized tileset.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BattleMap {
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_bytes"))]
     pub unk0: Vec<u8>,
     /// Compressing and decompressing the tileset is slow,
     /// so you should only deserialize it when necessary.
+    #[cfg_attr(feature = "serde", serde(with = "battle_map_tileset"))]
     pub tileset: MaybeSerialized<Tileset>,
     pub palette: Palette,
     pub tile_layers: [TileLayer; 3],
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_bytes"))]
     pub unk6: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_bytes"))]
     pub unk7: Vec<u8>,
 }
 
@@ -658,27 +2162,69 @@ pub enum BattleMapTilesetSerializationError {
     Compression(#[from] CompressionError),
 }
 
+fn deserialize_compressed_tileset(
+    data: &[u8],
+    pixel_size: PixelSize,
+) -> Result<Tileset, BattleMapTilesetDeserializationError> {
+    let mut buf = Cursor::new(Vec::new());
+    decompress(Cursor::new(data), &mut buf, false)?;
+    let mut buf = buf.into_inner();
+    buf.align_to_elements(pixel_size.tile_size());
+    Ok(Tileset::from_bytes(&buf, pixel_size)?)
+}
+fn serialize_compressed_tileset(
+    tileset: &Tileset,
+    pixel_size: PixelSize,
+) -> Result<Vec<u8>, BattleMapTilesetSerializationError> {
+    let uncompressed = tileset.to_bytes(pixel_size)?;
+    let end = uncompressed
+        .iter()
+        .rposition(|&x| x != 0)
+        // An empty input can't be compressed, so a blank tileset keeps one byte.
+        .map_or(uncompressed.len().min(1), |x| x + 1);
+    let mut buf = Cursor::new(Vec::new());
+    compress(&uncompressed[..end], &mut buf)?;
+    Ok(buf.into_inner())
+}
+
 impl BattleMap {
+    #[inline]
     pub fn deserialize_tileset(
         data: &[u8],
     ) -> Result<Tileset, BattleMapTilesetDeserializationError> {
-        let mut buf = Cursor::new(Vec::new());
-        decompress(Cursor::new(data), &mut buf, false)?;
-        let mut buf = buf.into_inner();
-        buf.align_to_elements(TILE_AREA / 2);
-        Ok(Tileset::from_bytes(&buf, BATTLE_TILESET_PIXEL_SIZE)?)
+        deserialize_compressed_tileset(data, BATTLE_TILESET_PIXEL_SIZE)
     }
+    #[inline]
     pub fn serialize_tileset(
         tileset: &Tileset,
     ) -> Result<Vec<u8>, BattleMapTilesetSerializationError> {
-        let uncompressed = tileset.to_bytes(BATTLE_TILESET_PIXEL_SIZE)?;
-        let last_non_zero = uncompressed
-            .iter()
-            .rposition(|&x| x != 0)
-            .unwrap_or(uncompressed.len());
-        let mut buf = Cursor::new(Vec::new());
-        compress(&uncompressed[..=last_non_zero], &mut buf)?;
-        Ok(buf.into_inner())
+        serialize_compressed_tileset(tileset, BATTLE_TILESET_PIXEL_SIZE)
+    }
+
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(toml)
+    }
+    /// The tileset is decompressed if it's still serialized.
+    #[cfg(feature = "toml")]
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string_pretty(self)
+    }
+}
+/// A blank map like most in the game: a tileset of a single blank tile, a black palette
+/// and 3 full-size tile layers of that tile, without any effects.
+impl Default for BattleMap {
+    fn default() -> Self {
+        Self {
+            unk0: Vec::new(),
+            tileset: MaybeSerialized::Deserialized(Tileset(vec![TilesetTile([0; TILE_AREA])])),
+            palette: Palette(vec![Rgb555::default(); MAX_PALETTE_COLORS]),
+            tile_layers: std::array::from_fn(|_| {
+                TileLayer(Grid::init(BATTLE_MAP_HEIGHT, BATTLE_MAP_WIDTH, Tile::new()))
+            }),
+            unk6: Vec::new(),
+            unk7: Vec::new(),
+        }
     }
 }
 
@@ -693,13 +2239,26 @@ pub struct BattleMapFile {
 pub enum BattleMapFileFromTableError {
     #[error("the number of chunks of the input ({0}) minus 1 isn't divisible by 8")]
     InvalidNumberOfChunks(usize),
-    #[error(transparent)]
-    PaletteDeserialization(#[from] PaletteDeserializationError),
+    #[error("failed to deserialize the palette of map {map_index}")]
+    PaletteDeserialization {
+        map_index: usize,
+        #[source]
+        source: PaletteDeserializationError,
+    },
+    #[error("failed to deserialize tile layer {layer} of map {map_index}")]
+    TileLayerDeserialization {
+        map_index: usize,
+        layer: usize,
+        #[source]
+        source: TileLayerDeserializationError,
+    },
 }
 #[derive(Error, Debug)]
 pub enum BattleMapFileIntoTableError {
     #[error(transparent)]
     BattleMapTilesetSerialization(#[from] BattleMapTilesetSerializationError),
+    #[error(transparent)]
+    DataWithOffsetTableSerialization(#[from] DataWithOffsetTableSerializationError),
 }
 
 impl TryFrom<DataWithOffsetTable> for BattleMapFile {
@@ -719,16 +2278,28 @@ impl TryFrom<DataWithOffsetTable> for BattleMapFile {
                 // UNSTABLE: Use `Iterator::array_chunks`.
                 .chunks(8)
                 .into_iter()
-                .map(|mut chunks| -> Result<_, Self::Error> {
+                .enumerate()
+                .map(|(map_index, mut chunks)| -> Result<_, Self::Error> {
                     Ok(BattleMap {
                         unk0: chunks.next().unwrap(),
                         tileset: MaybeSerialized::Serialized(chunks.next().unwrap()),
-                        palette: Palette::from_bytes(&chunks.next().unwrap())?,
+                        palette: Palette::from_bytes(&chunks.next().unwrap()).map_err(
+                            |source| Self::Error::PaletteDeserialization { map_index, source },
+                        )?,
                         tile_layers: chunks
                             .by_ref()
                             .take(3)
-                            .map(|x| TileLayer::from_bytes(&x, BATTLE_MAP_WIDTH))
-                            .collect::<Vec<_>>()
+                            .enumerate()
+                            .map(|(layer, x)| {
+                                TileLayer::from_bytes(&x, BATTLE_MAP_WIDTH).map_err(|source| {
+                                    Self::Error::TileLayerDeserialization {
+                                        map_index,
+                                        layer,
+                                        source,
+                                    }
+                                })
+                            })
+                            .collect::<Result<Vec<_>, _>>()?
                             .try_into()
                             .unwrap(),
                         unk6: chunks.next().unwrap(),
@@ -751,12 +2322,8 @@ impl TryFrom<BattleMapFile> for DataWithOffsetTable {
                 .map(|map| -> Result<_, Self::Error> {
                     Ok([
                         map.unk0,
-                        match map.tileset {
-                            MaybeSerialized::Serialized(data) => data,
-                            MaybeSerialized::Deserialized(tileset) => {
-                                BattleMap::serialize_tileset(&tileset)?
-                            }
-                        },
+                        map.tileset
+                            .into_serialized_with(BattleMap::serialize_tileset)?,
                         map.palette.to_bytes(),
                     ]
                     .into_iter()
@@ -770,3 +2337,349 @@ impl TryFrom<BattleMapFile> for DataWithOffsetTable {
         })
     }
 }
+impl BattleMapFile {
+    /// Like converting into a [`DataWithOffsetTable`], but without consuming `self`,
+    /// so the tilesets which are still serialized are copied.
+    pub fn to_offset_table(&self) -> Result<DataWithOffsetTable, BattleMapFileIntoTableError> {
+        Ok(DataWithOffsetTable {
+            chunks: self
+                .maps
+                .iter()
+                .map(|map| -> Result<_, BattleMapFileIntoTableError> {
+                    Ok([
+                        map.unk0.clone(),
+                        map.tileset
+                            .serialize_with(BattleMap::serialize_tileset)?
+                            .into_owned(),
+                        map.palette.to_bytes(),
+                    ]
+                    .into_iter()
+                    .chain(map.tile_layers.iter().map(TileLayer::to_bytes))
+                    .chain([map.unk6.clone(), map.unk7.clone()]))
+                })
+                .flatten_ok()
+                .chain(self.unk_last.iter().cloned().map(Ok))
+                .collect::<Result<Vec<_>, _>>()?,
+            footer: self.padding.clone(),
+        })
+    }
+    pub fn to_writer(&self, out: impl Write) -> Result<(), BattleMapFileIntoTableError> {
+        self.to_offset_table()?.to_writer(
+            out,
+            Some(STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT),
+            true,
+        )?;
+        Ok(())
+    }
+}
+
+/// The layout of [`GiantBattleMap`]s, which differs from the one of [`BattleMap`]s.
+///
+/// Every map consists of `4 + num_tile_layers` consecutive chunks:
+/// an unknown one, the compressed tileset, the palette, the tile layers and
+/// another unknown one. They are followed by `num_trailing_chunks` unknown chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GiantBattleMapFormat {
+    pub tileset_pixel_size: PixelSize,
+    /// The width of the tile layers, in tiles.
+    pub width: usize,
+    pub num_tile_layers: usize,
+    pub num_trailing_chunks: usize,
+}
+
+impl GiantBattleMapFormat {
+    #[inline]
+    pub fn chunks_per_map(&self) -> usize {
+        4 + self.num_tile_layers
+    }
+}
+
+/// An arena of a giant battle.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GiantBattleMap {
+    pub unk0: Vec<u8>,
+    /// Compressing and decompressing the tileset is slow,
+    /// so you should only deserialize it when necessary.
+    pub tileset: MaybeSerialized<Tileset>,
+    pub palette: Palette,
+    pub tile_layers: Vec<TileLayer>,
+    pub unk_last: Vec<u8>,
+}
+
+impl GiantBattleMap {
+    #[inline]
+    pub fn deserialize_tileset(
+        data: &[u8],
+        format: &GiantBattleMapFormat,
+    ) -> Result<Tileset, BattleMapTilesetDeserializationError> {
+        deserialize_compressed_tileset(data, format.tileset_pixel_size)
+    }
+    #[inline]
+    pub fn serialize_tileset(
+        tileset: &Tileset,
+        format: &GiantBattleMapFormat,
+    ) -> Result<Vec<u8>, BattleMapTilesetSerializationError> {
+        serialize_compressed_tileset(tileset, format.tileset_pixel_size)
+    }
+}
+
+/// The file containing all [`GiantBattleMap`]s.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GiantBattleMapFile {
+    pub format: GiantBattleMapFormat,
+    pub maps: Vec<GiantBattleMap>,
+    pub unk_trailing: Vec<Vec<u8>>,
+    pub padding: Vec<u8>,
+}
+
+#[derive(Error, Debug)]
+pub enum GiantBattleMapFileFromTableError {
+    #[error("the number of chunks of the input ({0}) doesn't match the format")]
+    InvalidNumberOfChunks(usize),
+    #[error("failed to deserialize the palette of map {map_index}")]
+    PaletteDeserialization {
+        map_index: usize,
+        #[source]
+        source: PaletteDeserializationError,
+    },
+    #[error("failed to deserialize tile layer {layer} of map {map_index}")]
+    TileLayerDeserialization {
+        map_index: usize,
+        layer: usize,
+        #[source]
+        source: TileLayerDeserializationError,
+    },
+}
+#[derive(Error, Debug)]
+pub enum GiantBattleMapFileIntoTableError {
+    #[error("map {map_index} has {actual} tile layers instead of {expected}")]
+    WrongNumberOfTileLayers {
+        map_index: usize,
+        expected: usize,
+        actual: usize,
+    },
+    #[error(transparent)]
+    BattleMapTilesetSerialization(#[from] BattleMapTilesetSerializationError),
+    #[error(transparent)]
+    DataWithOffsetTableSerialization(#[from] DataWithOffsetTableSerializationError),
+}
+
+impl GiantBattleMapFile {
+    pub fn from_table(
+        mut table: DataWithOffsetTable,
+        format: GiantBattleMapFormat,
+    ) -> Result<Self, GiantBattleMapFileFromTableError> {
+        let chunks_len = table.chunks.len();
+        if chunks_len < format.num_trailing_chunks
+            || !(chunks_len - format.num_trailing_chunks).is_multiple_of(format.chunks_per_map())
+        {
+            return Err(GiantBattleMapFileFromTableError::InvalidNumberOfChunks(
+                chunks_len,
+            ));
+        }
+
+        Ok(Self {
+            format,
+            unk_trailing: table
+                .chunks
+                .split_off(chunks_len - format.num_trailing_chunks),
+            maps: table
+                .chunks
+                .into_iter()
+                .chunks(format.chunks_per_map())
+                .into_iter()
+                .enumerate()
+                .map(|(map_index, mut chunks)| {
+                    Ok(GiantBattleMap {
+                        unk0: chunks.next().unwrap(),
+                        tileset: MaybeSerialized::Serialized(chunks.next().unwrap()),
+                        palette: Palette::from_bytes(&chunks.next().unwrap()).map_err(
+                            |source| GiantBattleMapFileFromTableError::PaletteDeserialization {
+                                map_index,
+                                source,
+                            },
+                        )?,
+                        tile_layers: chunks
+                            .by_ref()
+                            .take(format.num_tile_layers)
+                            .enumerate()
+                            .map(|(layer, x)| {
+                                TileLayer::from_bytes(&x, format.width).map_err(|source| {
+                                    GiantBattleMapFileFromTableError::TileLayerDeserialization {
+                                        map_index,
+                                        layer,
+                                        source,
+                                    }
+                                })
+                            })
+                            .collect::<Result<_, _>>()?,
+                        unk_last: chunks.next().unwrap(),
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+            padding: table.footer,
+        })
+    }
+    /// Like converting into a [`DataWithOffsetTable`], but without consuming `self`,
+    /// so the tilesets which are still serialized are copied.
+    pub fn to_offset_table(&self) -> Result<DataWithOffsetTable, GiantBattleMapFileIntoTableError> {
+        let format = &self.format;
+        Ok(DataWithOffsetTable {
+            chunks: self
+                .maps
+                .iter()
+                .enumerate()
+                .map(|(map_index, map)| {
+                    if map.tile_layers.len() != format.num_tile_layers {
+                        return Err(GiantBattleMapFileIntoTableError::WrongNumberOfTileLayers {
+                            map_index,
+                            expected: format.num_tile_layers,
+                            actual: map.tile_layers.len(),
+                        });
+                    }
+                    Ok([
+                        map.unk0.clone(),
+                        map.tileset
+                            .serialize_with(|x| GiantBattleMap::serialize_tileset(x, format))?
+                            .into_owned(),
+                        map.palette.to_bytes(),
+                    ]
+                    .into_iter()
+                    .chain(map.tile_layers.iter().map(TileLayer::to_bytes))
+                    .chain([map.unk_last.clone()]))
+                })
+                .flatten_ok()
+                .chain(self.unk_trailing.iter().cloned().map(Ok))
+                .collect::<Result<Vec<_>, _>>()?,
+            footer: self.padding.clone(),
+        })
+    }
+    pub fn to_writer(&self, out: impl Write) -> Result<(), GiantBattleMapFileIntoTableError> {
+        self.to_offset_table()?.to_writer(
+            out,
+            Some(STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT),
+            true,
+        )?;
+        Ok(())
+    }
+}
+impl TryFrom<GiantBattleMapFile> for DataWithOffsetTable {
+    type Error = GiantBattleMapFileIntoTableError;
+
+    fn try_from(value: GiantBattleMapFile) -> Result<Self, Self::Error> {
+        let format = value.format;
+        Ok(Self {
+            chunks: value
+                .maps
+                .into_iter()
+                .enumerate()
+                .map(|(map_index, map)| -> Result<_, Self::Error> {
+                    if map.tile_layers.len() != format.num_tile_layers {
+                        return Err(Self::Error::WrongNumberOfTileLayers {
+                            map_index,
+                            expected: format.num_tile_layers,
+                            actual: map.tile_layers.len(),
+                        });
+                    }
+                    Ok([
+                        map.unk0,
+                        map.tileset.into_serialized_with(|x| {
+                            GiantBattleMap::serialize_tileset(x, &format)
+                        })?,
+                        map.palette.to_bytes(),
+                    ]
+                    .into_iter()
+                    .chain(map.tile_layers.into_iter().map(|x| x.to_bytes()))
+                    .chain([map.unk_last]))
+                })
+                .flatten_ok()
+                .chain(value.unk_trailing.into_iter().map(Ok))
+                .collect::<Result<Vec<_>, _>>()?,
+            footer: value.padding,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+mod tilesets_properties {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{PixelSize, TilesetsProperties};
+
+    #[derive(Serialize, Deserialize)]
+    struct Fields {
+        tileset_pixel_sizes: [PixelSize; 3],
+        unk: u8,
+    }
+
+    pub fn serialize<S: Serializer>(
+        properties: &TilesetsProperties,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Fields {
+            tileset_pixel_sizes: properties.tileset_pixel_sizes(),
+            unk: properties.unk(),
+        }
+        .serialize(serializer)
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<TilesetsProperties, D::Error> {
+        let fields = Fields::deserialize(deserializer)?;
+        TilesetsProperties::new()
+            .with_tileset_pixel_sizes(fields.tileset_pixel_sizes)
+            .with_unk_checked(fields.unk)
+            .map_err(|_| serde::de::Error::custom("`unk` must fit into 5 bits"))
+    }
+}
+
+/// `None` as the default value, since TOML doesn't have null values.
+#[cfg(feature = "serde")]
+mod empty_if_none_array {
+    use std::borrow::Cow;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer, T: Serialize + Clone + Default>(
+        values: &[Option<T>; 3],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        values
+            .each_ref()
+            .map(|x| {
+                x.as_ref()
+                    .map_or_else(|| Cow::Owned(T::default()), Cow::Borrowed)
+            })
+            .serialize(serializer)
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de> + PartialEq + Default>(
+        deserializer: D,
+    ) -> Result<[Option<T>; 3], D::Error> {
+        Ok(<[T; 3]>::deserialize(deserializer)?.map(|x| (x != T::default()).then_some(x)))
+    }
+}
+
+#[cfg(feature = "serde")]
+mod battle_map_tileset {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{BattleMap, Tileset};
+    use crate::misc::MaybeSerialized;
+
+    pub fn serialize<S: Serializer>(
+        tileset: &MaybeSerialized<Tileset>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match tileset {
+            MaybeSerialized::Serialized(data) => BattleMap::deserialize_tileset(data)
+                .map_err(serde::ser::Error::custom)?
+                .serialize(serializer),
+            MaybeSerialized::Deserialized(tileset) => tileset.serialize(serializer),
+        }
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<MaybeSerialized<Tileset>, D::Error> {
+        Tileset::deserialize(deserializer).map(MaybeSerialized::Deserialized)
+    }
+}
@@ -0,0 +1,156 @@
+use std::io;
+
+use thiserror::Error;
+
+use crate::misc::{
+    DataWithOffsetTable, DataWithOffsetTableDeserializationError,
+    DataWithOffsetTableSerializationError, OverlayRecord,
+};
+#[cfg(feature = "fs")]
+use crate::{
+    consts::STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT,
+    misc::{ProjectPaths, SaveOptions},
+};
+#[cfg(feature = "fs")]
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+/// A data file of a minigame, whose chunks contain its layouts and parameters.
+///
+/// Chunks which are tables of fixed-size records can be accessed
+/// as any [`OverlayRecord`] with [`Self::records`] and [`Self::set_records`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct MinigameDataFile {
+    pub chunks: Vec<Vec<u8>>,
+    pub padding: Vec<u8>,
+}
+
+#[derive(Error, Debug)]
+pub enum MinigameRecordsError {
+    #[error("chunk {0} doesn't exist")]
+    ChunkOutOfBounds(usize),
+    #[error("the entries are {entry_size} bytes large, but must be at least {min_size}")]
+    EntryTooSmall { entry_size: usize, min_size: usize },
+    #[error("the size of chunk {chunk} ({len:#X}) isn't a multiple of {entry_size}")]
+    InvalidChunkSize {
+        chunk: usize,
+        len: usize,
+        entry_size: usize,
+    },
+}
+#[derive(Error, Debug)]
+pub enum MinigameDataFileFromFileError {
+    #[error(transparent)]
+    DataWithOffsetTableDeserialization(#[from] DataWithOffsetTableDeserializationError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+#[derive(Error, Debug)]
+pub enum MinigameDataFileToFileError {
+    #[error(transparent)]
+    DataWithOffsetTableSerialization(#[from] DataWithOffsetTableSerializationError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl MinigameDataFile {
+    fn check_entry_size<T: OverlayRecord>(entry_size: usize) -> Result<(), MinigameRecordsError> {
+        if entry_size < T::MIN_SIZE {
+            return Err(MinigameRecordsError::EntryTooSmall {
+                entry_size,
+                min_size: T::MIN_SIZE,
+            });
+        }
+        Ok(())
+    }
+
+    /// Reads chunk `chunk` as a table of `entry_size`-byte records.
+    pub fn records<T: OverlayRecord>(
+        &self,
+        chunk: usize,
+        entry_size: usize,
+    ) -> Result<Vec<T>, MinigameRecordsError> {
+        Self::check_entry_size::<T>(entry_size)?;
+        let data = self
+            .chunks
+            .get(chunk)
+            .ok_or(MinigameRecordsError::ChunkOutOfBounds(chunk))?;
+        if !data.len().is_multiple_of(entry_size) {
+            return Err(MinigameRecordsError::InvalidChunkSize {
+                chunk,
+                len: data.len(),
+                entry_size,
+            });
+        }
+        Ok(data.chunks_exact(entry_size).map(T::from_bytes).collect())
+    }
+    /// Replaces chunk `chunk` with a table of `records`, which can change its size.
+    pub fn set_records<T: OverlayRecord>(
+        &mut self,
+        chunk: usize,
+        entry_size: usize,
+        records: &[T],
+    ) -> Result<(), MinigameRecordsError> {
+        Self::check_entry_size::<T>(entry_size)?;
+        *self
+            .chunks
+            .get_mut(chunk)
+            .ok_or(MinigameRecordsError::ChunkOutOfBounds(chunk))? = records
+            .iter()
+            .flat_map(|x| x.to_bytes(entry_size))
+            .collect();
+        Ok(())
+    }
+
+    /// `filename` is relative to the data directory.
+    #[cfg(feature = "fs")]
+    pub fn load_from(
+        paths: &ProjectPaths,
+        filename: impl AsRef<Path>,
+    ) -> Result<Self, MinigameDataFileFromFileError> {
+        Ok(DataWithOffsetTable::from_reader(BufReader::new(File::open(
+            paths.data_path(filename),
+        )?))?
+        .into())
+    }
+    #[cfg(feature = "fs")]
+    pub fn save_to(
+        &self,
+        paths: &ProjectPaths,
+        filename: impl AsRef<Path>,
+        options: &SaveOptions,
+    ) -> Result<(), MinigameDataFileToFileError> {
+        let path = paths.data_path(filename);
+        let (pending, [file], []) = options.open_files([&path], [])?;
+        let mut file = BufWriter::new(file);
+        DataWithOffsetTable::from(self.clone()).to_writer(
+            &mut file,
+            Some(STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT),
+            true,
+        )?;
+        file.flush()?;
+        drop(file);
+        pending.commit()?;
+        Ok(())
+    }
+}
+
+impl From<DataWithOffsetTable> for MinigameDataFile {
+    fn from(value: DataWithOffsetTable) -> Self {
+        Self {
+            chunks: value.chunks,
+            padding: value.footer,
+        }
+    }
+}
+impl From<MinigameDataFile> for DataWithOffsetTable {
+    fn from(value: MinigameDataFile) -> Self {
+        Self {
+            chunks: value.chunks,
+            footer: value.padding,
+        }
+    }
+}
@@ -1,17 +1,38 @@
 use std::{
     borrow::Cow,
-    fmt::Display,
-    io::{self, Cursor, Read, Write},
-    num::TryFromIntError,
+    fmt::{Debug, Display},
+    hash::{Hash, Hasher},
+    io::{self, Cursor, Read, Seek, SeekFrom, Write},
+    num::NonZeroUsize,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
 };
 
 use bitfield_struct::bitfield;
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use endian_num::le16;
 use rgb::{Rgb, Rgba};
 use thiserror::Error;
 
-use crate::{compress, decompress, utils::AlignToElements, CompressionError, DecompressionError};
+use crate::{
+    decompress,
+    rom::{Overlay, OverlayAddressError},
+    utils::hash_bytes,
+    CompressionError, Compressor, DecompressionError, Decompressor,
+};
+#[cfg(feature = "fs")]
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+};
+
+pub use crate::cache::{CompressionCache, CompressionCacheError, DecompressedChunkCache};
+pub use crate::offset_table::{
+    ChunkAlignment, DataWithOffsetTable, DataWithOffsetTableDeserializationError,
+    DataWithOffsetTableSerializationError, OffsetTableEntrySize, PackedChunks,
+    PackedDataWithOffsetTable, SalvageProblem, SalvageReport,
+};
+#[cfg(feature = "fs")]
+pub use crate::project::{BackupOptions, PendingFiles, ProjectPaths, SaveOptions};
 
 pub fn filesystem_standard_data_path(filename: impl Display) -> String {
     format!("data/data/{}", filename)
@@ -20,40 +41,228 @@ pub fn filesystem_standard_overlay_path(overlay_number: impl Display) -> String
     format!("data/overlay.dec/overlay_{:04}.dec.bin", overlay_number)
 }
 
+/// Where a table of fixed-size [`OverlayRecord`]s is stored in an overlay.
+///
+/// `address` is the table's offset in the decompressed overlay;
+/// [`Self::at_address`] locates a table by its RAM address instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OverlayTableLocation {
+    pub overlay_number: u32,
+    pub address: u64,
+    pub entry_size: usize,
+    pub count: usize,
+}
+
+/// A record of a table at an [`OverlayTableLocation`].
+pub trait OverlayRecord: Sized {
+    /// The smallest entry size which the record fits into.
+    const MIN_SIZE: usize;
+
+    /// `data` is a whole entry, and at least [`Self::MIN_SIZE`] bytes long.
+    fn from_bytes(data: &[u8]) -> Self;
+    /// Returns exactly `size` bytes, which is at least [`Self::MIN_SIZE`].
+    fn to_bytes(&self, size: usize) -> Vec<u8>;
+}
+
+#[derive(Error, Debug)]
+pub enum OverlayTableError {
+    #[error("the entries are {entry_size} bytes large, but must be at least {min_size}")]
+    EntryTooSmall { entry_size: usize, min_size: usize },
+    #[error("the table has {expected} entries, but {actual} were given")]
+    WrongCount { expected: usize, actual: usize },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl OverlayTableLocation {
+    /// Locates a table by its RAM address, checking that all of it is inside `overlay`.
+    pub fn at_address(
+        overlay: &Overlay,
+        address: u32,
+        entry_size: usize,
+        count: usize,
+    ) -> Result<Self, OverlayAddressError> {
+        Ok(Self {
+            overlay_number: overlay.overlay_number,
+            address: overlay.offset_of(address, entry_size * count)? as u64,
+            entry_size,
+            count,
+        })
+    }
+
+    fn check_entry_size<T: OverlayRecord>(&self) -> Result<(), OverlayTableError> {
+        if self.entry_size < T::MIN_SIZE {
+            return Err(OverlayTableError::EntryTooSmall {
+                entry_size: self.entry_size,
+                min_size: T::MIN_SIZE,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn read<T: OverlayRecord>(
+        &self,
+        mut overlay: impl Read + Seek,
+    ) -> Result<Vec<T>, OverlayTableError> {
+        self.check_entry_size::<T>()?;
+        overlay.seek(SeekFrom::Start(self.address))?;
+        let mut buf = vec![0u8; self.entry_size];
+        (0..self.count)
+            .map(|_| {
+                overlay.read_exact(&mut buf)?;
+                Ok(T::from_bytes(&buf))
+            })
+            .collect()
+    }
+    pub fn write<T: OverlayRecord>(
+        &self,
+        records: &[T],
+        mut overlay: impl Write + Seek,
+    ) -> Result<(), OverlayTableError> {
+        self.check_entry_size::<T>()?;
+        if records.len() != self.count {
+            return Err(OverlayTableError::WrongCount {
+                expected: self.count,
+                actual: records.len(),
+            });
+        }
+        overlay.seek(SeekFrom::Start(self.address))?;
+        for record in records {
+            overlay.write_all(&record.to_bytes(self.entry_size))?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn load<T: OverlayRecord>(
+        &self,
+        paths: &ProjectPaths,
+    ) -> Result<Vec<T>, OverlayTableError> {
+        self.read(BufReader::new(File::open(
+            paths.overlay_path(self.overlay_number),
+        )?))
+    }
+    #[cfg(feature = "fs")]
+    pub fn save<T: OverlayRecord>(
+        &self,
+        records: &[T],
+        paths: &ProjectPaths,
+        options: &SaveOptions,
+    ) -> Result<(), OverlayTableError> {
+        let (pending, [], [overlay]) =
+            options.open_files([], [&paths.overlay_path(self.overlay_number)])?;
+        let mut overlay = BufWriter::new(overlay);
+        self.write(records, &mut overlay)?;
+        overlay.flush()?;
+        drop(overlay);
+        pending.commit()?;
+        Ok(())
+    }
+}
+
+/// The maximum number of bytes that can follow the first byte of a varint.
+pub const VARINT_MAX_EXTRA_BYTES: usize = 3;
+/// The largest value that can be encoded as a varint.
+pub const VARINT_MAX_VALUE: u64 = (1 << (6 * VARINT_MAX_EXTRA_BYTES + 8)) - 1;
+
+#[derive(Error, Debug)]
+pub enum VarIntError {
+    #[error("the value {0} is too large to be encoded as a varint (the maximum is {max})", max = VARINT_MAX_VALUE)]
+    ValueTooLarge(u64),
+    #[error("the varint is not encoded in its shortest form")]
+    OverlongEncoding,
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
 pub trait VarIntReader {
-    fn read_varint(&mut self) -> io::Result<u32>;
+    #[inline]
+    fn read_varint(&mut self) -> Result<u32, VarIntError> {
+        // Can't fail, since `VARINT_MAX_VALUE` fits in a `u32`.
+        Ok(self.read_varint_u64()? as u32)
+    }
+    /// Rejects encodings which aren't exactly what [`VarInt::encode_var`]
+    /// would produce for the decoded value.
+    fn read_varint_u64(&mut self) -> Result<u64, VarIntError>;
 }
 
 impl<T: Read> VarIntReader for T {
-    fn read_varint(&mut self) -> io::Result<u32> {
-        let data = self.read_u8()?;
-        let size = data >> 6;
-        let mut result = u32::from(data & 0b00111111);
-        for i in 0..size {
-            result |= u32::from(self.read_u8()?) << ((i + 1) * 6);
+    fn read_varint_u64(&mut self) -> Result<u64, VarIntError> {
+        let mut data = [0u8; VARINT_MAX_EXTRA_BYTES + 1];
+        self.read_exact(&mut data[..1])?;
+        let size = usize::from(data[0] >> 6);
+        self.read_exact(&mut data[1..=size])?;
+
+        let mut result = u64::from(data[0] & 0b00111111);
+        for (i, &byte) in data[1..=size].iter().enumerate() {
+            result |= u64::from(byte) << ((i + 1) * 6);
+        }
+        let (encoded, encoded_len) = encode_var_into_array(result)?;
+        if encoded[..encoded_len] != data[..=size] {
+            return Err(VarIntError::OverlongEncoding);
         }
         Ok(result)
     }
 }
 
+pub trait VarIntWriter {
+    #[inline]
+    fn write_varint(&mut self, value: u32) -> Result<(), VarIntError> {
+        self.write_varint_u64(value.into())
+    }
+    /// Fails if `value` is larger than [`VARINT_MAX_VALUE`].
+    fn write_varint_u64(&mut self, value: u64) -> Result<(), VarIntError>;
+}
+
+impl<T: Write> VarIntWriter for T {
+    fn write_varint_u64(&mut self, value: u64) -> Result<(), VarIntError> {
+        let (encoded, encoded_len) = encode_var_into_array(value)?;
+        self.write_all(&encoded[..encoded_len])?;
+        Ok(())
+    }
+}
+
+fn encode_var_into_array(
+    mut value: u64,
+) -> Result<([u8; VARINT_MAX_EXTRA_BYTES + 1], usize), VarIntError> {
+    if value > VARINT_MAX_VALUE {
+        return Err(VarIntError::ValueTooLarge(value));
+    }
+    let mut result = [(value & 0b00111111) as u8, 0, 0, 0];
+    let mut len = 1;
+    value >>= 6;
+    while value > 255 {
+        result[len] = value as u8;
+        len += 1;
+        value >>= 6;
+    }
+    if value > 0 {
+        result[len] = value as u8;
+        len += 1;
+    }
+    result[0] |= ((len - 1) as u8) << 6;
+    Ok((result, len))
+}
+
 pub trait VarInt {
-    fn encode_var(self) -> Vec<u8>;
+    /// Fails if `self` is larger than [`VARINT_MAX_VALUE`].
+    ///
+    /// Prefer [`VarIntWriter::write_varint`] when writing to a stream,
+    /// as it doesn't allocate.
+    fn encode_var(self) -> Result<Vec<u8>, VarIntError>;
 }
 
+impl VarInt for u64 {
+    #[inline]
+    fn encode_var(self) -> Result<Vec<u8>, VarIntError> {
+        let (encoded, encoded_len) = encode_var_into_array(self)?;
+        Ok(encoded[..encoded_len].to_vec())
+    }
+}
 impl VarInt for u32 {
-    fn encode_var(mut self) -> Vec<u8> {
-        let mut result = vec![(self & 0b00111111) as u8];
-        self >>= 6;
-        while self > 255 {
-            result.push(self as u8);
-            result[0] += 1 << 6;
-            self >>= 6;
-        }
-        if self > 0 {
-            result.push(self as u8);
-            result[0] += 1 << 6;
-        }
-        result
+    #[inline]
+    fn encode_var(self) -> Result<Vec<u8>, VarIntError> {
+        u64::from(self).encode_var()
     }
 }
 
@@ -61,28 +270,64 @@ impl VarInt for u32 {
 pub enum MaybeCompressedData {
     Uncompressed(Vec<u8>),
     Compressed(Vec<u8>),
+    /// Both representations of the same data, so that data which is only read
+    /// doesn't have to be compressed again when saving.
+    Cached {
+        compressed: Vec<u8>,
+        uncompressed: Vec<u8>,
+        /// Whether `uncompressed` may have been modified since `compressed`
+        /// was produced, in which case `compressed` is stale.
+        dirty: bool,
+    },
+}
+
+pub(crate) fn decompress_to_vec(data: &[u8], strict: bool) -> Result<Vec<u8>, DecompressionError> {
+    let mut buf = Cursor::new(Vec::new());
+    decompress(Cursor::new(data), &mut buf, strict)?;
+    Ok(buf.into_inner())
+}
+#[inline]
+pub(crate) fn compress_to_vec(data: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    Compressor::new().compress_to_vec(data)
+}
+
+#[derive(Error, Debug)]
+pub enum ChunksCompressionError {
+    #[error("failed to compress chunk {index}")]
+    Chunk {
+        index: usize,
+        #[source]
+        source: CompressionError,
+    },
 }
 
 impl MaybeCompressedData {
-    pub fn to_uncompressed(&self, strict: bool) -> Result<Cow<[u8]>, DecompressionError> {
+    pub fn to_uncompressed(&self, strict: bool) -> Result<Cow<'_, [u8]>, DecompressionError> {
         Ok(match self {
-            Self::Uncompressed(data) => Cow::Borrowed(data),
-            Self::Compressed(data) => {
-                let mut buf = Cursor::new(Vec::new());
-                decompress(Cursor::new(data), &mut buf, strict)?;
-                Cow::Owned(buf.into_inner())
-            }
+            Self::Uncompressed(data)
+            | Self::Cached {
+                uncompressed: data, ..
+            } => Cow::Borrowed(data),
+            Self::Compressed(data) => Cow::Owned(decompress_to_vec(data, strict)?),
         })
     }
     /// Decompresses the data in-place if it isn't uncompressed already,
     /// and returns a mutable reference to the uncompressed data inside `self`.
+    ///
+    /// If `self` is [`Self::Cached`], it is marked as dirty.
     pub fn make_uncompressed(&mut self, strict: bool) -> Result<&mut Vec<u8>, DecompressionError> {
         Ok(match self {
             Self::Uncompressed(data) => data,
+            Self::Cached {
+                uncompressed,
+                dirty,
+                ..
+            } => {
+                *dirty = true;
+                uncompressed
+            }
             Self::Compressed(data) => {
-                let mut buf = Cursor::new(Vec::new());
-                decompress(Cursor::new(data), &mut buf, strict)?;
-                *self = Self::Uncompressed(buf.into_inner());
+                *self = Self::Uncompressed(decompress_to_vec(data, strict)?);
                 match self {
                     Self::Uncompressed(data) => data,
                     _ => unreachable!(),
@@ -90,26 +335,185 @@ impl MaybeCompressedData {
             }
         })
     }
-
-    pub fn to_compressed(&self) -> Result<Cow<[u8]>, CompressionError> {
+    /// Decompresses the data while keeping the compressed copy
+    /// if it isn't uncompressed already, and returns a shared reference
+    /// to the uncompressed data inside `self`.
+    pub fn make_cached(&mut self, strict: bool) -> Result<&[u8], DecompressionError> {
         Ok(match self {
-            Self::Compressed(data) => Cow::Borrowed(data),
-            Self::Uncompressed(data) => {
-                let mut buf = Cursor::new(Vec::new());
-                compress(data, &mut buf)?;
-                Cow::Owned(buf.into_inner())
+            Self::Uncompressed(data)
+            | Self::Cached {
+                uncompressed: data, ..
+            } => data,
+            Self::Compressed(data) => {
+                let uncompressed = decompress_to_vec(data, strict)?;
+                *self = Self::Cached {
+                    compressed: std::mem::take(data),
+                    uncompressed,
+                    dirty: false,
+                };
+                match self {
+                    Self::Cached { uncompressed, .. } => uncompressed,
+                    _ => unreachable!(),
+                }
             }
         })
     }
+
+    /// Returns a reader over the uncompressed data,
+    /// which decompresses it as it's read if it's only compressed.
+    pub fn reader(
+        &self,
+        strict: bool,
+    ) -> Result<MaybeCompressedDataReader<'_>, DecompressionError> {
+        Ok(match self {
+            Self::Uncompressed(data)
+            | Self::Cached {
+                uncompressed: data, ..
+            } => MaybeCompressedDataReader::Uncompressed(data),
+            Self::Compressed(data) => MaybeCompressedDataReader::Compressed(Decompressor::new(
+                Cursor::new(&data[..]),
+                strict,
+            )?),
+        })
+    }
+
+    pub fn to_compressed(&self) -> Result<Cow<'_, [u8]>, CompressionError> {
+        Ok(match self {
+            Self::Compressed(data)
+            | Self::Cached {
+                compressed: data,
+                dirty: false,
+                ..
+            } => Cow::Borrowed(data),
+            Self::Uncompressed(data)
+            | Self::Cached {
+                uncompressed: data,
+                dirty: true,
+                ..
+            } => Cow::Owned(compress_to_vec(data)?),
+        })
+    }
+    /// Like [`Self::to_compressed`], but looks the data up in `cache` before compressing it,
+    /// and adds it to `cache` afterwards.
+    #[inline]
+    pub fn to_compressed_cached(
+        &self,
+        cache: &CompressionCache,
+    ) -> Result<Cow<'_, [u8]>, CompressionError> {
+        self.to_compressed_with(&mut Compressor::new(), Some(cache))
+    }
+    /// Like [`Self::to_compressed`], or [`Self::to_compressed_cached`] if there's a `cache`,
+    /// but reuses `compressor`.
+    pub fn to_compressed_with(
+        &self,
+        compressor: &mut Compressor,
+        cache: Option<&CompressionCache>,
+    ) -> Result<Cow<'_, [u8]>, CompressionError> {
+        Ok(match self {
+            Self::Compressed(data)
+            | Self::Cached {
+                compressed: data,
+                dirty: false,
+                ..
+            } => Cow::Borrowed(data),
+            Self::Uncompressed(data)
+            | Self::Cached {
+                uncompressed: data,
+                dirty: true,
+                ..
+            } => Cow::Owned(match cache {
+                Some(cache) => cache.compress_with(data, compressor)?,
+                None => compressor.compress_to_vec(data)?,
+            }),
+        })
+    }
+    /// Calls [`Self::to_compressed`] for every chunk, or [`Self::to_compressed_cached`]
+    /// if there's a `cache`, compressing them in parallel on all available cores,
+    /// and returns the results in the same order.
+    ///
+    /// If several chunks fail to compress, the error of the first one is returned.
+    pub fn to_compressed_parallel<'a>(
+        chunks: &'a [Self],
+        cache: Option<&CompressionCache>,
+    ) -> Result<Vec<Cow<'a, [u8]>>, ChunksCompressionError> {
+        let chunk_error = |index| move |source| ChunksCompressionError::Chunk { index, source };
+        let num_threads = thread::available_parallelism()
+            .map_or(1, NonZeroUsize::get)
+            .min(chunks.len());
+        if num_threads <= 1 {
+            let mut compressor = Compressor::new();
+            return chunks
+                .iter()
+                .enumerate()
+                .map(|(index, chunk)| {
+                    chunk
+                        .to_compressed_with(&mut compressor, cache)
+                        .map_err(chunk_error(index))
+                })
+                .collect();
+        }
+
+        // The threads take the next chunk whenever they're done,
+        // since the chunks differ a lot in size.
+        let next_index = AtomicUsize::new(0);
+        let mut results: Vec<Option<Result<Cow<'_, [u8]>, CompressionError>>> =
+            (0..chunks.len()).map(|_| None).collect();
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..num_threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut compressor = Compressor::new();
+                        let mut done = Vec::new();
+                        loop {
+                            let index = next_index.fetch_add(1, Ordering::Relaxed);
+                            let Some(chunk) = chunks.get(index) else {
+                                return done;
+                            };
+                            done.push((index, chunk.to_compressed_with(&mut compressor, cache)));
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                let done = handle
+                    .join()
+                    .unwrap_or_else(|payload| std::panic::resume_unwind(payload));
+                for (index, result) in done {
+                    results[index] = Some(result);
+                }
+            }
+        });
+        results
+            .into_iter()
+            .enumerate()
+            .map(|(index, result)| result.unwrap().map_err(chunk_error(index)))
+            .collect()
+    }
     /// Compresses the data in-place if it isn't compressed already,
     /// and returns a mutable reference to the compressed data inside `self`.
+    ///
+    /// If `self` is [`Self::Cached`], the uncompressed copy is discarded.
     pub fn make_compressed(&mut self) -> Result<&mut Vec<u8>, CompressionError> {
         Ok(match self {
             Self::Compressed(data) => data,
-            Self::Uncompressed(data) => {
-                let mut buf = Cursor::new(Vec::new());
-                compress(data, &mut buf)?;
-                *self = Self::Compressed(buf.into_inner());
+            Self::Cached {
+                compressed,
+                dirty: false,
+                ..
+            } => {
+                *self = Self::Compressed(std::mem::take(compressed));
+                match self {
+                    Self::Compressed(data) => data,
+                    _ => unreachable!(),
+                }
+            }
+            Self::Uncompressed(data)
+            | Self::Cached {
+                uncompressed: data,
+                dirty: true,
+                ..
+            } => {
+                *self = Self::Compressed(compress_to_vec(data)?);
                 match self {
                     Self::Compressed(data) => data,
                     _ => unreachable!(),
@@ -117,102 +521,131 @@ impl MaybeCompressedData {
             }
         })
     }
-}
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum MaybeSerialized<T> {
-    Serialized(Vec<u8>),
-    Deserialized(T),
-}
+    /// Compares the uncompressed contents of `self` and `other`,
+    /// regardless of how either of them is currently represented.
+    pub fn content_eq(&self, other: &Self, strict: bool) -> Result<bool, DecompressionError> {
+        if let (Self::Compressed(a), Self::Compressed(b)) = (self, other) {
+            if a == b {
+                return Ok(true);
+            }
+        }
+        Ok(self.to_uncompressed(strict)? == other.to_uncompressed(strict)?)
+    }
+    /// Hashes the uncompressed contents of `self`, consistently with [`Self::content_eq`].
+    pub fn content_hash<H: Hasher>(
+        &self,
+        state: &mut H,
+        strict: bool,
+    ) -> Result<(), DecompressionError> {
+        self.to_uncompressed(strict)?.hash(state);
+        Ok(())
+    }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct DataWithOffsetTable {
-    pub chunks: Vec<Vec<u8>>,
-    pub footer: Vec<u8>,
-}
+    /// Returns the [`hash_bytes`] of the uncompressed contents of `self`.
+    #[inline]
+    pub fn content_digest(&self, strict: bool) -> Result<u64, DecompressionError> {
+        Ok(hash_bytes(&self.to_uncompressed(strict)?))
+    }
 
-#[derive(Error, Debug)]
-pub enum DataWithOffsetTableDeserializationError {
-    #[error(transparent)]
-    TryFromInt(#[from] TryFromIntError),
-    #[error(transparent)]
-    Io(#[from] io::Error),
+    /// Returns `true` if `self` is [`Self::Cached`] and its compressed copy is stale.
+    #[inline]
+    pub fn is_dirty(&self) -> bool {
+        matches!(self, Self::Cached { dirty: true, .. })
+    }
+    /// Recompresses the data of a dirty [`Self::Cached`],
+    /// marking it as clean again. Does nothing for the other variants.
+    pub fn refresh_cache(&mut self) -> Result<(), CompressionError> {
+        if let Self::Cached {
+            compressed,
+            uncompressed,
+            dirty: dirty @ true,
+        } = self
+        {
+            *compressed = compress_to_vec(uncompressed)?;
+            *dirty = false;
+        }
+        Ok(())
+    }
 }
-#[derive(Error, Debug)]
-pub enum DataWithOffsetTableSerializationError {
-    #[error(transparent)]
-    TryFromInt(#[from] TryFromIntError),
-    #[error(transparent)]
-    Io(#[from] io::Error),
+
+/// The uncompressed contents of a [`MaybeCompressedData`] as a [`Read`],
+/// returned by [`MaybeCompressedData::reader`].
+#[derive(Debug)]
+pub enum MaybeCompressedDataReader<'a> {
+    Uncompressed(&'a [u8]),
+    Compressed(Decompressor<Cursor<&'a [u8]>>),
 }
 
-impl DataWithOffsetTable {
-    pub fn from_reader(
-        mut inp: impl Read,
-    ) -> Result<Self, DataWithOffsetTableDeserializationError> {
-        let first_offset = inp.read_u32::<LittleEndian>()?;
-        let (num_offsets, padding) = (first_offset / 4, first_offset % 4);
-        let mut offsets: Vec<u32> = Vec::with_capacity(num_offsets.try_into()?);
-        offsets.push(first_offset);
-        for _ in 1..num_offsets {
-            offsets.push(inp.read_u32::<LittleEndian>()?);
+impl Read for MaybeCompressedDataReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Uncompressed(data) => data.read(buf),
+            Self::Compressed(decompressor) => decompressor.read(buf),
         }
-        if padding != 0 {
-            // Alternative to seeking so that we don't require `Seek` for this one operation.
-            let mut padding_buf = vec![0u8; padding.try_into()?];
-            inp.read_exact(&mut padding_buf)?;
-        }
-
-        Ok(Self {
-            chunks: offsets
-                // UNSTABLE: Use `slice::array_windows`.
-                .windows(2)
-                .map(
-                    |offset_pair| -> Result<_, DataWithOffsetTableDeserializationError> {
-                        let (current_offset, next_offset) = (offset_pair[0], offset_pair[1]);
-                        let mut buf = vec![0u8; (next_offset - current_offset).try_into()?];
-                        inp.read_exact(&mut buf)?;
-                        Ok(buf)
-                    },
-                )
-                .collect::<Result<Vec<_>, _>>()?,
-            footer: {
-                let mut buf: Vec<u8> = Vec::new();
-                inp.read_to_end(&mut buf)?;
-                buf
-            },
-        })
     }
+}
 
-    /// If `chunk_alignment` is set, this function will align
-    /// `self.chunks` in-place, mutating them.
-    pub fn to_writer(
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MaybeSerialized<T> {
+    Serialized(Vec<u8>),
+    Deserialized(T),
+}
+
+impl<T> MaybeSerialized<T> {
+    /// Deserializes the data in-place using `f` if it isn't deserialized already,
+    /// and returns a mutable reference to the deserialized data inside `self`.
+    pub fn get_or_deserialize_with<E>(
         &mut self,
-        mut out: impl Write,
-        chunk_alignment: Option<usize>,
-        write_footer: bool,
-    ) -> Result<(), DataWithOffsetTableSerializationError> {
-        let mut current_offset = (self.chunks.len() + 1) * 4;
-        out.write_u32::<LittleEndian>(current_offset.try_into()?)?;
-        for chunk in &mut self.chunks {
-            if let Some(alignment) = chunk_alignment {
-                chunk.align_to_elements(alignment);
-            }
-            current_offset += chunk.len();
-            out.write_u32::<LittleEndian>(current_offset.try_into()?)?;
+        f: impl FnOnce(&[u8]) -> Result<T, E>,
+    ) -> Result<&mut T, E> {
+        if let Self::Serialized(data) = self {
+            *self = Self::Deserialized(f(data)?);
         }
+        match self {
+            Self::Deserialized(value) => Ok(value),
+            Self::Serialized(_) => unreachable!(),
+        }
+    }
 
-        for chunk in &self.chunks {
-            out.write_all(chunk)?;
+    #[inline]
+    pub fn as_deserialized(&self) -> Option<&T> {
+        match self {
+            Self::Deserialized(value) => Some(value),
+            Self::Serialized(_) => None,
         }
-        if write_footer {
-            out.write_all(&self.footer)?;
+    }
+    #[inline]
+    pub fn as_deserialized_mut(&mut self) -> Option<&mut T> {
+        match self {
+            Self::Deserialized(value) => Some(value),
+            Self::Serialized(_) => None,
         }
+    }
 
-        Ok(())
+    /// Returns the serialized data, using `f` to serialize it if necessary.
+    pub fn serialize_with<E>(
+        &self,
+        f: impl FnOnce(&T) -> Result<Vec<u8>, E>,
+    ) -> Result<Cow<'_, [u8]>, E> {
+        Ok(match self {
+            Self::Serialized(data) => Cow::Borrowed(data),
+            Self::Deserialized(value) => Cow::Owned(f(value)?),
+        })
+    }
+    /// Like [`Self::serialize_with`], but consumes `self` to avoid copying.
+    pub fn into_serialized_with<E>(
+        self,
+        f: impl FnOnce(&T) -> Result<Vec<u8>, E>,
+    ) -> Result<Vec<u8>, E> {
+        match self {
+            Self::Serialized(data) => Ok(data),
+            Self::Deserialized(value) => f(&value),
+        }
     }
 }
 
+/// A 15-bit color with red in the lowest bits, as used by the DS hardware.
 #[bitfield(u16, new = false, repr = le16, from = le16::from_ne, into = le16::to_ne)]
 #[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Rgb555 {
@@ -225,32 +658,126 @@ pub struct Rgb555 {
     __: bool, // Padding
 }
 
-impl Rgb555 {
-    pub fn new(r: u8, g: u8, b: u8) -> Self {
-        Self::default().with_r(r).with_g(g).with_b(b)
-    }
-    #[allow(clippy::result_unit_err)]
-    pub fn new_checked(r: u8, g: u8, b: u8) -> Result<Self, ()> {
-        Self::default()
-            .with_r_checked(r)?
-            .with_g_checked(g)?
-            .with_b_checked(b)
-    }
+/// A 15-bit color with blue in the lowest bits.
+#[bitfield(u16, new = false, repr = le16, from = le16::from_ne, into = le16::to_ne)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Bgr555 {
+    #[bits(5)]
+    pub b: u8,
+    #[bits(5)]
+    pub g: u8,
+    #[bits(5)]
+    pub r: u8,
+    __: bool, // Padding
 }
-impl From<Rgb<u8>> for Rgb555 {
+
+macro_rules! impl_color555 {
+    ($type:ty) => {
+        impl $type {
+            pub fn new(r: u8, g: u8, b: u8) -> Self {
+                Self::default().with_r(r).with_g(g).with_b(b)
+            }
+            #[allow(clippy::result_unit_err)]
+            pub fn new_checked(r: u8, g: u8, b: u8) -> Result<Self, ()> {
+                Self::default()
+                    .with_r_checked(r)?
+                    .with_g_checked(g)?
+                    .with_b_checked(b)
+            }
+        }
+        impl From<Rgb<u8>> for $type {
+            /// Keeps the top 5 bits of each channel (rounding down),
+            /// which undoes the conversion to [`Rgb<u8>`].
+            #[inline]
+            fn from(value: Rgb<u8>) -> Self {
+                Self::new(value.r >> 3, value.g >> 3, value.b >> 3)
+            }
+        }
+        impl From<Rgba<u8>> for $type {
+            /// Like the conversion from [`Rgb<u8>`]. The alpha is ignored,
+            /// since transparency is given by index 0 of a palette instead.
+            #[inline]
+            fn from(value: Rgba<u8>) -> Self {
+                value.rgb().into()
+            }
+        }
+        impl From<$type> for Rgb<u8> {
+            /// Multiplies each channel by 8, so white is `#F8F8F8`.
+            #[inline]
+            fn from(value: $type) -> Self {
+                Self::new(value.r() << 3, value.g() << 3, value.b() << 3)
+            }
+        }
+        impl From<$type> for Rgba<u8> {
+            /// The resulting color is fully opaque.
+            #[inline]
+            fn from(value: $type) -> Self {
+                <Rgb<u8>>::from(value).with_alpha(0xFF)
+            }
+        }
+    };
+}
+impl_color555!(Rgb555);
+impl_color555!(Bgr555);
+
+impl From<Bgr555> for Rgb555 {
     #[inline]
-    fn from(value: Rgb<u8>) -> Self {
-        Self::new(value.r >> 3, value.g >> 3, value.b >> 3)
+    fn from(value: Bgr555) -> Self {
+        Self::new(value.r(), value.g(), value.b())
     }
 }
-impl From<Rgb555> for Rgb<u8> {
+impl From<Rgb555> for Bgr555 {
     #[inline]
     fn from(value: Rgb555) -> Self {
-        Self::new(value.r() << 3, value.g() << 3, value.b() << 3)
+        Self::new(value.r(), value.g(), value.b())
+    }
+}
+
+/// Serialized as `#RRGGBB` (like [`Rgb<u8>`]), followed by `+` if the unused top bit is set.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Rgb555 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let color = <Rgb<u8>>::from(*self);
+        serializer.collect_str(&format_args!(
+            "#{:02X}{:02X}{:02X}{}",
+            color.r,
+            color.g,
+            color.b,
+            if self.into_bits().to_ne() & 0x8000 != 0 {
+                "+"
+            } else {
+                ""
+            }
+        ))
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Rgb555 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        let invalid = || serde::de::Error::custom(format!("invalid color `{text}`"));
+        let (hex, top_bit) = match text.strip_suffix('+') {
+            Some(hex) => (hex, 0x8000),
+            None => (text.as_str(), 0),
+        };
+        let rgb = hex
+            .strip_prefix('#')
+            .filter(|x| x.len() == 6)
+            .and_then(|x| u32::from_str_radix(x, 16).ok())
+            .ok_or_else(invalid)?;
+        let color = Self::from(Rgb::new((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8));
+        Ok(Self::from_bits(le16::from_ne(
+            color.into_bits().to_ne() | top_bit,
+        )))
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct Palette(pub Vec<Rgb555>);
 
 #[derive(Error, Debug)]
@@ -261,7 +788,7 @@ pub enum PaletteDeserializationError {
 
 impl Palette {
     pub fn from_bytes(data: &[u8]) -> Result<Self, PaletteDeserializationError> {
-        if data.len() % 2 != 0 {
+        if !data.len().is_multiple_of(2) {
             return Err(PaletteDeserializationError::ExtraBytesInInput);
         }
         Ok(Self(
@@ -273,10 +800,16 @@ impl Palette {
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
-        self.0
-            .iter()
-            .flat_map(|x| x.into_bits().to_le_bytes())
-            .collect()
+        let mut buf = Vec::with_capacity(self.0.len() * 2);
+        self.write_to(&mut buf).unwrap();
+        buf
+    }
+    /// Each color is written separately, so `out` should be buffered.
+    pub fn write_to(&self, mut out: impl Write) -> io::Result<()> {
+        for color in &self.0 {
+            out.write_all(&color.into_bits().to_le_bytes())?;
+        }
+        Ok(())
     }
 
     #[inline]
@@ -284,3 +817,26 @@ impl Palette {
         <Rgb<u8>>::from(self.0[index]).with_alpha(if index == 0 { 0x00 } else { 0xFF })
     }
 }
+
+/// Every color of a [`Palette`] converted with [`Palette::color_as_rgba8888`],
+/// so that rendering converts each color once instead of once per pixel.
+///
+/// It's a copy, so it has to be created again after the palette is modified.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PaletteLut(pub Vec<Rgba<u8>>);
+
+impl PaletteLut {
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<Rgba<u8>> {
+        self.0.get(index).copied()
+    }
+}
+impl From<&Palette> for PaletteLut {
+    fn from(palette: &Palette) -> Self {
+        Self(
+            (0..palette.0.len())
+                .map(|index| palette.color_as_rgba8888(index))
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,563 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::{self, Display},
+};
+
+use thiserror::Error;
+
+use crate::{
+    map::FieldMap,
+    misc::MaybeCompressedData,
+    project::{Project, ProjectError},
+    text::{ArchiveMessageId, MessageId},
+    DecompressionError,
+};
+
+/// A single change to a [`Project`].
+///
+/// Edits only contain the changed data, so that they can be distributed
+/// without the rest of the game.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "kind", rename_all = "snake_case")
+)]
+pub enum Edit {
+    /// Replaces a chunk of a file with an offset table,
+    /// or appends it if `chunk` is the number of chunks.
+    Chunk {
+        /// Relative to the data directory.
+        file: String,
+        chunk: usize,
+        #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_bytes"))]
+        data: Vec<u8>,
+    },
+    /// Replaces a chunk of [`FieldMaps::fmapdata_chunks`](crate::map::FieldMaps::fmapdata_chunks)
+    /// with uncompressed data, or appends it if `chunk` is the number of chunks.
+    FieldMapChunk {
+        chunk: usize,
+        #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_bytes"))]
+        data: Vec<u8>,
+    },
+    /// Replaces an entry of [`FieldMaps::maps`](crate::map::FieldMaps::maps).
+    FieldMap {
+        map: usize,
+        /// Missing indexes are written as `-1`.
+        #[cfg_attr(feature = "serde", serde(with = "optional_indexes"))]
+        tileset_indexes: [Option<usize>; 3],
+        map_chunk_index: usize,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "Option::is_none")
+        )]
+        treasure_data_index: Option<usize>,
+    },
+    /// Replaces an entry of
+    /// [`FieldMaps::treasure_data`](crate::map::FieldMaps::treasure_data).
+    TreasureData {
+        index: usize,
+        #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_bytes"))]
+        data: Vec<u8>,
+    },
+    /// Replaces a message of a [`MessageArchive`](crate::text::MessageArchive).
+    Message {
+        /// Relative to the data directory.
+        file: String,
+        set: usize,
+        list: usize,
+        message: usize,
+        /// Encoded, including the [`MESSAGE_TERMINATOR`](crate::text::MESSAGE_TERMINATOR).
+        #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_bytes"))]
+        text: Vec<u8>,
+    },
+    /// Replaces a message of a [`MessageListSet`](crate::text::MessageListSet).
+    ListMessage {
+        /// Relative to the data directory.
+        file: String,
+        list: usize,
+        message: usize,
+        /// Encoded, including the [`MESSAGE_TERMINATOR`](crate::text::MESSAGE_TERMINATOR).
+        #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_bytes"))]
+        text: Vec<u8>,
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum EditError {
+    #[error("{what} {index} doesn't exist")]
+    OutOfBounds { what: &'static str, index: usize },
+    #[error(transparent)]
+    Project(#[from] ProjectError),
+    #[error(transparent)]
+    Decompression(#[from] DecompressionError),
+}
+#[derive(Error, Debug)]
+pub enum ModApplyError {
+    #[error("edits {first} and {second} change {file} both by chunk and by message")]
+    MixedFileEdits {
+        file: String,
+        first: usize,
+        second: usize,
+    },
+    #[error("failed to apply edit {index}")]
+    Edit {
+        index: usize,
+        #[source]
+        source: EditError,
+    },
+}
+
+fn replace_or_push<T>(
+    items: &mut Vec<T>,
+    index: usize,
+    item: T,
+    what: &'static str,
+) -> Result<(), EditError> {
+    match index.cmp(&items.len()) {
+        Ordering::Less => items[index] = item,
+        Ordering::Equal => items.push(item),
+        Ordering::Greater => return Err(EditError::OutOfBounds { what, index }),
+    }
+    Ok(())
+}
+
+/// What an [`Edit`] replaces, so that edits of the same thing can be matched up.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EditTarget {
+    Chunk {
+        file: String,
+        chunk: usize,
+    },
+    FieldMapChunk(usize),
+    FieldMap(usize),
+    TreasureData(usize),
+    Message {
+        file: String,
+        set: usize,
+        list: usize,
+        message: usize,
+    },
+    ListMessage {
+        file: String,
+        list: usize,
+        message: usize,
+    },
+}
+
+impl Display for EditTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Chunk { file, chunk } => write!(f, "chunk {chunk} of {file}"),
+            Self::FieldMapChunk(chunk) => write!(f, "field map chunk {chunk}"),
+            Self::FieldMap(map) => write!(f, "field map {map}"),
+            Self::TreasureData(index) => write!(f, "treasure data {index}"),
+            Self::Message {
+                file,
+                set,
+                list,
+                message,
+            } => write!(f, "message {set}/{list}/{message} of {file}"),
+            Self::ListMessage {
+                file,
+                list,
+                message,
+            } => write!(f, "message {list}/{message} of {file}"),
+        }
+    }
+}
+
+impl Edit {
+    pub fn target(&self) -> EditTarget {
+        match self {
+            Self::Chunk { file, chunk, .. } => EditTarget::Chunk {
+                file: file.clone(),
+                chunk: *chunk,
+            },
+            Self::FieldMapChunk { chunk, .. } => EditTarget::FieldMapChunk(*chunk),
+            Self::FieldMap { map, .. } => EditTarget::FieldMap(*map),
+            Self::TreasureData { index, .. } => EditTarget::TreasureData(*index),
+            Self::Message {
+                file,
+                set,
+                list,
+                message,
+                ..
+            } => EditTarget::Message {
+                file: file.clone(),
+                set: *set,
+                list: *list,
+                message: *message,
+            },
+            Self::ListMessage {
+                file,
+                list,
+                message,
+                ..
+            } => EditTarget::ListMessage {
+                file: file.clone(),
+                list: *list,
+                message: *message,
+            },
+        }
+    }
+
+    /// Whether `project` already contains the data of the edit,
+    /// so that applying it wouldn't change anything.
+    ///
+    /// Edits which append or are out of bounds are never no-ops.
+    pub fn is_noop(&self, project: &mut Project) -> Result<bool, EditError> {
+        Ok(match self {
+            Self::Chunk { file, chunk, data } => {
+                project.data_file(file)?.chunks.get(*chunk) == Some(data)
+            }
+            Self::FieldMapChunk { chunk, data } => {
+                match project.field_maps()?.fmapdata_chunks.get(*chunk) {
+                    Some(x) => *x.to_uncompressed(false)? == data[..],
+                    None => false,
+                }
+            }
+            &Self::FieldMap {
+                map,
+                tileset_indexes,
+                map_chunk_index,
+                treasure_data_index,
+            } => {
+                project.field_maps()?.maps.get(map)
+                    == Some(&FieldMap {
+                        tileset_indexes,
+                        map_chunk_index,
+                        treasure_data_index,
+                    })
+            }
+            Self::TreasureData { index, data } => {
+                project.field_maps()?.treasure_data.get(*index) == Some(data)
+            }
+            Self::Message {
+                file,
+                set,
+                list,
+                message,
+                text,
+            } => {
+                project.messages(file)?.get(ArchiveMessageId {
+                    set: *set,
+                    id: MessageId {
+                        list: *list,
+                        message: *message,
+                    },
+                }) == Some(text)
+            }
+            Self::ListMessage {
+                file,
+                list,
+                message,
+                text,
+            } => {
+                project.message_list_set(file)?.get(MessageId {
+                    list: *list,
+                    message: *message,
+                }) == Some(text)
+            }
+        })
+    }
+
+    /// Applies the edit to `project`, marking the affected subsystem as modified.
+    pub fn apply(&self, project: &mut Project) -> Result<(), EditError> {
+        match self {
+            Self::Chunk { file, chunk, data } => replace_or_push(
+                &mut project.data_file_mut(file)?.chunks,
+                *chunk,
+                data.clone(),
+                "chunk",
+            ),
+            Self::FieldMapChunk { chunk, data } => replace_or_push(
+                &mut project.field_maps_mut()?.fmapdata_chunks,
+                *chunk,
+                MaybeCompressedData::Uncompressed(data.clone()),
+                "field map chunk",
+            ),
+            &Self::FieldMap {
+                map,
+                tileset_indexes,
+                map_chunk_index,
+                treasure_data_index,
+            } => {
+                *project
+                    .field_maps_mut()?
+                    .maps
+                    .get_mut(map)
+                    .ok_or(EditError::OutOfBounds {
+                        what: "field map",
+                        index: map,
+                    })? = FieldMap {
+                    tileset_indexes,
+                    map_chunk_index,
+                    treasure_data_index,
+                };
+                Ok(())
+            }
+            Self::TreasureData { index, data } => {
+                *project
+                    .field_maps_mut()?
+                    .treasure_data
+                    .get_mut(*index)
+                    .ok_or(EditError::OutOfBounds {
+                        what: "treasure data",
+                        index: *index,
+                    })? = data.clone();
+                Ok(())
+            }
+            Self::Message {
+                file,
+                set,
+                list,
+                message,
+                text,
+            } => {
+                *project
+                    .messages_mut(file)?
+                    .get_mut(ArchiveMessageId {
+                        set: *set,
+                        id: MessageId {
+                            list: *list,
+                            message: *message,
+                        },
+                    })
+                    .ok_or(EditError::OutOfBounds {
+                        what: "message",
+                        index: *message,
+                    })? = text.clone();
+                Ok(())
+            }
+            Self::ListMessage {
+                file,
+                list,
+                message,
+                text,
+            } => {
+                *project
+                    .message_list_set_mut(file)?
+                    .get_mut(MessageId {
+                        list: *list,
+                        message: *message,
+                    })
+                    .ok_or(EditError::OutOfBounds {
+                        what: "message",
+                        index: *message,
+                    })? = text.clone();
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A mod, distributed as a list of [`Edit`]s which are applied to a clean dump.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ModPackage {
+    pub name: String,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub description: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub edits: Vec<Edit>,
+}
+
+impl ModPackage {
+    /// Applies all edits in order.
+    /// Nothing is written until the project is [saved](Project::save).
+    ///
+    /// A file can't be changed both by [`Edit::Chunk`] and by [`Edit::Message`]
+    /// or [`Edit::ListMessage`], as the project would save only one of them.
+    /// Such packages are rejected before anything is applied.
+    pub fn apply(&self, project: &mut Project) -> Result<(), ModApplyError> {
+        // The first edit of each file, and whether it replaces a chunk.
+        let mut files: HashMap<&str, (usize, bool)> = HashMap::new();
+        for (index, edit) in self.edits.iter().enumerate() {
+            let (file, by_chunk) = match edit {
+                Edit::Chunk { file, .. } => (file, true),
+                Edit::Message { file, .. } | Edit::ListMessage { file, .. } => (file, false),
+                _ => continue,
+            };
+            let (first, first_by_chunk) = *files.entry(file).or_insert((index, by_chunk));
+            if first_by_chunk != by_chunk {
+                return Err(ModApplyError::MixedFileEdits {
+                    file: file.clone(),
+                    first,
+                    second: index,
+                });
+            }
+        }
+
+        for (index, edit) in self.edits.iter().enumerate() {
+            edit.apply(project)
+                .map_err(|source| ModApplyError::Edit { index, source })?;
+        }
+        Ok(())
+    }
+
+    /// The edits which change `base`, with only the last one of each [`EditTarget`],
+    /// at the position of the first one.
+    fn effective_edits(&self, base: &mut Project) -> Result<Vec<Edit>, ModMergeError> {
+        let mut edits: Vec<(usize, &Edit)> = Vec::new();
+        let mut positions = HashMap::new();
+        for (index, edit) in self.edits.iter().enumerate() {
+            match positions.get(&edit.target()) {
+                Some(&position) => edits[position] = (index, edit),
+                None => {
+                    positions.insert(edit.target(), edits.len());
+                    edits.push((index, edit));
+                }
+            }
+        }
+        let mut effective = Vec::with_capacity(edits.len());
+        for (index, edit) in edits {
+            let is_noop = edit.is_noop(base).map_err(|source| ModMergeError::Edit {
+                package: self.name.clone(),
+                index,
+                source,
+            })?;
+            if !is_noop {
+                effective.push(edit.clone());
+            }
+        }
+        Ok(effective)
+    }
+
+    /// Merges two packages made for the same clean dump `base`,
+    /// comparing their edits by [`EditTarget`], i.e. by map, message and chunk.
+    ///
+    /// Edits which don't change `base` are dropped, edits made by only one package
+    /// or identically by both are kept, and edits of the same target with different
+    /// data are left out of the merged package and reported as conflicts.
+    /// The edits of `ours` come first, in their original order.
+    pub fn merge(
+        base: &mut Project,
+        ours: &Self,
+        theirs: &Self,
+    ) -> Result<ModMerge, ModMergeError> {
+        let our_edits = ours.effective_edits(base)?;
+        let their_edits = theirs.effective_edits(base)?;
+        let their_targets: HashMap<EditTarget, &Edit> =
+            their_edits.iter().map(|x| (x.target(), x)).collect();
+        let our_targets: HashMap<EditTarget, &Edit> =
+            our_edits.iter().map(|x| (x.target(), x)).collect();
+
+        let mut edits = Vec::new();
+        let mut conflicts = Vec::new();
+        for edit in &our_edits {
+            let target = edit.target();
+            match their_targets.get(&target) {
+                Some(&theirs) if theirs != edit => conflicts.push(MergeConflict {
+                    target,
+                    ours: edit.clone(),
+                    theirs: theirs.clone(),
+                }),
+                _ => edits.push(edit.clone()),
+            }
+        }
+        edits.extend(
+            their_edits
+                .iter()
+                .filter(|x| !our_targets.contains_key(&x.target()))
+                .cloned(),
+        );
+
+        Ok(ModMerge {
+            package: Self {
+                name: format!("{} + {}", ours.name, theirs.name),
+                description: None,
+                edits,
+            },
+            conflicts,
+        })
+    }
+
+    /// Parses a package from TOML like this, where data is hexadecimal:
+    ///
+    /// ```toml
+    /// name = "Example"
+    ///
+    /// [[edits]]
+    /// kind = "list_message"
+    /// file = "BData/mfset_UItmN.dat"
+    /// list = 2
+    /// message = 0
+    /// text = "2C 4F FF 0A"
+    /// ```
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(toml)
+    }
+    /// Serializes the package in the format accepted by [`Self::from_toml`].
+    #[cfg(feature = "toml")]
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(self)
+    }
+}
+
+/// Edits of the same [`EditTarget`] with different data in the two merged packages.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MergeConflict {
+    pub target: EditTarget,
+    pub ours: Edit,
+    pub theirs: Edit,
+}
+
+impl Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "both packages change {} differently", self.target)
+    }
+}
+
+/// The result of [`ModPackage::merge`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct ModMerge {
+    /// All edits except the conflicting ones.
+    pub package: ModPackage,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+#[derive(Error, Debug)]
+pub enum ModMergeError {
+    #[error("failed to compare edit {index} of {package:?} with the base")]
+    Edit {
+        package: String,
+        index: usize,
+        #[source]
+        source: EditError,
+    },
+}
+
+/// Optional indexes, with `None` as `-1`, since TOML doesn't have null values.
+#[cfg(feature = "serde")]
+mod optional_indexes {
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        indexes: &[Option<usize>; 3],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        indexes
+            .map(|x| x.map_or(-1, |x| x as i64))
+            .serialize(serializer)
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<[Option<usize>; 3], D::Error> {
+        <[i64; 3]>::deserialize(deserializer)?
+            .map(|x| match x {
+                -1 => Ok(None),
+                _ => usize::try_from(x)
+                    .map(Some)
+                    .map_err(|_| D::Error::custom("invalid index")),
+            })
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .map(|x| x.try_into().unwrap())
+    }
+}
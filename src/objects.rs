@@ -0,0 +1,205 @@
+use byteorder::{ByteOrder, LittleEndian};
+use thiserror::Error;
+
+use crate::misc::DataWithOffsetTable;
+
+/// The box which an object collides with, relative to its position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Hitbox {
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    /// The extent along the Z axis.
+    pub depth: u16,
+}
+
+/// The definition of a field object, which the objects placed in field maps refer to.
+///
+/// Each is a chunk of `FObj.dat`, consisting of the fields below
+/// followed by data which is preserved as-is.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct FieldObject {
+    /// An index into the field [`SpriteArchive`](crate::sprites::SpriteArchive).
+    pub sprite: u16,
+    /// The animation of the sprite which is played by default.
+    pub animation: u16,
+    pub hitbox: Hitbox,
+    /// What the object does, e.g. when it's talked to or jumped on.
+    pub behavior: u16,
+    pub extra: Vec<u8>,
+}
+
+#[derive(Error, Debug)]
+pub enum ObjectDeserializationError {
+    #[error("the data is {actual} bytes long, but it should be at least {min} bytes long")]
+    TooShort { min: usize, actual: usize },
+}
+
+impl FieldObject {
+    pub const MIN_SIZE: usize = 0x10;
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ObjectDeserializationError> {
+        if data.len() < Self::MIN_SIZE {
+            return Err(ObjectDeserializationError::TooShort {
+                min: Self::MIN_SIZE,
+                actual: data.len(),
+            });
+        }
+        Ok(Self {
+            sprite: LittleEndian::read_u16(&data[0x00..]),
+            animation: LittleEndian::read_u16(&data[0x02..]),
+            hitbox: Hitbox {
+                x: LittleEndian::read_i16(&data[0x04..]),
+                y: LittleEndian::read_i16(&data[0x06..]),
+                width: LittleEndian::read_u16(&data[0x08..]),
+                height: LittleEndian::read_u16(&data[0x0A..]),
+                depth: LittleEndian::read_u16(&data[0x0C..]),
+            },
+            behavior: LittleEndian::read_u16(&data[0x0E..]),
+            extra: data[Self::MIN_SIZE..].to_vec(),
+        })
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![0u8; Self::MIN_SIZE];
+        LittleEndian::write_u16(&mut data[0x00..], self.sprite);
+        LittleEndian::write_u16(&mut data[0x02..], self.animation);
+        LittleEndian::write_i16(&mut data[0x04..], self.hitbox.x);
+        LittleEndian::write_i16(&mut data[0x06..], self.hitbox.y);
+        LittleEndian::write_u16(&mut data[0x08..], self.hitbox.width);
+        LittleEndian::write_u16(&mut data[0x0A..], self.hitbox.height);
+        LittleEndian::write_u16(&mut data[0x0C..], self.hitbox.depth);
+        LittleEndian::write_u16(&mut data[0x0E..], self.behavior);
+        data.extend_from_slice(&self.extra);
+        data
+    }
+}
+
+/// `FObj.dat`, which contains all [`FieldObject`]s.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct FieldObjectFile {
+    pub objects: Vec<FieldObject>,
+    pub padding: Vec<u8>,
+}
+
+#[derive(Error, Debug)]
+pub enum ObjectFileFromTableError {
+    #[error("failed to deserialize object {index}")]
+    Object {
+        index: usize,
+        #[source]
+        source: ObjectDeserializationError,
+    },
+}
+
+fn objects_from_table<T>(
+    table: &DataWithOffsetTable,
+    from_bytes: impl Fn(&[u8]) -> Result<T, ObjectDeserializationError>,
+) -> Result<Vec<T>, ObjectFileFromTableError> {
+    table
+        .chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            from_bytes(chunk).map_err(|source| ObjectFileFromTableError::Object { index, source })
+        })
+        .collect()
+}
+
+impl TryFrom<DataWithOffsetTable> for FieldObjectFile {
+    type Error = ObjectFileFromTableError;
+
+    fn try_from(value: DataWithOffsetTable) -> Result<Self, Self::Error> {
+        Ok(Self {
+            objects: objects_from_table(&value, FieldObject::from_bytes)?,
+            padding: value.footer,
+        })
+    }
+}
+impl From<FieldObjectFile> for DataWithOffsetTable {
+    fn from(value: FieldObjectFile) -> Self {
+        Self {
+            chunks: value.objects.iter().map(FieldObject::to_bytes).collect(),
+            footer: value.padding,
+        }
+    }
+}
+
+/// The definition of a battle object, such as an enemy's appearance in battle.
+///
+/// Each is a chunk of `BObj.dat`, consisting of the fields below
+/// followed by data which is preserved as-is.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct BattleObject {
+    /// An index into the battle [`SpriteArchive`](crate::sprites::SpriteArchive).
+    pub sprite: u16,
+    pub palette: u16,
+    /// The effects which the object uses, or [`Self::NO_EFFECT`].
+    pub effects: [u16; Self::NUMBER_OF_EFFECTS],
+    pub extra: Vec<u8>,
+}
+
+impl BattleObject {
+    pub const NUMBER_OF_EFFECTS: usize = 4;
+    pub const NO_EFFECT: u16 = 0xFFFF;
+    pub const MIN_SIZE: usize = 4 + Self::NUMBER_OF_EFFECTS * 2;
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ObjectDeserializationError> {
+        if data.len() < Self::MIN_SIZE {
+            return Err(ObjectDeserializationError::TooShort {
+                min: Self::MIN_SIZE,
+                actual: data.len(),
+            });
+        }
+        Ok(Self {
+            sprite: LittleEndian::read_u16(&data[0x00..]),
+            palette: LittleEndian::read_u16(&data[0x02..]),
+            effects: std::array::from_fn(|i| LittleEndian::read_u16(&data[0x04 + i * 2..])),
+            extra: data[Self::MIN_SIZE..].to_vec(),
+        })
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![0u8; Self::MIN_SIZE];
+        LittleEndian::write_u16(&mut data[0x00..], self.sprite);
+        LittleEndian::write_u16(&mut data[0x02..], self.palette);
+        for (i, &effect) in self.effects.iter().enumerate() {
+            LittleEndian::write_u16(&mut data[0x04 + i * 2..], effect);
+        }
+        data.extend_from_slice(&self.extra);
+        data
+    }
+
+    /// The effects which are actually used.
+    pub fn used_effects(&self) -> impl Iterator<Item = u16> + '_ {
+        self.effects
+            .iter()
+            .copied()
+            .filter(|&x| x != Self::NO_EFFECT)
+    }
+}
+
+/// `BObj.dat`, which contains all [`BattleObject`]s.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct BattleObjectFile {
+    pub objects: Vec<BattleObject>,
+    pub padding: Vec<u8>,
+}
+
+impl TryFrom<DataWithOffsetTable> for BattleObjectFile {
+    type Error = ObjectFileFromTableError;
+
+    fn try_from(value: DataWithOffsetTable) -> Result<Self, Self::Error> {
+        Ok(Self {
+            objects: objects_from_table(&value, BattleObject::from_bytes)?,
+            padding: value.footer,
+        })
+    }
+}
+impl From<BattleObjectFile> for DataWithOffsetTable {
+    fn from(value: BattleObjectFile) -> Self {
+        Self {
+            chunks: value.objects.iter().map(BattleObject::to_bytes).collect(),
+            footer: value.padding,
+        }
+    }
+}
@@ -0,0 +1,785 @@
+use std::{
+    fmt::{self, Debug},
+    hash::Hash,
+    io::{self, Cursor, Read, Write},
+    num::TryFromIntError,
+    ops::{Index, Range},
+};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use thiserror::Error;
+
+use crate::utils::{
+    hash_bytes, necessary_padding_for, write_zeros, AlignToElements, ByteCounter, IndexRemap,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DataWithOffsetTable {
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_chunks"))]
+    pub chunks: Vec<Vec<u8>>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex_bytes"))]
+    pub footer: Vec<u8>,
+}
+
+#[derive(Error, Debug)]
+pub enum DataWithOffsetTableDeserializationError {
+    #[error("failed to read entry {index} of the offset table")]
+    OffsetTable {
+        index: usize,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to read chunk {index} (at offset {offset:#X})")]
+    Chunk {
+        index: usize,
+        offset: u32,
+        #[source]
+        source: io::Error,
+    },
+    #[error(
+        "offset {index} ({offset:#X}) is smaller than the previous one ({previous_offset:#X})"
+    )]
+    DecreasingOffset {
+        index: usize,
+        offset: u32,
+        previous_offset: u32,
+    },
+    #[error(transparent)]
+    TryFromInt(#[from] TryFromIntError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+#[derive(Error, Debug)]
+pub enum DataWithOffsetTableSerializationError {
+    /// Offset 0 is the start of the first chunk and offset `n + 1` the end of chunk `n`.
+    #[error("offset {index} ({offset:#X}) exceeds the largest one the offset table can hold ({limit:#X})")]
+    OffsetOverflow {
+        index: usize,
+        offset: usize,
+        limit: u32,
+    },
+    #[error(transparent)]
+    TryFromInt(#[from] TryFromIntError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Something that was wrong with damaged data and had to be worked around
+/// while reading it leniently.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SalvageProblem {
+    /// The offset table ended early, only its first `num_offsets` entries could be read.
+    TruncatedOffsetTable { num_offsets: usize },
+    /// Offset `index` was smaller than the previous one,
+    /// so chunk `index - 1` was left empty.
+    DecreasingOffset {
+        index: usize,
+        offset: u32,
+        previous_offset: u32,
+    },
+    /// Chunk `index` starts at `offset`, which is before the start of the data,
+    /// so it was left empty.
+    OffsetBeforeData { index: usize, offset: u32 },
+    /// The chunk extends past the end of the data,
+    /// so only its first `actual_size` of `expected_size` bytes could be read.
+    TruncatedChunk {
+        index: usize,
+        offset: u32,
+        expected_size: usize,
+        actual_size: usize,
+    },
+    /// Chunk `index` borders on a [`Self::DecreasingOffset`], one of whose offsets is wrong,
+    /// so it may be cut short or contain data of the chunk after or before it.
+    UnreliableChunk { index: usize },
+}
+/// What had to be worked around while reading damaged data leniently.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SalvageReport {
+    pub problems: Vec<SalvageProblem>,
+}
+
+impl SalvageReport {
+    /// Whether nothing had to be worked around.
+    #[inline]
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// How the chunks of a [`DataWithOffsetTable`] are aligned when writing.
+///
+/// `Option<usize>` and `usize` convert into this,
+/// the former into [`Self::None`] or [`Self::All`].
+#[derive(Clone, Copy, Default)]
+pub enum ChunkAlignment<'a> {
+    #[default]
+    None,
+    All(usize),
+    /// Used by files whose final chunk is aligned differently from the others.
+    AllButLast {
+        others: Option<usize>,
+        last: Option<usize>,
+    },
+    /// Called with the index of each chunk and the total number of chunks,
+    /// returning the alignment of that chunk.
+    PerChunk(&'a dyn Fn(usize, usize) -> Option<usize>),
+}
+
+impl ChunkAlignment<'_> {
+    pub fn for_chunk(&self, index: usize, num_chunks: usize) -> Option<usize> {
+        match *self {
+            Self::None => None,
+            Self::All(alignment) => Some(alignment),
+            Self::AllButLast { others, last } => {
+                if index + 1 == num_chunks {
+                    last
+                } else {
+                    others
+                }
+            }
+            Self::PerChunk(f) => f(index, num_chunks),
+        }
+    }
+}
+impl Debug for ChunkAlignment<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "None"),
+            Self::All(alignment) => f.debug_tuple("All").field(alignment).finish(),
+            Self::AllButLast { others, last } => f
+                .debug_struct("AllButLast")
+                .field("others", others)
+                .field("last", last)
+                .finish(),
+            Self::PerChunk(_) => f.debug_tuple("PerChunk").finish_non_exhaustive(),
+        }
+    }
+}
+impl From<Option<usize>> for ChunkAlignment<'_> {
+    #[inline]
+    fn from(value: Option<usize>) -> Self {
+        value.map_or(Self::None, Self::All)
+    }
+}
+impl From<usize> for ChunkAlignment<'_> {
+    #[inline]
+    fn from(value: usize) -> Self {
+        Self::All(value)
+    }
+}
+
+/// The width of the entries of the offset table of a [`DataWithOffsetTable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum OffsetTableEntrySize {
+    /// Used by some smaller data files.
+    U16,
+    #[default]
+    U32,
+}
+
+impl OffsetTableEntrySize {
+    #[inline]
+    pub const fn size(self) -> usize {
+        match self {
+            Self::U16 => 2,
+            Self::U32 => 4,
+        }
+    }
+
+    /// The largest offset an entry can hold.
+    #[inline]
+    pub const fn max_offset(self) -> u32 {
+        match self {
+            Self::U16 => u16::MAX as u32,
+            Self::U32 => u32::MAX,
+        }
+    }
+
+    fn read_offset(self, mut inp: impl Read) -> io::Result<u32> {
+        Ok(match self {
+            Self::U16 => inp.read_u16::<LittleEndian>()?.into(),
+            Self::U32 => inp.read_u32::<LittleEndian>()?,
+        })
+    }
+    fn write_offset(
+        self,
+        mut out: impl Write,
+        index: usize,
+        offset: usize,
+    ) -> Result<(), DataWithOffsetTableSerializationError> {
+        let overflow = |_| DataWithOffsetTableSerializationError::OffsetOverflow {
+            index,
+            offset,
+            limit: self.max_offset(),
+        };
+        match self {
+            Self::U16 => out.write_u16::<LittleEndian>(offset.try_into().map_err(overflow)?)?,
+            Self::U32 => out.write_u32::<LittleEndian>(offset.try_into().map_err(overflow)?)?,
+        }
+        Ok(())
+    }
+}
+
+impl DataWithOffsetTable {
+    #[inline]
+    pub fn from_reader(inp: impl Read) -> Result<Self, DataWithOffsetTableDeserializationError> {
+        Self::from_reader_with_entry_size(inp, OffsetTableEntrySize::U32)
+    }
+    pub fn from_reader_with_entry_size(
+        mut inp: impl Read,
+        entry_size: OffsetTableEntrySize,
+    ) -> Result<Self, DataWithOffsetTableDeserializationError> {
+        let offsets = read_offset_table(&mut inp, entry_size, None)?;
+        let mut chunks = Vec::with_capacity(offsets.len().saturating_sub(1));
+        for_each_chunk_size(&offsets, |index, offset, size| {
+            let mut buf = vec![0u8; size];
+            inp.read_exact(&mut buf).map_err(|source| {
+                DataWithOffsetTableDeserializationError::Chunk {
+                    index,
+                    offset,
+                    source,
+                }
+            })?;
+            chunks.push(buf);
+            Ok(())
+        })?;
+
+        Ok(Self {
+            chunks,
+            footer: {
+                let mut buf: Vec<u8> = Vec::new();
+                inp.read_to_end(&mut buf)?;
+                buf
+            },
+        })
+    }
+    /// Like [`Self::from_reader`], but recovers as much as possible from damaged data
+    /// instead of failing: a truncated offset table is cut short,
+    /// chunks with a decreasing offset are left empty
+    /// and truncated chunks keep the bytes which could be read.
+    /// Every chunk is taken from the data at its own offset,
+    /// so one bad offset only affects the chunks next to it.
+    /// Everything that was worked around is listed in the returned [`SalvageReport`].
+    ///
+    /// The rest of `inp` after the offset table is read into memory at once,
+    /// no matter what sizes the offsets claim.
+    #[inline]
+    pub fn from_reader_lenient(
+        inp: impl Read,
+    ) -> Result<(Self, SalvageReport), DataWithOffsetTableDeserializationError> {
+        Self::from_reader_lenient_with_entry_size(inp, OffsetTableEntrySize::U32)
+    }
+    pub fn from_reader_lenient_with_entry_size(
+        mut inp: impl Read,
+        entry_size: OffsetTableEntrySize,
+    ) -> Result<(Self, SalvageReport), DataWithOffsetTableDeserializationError> {
+        let mut report = SalvageReport::default();
+        let offsets = read_offset_table(&mut inp, entry_size, Some(&mut report))?;
+        let mut data: Vec<u8> = Vec::new();
+        inp.read_to_end(&mut data)?;
+        let (chunks, footer) = salvage_chunks(
+            &data,
+            offsets.first().copied().unwrap_or(0),
+            &offsets,
+            &mut report,
+        );
+
+        Ok((Self { chunks, footer }, report))
+    }
+
+    /// Padding is emitted after each chunk according to `chunk_alignment`
+    /// while writing; `self.chunks` are left untouched.
+    #[inline]
+    pub fn to_writer<'a>(
+        &self,
+        out: impl Write,
+        chunk_alignment: impl Into<ChunkAlignment<'a>>,
+        write_footer: bool,
+    ) -> Result<(), DataWithOffsetTableSerializationError> {
+        self.to_writer_with_entry_size(
+            out,
+            OffsetTableEntrySize::U32,
+            chunk_alignment,
+            write_footer,
+        )
+    }
+    /// See [`Self::to_writer`].
+    pub fn to_writer_with_entry_size<'a>(
+        &self,
+        mut out: impl Write,
+        entry_size: OffsetTableEntrySize,
+        chunk_alignment: impl Into<ChunkAlignment<'a>>,
+        write_footer: bool,
+    ) -> Result<(), DataWithOffsetTableSerializationError> {
+        write_chunks(
+            &mut out,
+            self.chunks.iter().map(Vec::as_slice),
+            entry_size,
+            chunk_alignment.into(),
+        )?;
+        if write_footer {
+            out.write_all(&self.footer)?;
+        }
+        Ok(())
+    }
+    /// The exact number of bytes [`Self::to_writer`] would write, without writing anything.
+    #[inline]
+    pub fn computed_size<'a>(
+        &self,
+        chunk_alignment: impl Into<ChunkAlignment<'a>>,
+        write_footer: bool,
+    ) -> Result<usize, DataWithOffsetTableSerializationError> {
+        self.computed_size_with_entry_size(OffsetTableEntrySize::U32, chunk_alignment, write_footer)
+    }
+    pub fn computed_size_with_entry_size<'a>(
+        &self,
+        entry_size: OffsetTableEntrySize,
+        chunk_alignment: impl Into<ChunkAlignment<'a>>,
+        write_footer: bool,
+    ) -> Result<usize, DataWithOffsetTableSerializationError> {
+        let mut counter = ByteCounter::default();
+        self.to_writer_with_entry_size(&mut counter, entry_size, chunk_alignment, write_footer)?;
+        Ok(counter.0)
+    }
+
+    /// Returns a reader over the chunk at `index`, or [`None`] if there is none.
+    ///
+    /// Compressed chunks can be read through a [`Decompressor`](crate::Decompressor) wrapping it.
+    #[inline]
+    pub fn chunk_reader(&self, index: usize) -> Option<Cursor<&[u8]>> {
+        self.chunks.get(index).map(|chunk| Cursor::new(&chunk[..]))
+    }
+
+    /// Inserts `chunk` at `index`, shifting all chunks after it.
+    /// The returned [`IndexRemap`] should be applied to any references to the chunks.
+    pub fn insert_chunk(&mut self, index: usize, chunk: Vec<u8>) -> IndexRemap {
+        self.chunks.insert(index, chunk);
+        IndexRemap::Inserted(index)
+    }
+    /// Removes the chunk at `index`, shifting all chunks after it.
+    /// The returned [`IndexRemap`] should be applied to any references to the chunks.
+    pub fn remove_chunk(&mut self, index: usize) -> (Vec<u8>, IndexRemap) {
+        (self.chunks.remove(index), IndexRemap::Removed(index))
+    }
+
+    /// Returns the [`hash_bytes`] of each chunk, which can be compared
+    /// with [`changed_indexes`](crate::utils::changed_indexes).
+    pub fn chunk_hashes(&self) -> Vec<u64> {
+        self.chunks.iter().map(|chunk| hash_bytes(chunk)).collect()
+    }
+
+    /// Aligns `self.chunks` in-place, as they would be written
+    /// by [`Self::to_writer`] with the same `alignment`.
+    pub fn align_chunks<'a>(&mut self, alignment: impl Into<ChunkAlignment<'a>>) {
+        let alignment = alignment.into();
+        let num_chunks = self.chunks.len();
+        for (index, chunk) in self.chunks.iter_mut().enumerate() {
+            if let Some(alignment) = alignment.for_chunk(index, num_chunks) {
+                chunk.align_to_elements(alignment);
+            }
+        }
+    }
+}
+
+/// With a `report`, an offset table which ends early is cut short instead of failing.
+fn read_offset_table(
+    mut inp: impl Read,
+    entry_size: OffsetTableEntrySize,
+    report: Option<&mut SalvageReport>,
+) -> Result<Vec<u32>, DataWithOffsetTableDeserializationError> {
+    let offset_table_error = |index| {
+        move |source| DataWithOffsetTableDeserializationError::OffsetTable { index, source }
+    };
+
+    let first_offset = match entry_size.read_offset(&mut inp) {
+        Ok(offset) => offset,
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+            let Some(report) = report else {
+                return Err(offset_table_error(0)(err));
+            };
+            report
+                .problems
+                .push(SalvageProblem::TruncatedOffsetTable { num_offsets: 0 });
+            return Ok(Vec::new());
+        }
+        Err(err) => return Err(offset_table_error(0)(err)),
+    };
+    let entry_size_u32 = u32::try_from(entry_size.size())?;
+    let (num_offsets, padding) = (first_offset / entry_size_u32, first_offset % entry_size_u32);
+    // Not preallocated, as `num_offsets` comes from the data itself.
+    let mut offsets: Vec<u32> = vec![first_offset];
+    for index in 1..num_offsets.try_into()? {
+        match entry_size.read_offset(&mut inp) {
+            Ok(offset) => offsets.push(offset),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                let Some(report) = report else {
+                    return Err(offset_table_error(index)(err));
+                };
+                report.problems.push(SalvageProblem::TruncatedOffsetTable {
+                    num_offsets: offsets.len(),
+                });
+                return Ok(offsets);
+            }
+            Err(err) => return Err(offset_table_error(index)(err)),
+        }
+    }
+    if padding != 0 {
+        // Alternative to seeking so that we don't require `Seek` for this one operation.
+        let mut padding_buf = vec![0u8; padding.try_into()?];
+        match inp.read_exact(&mut padding_buf) {
+            // Nothing is left for the chunks then, which they will report.
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof && report.is_some() => {}
+            result => result.map_err(offset_table_error(offsets.len()))?,
+        }
+    }
+    Ok(offsets)
+}
+/// Calls `f` with the index, offset and size of each chunk described by `offsets`.
+fn for_each_chunk_size(
+    offsets: &[u32],
+    mut f: impl FnMut(usize, u32, usize) -> Result<(), DataWithOffsetTableDeserializationError>,
+) -> Result<(), DataWithOffsetTableDeserializationError> {
+    // UNSTABLE: Use `slice::array_windows`.
+    for (index, offset_pair) in offsets.windows(2).enumerate() {
+        let (current_offset, next_offset) = (offset_pair[0], offset_pair[1]);
+        let size = next_offset.checked_sub(current_offset).ok_or(
+            DataWithOffsetTableDeserializationError::DecreasingOffset {
+                index: index + 1,
+                offset: next_offset,
+                previous_offset: current_offset,
+            },
+        )?;
+        f(index, current_offset, size.try_into()?)?;
+    }
+    Ok(())
+}
+/// Takes the chunks described by `offsets` out of `data`, which starts at offset `base`,
+/// working around damage as described in [`DataWithOffsetTable::from_reader_lenient`].
+/// Also returns everything after the end of the last chunk.
+pub(crate) fn salvage_chunks(
+    data: &[u8],
+    base: u32,
+    offsets: &[u32],
+    report: &mut SalvageReport,
+) -> (Vec<Vec<u8>>, Vec<u8>) {
+    // Positions in `data`, clamped to its end.
+    let position = |offset: u32| ((offset - base) as usize).min(data.len());
+
+    let mut chunks = Vec::with_capacity(offsets.len().saturating_sub(1));
+    for (index, offset_pair) in offsets.windows(2).enumerate() {
+        let (offset, next_offset) = (offset_pair[0], offset_pair[1]);
+        if next_offset < offset {
+            report.problems.push(SalvageProblem::DecreasingOffset {
+                index: index + 1,
+                offset: next_offset,
+                previous_offset: offset,
+            });
+            if let Some(previous) = index.checked_sub(1) {
+                report
+                    .problems
+                    .push(SalvageProblem::UnreliableChunk { index: previous });
+            }
+            if index + 2 < offsets.len() {
+                report
+                    .problems
+                    .push(SalvageProblem::UnreliableChunk { index: index + 1 });
+            }
+            chunks.push(Vec::new());
+            continue;
+        }
+        if offset < base {
+            report
+                .problems
+                .push(SalvageProblem::OffsetBeforeData { index, offset });
+            chunks.push(Vec::new());
+            continue;
+        }
+        let chunk = &data[position(offset)..position(next_offset)];
+        let expected_size = (next_offset - offset) as usize;
+        if chunk.len() < expected_size {
+            report.problems.push(SalvageProblem::TruncatedChunk {
+                index,
+                offset,
+                expected_size,
+                actual_size: chunk.len(),
+            });
+        }
+        chunks.push(chunk.to_vec());
+    }
+    let end = offsets
+        .last()
+        .map_or(0, |&offset| position(offset.max(base)));
+    (chunks, data[end..].to_vec())
+}
+fn write_chunks<'c>(
+    mut out: impl Write,
+    chunks: impl ExactSizeIterator<Item = &'c [u8]> + Clone,
+    entry_size: OffsetTableEntrySize,
+    chunk_alignment: ChunkAlignment,
+) -> Result<(), DataWithOffsetTableSerializationError> {
+    let num_chunks = chunks.len();
+    let padding_for = |index: usize, chunk: &[u8]| {
+        chunk_alignment
+            .for_chunk(index, num_chunks)
+            .map_or(0, |alignment| necessary_padding_for(chunk.len(), alignment))
+    };
+
+    let mut current_offset = (num_chunks + 1) * entry_size.size();
+    entry_size.write_offset(&mut out, 0, current_offset)?;
+    for (index, chunk) in chunks.clone().enumerate() {
+        current_offset += chunk.len() + padding_for(index, chunk);
+        entry_size.write_offset(&mut out, index + 1, current_offset)?;
+    }
+
+    for (index, chunk) in chunks.enumerate() {
+        out.write_all(chunk)?;
+        write_zeros(&mut out, padding_for(index, chunk))?;
+    }
+    Ok(())
+}
+
+/// Chunks stored back to back in a single buffer.
+///
+/// Holding thousands of small chunks this way avoids fragmenting the heap
+/// and makes dropping them cheap, at the cost of not being able to resize
+/// a chunk in-place. Useful for read-heavy workloads.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PackedChunks {
+    data: Vec<u8>,
+    /// The offset of each chunk into `data`, followed by `data.len()`.
+    offsets: Vec<usize>,
+}
+
+impl PackedChunks {
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_capacity(0, 0)
+    }
+    pub fn with_capacity(num_chunks: usize, data_len: usize) -> Self {
+        let mut offsets = Vec::with_capacity(num_chunks + 1);
+        offsets.push(0);
+        Self {
+            data: Vec::with_capacity(data_len),
+            offsets,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&[u8]> {
+        self.chunk_range(index).map(|range| &self.data[range])
+    }
+    /// The range of the chunk at `index` in [`Self::as_bytes`].
+    #[inline]
+    pub fn chunk_range(&self, index: usize) -> Option<Range<usize>> {
+        Some(*self.offsets.get(index)?..*self.offsets.get(index + 1)?)
+    }
+    /// All chunks, concatenated.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &[u8]> + ExactSizeIterator + Clone {
+        self.offsets
+            .windows(2)
+            .map(|offsets| &self.data[offsets[0]..offsets[1]])
+    }
+    /// See [`DataWithOffsetTable::chunk_reader`].
+    #[inline]
+    pub fn chunk_reader(&self, index: usize) -> Option<Cursor<&[u8]>> {
+        self.get(index).map(Cursor::new)
+    }
+
+    #[inline]
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.data.extend_from_slice(chunk);
+        self.offsets.push(self.data.len());
+    }
+    /// Reads exactly `len` bytes from `inp` as a new chunk.
+    /// On error, `self` is left unchanged.
+    ///
+    /// The buffer grows as data is read, so a bogus `len` doesn't allocate
+    /// more than `inp` actually holds.
+    pub fn push_from_reader(&mut self, inp: impl Read, len: usize) -> io::Result<()> {
+        let start = self.data.len();
+        let result = match inp.take(len as u64).read_to_end(&mut self.data) {
+            Ok(read) if read < len => Err(io::ErrorKind::UnexpectedEof.into()),
+            result => result,
+        };
+        if let Err(err) = result {
+            self.data.truncate(start);
+            return Err(err);
+        }
+        self.offsets.push(self.data.len());
+        Ok(())
+    }
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.offsets.truncate(1);
+    }
+
+    pub fn to_vecs(&self) -> Vec<Vec<u8>> {
+        self.iter().map(<[u8]>::to_vec).collect()
+    }
+}
+impl Default for PackedChunks {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Index<usize> for PackedChunks {
+    type Output = [u8];
+
+    #[inline]
+    fn index(&self, index: usize) -> &Self::Output {
+        self.get(index).unwrap_or_else(|| {
+            panic!(
+                "chunk index {} out of range for {} chunks",
+                index,
+                self.len()
+            )
+        })
+    }
+}
+impl<T: AsRef<[u8]>> FromIterator<T> for PackedChunks {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut chunks = Self::new();
+        chunks.extend(iter);
+        chunks
+    }
+}
+impl<T: AsRef<[u8]>> Extend<T> for PackedChunks {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for chunk in iter {
+            self.push(chunk.as_ref());
+        }
+    }
+}
+
+/// A [`DataWithOffsetTable`] whose chunks are [`PackedChunks`],
+/// for when the chunks are mostly read rather than modified.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PackedDataWithOffsetTable {
+    pub chunks: PackedChunks,
+    pub footer: Vec<u8>,
+}
+
+impl PackedDataWithOffsetTable {
+    #[inline]
+    pub fn from_reader(inp: impl Read) -> Result<Self, DataWithOffsetTableDeserializationError> {
+        Self::from_reader_with_entry_size(inp, OffsetTableEntrySize::U32)
+    }
+    pub fn from_reader_with_entry_size(
+        mut inp: impl Read,
+        entry_size: OffsetTableEntrySize,
+    ) -> Result<Self, DataWithOffsetTableDeserializationError> {
+        let offsets = read_offset_table(&mut inp, entry_size, None)?;
+        // The data isn't preallocated, as its size comes from the offsets,
+        // which may be much larger than the input.
+        let mut chunks = PackedChunks::with_capacity(offsets.len().saturating_sub(1), 0);
+        for_each_chunk_size(&offsets, |index, offset, size| {
+            chunks.push_from_reader(&mut inp, size).map_err(|source| {
+                DataWithOffsetTableDeserializationError::Chunk {
+                    index,
+                    offset,
+                    source,
+                }
+            })?;
+            Ok(())
+        })?;
+
+        Ok(Self {
+            chunks,
+            footer: {
+                let mut buf: Vec<u8> = Vec::new();
+                inp.read_to_end(&mut buf)?;
+                buf
+            },
+        })
+    }
+
+    /// See [`DataWithOffsetTable::to_writer`].
+    #[inline]
+    pub fn to_writer<'a>(
+        &self,
+        out: impl Write,
+        chunk_alignment: impl Into<ChunkAlignment<'a>>,
+        write_footer: bool,
+    ) -> Result<(), DataWithOffsetTableSerializationError> {
+        self.to_writer_with_entry_size(
+            out,
+            OffsetTableEntrySize::U32,
+            chunk_alignment,
+            write_footer,
+        )
+    }
+    /// See [`DataWithOffsetTable::to_writer`].
+    pub fn to_writer_with_entry_size<'a>(
+        &self,
+        mut out: impl Write,
+        entry_size: OffsetTableEntrySize,
+        chunk_alignment: impl Into<ChunkAlignment<'a>>,
+        write_footer: bool,
+    ) -> Result<(), DataWithOffsetTableSerializationError> {
+        write_chunks(
+            &mut out,
+            self.chunks.iter(),
+            entry_size,
+            chunk_alignment.into(),
+        )?;
+        if write_footer {
+            out.write_all(&self.footer)?;
+        }
+        Ok(())
+    }
+    /// See [`DataWithOffsetTable::computed_size`].
+    #[inline]
+    pub fn computed_size<'a>(
+        &self,
+        chunk_alignment: impl Into<ChunkAlignment<'a>>,
+        write_footer: bool,
+    ) -> Result<usize, DataWithOffsetTableSerializationError> {
+        self.computed_size_with_entry_size(OffsetTableEntrySize::U32, chunk_alignment, write_footer)
+    }
+    pub fn computed_size_with_entry_size<'a>(
+        &self,
+        entry_size: OffsetTableEntrySize,
+        chunk_alignment: impl Into<ChunkAlignment<'a>>,
+        write_footer: bool,
+    ) -> Result<usize, DataWithOffsetTableSerializationError> {
+        let mut counter = ByteCounter::default();
+        self.to_writer_with_entry_size(&mut counter, entry_size, chunk_alignment, write_footer)?;
+        Ok(counter.0)
+    }
+
+    #[inline]
+    pub fn chunk_reader(&self, index: usize) -> Option<Cursor<&[u8]>> {
+        self.chunks.chunk_reader(index)
+    }
+}
+impl From<DataWithOffsetTable> for PackedDataWithOffsetTable {
+    fn from(value: DataWithOffsetTable) -> Self {
+        Self {
+            chunks: value.chunks.into_iter().collect(),
+            footer: value.footer,
+        }
+    }
+}
+impl From<PackedDataWithOffsetTable> for DataWithOffsetTable {
+    fn from(value: PackedDataWithOffsetTable) -> Self {
+        Self {
+            chunks: value.chunks.to_vecs(),
+            footer: value.footer,
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use std::ops::Range;
+
+use thiserror::Error;
+
+const IPS_HEADER: &[u8] = b"PATCH";
+const IPS_FOOTER: &[u8] = b"EOF";
+/// Offsets are 24-bit.
+const IPS_MAX_SIZE: usize = 1 << 24;
+const IPS_MAX_RECORD_SIZE: usize = 0xFFFF;
+/// A record at this offset would be mistaken for [`IPS_FOOTER`].
+const IPS_FOOTER_OFFSET: usize = 0x454F46;
+/// The size of a record's offset and size.
+const IPS_RECORD_HEADER_SIZE: usize = 5;
+const IPS_MIN_RLE_SIZE: usize = 8;
+
+const BPS_HEADER: &[u8] = b"BPS1";
+const BPS_SOURCE_READ: u64 = 0;
+const BPS_TARGET_READ: u64 = 1;
+const BPS_SOURCE_COPY: u64 = 2;
+const BPS_TARGET_COPY: u64 = 3;
+const BPS_FOOTER_SIZE: usize = 12;
+
+/// The CRC-32 used by BPS patches (and ZIP, PNG, etc.).
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[derive(Error, Debug)]
+pub enum IpsCreationError {
+    #[error("byte {0:#X} differs, but IPS patches can only modify the first 16 MiB")]
+    TooLarge(usize),
+}
+#[derive(Error, Debug)]
+pub enum IpsApplicationError {
+    #[error("invalid IPS header")]
+    InvalidHeader,
+    #[error("the patch ended early")]
+    UnexpectedEnd,
+}
+#[derive(Error, Debug)]
+pub enum BpsApplicationError {
+    #[error("invalid BPS header")]
+    InvalidHeader,
+    #[error("the patch ended early")]
+    UnexpectedEnd,
+    #[error("the checksum of the patch is wrong")]
+    PatchChecksumMismatch,
+    #[error("the patch is for a different file")]
+    SourceMismatch,
+    #[error("the patched file is different from the one the patch was made for")]
+    TargetChecksumMismatch,
+    #[error("an action of the patch reads or writes out of bounds")]
+    OutOfBounds,
+}
+
+/// The ranges of `modified` which differ from `original`,
+/// merging ranges which are separated by fewer bytes than it takes to start a new one.
+fn differing_ranges(original: &[u8], modified: &[u8], merge_distance: usize) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for (i, &byte) in modified.iter().enumerate() {
+        if original.get(i) == Some(&byte) {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(last) if i - last.end <= merge_distance => last.end = i + 1,
+            _ => ranges.push(i..i + 1),
+        }
+    }
+    ranges
+}
+
+/// Creates an IPS patch which turns `original` into `modified`.
+///
+/// If `modified` is shorter, the patch uses the truncation extension.
+pub fn create_ips(original: &[u8], modified: &[u8]) -> Result<Vec<u8>, IpsCreationError> {
+    let mut patch = IPS_HEADER.to_vec();
+    for range in differing_ranges(original, modified, IPS_RECORD_HEADER_SIZE) {
+        if range.end > IPS_MAX_SIZE {
+            return Err(IpsCreationError::TooLarge(range.start.max(IPS_MAX_SIZE)));
+        }
+        let mut start = range.start;
+        while start < range.end {
+            // Records may overlap, so one byte can be repeated to avoid the footer offset.
+            let offset = if start == IPS_FOOTER_OFFSET {
+                start - 1
+            } else {
+                start
+            };
+            let end = range.end.min(offset + IPS_MAX_RECORD_SIZE);
+            let data = &modified[offset..end];
+            patch.extend_from_slice(&(offset as u32).to_be_bytes()[1..]);
+            if data.len() >= IPS_MIN_RLE_SIZE && data.iter().all(|&x| x == data[0]) {
+                patch.extend_from_slice(&[0, 0]);
+                patch.extend_from_slice(&(data.len() as u16).to_be_bytes());
+                patch.push(data[0]);
+            } else {
+                patch.extend_from_slice(&(data.len() as u16).to_be_bytes());
+                patch.extend_from_slice(data);
+            }
+            start = end;
+        }
+    }
+    patch.extend_from_slice(IPS_FOOTER);
+    if modified.len() < original.len() {
+        if modified.len() >= IPS_MAX_SIZE {
+            return Err(IpsCreationError::TooLarge(modified.len()));
+        }
+        patch.extend_from_slice(&(modified.len() as u32).to_be_bytes()[1..]);
+    }
+    Ok(patch)
+}
+
+/// Applies an IPS patch, including the truncation extension.
+pub fn apply_ips(original: &[u8], patch: &[u8]) -> Result<Vec<u8>, IpsApplicationError> {
+    let mut patch = patch
+        .strip_prefix(IPS_HEADER)
+        .ok_or(IpsApplicationError::InvalidHeader)?;
+    let mut take = |len: usize| -> Result<&[u8], IpsApplicationError> {
+        let (taken, rest) = patch
+            .split_at_checked(len)
+            .ok_or(IpsApplicationError::UnexpectedEnd)?;
+        patch = rest;
+        Ok(taken)
+    };
+    let be = |bytes: &[u8]| bytes.iter().fold(0, |acc, &x| acc << 8 | usize::from(x));
+
+    let mut output = original.to_vec();
+    loop {
+        let offset = take(3)?;
+        if offset == IPS_FOOTER {
+            break;
+        }
+        let offset = be(offset);
+        let (data, len) = match be(take(2)?) {
+            0 => {
+                let len = be(take(2)?);
+                (None, len)
+            }
+            len => (Some(take(len)?), len),
+        };
+        if output.len() < offset + len {
+            output.resize(offset + len, 0);
+        }
+        match data {
+            Some(data) => output[offset..offset + len].copy_from_slice(data),
+            None => output[offset..offset + len].fill(take(1)?[0]),
+        }
+    }
+    if let Ok(size) = take(3) {
+        output.truncate(be(size));
+    }
+    Ok(output)
+}
+
+fn write_bps_number(out: &mut Vec<u8>, mut number: u64) {
+    loop {
+        let x = (number & 0x7F) as u8;
+        number >>= 7;
+        if number == 0 {
+            out.push(0x80 | x);
+            break;
+        }
+        out.push(x);
+        number -= 1;
+    }
+}
+fn read_bps_number(data: &mut &[u8]) -> Result<u64, BpsApplicationError> {
+    let mut number = 0u64;
+    let mut shift = 1u64;
+    loop {
+        let (&x, rest) = data
+            .split_first()
+            .ok_or(BpsApplicationError::UnexpectedEnd)?;
+        *data = rest;
+        number = u64::from(x & 0x7F)
+            .checked_mul(shift)
+            .and_then(|x| number.checked_add(x))
+            .ok_or(BpsApplicationError::OutOfBounds)?;
+        if x & 0x80 != 0 {
+            return Ok(number);
+        }
+        shift = shift
+            .checked_mul(0x80)
+            .ok_or(BpsApplicationError::OutOfBounds)?;
+        number = number
+            .checked_add(shift)
+            .ok_or(BpsApplicationError::OutOfBounds)?;
+    }
+}
+
+/// Creates a BPS patch which turns `original` into `modified`.
+///
+/// Unchanged bytes are read from `original` at the same offset,
+/// and everything else is stored in the patch,
+/// which suits files whose data was modified in place.
+pub fn create_bps(original: &[u8], modified: &[u8]) -> Vec<u8> {
+    let mut patch = BPS_HEADER.to_vec();
+    write_bps_number(&mut patch, original.len() as u64);
+    write_bps_number(&mut patch, modified.len() as u64);
+    // No metadata.
+    write_bps_number(&mut patch, 0);
+
+    let mut position = 0;
+    // A command is up to a few bytes, so tiny unchanged gaps aren't worth reading.
+    for range in differing_ranges(original, modified, 2) {
+        if range.start > position {
+            write_bps_number(
+                &mut patch,
+                ((range.start - position - 1) as u64) << 2 | BPS_SOURCE_READ,
+            );
+        }
+        write_bps_number(
+            &mut patch,
+            ((range.len() - 1) as u64) << 2 | BPS_TARGET_READ,
+        );
+        patch.extend_from_slice(&modified[range.clone()]);
+        position = range.end;
+    }
+    if modified.len() > position {
+        write_bps_number(
+            &mut patch,
+            ((modified.len() - position - 1) as u64) << 2 | BPS_SOURCE_READ,
+        );
+    }
+
+    patch.extend_from_slice(&crc32(original).to_le_bytes());
+    patch.extend_from_slice(&crc32(modified).to_le_bytes());
+    patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+    patch
+}
+
+/// Applies a BPS patch, checking all of its checksums.
+pub fn apply_bps(original: &[u8], patch: &[u8]) -> Result<Vec<u8>, BpsApplicationError> {
+    let (body, footer) = patch
+        .len()
+        .checked_sub(BPS_FOOTER_SIZE)
+        .map(|x| patch.split_at(x))
+        .ok_or(BpsApplicationError::UnexpectedEnd)?;
+    let footer_crc = |i: usize| u32::from_le_bytes(footer[i * 4..][..4].try_into().unwrap());
+    if crc32(&patch[..patch.len() - 4]) != footer_crc(2) {
+        return Err(BpsApplicationError::PatchChecksumMismatch);
+    }
+    let mut data = body
+        .strip_prefix(BPS_HEADER)
+        .ok_or(BpsApplicationError::InvalidHeader)?;
+    let to_usize = |x: u64| usize::try_from(x).map_err(|_| BpsApplicationError::OutOfBounds);
+
+    let source_size = to_usize(read_bps_number(&mut data)?)?;
+    if source_size != original.len() || crc32(original) != footer_crc(0) {
+        return Err(BpsApplicationError::SourceMismatch);
+    }
+    let target_size = to_usize(read_bps_number(&mut data)?)?;
+    let metadata_size = to_usize(read_bps_number(&mut data)?)?;
+    data = data
+        .get(metadata_size..)
+        .ok_or(BpsApplicationError::UnexpectedEnd)?;
+
+    // `target_size` comes from the patch, so the reservation is capped at what
+    // the actions can plausibly produce; target copies may still grow the output past it.
+    let mut output = Vec::with_capacity(target_size.min(original.len() + body.len()));
+    let (mut source_offset, mut target_offset) = (0usize, 0usize);
+    let relative = |offset: usize, data: &mut &[u8]| -> Result<usize, BpsApplicationError> {
+        let number = to_usize(read_bps_number(data)?)?;
+        if number & 1 != 0 {
+            offset.checked_sub(number >> 1)
+        } else {
+            offset.checked_add(number >> 1)
+        }
+        .ok_or(BpsApplicationError::OutOfBounds)
+    };
+    while !data.is_empty() {
+        let command = read_bps_number(&mut data)?;
+        let len = to_usize(command >> 2)?
+            .checked_add(1)
+            .ok_or(BpsApplicationError::OutOfBounds)?;
+        if output.len() + len > target_size {
+            return Err(BpsApplicationError::OutOfBounds);
+        }
+        match command & 3 {
+            BPS_SOURCE_READ => {
+                let start = output.len();
+                output.extend_from_slice(
+                    original
+                        .get(start..start + len)
+                        .ok_or(BpsApplicationError::OutOfBounds)?,
+                );
+            }
+            BPS_TARGET_READ => {
+                let (bytes, rest) = data
+                    .split_at_checked(len)
+                    .ok_or(BpsApplicationError::UnexpectedEnd)?;
+                output.extend_from_slice(bytes);
+                data = rest;
+            }
+            BPS_SOURCE_COPY => {
+                source_offset = relative(source_offset, &mut data)?;
+                output.extend_from_slice(
+                    original
+                        .get(source_offset..source_offset + len)
+                        .ok_or(BpsApplicationError::OutOfBounds)?,
+                );
+                source_offset += len;
+            }
+            BPS_TARGET_COPY => {
+                target_offset = relative(target_offset, &mut data)?;
+                if target_offset >= output.len() {
+                    return Err(BpsApplicationError::OutOfBounds);
+                }
+                // The copy may overlap with its own output.
+                for i in target_offset..target_offset + len {
+                    output.push(output[i]);
+                }
+                target_offset += len;
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    if output.len() != target_size || crc32(&output) != footer_crc(1) {
+        return Err(BpsApplicationError::TargetChecksumMismatch);
+    }
+    Ok(output)
+}
+
+/// A patch format supported by [`create_patch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PatchFormat {
+    Ips,
+    Bps,
+}
+
+impl PatchFormat {
+    /// The usual file extension, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Ips => "ips",
+            Self::Bps => "bps",
+        }
+    }
+}
+
+/// Creates a patch which turns `original` into `modified`,
+/// e.g. for a single file or a whole [rebuilt ROM](crate::rom::NdsRom::to_bytes).
+pub fn create_patch(
+    format: PatchFormat,
+    original: &[u8],
+    modified: &[u8],
+) -> Result<Vec<u8>, IpsCreationError> {
+    match format {
+        PatchFormat::Ips => create_ips(original, modified),
+        PatchFormat::Bps => Ok(create_bps(original, modified)),
+    }
+}
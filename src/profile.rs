@@ -0,0 +1,204 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    consts::{
+        FEVENT_OFFSET_TABLE_LENGTH_ADDRESS, FIELD_MAP_CHUNK_TABLE_ADDRESS,
+        FMAPDATA_OFFSET_TABLE_LENGTH_ADDRESS, TREASURE_INFO_OFFSET_TABLE_LENGTH_ADDRESS,
+    },
+    rom::{NdsHeader, NdsRom},
+};
+#[cfg(feature = "fs")]
+use crate::{misc::ProjectPaths, patch::crc32};
+#[cfg(feature = "fs")]
+use std::{collections::BTreeMap, fs, io};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GameTitle {
+    PartnersInTime,
+    BowsersInsideStory,
+}
+
+impl GameTitle {
+    /// The first three characters of the game code.
+    pub const fn code(self) -> [u8; 3] {
+        match self {
+            Self::PartnersInTime => *b"ARM",
+            Self::BowsersInsideStory => *b"CLJ",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Region {
+    Japan,
+    NorthAmerica,
+    Europe,
+    Korea,
+    /// Any other region character of the game code.
+    Other(u8),
+}
+
+impl Region {
+    pub const fn from_code(code: u8) -> Self {
+        match code {
+            b'J' => Self::Japan,
+            b'E' => Self::NorthAmerica,
+            b'P' => Self::Europe,
+            b'K' => Self::Korea,
+            _ => Self::Other(code),
+        }
+    }
+    /// The last character of the game code.
+    pub const fn code(self) -> u8 {
+        match self {
+            Self::Japan => b'J',
+            Self::NorthAmerica => b'E',
+            Self::Europe => b'P',
+            Self::Korea => b'K',
+            Self::Other(code) => code,
+        }
+    }
+}
+
+/// Identifies a release of a game, which determines
+/// where its tables are located.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GameProfile {
+    pub title: GameTitle,
+    pub region: Region,
+    /// The ROM version from the header, which is incremented by revisions.
+    pub version: u8,
+}
+
+impl Display for GameProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = self.game_code();
+        write!(
+            f,
+            "{} (rev {})",
+            String::from_utf8_lossy(&code),
+            self.version
+        )
+    }
+}
+
+/// Identifies a [`GameProfile`] by the CRC-32 of a decompressed overlay,
+/// for extracted dumps without a header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OverlaySignature {
+    pub profile: GameProfile,
+    pub overlay_number: u32,
+    pub crc32: u32,
+}
+
+/// The addresses of the tables in the overlays of a release, as in [`consts`](crate::consts).
+///
+/// They're informational only: the loaders, such as
+/// [`FieldMaps::from_files`](crate::map::FieldMaps::from_files), always use the
+/// addresses in [`consts`](crate::consts), so these only tell whether a release
+/// is supported and where its tables are, e.g. for [`symbols`](crate::symbols).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TableAddresses {
+    /// Overlay 3.
+    pub fmapdata_offset_table_length: u64,
+    /// Overlay 4.
+    pub treasure_info_offset_table_length: u64,
+    /// Overlay 3.
+    pub field_map_chunk_table: u64,
+    /// Overlay 3.
+    pub fevent_offset_table_length: u64,
+}
+
+/// The North American release of Bowser's Inside Story,
+/// which the addresses in [`consts`](crate::consts) are for.
+pub const BOWSERS_INSIDE_STORY_NORTH_AMERICA: GameProfile = GameProfile {
+    title: GameTitle::BowsersInsideStory,
+    region: Region::NorthAmerica,
+    version: 0,
+};
+
+/// The signatures of the releases which [`GameProfile::table_addresses`] knows.
+pub const BUILTIN_SIGNATURES: &[OverlaySignature] = &[
+    OverlaySignature {
+        profile: BOWSERS_INSIDE_STORY_NORTH_AMERICA,
+        overlay_number: 3,
+        crc32: 0x3B90586B,
+    },
+    OverlaySignature {
+        profile: BOWSERS_INSIDE_STORY_NORTH_AMERICA,
+        overlay_number: 4,
+        crc32: 0xDF7DBAC4,
+    },
+];
+
+impl GameProfile {
+    pub fn from_game_code(game_code: [u8; 4], version: u8) -> Option<Self> {
+        let title = [GameTitle::PartnersInTime, GameTitle::BowsersInsideStory]
+            .into_iter()
+            .find(|x| game_code[..3] == x.code())?;
+        Some(Self {
+            title,
+            region: Region::from_code(game_code[3]),
+            version,
+        })
+    }
+    pub fn from_header(header: &NdsHeader) -> Option<Self> {
+        Self::from_game_code(
+            header.game_code,
+            *header.raw.get(NdsHeader::ROM_VERSION_OFFSET)?,
+        )
+    }
+    #[inline]
+    pub fn detect_rom(rom: &NdsRom) -> Option<Self> {
+        Self::from_header(&rom.header)
+    }
+    /// Uses `header.bin` in the root directory if it exists,
+    /// and otherwise the first matching signature, e.g. of [`BUILTIN_SIGNATURES`].
+    #[cfg(feature = "fs")]
+    pub fn detect_dump(
+        paths: &ProjectPaths,
+        signatures: &[OverlaySignature],
+    ) -> io::Result<Option<Self>> {
+        match fs::read(paths.root.join("header.bin")) {
+            Ok(data) => return Ok(NdsHeader::from_bytes(&data).and_then(|x| Self::from_header(&x))),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(error),
+        }
+        let mut checksums = BTreeMap::new();
+        for signature in signatures {
+            let checksum = match checksums.get(&signature.overlay_number) {
+                Some(&checksum) => checksum,
+                None => {
+                    let checksum = match fs::read(paths.overlay_path(signature.overlay_number)) {
+                        Ok(data) => Some(crc32(&data)),
+                        Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+                        Err(error) => return Err(error),
+                    };
+                    checksums.insert(signature.overlay_number, checksum);
+                    checksum
+                }
+            };
+            if checksum == Some(signature.crc32) {
+                return Ok(Some(signature.profile));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn game_code(&self) -> [u8; 4] {
+        let [a, b, c] = self.title.code();
+        [a, b, c, self.region.code()]
+    }
+
+    /// `None` if the release isn't known, in which case the loaders,
+    /// which only support the addresses of [`BOWSERS_INSIDE_STORY_NORTH_AMERICA`],
+    /// can't be used with it. See [`TableAddresses`].
+    pub fn table_addresses(&self) -> Option<TableAddresses> {
+        (*self == BOWSERS_INSIDE_STORY_NORTH_AMERICA).then_some(TableAddresses {
+            fmapdata_offset_table_length: FMAPDATA_OFFSET_TABLE_LENGTH_ADDRESS,
+            treasure_info_offset_table_length: TREASURE_INFO_OFFSET_TABLE_LENGTH_ADDRESS,
+            field_map_chunk_table: FIELD_MAP_CHUNK_TABLE_ADDRESS,
+            fevent_offset_table_length: FEVENT_OFFSET_TABLE_LENGTH_ADDRESS,
+        })
+    }
+}
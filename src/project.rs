@@ -0,0 +1,951 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+#[cfg(feature = "zip")]
+use std::{collections::btree_map::Entry, fmt::Write as _, io::Cursor, mem, path::Component};
+
+use itertools::Itertools;
+use thiserror::Error;
+
+use crate::{
+    consts::STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT,
+    event::{FieldEvents, FieldEventsFromFilesError, FieldEventsToFilesError},
+    map::{
+        BattleMapFile, BattleMapFileFromTableError, BattleMapFileIntoTableError, FieldMaps,
+        FieldMapsFromFilesError, FieldMapsToFilesError,
+    },
+    misc::{
+        DataWithOffsetTable, DataWithOffsetTableDeserializationError,
+        DataWithOffsetTableSerializationError,
+    },
+    rom::{NdsRom, NdsRomDeserializationError, NdsRomSerializationError},
+    text::{
+        MessageArchive, MessageArchiveFromTableError, MessageArchiveIntoTableError, MessageListSet,
+        MessageListSetIntoTableError,
+    },
+    vfs::Vfs,
+};
+#[cfg(feature = "zip")]
+use crate::{
+    vfs::ZipVfs,
+    zip::{path_to_name, ZipArchive, ZipError, ZipWriter},
+};
+
+/// Describes where the files of an extracted ROM are located on the filesystem.
+///
+/// The [`Default`] matches [`filesystem_standard_data_path`](crate::misc::filesystem_standard_data_path) and
+/// [`filesystem_standard_overlay_path`](crate::misc::filesystem_standard_overlay_path), relative to the current directory.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ProjectPaths {
+    pub root: PathBuf,
+    /// Relative to `root`.
+    pub data_dir: PathBuf,
+    /// Relative to `root`.
+    pub overlay_dir: PathBuf,
+    /// `{}` is replaced by the overlay number,
+    /// zero-padded to `overlay_number_width` digits.
+    pub overlay_filename_pattern: String,
+    pub overlay_number_width: usize,
+}
+
+impl ProjectPaths {
+    /// Uses the standard layout inside `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn data_path(&self, filename: impl AsRef<Path>) -> PathBuf {
+        self.root.join(&self.data_dir).join(filename)
+    }
+    pub fn overlay_path(&self, overlay_number: u32) -> PathBuf {
+        self.root
+            .join(&self.overlay_dir)
+            .join(self.overlay_filename_pattern.replacen(
+                "{}",
+                &format!(
+                    "{:0width$}",
+                    overlay_number,
+                    width = self.overlay_number_width
+                ),
+                1,
+            ))
+    }
+}
+impl Default for ProjectPaths {
+    fn default() -> Self {
+        Self {
+            root: PathBuf::from("."),
+            data_dir: PathBuf::from("data/data"),
+            overlay_dir: PathBuf::from("data/overlay.dec"),
+            overlay_filename_pattern: "overlay_{}.dec.bin".to_owned(),
+            overlay_number_width: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct SaveOptions {
+    /// Write everything to temporary files first, and only move them into place
+    /// once all of them were written successfully.
+    pub atomic: bool,
+    /// Copy the files which are about to be overwritten into a backup first.
+    pub backup: Option<BackupOptions>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BackupOptions {
+    /// Each backup is stored in a new timestamped subdirectory of this directory.
+    pub directory: PathBuf,
+    /// The maximum number of backups to keep in `directory`, including the new one;
+    /// the oldest ones are removed first. `None` keeps all of them.
+    pub retention: Option<NonZeroUsize>,
+}
+
+impl BackupOptions {
+    const SUBDIRECTORY_PREFIX: &str = "backup-";
+
+    /// Copies `files` which exist into a new backup, and returns its path.
+    pub fn back_up<'a>(&self, files: impl IntoIterator<Item = &'a Path>) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.directory)?;
+        let mut timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        // Backups made in the same millisecond (or after the clock went back)
+        // are ordered by a counter, which continues from the latest backup's.
+        let mut counter = 0;
+        if let Some((latest_timestamp, latest_counter)) = self
+            .backups()?
+            .last()
+            .and_then(|(_, name)| Self::parse_subdirectory_name(name))
+        {
+            if latest_timestamp >= timestamp {
+                (timestamp, counter) = (latest_timestamp, latest_counter + 1);
+            }
+        }
+        let backup_directory = loop {
+            let path = self.directory.join(format!(
+                "{}{:020}-{:010}",
+                Self::SUBDIRECTORY_PREFIX,
+                timestamp,
+                counter
+            ));
+            match fs::create_dir(&path) {
+                Ok(()) => break path,
+                // Another backup was made concurrently.
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => counter += 1,
+                Err(err) => return Err(err),
+            }
+        };
+        for path in files {
+            if let (true, Some(filename)) = (path.try_exists()?, path.file_name()) {
+                fs::copy(path, backup_directory.join(filename))?;
+            }
+        }
+
+        if let Some(retention) = self.retention {
+            let backups = self.backups()?;
+            for (old_backup, _) in &backups[..backups.len().saturating_sub(retention.get())] {
+                fs::remove_dir_all(old_backup)?;
+            }
+        }
+
+        Ok(backup_directory)
+    }
+
+    /// The paths and names of the backups in `self.directory`, from oldest to newest.
+    fn backups(&self) -> io::Result<Vec<(PathBuf, String)>> {
+        let mut backups = fs::read_dir(&self.directory)?
+            .map(|entry| {
+                let entry = entry?;
+                Ok((entry.path(), entry.file_name()))
+            })
+            .filter_map_ok(|(path, name)| {
+                let name = name.into_string().ok()?;
+                (path.is_dir() && Self::parse_subdirectory_name(&name).is_some())
+                    .then_some((path, name))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        // The zero-padded timestamps and counters sort chronologically.
+        backups.sort_by(|(_, a), (_, b)| a.cmp(b));
+        Ok(backups)
+    }
+    /// Returns the timestamp and counter.
+    fn parse_subdirectory_name(name: &str) -> Option<(u128, u64)> {
+        let (timestamp, counter) = name
+            .strip_prefix(Self::SUBDIRECTORY_PREFIX)?
+            .split_once('-')?;
+        Some((timestamp.parse().ok()?, counter.parse().ok()?))
+    }
+}
+
+impl SaveOptions {
+    /// Opens the files which are about to be written to, following `self`.
+    ///
+    /// `created` are truncated, while `patched` keep their original contents.
+    /// The returned [`PendingFiles`] must be [committed](PendingFiles::commit)
+    /// after writing.
+    pub fn open_files<const C: usize, const P: usize>(
+        &self,
+        created: [&Path; C],
+        patched: [&Path; P],
+    ) -> io::Result<(PendingFiles, [File; C], [File; P])> {
+        if let Some(backup) = &self.backup {
+            backup.back_up(created.into_iter().chain(patched))?;
+        }
+        let mut pending = PendingFiles {
+            atomic: self.atomic,
+            files: Vec::new(),
+        };
+        // UNSTABLE: Use `array::try_map`.
+        let created = created
+            .into_iter()
+            .map(|path| pending.create(path))
+            .collect::<Result<Vec<_>, _>>()?
+            .try_into()
+            .unwrap();
+        let patched = patched
+            .into_iter()
+            .map(|path| pending.patch(path))
+            .collect::<Result<Vec<_>, _>>()?
+            .try_into()
+            .unwrap();
+        Ok((pending, created, patched))
+    }
+}
+
+/// Files opened by [`SaveOptions::open_files`].
+///
+/// In atomic mode, any temporary files which weren't committed
+/// are removed when this is dropped.
+#[derive(Debug)]
+pub struct PendingFiles {
+    atomic: bool,
+    /// Target and temporary paths.
+    files: Vec<(PathBuf, PathBuf)>,
+}
+
+impl PendingFiles {
+    fn temporary_path_for(path: &Path) -> PathBuf {
+        Self::with_suffix(path, ".mnllib-tmp")
+    }
+    fn original_path_for(path: &Path) -> PathBuf {
+        Self::with_suffix(path, ".mnllib-old")
+    }
+    fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+        let mut filename = path.file_name().unwrap_or_default().to_owned();
+        filename.push(suffix);
+        path.with_file_name(filename)
+    }
+
+    fn create(&mut self, path: &Path) -> io::Result<File> {
+        if !self.atomic {
+            return File::create(path);
+        }
+        let temporary_path = Self::temporary_path_for(path);
+        let file = File::create(&temporary_path)?;
+        self.files.push((path.to_owned(), temporary_path));
+        Ok(file)
+    }
+    fn patch(&mut self, path: &Path) -> io::Result<File> {
+        if !self.atomic {
+            return File::options().write(true).open(path);
+        }
+        let temporary_path = Self::temporary_path_for(path);
+        fs::copy(path, &temporary_path)?;
+        self.files.push((path.to_owned(), temporary_path.clone()));
+        File::options().write(true).open(temporary_path)
+    }
+
+    /// Moves all temporary files into place.
+    /// All files returned alongside `self` must have been flushed and dropped by now.
+    ///
+    /// If moving any of them fails, the files which were already moved are rolled back,
+    /// so that either all or none of the files are replaced. Should rolling back
+    /// a file fail too, its original is left next to it, with `.mnllib-old`
+    /// appended to its name.
+    pub fn commit(mut self) -> io::Result<()> {
+        for (_, temporary_path) in &self.files {
+            File::open(temporary_path)?.sync_all()?;
+        }
+        // Target paths and the paths their originals were kept at.
+        let mut committed: Vec<(&Path, Option<PathBuf>)> = Vec::new();
+        for (path, temporary_path) in &self.files {
+            match Self::replace(path, temporary_path) {
+                Ok(original_path) => committed.push((path, original_path)),
+                Err(err) => {
+                    for (path, original_path) in committed.into_iter().rev() {
+                        let _ = match original_path {
+                            Some(original_path) => fs::rename(original_path, path),
+                            None => fs::remove_file(path),
+                        };
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        for original_path in committed.into_iter().filter_map(|(_, x)| x) {
+            let _ = fs::remove_file(original_path);
+        }
+        self.files.clear();
+        Ok(())
+    }
+    /// Atomically replaces `path` with `temporary_path`,
+    /// returning where the original of `path` was kept, if it existed.
+    fn replace(path: &Path, temporary_path: &Path) -> io::Result<Option<PathBuf>> {
+        let original_path = if path.try_exists()? {
+            let original_path = Self::original_path_for(path);
+            let _ = fs::remove_file(&original_path);
+            // Copying is only needed on file systems without hard links.
+            if fs::hard_link(path, &original_path).is_err() {
+                fs::copy(path, &original_path)?;
+            }
+            Some(original_path)
+        } else {
+            None
+        };
+        if let Err(err) = fs::rename(temporary_path, path) {
+            if let Some(original_path) = original_path {
+                let _ = fs::remove_file(original_path);
+            }
+            return Err(err);
+        }
+        Ok(original_path)
+    }
+}
+impl Drop for PendingFiles {
+    fn drop(&mut self) {
+        for (_, temporary_path) in &self.files {
+            let _ = fs::remove_file(temporary_path);
+        }
+    }
+}
+
+/// Where a [`Project`] is read from and saved to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ProjectSource {
+    /// An extracted dump of a ROM.
+    Filesystem(ProjectPaths),
+    /// A ROM, which is written to `path` by [`Project::save`].
+    Rom { rom: NdsRom, path: PathBuf },
+}
+
+impl ProjectSource {
+    /// `filename` is relative to the data directory.
+    fn write_data(
+        &mut self,
+        filename: &str,
+        data: Vec<u8>,
+        options: &SaveOptions,
+    ) -> io::Result<()> {
+        match self {
+            Self::Filesystem(paths) => {
+                let (pending, [mut file], []) =
+                    options.open_files([&paths.data_path(filename)], [])?;
+                file.write_all(&data)?;
+                drop(file);
+                pending.commit()
+            }
+            Self::Rom { rom, .. } => rom.replace_file(filename, data),
+        }
+    }
+
+    #[cfg(feature = "zip")]
+    fn write_overlay(
+        &mut self,
+        overlay_number: u32,
+        data: Vec<u8>,
+        options: &SaveOptions,
+    ) -> io::Result<()> {
+        match self {
+            Self::Filesystem(paths) => {
+                let (pending, [mut file], []) =
+                    options.open_files([&paths.overlay_path(overlay_number)], [])?;
+                file.write_all(&data)?;
+                drop(file);
+                pending.commit()
+            }
+            Self::Rom { rom, .. } => rom.replace_overlay(overlay_number, data),
+        }
+    }
+}
+
+impl Vfs for ProjectSource {
+    fn read_file(&self, path: &str) -> io::Result<Cow<'_, [u8]>> {
+        match self {
+            Self::Filesystem(paths) => paths.read_file(path),
+            Self::Rom { rom, .. } => rom.read_file(path),
+        }
+    }
+    fn read_overlay(&self, overlay_number: u32) -> io::Result<Cow<'_, [u8]>> {
+        match self {
+            Self::Filesystem(paths) => paths.read_overlay(overlay_number),
+            Self::Rom { rom, .. } => rom.read_overlay(overlay_number),
+        }
+    }
+}
+
+/// The overlay `overlay_number` in `overlays`, which is read from `source` if it isn't there yet.
+#[cfg(feature = "zip")]
+fn load_overlay<'a>(
+    overlays: &'a mut BTreeMap<u32, Vec<u8>>,
+    source: &ProjectSource,
+    overlay_number: u32,
+) -> io::Result<&'a mut Vec<u8>> {
+    Ok(match overlays.entry(overlay_number) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => entry.insert(source.read_overlay(overlay_number)?.into_owned()),
+    })
+}
+
+/// The contents of data files by filename and of overlays by number.
+#[cfg(feature = "zip")]
+type ModifiedFiles = (BTreeMap<String, Vec<u8>>, BTreeMap<u32, Vec<u8>>);
+
+#[derive(Error, Debug)]
+pub enum ProjectError {
+    #[error("failed to load {filename}")]
+    DataFileDeserialization {
+        filename: String,
+        #[source]
+        source: DataWithOffsetTableDeserializationError,
+    },
+    #[error("failed to save {filename}")]
+    DataFileSerialization {
+        filename: String,
+        #[source]
+        source: DataWithOffsetTableSerializationError,
+    },
+    #[error(transparent)]
+    FieldMapsFromFiles(#[from] FieldMapsFromFilesError),
+    #[error(transparent)]
+    FieldMapsToFiles(#[from] FieldMapsToFilesError),
+    #[error(transparent)]
+    FieldEventsFromFiles(#[from] FieldEventsFromFilesError),
+    #[error(transparent)]
+    FieldEventsToFiles(#[from] FieldEventsToFilesError),
+    #[error(transparent)]
+    BattleMapFileFromTable(#[from] BattleMapFileFromTableError),
+    #[error(transparent)]
+    BattleMapFileIntoTable(#[from] BattleMapFileIntoTableError),
+    #[error(transparent)]
+    MessageArchiveFromTable(#[from] MessageArchiveFromTableError),
+    #[error(transparent)]
+    MessageArchiveIntoTable(#[from] MessageArchiveIntoTableError),
+    #[error(transparent)]
+    MessageListSetIntoTable(#[from] MessageListSetIntoTableError),
+    #[error(transparent)]
+    NdsRomDeserialization(#[from] NdsRomDeserializationError),
+    #[error(transparent)]
+    NdsRomSerialization(#[from] NdsRomSerializationError),
+    #[cfg(feature = "zip")]
+    #[error("the project has unsaved modifications")]
+    UnsavedModifications,
+    #[cfg(feature = "zip")]
+    #[error("the manifest of the archive is invalid: {0}")]
+    InvalidManifest(String),
+    #[cfg(feature = "zip")]
+    #[error(transparent)]
+    Zip(#[from] ZipError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// A subsystem of a [`Project`], which is only loaded when it's first accessed.
+#[derive(Debug, Clone)]
+struct Lazy<T> {
+    value: Option<T>,
+    modified: bool,
+}
+
+impl<T> Default for Lazy<T> {
+    fn default() -> Self {
+        Self {
+            value: None,
+            modified: false,
+        }
+    }
+}
+
+impl<T> Lazy<T> {
+    fn get_or_try_load<E>(&mut self, load: impl FnOnce() -> Result<T, E>) -> Result<&mut T, E> {
+        if self.value.is_none() {
+            self.value = Some(load()?);
+        }
+        Ok(self.value.as_mut().unwrap())
+    }
+    fn get_mut_or_try_load<E>(&mut self, load: impl FnOnce() -> Result<T, E>) -> Result<&mut T, E> {
+        self.get_or_try_load(load)?;
+        self.modified = true;
+        Ok(self.value.as_mut().unwrap())
+    }
+
+    /// The value, if it was accessed mutably since it was last saved.
+    fn modified(&self) -> Option<&T> {
+        self.value.as_ref().filter(|_| self.modified)
+    }
+}
+
+/// A whole game, whose subsystems are loaded when they're first accessed.
+///
+/// The `*_mut` accessors mark their subsystem as modified,
+/// and [`Self::save`] writes only the modified subsystems back.
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub source: ProjectSource,
+    pub options: SaveOptions,
+    /// Passed to [`FieldMaps::save_to`] and [`FieldEvents::save_to`].
+    pub align_files: bool,
+    field_maps: Lazy<FieldMaps>,
+    field_events: Lazy<FieldEvents>,
+    battle_maps: Lazy<BattleMapFile>,
+    /// By filename, relative to the data directory.
+    data_files: BTreeMap<String, Lazy<DataWithOffsetTable>>,
+    /// By filename, relative to the data directory.
+    messages: BTreeMap<String, Lazy<MessageArchive>>,
+    /// By filename, relative to the data directory.
+    message_list_sets: BTreeMap<String, Lazy<MessageListSet>>,
+    /// Whether subsystems were replaced in the ROM, but it wasn't written yet.
+    rom_pending: bool,
+}
+
+impl Project {
+    pub fn new(source: ProjectSource) -> Self {
+        Self {
+            source,
+            options: SaveOptions::default(),
+            align_files: false,
+            field_maps: Lazy::default(),
+            field_events: Lazy::default(),
+            battle_maps: Lazy::default(),
+            data_files: BTreeMap::new(),
+            messages: BTreeMap::new(),
+            message_list_sets: BTreeMap::new(),
+            rom_pending: false,
+        }
+    }
+    /// Opens an extracted dump of a ROM.
+    #[inline]
+    pub fn open(paths: ProjectPaths) -> Self {
+        Self::new(ProjectSource::Filesystem(paths))
+    }
+    /// Opens a ROM, which is also where [`Self::save`] writes to.
+    pub fn open_rom(path: impl Into<PathBuf>) -> Result<Self, ProjectError> {
+        let path = path.into();
+        Ok(Self::new(ProjectSource::Rom {
+            rom: NdsRom::load(&path)?,
+            path,
+        }))
+    }
+
+    fn load_field_maps(source: &ProjectSource) -> Result<FieldMaps, FieldMapsFromFilesError> {
+        FieldMaps::load_from_vfs(source)
+    }
+    pub fn field_maps(&mut self) -> Result<&FieldMaps, ProjectError> {
+        let source = &self.source;
+        Ok(self
+            .field_maps
+            .get_or_try_load(|| Self::load_field_maps(source))?)
+    }
+    pub fn field_maps_mut(&mut self) -> Result<&mut FieldMaps, ProjectError> {
+        let source = &self.source;
+        Ok(self
+            .field_maps
+            .get_mut_or_try_load(|| Self::load_field_maps(source))?)
+    }
+
+    fn load_field_events(source: &ProjectSource) -> Result<FieldEvents, FieldEventsFromFilesError> {
+        FieldEvents::load_from_vfs(source)
+    }
+    pub fn field_events(&mut self) -> Result<&FieldEvents, ProjectError> {
+        let source = &self.source;
+        Ok(self
+            .field_events
+            .get_or_try_load(|| Self::load_field_events(source))?)
+    }
+    pub fn field_events_mut(&mut self) -> Result<&mut FieldEvents, ProjectError> {
+        let source = &self.source;
+        Ok(self
+            .field_events
+            .get_mut_or_try_load(|| Self::load_field_events(source))?)
+    }
+
+    fn load_data_file(
+        source: &ProjectSource,
+        filename: &str,
+    ) -> Result<DataWithOffsetTable, ProjectError> {
+        DataWithOffsetTable::from_reader(&source.read_file(filename)?[..]).map_err(|source| {
+            ProjectError::DataFileDeserialization {
+                filename: filename.to_owned(),
+                source,
+            }
+        })
+    }
+    fn data_file_to_bytes(
+        filename: &str,
+        table: DataWithOffsetTable,
+    ) -> Result<Vec<u8>, ProjectError> {
+        let mut data = Vec::new();
+        table
+            .to_writer(
+                &mut data,
+                Some(STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT),
+                true,
+            )
+            .map_err(|source| ProjectError::DataFileSerialization {
+                filename: filename.to_owned(),
+                source,
+            })?;
+        Ok(data)
+    }
+    fn save_data_file(
+        source: &mut ProjectSource,
+        filename: &str,
+        table: DataWithOffsetTable,
+        options: &SaveOptions,
+    ) -> Result<(), ProjectError> {
+        let data = Self::data_file_to_bytes(filename, table)?;
+        Ok(source.write_data(filename, data, options)?)
+    }
+
+    /// Any file with an offset table, relative to the data directory.
+    ///
+    /// Don't access the same file through this and a typed accessor,
+    /// as only one of them would be saved.
+    pub fn data_file(&mut self, filename: &str) -> Result<&DataWithOffsetTable, ProjectError> {
+        let source = &self.source;
+        Ok(self
+            .data_files
+            .entry(filename.to_owned())
+            .or_default()
+            .get_or_try_load(|| Self::load_data_file(source, filename))?)
+    }
+    pub fn data_file_mut(
+        &mut self,
+        filename: &str,
+    ) -> Result<&mut DataWithOffsetTable, ProjectError> {
+        let source = &self.source;
+        self.data_files
+            .entry(filename.to_owned())
+            .or_default()
+            .get_mut_or_try_load(|| Self::load_data_file(source, filename))
+    }
+
+    const BATTLE_MAPS_FILENAME: &str = "BMap/BMap.dat";
+
+    fn load_battle_maps(source: &ProjectSource) -> Result<BattleMapFile, ProjectError> {
+        Ok(Self::load_data_file(source, Self::BATTLE_MAPS_FILENAME)?.try_into()?)
+    }
+    pub fn battle_maps(&mut self) -> Result<&BattleMapFile, ProjectError> {
+        let source = &self.source;
+        Ok(self
+            .battle_maps
+            .get_or_try_load(|| Self::load_battle_maps(source))?)
+    }
+    pub fn battle_maps_mut(&mut self) -> Result<&mut BattleMapFile, ProjectError> {
+        let source = &self.source;
+        self.battle_maps
+            .get_mut_or_try_load(|| Self::load_battle_maps(source))
+    }
+
+    /// The message archive `filename`, relative to the data directory, e.g. `BAI/BMes_ji.dat`.
+    pub fn messages(&mut self, filename: &str) -> Result<&MessageArchive, ProjectError> {
+        let source = &self.source;
+        Ok(self
+            .messages
+            .entry(filename.to_owned())
+            .or_default()
+            .get_or_try_load(|| {
+                Ok::<_, ProjectError>(Self::load_data_file(source, filename)?.try_into()?)
+            })?)
+    }
+    pub fn messages_mut(&mut self, filename: &str) -> Result<&mut MessageArchive, ProjectError> {
+        let source = &self.source;
+        self.messages
+            .entry(filename.to_owned())
+            .or_default()
+            .get_mut_or_try_load(|| Ok(Self::load_data_file(source, filename)?.try_into()?))
+    }
+
+    /// The message list set `filename`, relative to the data directory,
+    /// e.g. `BData/mfset_UItmN.dat`.
+    pub fn message_list_set(&mut self, filename: &str) -> Result<&MessageListSet, ProjectError> {
+        let source = &self.source;
+        Ok(self
+            .message_list_sets
+            .entry(filename.to_owned())
+            .or_default()
+            .get_or_try_load(|| {
+                Ok::<_, ProjectError>(Self::load_data_file(source, filename)?.into())
+            })?)
+    }
+    pub fn message_list_set_mut(
+        &mut self,
+        filename: &str,
+    ) -> Result<&mut MessageListSet, ProjectError> {
+        let source = &self.source;
+        self.message_list_sets
+            .entry(filename.to_owned())
+            .or_default()
+            .get_mut_or_try_load(|| Ok(Self::load_data_file(source, filename)?.into()))
+    }
+
+    /// Drops all loaded subsystems, so that they're reloaded from [`Self::source`].
+    #[cfg(feature = "zip")]
+    fn unload(&mut self) {
+        self.field_maps = Lazy::default();
+        self.field_events = Lazy::default();
+        self.battle_maps = Lazy::default();
+        self.data_files.clear();
+        self.messages.clear();
+        self.message_list_sets.clear();
+    }
+
+    /// Whether any subsystem was accessed mutably since it was last saved.
+    pub fn is_modified(&self) -> bool {
+        self.field_maps.modified
+            || self.field_events.modified
+            || self.battle_maps.modified
+            || self.data_files.values().any(|x| x.modified)
+            || self.messages.values().any(|x| x.modified)
+            || self.message_list_sets.values().any(|x| x.modified)
+    }
+
+    /// Writes the modified subsystems back to [`Self::source`].
+    ///
+    /// A ROM is only written once all of them have been replaced in it.
+    pub fn save(&mut self) -> Result<(), ProjectError> {
+        self.rom_pending |= matches!(self.source, ProjectSource::Rom { .. }) && self.is_modified();
+        let Self {
+            source,
+            options,
+            align_files,
+            ..
+        } = self;
+
+        if let Some(field_maps) = self.field_maps.modified() {
+            match source {
+                ProjectSource::Filesystem(paths) => {
+                    field_maps.save_to(paths, *align_files, options)?
+                }
+                ProjectSource::Rom { rom, .. } => field_maps.save_to_rom(rom, *align_files)?,
+            }
+            self.field_maps.modified = false;
+        }
+        if let Some(field_events) = self.field_events.modified() {
+            match source {
+                ProjectSource::Filesystem(paths) => {
+                    field_events.save_to(paths, *align_files, options)?
+                }
+                ProjectSource::Rom { rom, .. } => field_events.save_to_rom(rom, *align_files)?,
+            }
+            self.field_events.modified = false;
+        }
+        if let Some(battle_maps) = self.battle_maps.modified() {
+            Self::save_data_file(
+                source,
+                Self::BATTLE_MAPS_FILENAME,
+                battle_maps.clone().try_into()?,
+                options,
+            )?;
+            self.battle_maps.modified = false;
+        }
+        for (filename, data_file) in &mut self.data_files {
+            if let Some(table) = data_file.modified() {
+                Self::save_data_file(source, filename, table.clone(), options)?;
+                data_file.modified = false;
+            }
+        }
+        for (filename, messages) in &mut self.messages {
+            if let Some(archive) = messages.modified() {
+                Self::save_data_file(source, filename, archive.clone().try_into()?, options)?;
+                messages.modified = false;
+            }
+        }
+        for (filename, sets) in &mut self.message_list_sets {
+            if let Some(set) = sets.modified() {
+                Self::save_data_file(source, filename, set.clone().try_into()?, options)?;
+                sets.modified = false;
+            }
+        }
+
+        if let (true, ProjectSource::Rom { rom, path }) = (self.rom_pending, source) {
+            rom.save(path, options)?;
+            self.rom_pending = false;
+        }
+        Ok(())
+    }
+
+    /// The first line of the manifest of an archive made by [`Self::export_archive`].
+    #[cfg(feature = "zip")]
+    const ARCHIVE_MANIFEST_HEADER: &str = "mnllib-project-archive 1";
+    #[cfg(feature = "zip")]
+    const ARCHIVE_MANIFEST_FILENAME: &str = "manifest.txt";
+
+    /// The files which [`Self::save`] would write, without writing them,
+    /// by filename relative to the data directory and by overlay number.
+    #[cfg(feature = "zip")]
+    fn modified_files(&self) -> Result<ModifiedFiles, ProjectError> {
+        let source = &self.source;
+        let mut data_files = BTreeMap::new();
+        let mut overlays = BTreeMap::new();
+        if let Some(field_maps) = self.field_maps.modified() {
+            let mut fmapdata = Vec::new();
+            let mut treasure_info = Vec::new();
+            // Both overlays are needed at once.
+            let mut overlay4 = mem::take(load_overlay(&mut overlays, source, 4)?);
+            field_maps.to_files(
+                &mut fmapdata,
+                &mut treasure_info,
+                Cursor::new(load_overlay(&mut overlays, source, 3)?),
+                Cursor::new(&mut overlay4),
+                self.align_files,
+            )?;
+            overlays.insert(4, overlay4);
+            data_files.insert("FMap/FMapData.dat".to_owned(), fmapdata);
+            data_files.insert("Treasure/TreasureInfo.dat".to_owned(), treasure_info);
+        }
+        if let Some(field_events) = self.field_events.modified() {
+            let mut fevent = Vec::new();
+            field_events.to_files(
+                &mut fevent,
+                Cursor::new(load_overlay(&mut overlays, source, 3)?),
+                self.align_files,
+            )?;
+            data_files.insert("FEvent/FEvent.dat".to_owned(), fevent);
+        }
+        if let Some(battle_maps) = self.battle_maps.modified() {
+            data_files.insert(
+                Self::BATTLE_MAPS_FILENAME.to_owned(),
+                Self::data_file_to_bytes(
+                    Self::BATTLE_MAPS_FILENAME,
+                    battle_maps.clone().try_into()?,
+                )?,
+            );
+        }
+        for (filename, data_file) in &self.data_files {
+            if let Some(table) = data_file.modified() {
+                data_files.insert(
+                    filename.clone(),
+                    Self::data_file_to_bytes(filename, table.clone())?,
+                );
+            }
+        }
+        for (filename, messages) in &self.messages {
+            if let Some(archive) = messages.modified() {
+                data_files.insert(
+                    filename.clone(),
+                    Self::data_file_to_bytes(filename, archive.clone().try_into()?)?,
+                );
+            }
+        }
+        for (filename, sets) in &self.message_list_sets {
+            if let Some(set) = sets.modified() {
+                data_files.insert(
+                    filename.clone(),
+                    Self::data_file_to_bytes(filename, set.clone().try_into()?)?,
+                );
+            }
+        }
+        Ok((data_files, overlays))
+    }
+
+    /// Writes the files of the modified subsystems to a ZIP archive at `path`,
+    /// which can be applied to another copy of the game with [`Self::import_archive`].
+    ///
+    /// The files are laid out like an extracted dump, so the archive can also be read
+    /// through a [`ZipVfs`]. Nothing is saved, and the subsystems stay modified.
+    #[cfg(feature = "zip")]
+    pub fn export_archive(&self, path: impl AsRef<Path>) -> Result<(), ProjectError> {
+        let (data_files, overlays) = self.modified_files()?;
+        let paths = ProjectPaths::new("");
+        let mut manifest = format!("{}\n", Self::ARCHIVE_MANIFEST_HEADER);
+        let mut zip = ZipWriter::new(Vec::new());
+        for (filename, data) in &data_files {
+            writeln!(manifest, "data {filename}").unwrap();
+            zip.add_file(&path_to_name(&paths.data_path(filename)), data)?;
+        }
+        for (overlay_number, data) in &overlays {
+            writeln!(manifest, "overlay {overlay_number}").unwrap();
+            zip.add_file(&path_to_name(&paths.overlay_path(*overlay_number)), data)?;
+        }
+        zip.add_file(Self::ARCHIVE_MANIFEST_FILENAME, manifest.as_bytes())?;
+
+        let (pending, [mut file], []) = self.options.open_files([path.as_ref()], [])?;
+        file.write_all(&zip.finish()?)?;
+        drop(file);
+        Ok(pending.commit()?)
+    }
+
+    /// Writes the files of an archive made by [`Self::export_archive`]
+    /// to [`Self::source`], replacing them.
+    ///
+    /// This fails if the project has unsaved modifications,
+    /// since the subsystems are reloaded from the imported files.
+    #[cfg(feature = "zip")]
+    pub fn import_archive(&mut self, path: impl AsRef<Path>) -> Result<(), ProjectError> {
+        if self.is_modified() {
+            return Err(ProjectError::UnsavedModifications);
+        }
+        let archive = ZipVfs::new(ZipArchive::from_bytes(fs::read(path)?)?);
+        let invalid_manifest = |x: &str| ProjectError::InvalidManifest(x.to_owned());
+        let manifest = archive
+            .archive
+            .file(Self::ARCHIVE_MANIFEST_FILENAME)?
+            .ok_or_else(|| invalid_manifest("it's missing"))?;
+        let manifest = String::from_utf8(manifest).map_err(|_| invalid_manifest("not UTF-8"))?;
+        let mut lines = manifest.lines();
+        if lines.next() != Some(Self::ARCHIVE_MANIFEST_HEADER) {
+            return Err(invalid_manifest("unknown format"));
+        }
+
+        // Everything is read before anything is written.
+        let mut data_files = Vec::new();
+        let mut overlays = Vec::new();
+        for line in lines.filter(|x| !x.is_empty()) {
+            match line.split_once(' ') {
+                Some(("data", filename))
+                    if Path::new(filename)
+                        .components()
+                        .all(|x| matches!(x, Component::Normal(_))) =>
+                {
+                    data_files.push((filename, archive.read_file(filename)?.into_owned()));
+                }
+                Some(("overlay", overlay_number)) => {
+                    let overlay_number =
+                        overlay_number.parse().map_err(|_| invalid_manifest(line))?;
+                    overlays.push((
+                        overlay_number,
+                        archive.read_overlay(overlay_number)?.into_owned(),
+                    ));
+                }
+                _ => return Err(invalid_manifest(line)),
+            }
+        }
+
+        let Self {
+            source, options, ..
+        } = self;
+        for (filename, data) in data_files {
+            source.write_data(filename, data, options)?;
+        }
+        for (overlay_number, data) in overlays {
+            source.write_overlay(overlay_number, data, options)?;
+        }
+        if let ProjectSource::Rom { rom, path } = source {
+            rom.save(path, options)?;
+            self.rom_pending = false;
+        }
+        self.unload();
+        Ok(())
+    }
+}
@@ -0,0 +1,673 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    io::{self, Cursor, Read, Write},
+    num::TryFromIntError,
+    ops::Range,
+};
+
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use thiserror::Error;
+
+#[cfg(feature = "fs")]
+use crate::misc::{ProjectPaths, SaveOptions};
+use crate::{
+    blz_compress, blz_decompress,
+    layout::{Endianness, FieldLayout, FieldType, HasLayout, StructLayout, Trailing},
+    BlzDecompressionError,
+};
+#[cfg(feature = "fs")]
+use std::{fs, path::Path};
+
+/// The location of a region of the ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RomRegion {
+    pub offset: u32,
+    pub size: u32,
+}
+
+impl RomRegion {
+    fn range(&self) -> Range<usize> {
+        self.offset as usize..self.offset as usize + self.size as usize
+    }
+}
+
+/// The ARM9 or ARM7 binary, as described by the header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RomBinary {
+    pub region: RomRegion,
+    pub entry_address: u32,
+    pub ram_address: u32,
+}
+
+/// The parts of the ROM header which describe the layout of the ROM.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NdsHeader {
+    pub game_title: [u8; 12],
+    pub game_code: [u8; 4],
+    pub arm9: RomBinary,
+    pub arm7: RomBinary,
+    pub fnt: RomRegion,
+    pub fat: RomRegion,
+    pub arm9_overlays: RomRegion,
+    pub arm7_overlays: RomRegion,
+    pub banner_offset: u32,
+    /// The size of the used part of the ROM.
+    pub rom_size: u32,
+    /// The whole header, including the fields above.
+    pub raw: Vec<u8>,
+}
+
+impl NdsHeader {
+    pub const SIZE: usize = 0x200;
+
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let raw = data.get(..Self::SIZE)?;
+        let field = |offset: usize| LittleEndian::read_u32(&raw[offset..]);
+        let region = |offset: usize| RomRegion {
+            offset: field(offset),
+            size: field(offset + 4),
+        };
+        let binary = |offset: usize| RomBinary {
+            region: RomRegion {
+                offset: field(offset),
+                size: field(offset + 0x0C),
+            },
+            entry_address: field(offset + 4),
+            ram_address: field(offset + 8),
+        };
+        Some(Self {
+            game_title: raw[0x00..0x0C].try_into().unwrap(),
+            game_code: raw[0x0C..0x10].try_into().unwrap(),
+            arm9: binary(0x20),
+            arm7: binary(0x30),
+            fnt: region(0x40),
+            fat: region(0x48),
+            arm9_overlays: region(0x50),
+            arm7_overlays: region(0x58),
+            banner_offset: field(0x68),
+            rom_size: field(0x80),
+            raw: raw.to_vec(),
+        })
+    }
+
+    /// Writes the fields into `raw` and updates the header checksum.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut raw = self.raw.clone();
+        raw.resize(raw.len().max(Self::SIZE), 0);
+        let mut field =
+            |offset: usize, value: u32| LittleEndian::write_u32(&mut raw[offset..], value);
+        for (offset, binary) in [(0x20, self.arm9), (0x30, self.arm7)] {
+            field(offset, binary.region.offset);
+            field(offset + 4, binary.entry_address);
+            field(offset + 8, binary.ram_address);
+            field(offset + 0x0C, binary.region.size);
+        }
+        for (offset, region) in [
+            (0x40, self.fnt),
+            (0x48, self.fat),
+            (0x50, self.arm9_overlays),
+            (0x58, self.arm7_overlays),
+        ] {
+            field(offset, region.offset);
+            field(offset + 4, region.size);
+        }
+        field(0x68, self.banner_offset);
+        field(0x80, self.rom_size);
+        raw[0x00..0x0C].copy_from_slice(&self.game_title);
+        raw[0x0C..0x10].copy_from_slice(&self.game_code);
+        let checksum = crc16(&raw[..Self::CHECKSUM_OFFSET]);
+        LittleEndian::write_u16(&mut raw[Self::CHECKSUM_OFFSET..], checksum);
+        raw
+    }
+
+    const DEVICE_CAPACITY_OFFSET: usize = 0x14;
+    pub const ROM_VERSION_OFFSET: usize = 0x1E;
+    const CHECKSUM_OFFSET: usize = 0x15E;
+}
+
+/// The CRC-16 used by the NDS, e.g. for the header checksum.
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for &byte in data {
+        crc ^= u16::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// An entry of an overlay table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct OverlayEntry {
+    pub overlay_number: u32,
+    pub ram_address: u32,
+    pub ram_size: u32,
+    pub bss_size: u32,
+    pub static_initializers_start: u32,
+    pub static_initializers_end: u32,
+    pub file_id: u32,
+    /// The compressed size in the lower 24 bits,
+    /// followed by [`Self::FLAG_COMPRESSED`] and [`Self::FLAG_AUTHENTICATED`].
+    pub flags: u32,
+}
+
+impl HasLayout for OverlayEntry {
+    const LAYOUT: StructLayout = StructLayout {
+        name: "OverlayEntry",
+        size: 32,
+        endianness: Endianness::Little,
+        fields: &[
+            FieldLayout::new("overlay_number", 0, FieldType::U32),
+            FieldLayout::new("ram_address", 4, FieldType::U32),
+            FieldLayout::new("ram_size", 8, FieldType::U32),
+            FieldLayout::new("bss_size", 12, FieldType::U32),
+            FieldLayout::new("static_initializers_start", 16, FieldType::U32),
+            FieldLayout::new("static_initializers_end", 20, FieldType::U32),
+            FieldLayout::new("file_id", 24, FieldType::U32),
+            FieldLayout::new("flags", 28, FieldType::U32),
+        ],
+        trailing: Trailing::Nothing,
+    };
+}
+
+impl OverlayEntry {
+    pub const SIZE: usize = Self::LAYOUT.size;
+    pub const FLAG_COMPRESSED: u32 = 1 << 24;
+    pub const FLAG_AUTHENTICATED: u32 = 1 << 25;
+    const COMPRESSED_SIZE_MASK: u32 = 0x00FF_FFFF;
+
+    pub fn from_reader(mut inp: impl Read) -> io::Result<Self> {
+        let mut fields = [0u32; 8];
+        inp.read_u32_into::<LittleEndian>(&mut fields)?;
+        let [overlay_number, ram_address, ram_size, bss_size, static_initializers_start, static_initializers_end, file_id, flags] =
+            fields;
+        Ok(Self {
+            overlay_number,
+            ram_address,
+            ram_size,
+            bss_size,
+            static_initializers_start,
+            static_initializers_end,
+            file_id,
+            flags,
+        })
+    }
+
+    pub fn to_writer(&self, mut out: impl Write) -> io::Result<()> {
+        for field in [
+            self.overlay_number,
+            self.ram_address,
+            self.ram_size,
+            self.bss_size,
+            self.static_initializers_start,
+            self.static_initializers_end,
+            self.file_id,
+            self.flags,
+        ] {
+            out.write_u32::<LittleEndian>(field)?;
+        }
+        Ok(())
+    }
+
+    /// Reads an overlay table, such as `y9.bin` of an extracted ROM.
+    pub fn table_from_bytes(data: &[u8]) -> io::Result<Vec<Self>> {
+        data.chunks_exact(Self::SIZE)
+            .map(Self::from_reader)
+            .collect()
+    }
+    pub fn table_to_bytes(entries: &[Self]) -> Vec<u8> {
+        let mut data = Vec::with_capacity(entries.len() * Self::SIZE);
+        for entry in entries {
+            // Writing to a `Vec` can't fail.
+            entry.to_writer(&mut data).unwrap();
+        }
+        data
+    }
+
+    #[inline]
+    pub fn is_compressed(&self) -> bool {
+        self.flags & Self::FLAG_COMPRESSED != 0
+    }
+    #[inline]
+    pub fn compressed_size(&self) -> u32 {
+        self.flags & Self::COMPRESSED_SIZE_MASK
+    }
+
+    /// Decompresses the overlay's file if it's compressed.
+    pub fn decompress<'a>(&self, data: &'a [u8]) -> Result<Cow<'a, [u8]>, BlzDecompressionError> {
+        if !self.is_compressed() {
+            return Ok(Cow::Borrowed(data));
+        }
+        let size = match self.compressed_size() as usize {
+            0 => data.len(),
+            size => size.min(data.len()),
+        };
+        blz_decompress(&data[..size]).map(Cow::Owned)
+    }
+    /// Updates the sizes for the uncompressed `data`, and compresses it
+    /// if `compress` is set and that makes it smaller.
+    pub fn compress(&mut self, data: Vec<u8>, compress: bool) -> io::Result<Vec<u8>> {
+        let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "overlay is too large");
+        self.ram_size = data.len().try_into().map_err(|_| too_large())?;
+        self.flags &= !(Self::FLAG_COMPRESSED | Self::COMPRESSED_SIZE_MASK);
+        match compress.then(|| blz_compress(&data)).flatten() {
+            Some(compressed) => {
+                self.flags |= Self::FLAG_COMPRESSED
+                    | u32::try_from(compressed.len())
+                        .ok()
+                        .filter(|&x| x <= Self::COMPRESSED_SIZE_MASK)
+                        .ok_or_else(too_large)?;
+                Ok(compressed)
+            }
+            None => Ok(data),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum NdsRomDeserializationError {
+    #[error("invalid ROM header")]
+    InvalidHeader,
+    #[error("the {0} is outside of the ROM")]
+    RegionOutOfBounds(&'static str),
+    #[error("invalid file name table")]
+    InvalidFileNameTable,
+    #[error("file {file_id} is outside of the ROM")]
+    FileOutOfBounds { file_id: u16 },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+#[derive(Error, Debug)]
+pub enum NdsRomSerializationError {
+    #[error("the device capacity {0} is too large to be represented")]
+    DeviceCapacityOverflow(u8),
+    #[error(transparent)]
+    TryFromInt(#[from] TryFromIntError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// A `.nds` ROM image, read through its Nitro filesystem.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NdsRom {
+    pub header: NdsHeader,
+    pub image: Vec<u8>,
+    /// Indexed by file ID.
+    pub fat: Vec<Range<u32>>,
+    /// The paths of all files, separated by `/`, to their file IDs.
+    pub file_names: BTreeMap<String, u16>,
+    pub arm9_overlays: Vec<OverlayEntry>,
+    pub arm7_overlays: Vec<OverlayEntry>,
+    /// Files which replace the ones in `image`, by file ID.
+    pub replaced_files: BTreeMap<u16, Vec<u8>>,
+}
+
+impl NdsRom {
+    const FNT_DIRECTORY_ID_BASE: u16 = 0xF000;
+    /// Files which grow past the next one move the following files
+    /// by a multiple of this.
+    pub const FILE_ALIGNMENT: usize = 0x200;
+    const PADDING_BYTE: u8 = 0xFF;
+    const MIN_DEVICE_CAPACITY: usize = 0x20000;
+
+    pub fn from_bytes(image: Vec<u8>) -> Result<Self, NdsRomDeserializationError> {
+        let header =
+            NdsHeader::from_bytes(&image).ok_or(NdsRomDeserializationError::InvalidHeader)?;
+        let region = |region: RomRegion, name| {
+            image
+                .get(region.range())
+                .ok_or(NdsRomDeserializationError::RegionOutOfBounds(name))
+        };
+
+        let fat = region(header.fat, "FAT")?
+            .chunks_exact(8)
+            .map(|x| LittleEndian::read_u32(&x[0..])..LittleEndian::read_u32(&x[4..]))
+            .collect::<Vec<_>>();
+        for (file_id, range) in fat.iter().enumerate() {
+            if range.start > range.end || range.end as usize > image.len() {
+                return Err(NdsRomDeserializationError::FileOutOfBounds {
+                    file_id: file_id as u16,
+                });
+            }
+        }
+        let file_names = Self::read_fnt(region(header.fnt, "FNT")?)
+            .ok_or(NdsRomDeserializationError::InvalidFileNameTable)?;
+        let arm9_overlays =
+            OverlayEntry::table_from_bytes(region(header.arm9_overlays, "ARM9 overlay table")?)?;
+        let arm7_overlays =
+            OverlayEntry::table_from_bytes(region(header.arm7_overlays, "ARM7 overlay table")?)?;
+
+        Ok(Self {
+            header,
+            image,
+            fat,
+            file_names,
+            arm9_overlays,
+            arm7_overlays,
+            replaced_files: BTreeMap::new(),
+        })
+    }
+    #[cfg(feature = "fs")]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, NdsRomDeserializationError> {
+        Self::from_bytes(fs::read(path)?)
+    }
+
+    fn read_fnt(fnt: &[u8]) -> Option<BTreeMap<String, u16>> {
+        let directory_entry = |directory_id: u16| {
+            let offset = usize::from(directory_id.checked_sub(Self::FNT_DIRECTORY_ID_BASE)?) * 8;
+            let entry = fnt.get(offset..offset + 8)?;
+            Some((
+                LittleEndian::read_u32(&entry[0..]) as usize,
+                LittleEndian::read_u16(&entry[4..]),
+            ))
+        };
+        let num_directories = LittleEndian::read_u16(fnt.get(6..8)?);
+
+        let mut file_names = BTreeMap::new();
+        let mut pending = vec![(Self::FNT_DIRECTORY_ID_BASE, String::new())];
+        let mut visited = 0;
+        while let Some((directory_id, prefix)) = pending.pop() {
+            visited += 1;
+            if visited > num_directories {
+                return None;
+            }
+            let (subtable_offset, mut file_id) = directory_entry(directory_id)?;
+            let mut inp = Cursor::new(fnt.get(subtable_offset..)?);
+            loop {
+                let kind = inp.read_u8().ok()?;
+                if kind == 0 {
+                    break;
+                }
+                let mut name = vec![0u8; usize::from(kind & 0x7F)];
+                inp.read_exact(&mut name).ok()?;
+                let path = prefix.clone() + &String::from_utf8_lossy(&name);
+                if kind & 0x80 != 0 {
+                    pending.push((inp.read_u16::<LittleEndian>().ok()?, path + "/"));
+                } else {
+                    file_names.insert(path, file_id);
+                    file_id += 1;
+                }
+            }
+        }
+        Some(file_names)
+    }
+
+    pub fn file_by_id(&self, file_id: u16) -> Option<&[u8]> {
+        if let Some(data) = self.replaced_files.get(&file_id) {
+            return Some(data);
+        }
+        let range = self.fat.get(usize::from(file_id))?;
+        Some(&self.image[range.start as usize..range.end as usize])
+    }
+    /// `path` is relative to the root of the filesystem, e.g. `FMap/FMapData.dat`.
+    pub fn file(&self, path: &str) -> io::Result<&[u8]> {
+        Ok(self.file_by_id(self.file_id(path)?).unwrap_or_default())
+    }
+
+    fn file_id(&self, path: &str) -> io::Result<u16> {
+        self.file_names
+            .get(path.trim_start_matches('/'))
+            .copied()
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, format!("no file {path:?} in ROM"))
+            })
+    }
+    pub fn replace_file(&mut self, path: &str, data: Vec<u8>) -> io::Result<()> {
+        let file_id = self.file_id(path)?;
+        self.replaced_files.insert(file_id, data);
+        Ok(())
+    }
+
+    pub fn arm9_overlay_entry(&self, overlay_number: u32) -> Option<&OverlayEntry> {
+        self.arm9_overlays
+            .iter()
+            .find(|x| x.overlay_number == overlay_number)
+    }
+    fn arm9_overlay_index(&self, overlay_number: u32) -> io::Result<usize> {
+        self.arm9_overlays
+            .iter()
+            .position(|x| x.overlay_number == overlay_number)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no overlay {overlay_number} in ROM"),
+                )
+            })
+    }
+    fn overlay_file_id(&self, index: usize) -> io::Result<u16> {
+        let entry = &self.arm9_overlays[index];
+        u16::try_from(entry.file_id)
+            .ok()
+            .filter(|&x| usize::from(x) < self.fat.len())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("overlay {} has an invalid file ID", entry.overlay_number),
+                )
+            })
+    }
+    /// Returns the data of an ARM9 overlay, decompressing it if needed.
+    pub fn overlay(&self, overlay_number: u32) -> io::Result<Cow<'_, [u8]>> {
+        let index = self.arm9_overlay_index(overlay_number)?;
+        let data = self
+            .file_by_id(self.overlay_file_id(index)?)
+            .unwrap_or_default();
+        self.arm9_overlays[index]
+            .decompress(data)
+            .map_err(|source| io::Error::new(io::ErrorKind::InvalidData, source))
+    }
+    /// Replaces an ARM9 overlay with uncompressed `data`,
+    /// which is compressed if the original overlay was.
+    pub fn replace_overlay(&mut self, overlay_number: u32, data: Vec<u8>) -> io::Result<()> {
+        let index = self.arm9_overlay_index(overlay_number)?;
+        let file_id = self.overlay_file_id(index)?;
+        let entry = &mut self.arm9_overlays[index];
+        let data = entry.compress(data, entry.is_compressed())?;
+        self.replaced_files.insert(file_id, data);
+        Ok(())
+    }
+
+    /// Rebuilds the ROM image with the replaced files.
+    ///
+    /// Files keep their positions as long as they fit; otherwise, all following files
+    /// are moved back. The FAT, the overlay tables and the header are updated to match.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, NdsRomSerializationError> {
+        let mut order = (0..self.fat.len())
+            .filter(|&i| !self.fat[i].is_empty() || self.replaced_files.contains_key(&(i as u16)))
+            .collect::<Vec<_>>();
+        // Files without a position are placed at the end.
+        let unplaced = |i: usize| self.fat[i] == (0..0);
+        order.sort_by_key(|&i| (unplaced(i), self.fat[i].start));
+        let files_start = order
+            .first()
+            .map(|&i| self.fat[i].start as usize)
+            .filter(|&x| x > 0)
+            .unwrap_or(self.image.len());
+        let mut out = self.image[..files_start].to_vec();
+
+        let mut fat = self.fat.clone();
+        let mut shift = 0;
+        let mut previous_end = files_start;
+        for file_id in order {
+            let original = &self.fat[file_id];
+            let data = self.file_by_id(file_id as u16).unwrap_or_default();
+            if unplaced(file_id) {
+                out.resize(
+                    out.len().next_multiple_of(Self::FILE_ALIGNMENT),
+                    Self::PADDING_BYTE,
+                );
+                let start = out.len();
+                out.extend_from_slice(data);
+                fat[file_id] = start.try_into()?..out.len().try_into()?;
+                continue;
+            }
+
+            let original_start = original.start as usize;
+            if original_start + shift < out.len() {
+                shift +=
+                    (out.len() - original_start - shift).next_multiple_of(Self::FILE_ALIGNMENT);
+            }
+            let start = original_start + shift;
+            let gap = self
+                .image
+                .get(previous_end..original_start)
+                .unwrap_or_default();
+            if start - out.len() == gap.len() {
+                out.extend_from_slice(gap);
+            } else {
+                out.resize(start, Self::PADDING_BYTE);
+            }
+            out.extend_from_slice(data);
+            fat[file_id] = start.try_into()?..out.len().try_into()?;
+            previous_end = previous_end.max(original.end as usize);
+        }
+
+        // Everything after the files, such as the padding, is kept.
+        out.resize(out.len().max(previous_end), Self::PADDING_BYTE);
+        let mut header = self.header.clone();
+        let growth = out.len() - previous_end;
+        header.rom_size = (header.rom_size as usize + growth).try_into()?;
+        out.extend_from_slice(self.image.get(previous_end..).unwrap_or_default());
+
+        let fat_range = header.fat.range();
+        let mut fat_writer = Cursor::new(&mut out[fat_range]);
+        for range in &fat {
+            fat_writer.write_u32::<LittleEndian>(range.start)?;
+            fat_writer.write_u32::<LittleEndian>(range.end)?;
+        }
+        for (region, overlays) in [
+            (header.arm9_overlays, &self.arm9_overlays),
+            (header.arm7_overlays, &self.arm7_overlays),
+        ] {
+            let table = OverlayEntry::table_to_bytes(overlays);
+            Cursor::new(&mut out[region.range()]).write_all(&table)?;
+        }
+
+        let mut capacity = header.raw[NdsHeader::DEVICE_CAPACITY_OFFSET];
+        loop {
+            let device_size = 1usize
+                .checked_shl(capacity.into())
+                .and_then(|x| x.checked_mul(Self::MIN_DEVICE_CAPACITY))
+                .ok_or(NdsRomSerializationError::DeviceCapacityOverflow(capacity))?;
+            if device_size >= out.len() {
+                break;
+            }
+            capacity += 1;
+        }
+        header.raw[NdsHeader::DEVICE_CAPACITY_OFFSET] = capacity;
+        let header = header.to_bytes();
+        out[..header.len()].copy_from_slice(&header);
+        Ok(out)
+    }
+    #[cfg(feature = "fs")]
+    pub fn save(
+        &self,
+        path: impl AsRef<Path>,
+        options: &SaveOptions,
+    ) -> Result<(), NdsRomSerializationError> {
+        let data = self.to_bytes()?;
+        let (pending, [mut file], []) = options.open_files([path.as_ref()], [])?;
+        file.write_all(&data)?;
+        drop(file);
+        pending.commit()?;
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum OverlayAddressError {
+    #[error(
+        "{len:#X} bytes at {address:#010X} are outside of overlay {overlay_number} \
+         ({start:#010X}..{end:#010X})"
+    )]
+    OutOfBounds {
+        overlay_number: u32,
+        address: u32,
+        len: usize,
+        start: u32,
+        end: u64,
+    },
+}
+
+/// An uncompressed ARM9 overlay, along with the RAM address it's loaded at.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Overlay {
+    pub overlay_number: u32,
+    pub ram_address: u32,
+    pub data: Vec<u8>,
+}
+
+impl Overlay {
+    pub fn from_rom(rom: &NdsRom, overlay_number: u32) -> io::Result<Self> {
+        let index = rom.arm9_overlay_index(overlay_number)?;
+        Ok(Self {
+            overlay_number,
+            ram_address: rom.arm9_overlays[index].ram_address,
+            data: rom.overlay(overlay_number)?.into_owned(),
+        })
+    }
+    pub fn to_rom(&self, rom: &mut NdsRom) -> io::Result<()> {
+        rom.replace_overlay(self.overlay_number, self.data.clone())
+    }
+
+    /// Extracted overlays don't record their RAM address, so it must be supplied.
+    #[cfg(feature = "fs")]
+    pub fn load(paths: &ProjectPaths, overlay_number: u32, ram_address: u32) -> io::Result<Self> {
+        Ok(Self {
+            overlay_number,
+            ram_address,
+            data: fs::read(paths.overlay_path(overlay_number))?,
+        })
+    }
+    #[cfg(feature = "fs")]
+    pub fn save(&self, paths: &ProjectPaths, options: &SaveOptions) -> io::Result<()> {
+        let (pending, [mut file], []) =
+            options.open_files([&paths.overlay_path(self.overlay_number)], [])?;
+        file.write_all(&self.data)?;
+        drop(file);
+        pending.commit()
+    }
+
+    #[inline]
+    pub fn end_address(&self) -> u64 {
+        u64::from(self.ram_address) + self.data.len() as u64
+    }
+    #[inline]
+    pub fn contains_address(&self, address: u32) -> bool {
+        (u64::from(self.ram_address)..self.end_address()).contains(&u64::from(address))
+    }
+
+    /// Returns the offset into `data` of `len` bytes at `address`.
+    pub fn offset_of(&self, address: u32, len: usize) -> Result<usize, OverlayAddressError> {
+        address
+            .checked_sub(self.ram_address)
+            .map(|x| x as usize)
+            .filter(|&offset| offset + len <= self.data.len())
+            .ok_or(OverlayAddressError::OutOfBounds {
+                overlay_number: self.overlay_number,
+                address,
+                len,
+                start: self.ram_address,
+                end: self.end_address(),
+            })
+    }
+    pub fn read_at_address(&self, address: u32, len: usize) -> Result<&[u8], OverlayAddressError> {
+        let offset = self.offset_of(address, len)?;
+        Ok(&self.data[offset..offset + len])
+    }
+    pub fn write_at_address(
+        &mut self,
+        address: u32,
+        bytes: &[u8],
+    ) -> Result<(), OverlayAddressError> {
+        let offset = self.offset_of(address, bytes.len())?;
+        self.data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+}
@@ -0,0 +1,800 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Display},
+    io::{self, Cursor},
+};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use thiserror::Error;
+
+use crate::flags::FlagId;
+
+/// How an operand of a command is encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum OperandType {
+    U8,
+    U16,
+    U32,
+    I8,
+    I16,
+    I32,
+    /// A jump target, as an `i16` relative to the start of the next command.
+    RelativeJump16,
+    /// A jump target, as a `u32` relative to the start of the script.
+    AbsoluteJump32,
+}
+
+impl OperandType {
+    #[inline]
+    pub const fn size(self) -> usize {
+        match self {
+            Self::U8 | Self::I8 => 1,
+            Self::U16 | Self::I16 | Self::RelativeJump16 => 2,
+            Self::U32 | Self::I32 | Self::AbsoluteJump32 => 4,
+        }
+    }
+    #[inline]
+    pub const fn is_jump(self) -> bool {
+        matches!(self, Self::RelativeJump16 | Self::AbsoluteJump32)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommandInfo {
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub name: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub operands: Vec<OperandType>,
+    /// The indexes of the operands which are [`FlagId`]s.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub flag_operands: Vec<usize>,
+    /// The indexes of the operands which are message IDs.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub message_operands: Vec<usize>,
+    /// The indexes of the operands which are field map indexes.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub map_operands: Vec<usize>,
+}
+
+/// Describes the operands of each command, by opcode.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CommandTable(pub HashMap<u16, CommandInfo>);
+
+#[cfg(feature = "toml")]
+#[derive(Error, Debug)]
+pub enum CommandTableFromTomlError {
+    #[error("invalid opcode `{0}`")]
+    InvalidOpcode(String),
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+}
+
+#[cfg(feature = "toml")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CommandTableToml {
+    commands: std::collections::BTreeMap<String, CommandInfo>,
+}
+
+impl CommandTable {
+    /// Parses command signatures from TOML like this,
+    /// where the opcodes are hexadecimal (with a `0x` prefix) or decimal:
+    ///
+    /// ```toml
+    /// [commands.0x0001]
+    /// name = "set_flag"
+    /// operands = ["u16", "i8"]
+    /// flag_operands = [0]
+    /// ```
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> Result<Self, CommandTableFromTomlError> {
+        let file: CommandTableToml = toml::from_str(toml)?;
+        Ok(Self(
+            file.commands
+                .into_iter()
+                .map(|(opcode, info)| {
+                    let parsed = match opcode.strip_prefix("0x") {
+                        Some(hex) => u16::from_str_radix(hex, 16),
+                        None => opcode.parse(),
+                    };
+                    Ok((
+                        parsed.map_err(|_| CommandTableFromTomlError::InvalidOpcode(opcode))?,
+                        info,
+                    ))
+                })
+                .collect::<Result<_, CommandTableFromTomlError>>()?,
+        ))
+    }
+    /// Serializes the table in the format accepted by [`Self::from_toml`].
+    #[cfg(feature = "toml")]
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(&CommandTableToml {
+            commands: self
+                .0
+                .iter()
+                .map(|(opcode, info)| (format!("0x{:04X}", opcode), info.clone()))
+                .collect(),
+        })
+    }
+
+    pub fn insert(
+        &mut self,
+        opcode: u16,
+        name: Option<impl Into<String>>,
+        operands: impl Into<Vec<OperandType>>,
+    ) {
+        self.0.insert(
+            opcode,
+            CommandInfo {
+                name: name.map(Into::into),
+                operands: operands.into(),
+                flag_operands: Vec::new(),
+                message_operands: Vec::new(),
+                map_operands: Vec::new(),
+            },
+        );
+    }
+
+    pub fn get(&self, opcode: u16) -> Option<&CommandInfo> {
+        self.0.get(&opcode)
+    }
+    pub fn get_mut(&mut self, opcode: u16) -> Option<&mut CommandInfo> {
+        self.0.get_mut(&opcode)
+    }
+}
+
+/// Identifies a position in a [`Script`] which commands can jump to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Label(pub usize);
+
+impl Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "label_{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operand {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    Label(Label),
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::U8(x) => write!(f, "{:#04X}", x),
+            Self::U16(x) => write!(f, "{:#06X}", x),
+            Self::U32(x) => write!(f, "{:#010X}", x),
+            Self::I8(x) => write!(f, "{}", x),
+            Self::I16(x) => write!(f, "{}", x),
+            Self::I32(x) => write!(f, "{}", x),
+            Self::Label(x) => write!(f, "@{}", x),
+        }
+    }
+}
+
+impl Operand {
+    /// The value of the operand if it's a non-negative integer.
+    pub fn as_unsigned(self) -> Option<u32> {
+        match self {
+            Self::U8(x) => Some(x.into()),
+            Self::U16(x) => Some(x.into()),
+            Self::U32(x) => Some(x),
+            Self::I8(x) => x.try_into().ok(),
+            Self::I16(x) => x.try_into().ok(),
+            Self::I32(x) => x.try_into().ok(),
+            Self::Label(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Command {
+    pub opcode: u16,
+    pub operands: Vec<Operand>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    Label(Label),
+    Command(Command),
+    /// Raw bytes which couldn't be disassembled.
+    Data(Vec<u8>),
+}
+
+/// A disassembled script, where jump targets are [`Label`]s.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Script {
+    pub instructions: Vec<Instruction>,
+}
+
+#[derive(Error, Debug)]
+pub enum DisassemblyError {
+    #[error("unknown opcode {opcode:#06X} at offset {offset:#X}")]
+    UnknownOpcode { offset: usize, opcode: u16 },
+    #[error("failed to read the command at offset {offset:#X}")]
+    Command {
+        offset: usize,
+        #[source]
+        source: io::Error,
+    },
+    #[error("the command at offset {offset:#X} jumps to {target:#X}, which isn't the start of a command")]
+    InvalidJumpTarget { offset: usize, target: i64 },
+}
+
+#[derive(Error, Debug)]
+pub enum AssemblyError {
+    #[error("unknown opcode {opcode:#06X} (instruction {index})")]
+    UnknownOpcode { index: usize, opcode: u16 },
+    #[error("instruction {index} has {actual} operands, but it should have {expected}")]
+    OperandCount {
+        index: usize,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("operand {operand_index} of instruction {index} should be {expected:?}")]
+    OperandType {
+        index: usize,
+        operand_index: usize,
+        expected: OperandType,
+    },
+    #[error("{label} is defined more than once")]
+    DuplicateLabel { label: Label },
+    #[error("{label} (used by instruction {index}) isn't defined")]
+    UndefinedLabel { index: usize, label: Label },
+    #[error("instruction {index} can't jump to {label}, since it's too far away")]
+    JumpOutOfRange { index: usize, label: Label },
+}
+
+#[derive(Error, Debug)]
+pub enum ScriptParseError {
+    #[error("unknown command `{name}` on line {line}")]
+    UnknownCommand { line: usize, name: String },
+    #[error("the command on line {line} has {actual} operands, but it should have {expected}")]
+    OperandCount {
+        line: usize,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("invalid operand `{operand}` on line {line}, expected {expected:?}")]
+    InvalidOperand {
+        line: usize,
+        operand: String,
+        expected: OperandType,
+    },
+    #[error("the label `{name}` on line {line} is defined more than once")]
+    DuplicateLabel { line: usize, name: String },
+    #[error("the label `{name}` on line {line} isn't defined")]
+    UndefinedLabel { line: usize, name: String },
+}
+
+impl Script {
+    /// Decodes `data`, which must consist of whole commands only:
+    /// a little-endian `u16` opcode followed by the operands listed in `table`.
+    #[inline]
+    pub fn disassemble(data: &[u8], table: &CommandTable) -> Result<Self, DisassemblyError> {
+        Self::disassemble_with(data, table, false)
+    }
+    /// Like [`Self::disassemble`], but a command with an unknown opcode
+    /// and everything after it become an [`Instruction::Data`],
+    /// since there's no way to tell where the command ends.
+    #[inline]
+    pub fn disassemble_lenient(
+        data: &[u8],
+        table: &CommandTable,
+    ) -> Result<Self, DisassemblyError> {
+        Self::disassemble_with(data, table, true)
+    }
+
+    fn disassemble_with(
+        data: &[u8],
+        table: &CommandTable,
+        lenient: bool,
+    ) -> Result<Self, DisassemblyError> {
+        // (offset, command with jump targets as `Label(offset)`)
+        let mut commands: Vec<(usize, Command)> = Vec::new();
+        let mut trailing_data: Option<Vec<u8>> = None;
+        let mut src = Cursor::new(data);
+        while (src.position() as usize) < data.len() {
+            let offset = src.position() as usize;
+            match Self::read_command(&mut src, offset, table) {
+                Ok(command) => commands.push((offset, command)),
+                Err(ReadCommandError::Disassembly(DisassemblyError::UnknownOpcode { .. }))
+                    if lenient =>
+                {
+                    trailing_data = Some(data[offset..].to_vec());
+                    break;
+                }
+                Err(ReadCommandError::Disassembly(err)) => return Err(err),
+                Err(ReadCommandError::Io(source)) => {
+                    return Err(DisassemblyError::Command { offset, source })
+                }
+            }
+        }
+        let code_len = data.len() - trailing_data.as_ref().map_or(0, Vec::len);
+
+        // Jumps may also target the end of the script.
+        let mut labels: BTreeMap<usize, Label> = BTreeMap::new();
+        for (offset, command) in &commands {
+            for operand in &command.operands {
+                if let Operand::Label(Label(target)) = *operand {
+                    if target != code_len
+                        && commands.binary_search_by_key(&target, |x| x.0).is_err()
+                    {
+                        return Err(DisassemblyError::InvalidJumpTarget {
+                            offset: *offset,
+                            target: target as i64,
+                        });
+                    }
+                    labels.insert(target, Label(0));
+                }
+            }
+        }
+        for (i, label) in labels.values_mut().enumerate() {
+            *label = Label(i);
+        }
+
+        let mut instructions = Vec::with_capacity(commands.len() + labels.len());
+        for (offset, mut command) in commands {
+            if let Some(&label) = labels.get(&offset) {
+                instructions.push(Instruction::Label(label));
+            }
+            for operand in &mut command.operands {
+                if let Operand::Label(Label(target)) = operand {
+                    *operand = Operand::Label(labels[target]);
+                }
+            }
+            instructions.push(Instruction::Command(command));
+        }
+        if let Some(&label) = labels.get(&code_len) {
+            instructions.push(Instruction::Label(label));
+        }
+        instructions.extend(trailing_data.map(Instruction::Data));
+        Ok(Self { instructions })
+    }
+
+    /// Jump targets in the returned command are [`Label`]s containing the target offset.
+    fn read_command(
+        src: &mut Cursor<&[u8]>,
+        offset: usize,
+        table: &CommandTable,
+    ) -> Result<Command, ReadCommandError> {
+        let opcode = src.read_u16::<LittleEndian>()?;
+        let info = table
+            .get(opcode)
+            .ok_or(DisassemblyError::UnknownOpcode { offset, opcode })?;
+        let end = offset + 2 + info.operands.iter().map(|x| x.size()).sum::<usize>();
+        let mut operands = Vec::with_capacity(info.operands.len());
+        for &operand_type in &info.operands {
+            operands.push(match operand_type {
+                OperandType::U8 => Operand::U8(src.read_u8()?),
+                OperandType::U16 => Operand::U16(src.read_u16::<LittleEndian>()?),
+                OperandType::U32 => Operand::U32(src.read_u32::<LittleEndian>()?),
+                OperandType::I8 => Operand::I8(src.read_i8()?),
+                OperandType::I16 => Operand::I16(src.read_i16::<LittleEndian>()?),
+                OperandType::I32 => Operand::I32(src.read_i32::<LittleEndian>()?),
+                OperandType::RelativeJump16 => {
+                    let target = end as i64 + i64::from(src.read_i16::<LittleEndian>()?);
+                    Operand::Label(Label(
+                        target
+                            .try_into()
+                            .map_err(|_| DisassemblyError::InvalidJumpTarget { offset, target })?,
+                    ))
+                }
+                OperandType::AbsoluteJump32 => {
+                    let target = src.read_u32::<LittleEndian>()?;
+                    Operand::Label(Label(target.try_into().map_err(|_| {
+                        DisassemblyError::InvalidJumpTarget {
+                            offset,
+                            target: target.into(),
+                        }
+                    })?))
+                }
+            });
+        }
+        Ok(Command { opcode, operands })
+    }
+
+    /// Formats the script as text, one instruction per line,
+    /// using the command names from `table` where available.
+    pub fn display<'a>(&'a self, table: &'a CommandTable) -> ScriptDisplay<'a> {
+        ScriptDisplay {
+            script: self,
+            table,
+        }
+    }
+}
+
+/// What [`Script::find`] looks for.
+///
+/// Flags, messages and maps are only found in the operands
+/// which the [`CommandTable`] marks as such.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScriptQuery {
+    Flag(FlagId),
+    Message(u32),
+    Map(u32),
+    Opcode(u16),
+}
+
+impl ScriptQuery {
+    pub fn matches(&self, command: &Command, table: &CommandTable) -> bool {
+        let (operand_indexes, value) = match *self {
+            Self::Opcode(opcode) => return command.opcode == opcode,
+            Self::Flag(flag) => (
+                table.get(command.opcode).map(|x| &x.flag_operands),
+                flag.0.into(),
+            ),
+            Self::Message(message) => (
+                table.get(command.opcode).map(|x| &x.message_operands),
+                message,
+            ),
+            Self::Map(map) => (table.get(command.opcode).map(|x| &x.map_operands), map),
+        };
+        operand_indexes.is_some_and(|x| {
+            x.iter().any(|&index| {
+                command.operands.get(index).and_then(|x| x.as_unsigned()) == Some(value)
+            })
+        })
+    }
+}
+
+/// A command found by [`search_scripts`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ScriptMatch {
+    pub script: usize,
+    /// An index into [`Script::instructions`].
+    pub instruction: usize,
+    pub opcode: u16,
+}
+
+impl Script {
+    /// The indexes of the instructions which are commands matching `query`.
+    pub fn find(&self, query: ScriptQuery, table: &CommandTable) -> Vec<usize> {
+        self.instructions
+            .iter()
+            .enumerate()
+            .filter(|(_, instruction)| {
+                matches!(instruction, Instruction::Command(command) if query.matches(command, table))
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+/// Finds the commands matching `query` in all of `scripts`,
+/// which are paired with their indexes.
+pub fn search_scripts<'a>(
+    scripts: impl IntoIterator<Item = (usize, &'a Script)>,
+    query: ScriptQuery,
+    table: &CommandTable,
+) -> Vec<ScriptMatch> {
+    scripts
+        .into_iter()
+        .flat_map(|(script_index, script)| {
+            script
+                .instructions
+                .iter()
+                .enumerate()
+                .filter_map(move |(index, instruction)| match instruction {
+                    Instruction::Command(command) if query.matches(command, table) => {
+                        Some(ScriptMatch {
+                            script: script_index,
+                            instruction: index,
+                            opcode: command.opcode,
+                        })
+                    }
+                    _ => None,
+                })
+        })
+        .collect()
+}
+
+impl Script {
+    /// The inverse of [`Self::disassemble`], which also validates
+    /// the operands against `table`.
+    pub fn assemble(&self, table: &CommandTable) -> Result<Vec<u8>, AssemblyError> {
+        let mut label_offsets: HashMap<Label, usize> = HashMap::new();
+        let mut offset = 0;
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            match instruction {
+                Instruction::Label(label) => {
+                    if label_offsets.insert(*label, offset).is_some() {
+                        return Err(AssemblyError::DuplicateLabel { label: *label });
+                    }
+                }
+                Instruction::Command(command) => {
+                    let info = table
+                        .get(command.opcode)
+                        .ok_or(AssemblyError::UnknownOpcode {
+                            index,
+                            opcode: command.opcode,
+                        })?;
+                    if command.operands.len() != info.operands.len() {
+                        return Err(AssemblyError::OperandCount {
+                            index,
+                            expected: info.operands.len(),
+                            actual: command.operands.len(),
+                        });
+                    }
+                    offset += 2 + info.operands.iter().map(|x| x.size()).sum::<usize>();
+                }
+                Instruction::Data(data) => offset += data.len(),
+            }
+        }
+
+        let mut result = Vec::with_capacity(offset);
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            let command = match instruction {
+                Instruction::Label(_) => continue,
+                Instruction::Command(command) => command,
+                Instruction::Data(data) => {
+                    result.extend_from_slice(data);
+                    continue;
+                }
+            };
+            let info = table.get(command.opcode).unwrap();
+            let end = result.len() + 2 + info.operands.iter().map(|x| x.size()).sum::<usize>();
+            result.write_u16::<LittleEndian>(command.opcode).unwrap();
+            for (operand_index, (&operand_type, &operand)) in
+                info.operands.iter().zip(&command.operands).enumerate()
+            {
+                let target = |label| {
+                    label_offsets
+                        .get(&label)
+                        .copied()
+                        .ok_or(AssemblyError::UndefinedLabel { index, label })
+                };
+                match (operand_type, operand) {
+                    (OperandType::U8, Operand::U8(x)) => result.push(x),
+                    (OperandType::U16, Operand::U16(x)) => {
+                        result.extend_from_slice(&x.to_le_bytes())
+                    }
+                    (OperandType::U32, Operand::U32(x)) => {
+                        result.extend_from_slice(&x.to_le_bytes())
+                    }
+                    (OperandType::I8, Operand::I8(x)) => result.extend_from_slice(&x.to_le_bytes()),
+                    (OperandType::I16, Operand::I16(x)) => {
+                        result.extend_from_slice(&x.to_le_bytes())
+                    }
+                    (OperandType::I32, Operand::I32(x)) => {
+                        result.extend_from_slice(&x.to_le_bytes())
+                    }
+                    (OperandType::RelativeJump16, Operand::Label(label)) => {
+                        let relative = i16::try_from(target(label)? as i64 - end as i64)
+                            .map_err(|_| AssemblyError::JumpOutOfRange { index, label })?;
+                        result.extend_from_slice(&relative.to_le_bytes());
+                    }
+                    (OperandType::AbsoluteJump32, Operand::Label(label)) => {
+                        let absolute = u32::try_from(target(label)?)
+                            .map_err(|_| AssemblyError::JumpOutOfRange { index, label })?;
+                        result.extend_from_slice(&absolute.to_le_bytes());
+                    }
+                    (expected, _) => {
+                        return Err(AssemblyError::OperandType {
+                            index,
+                            operand_index,
+                            expected,
+                        })
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Parses the format produced by [`Self::display`].
+    ///
+    /// Labels can have any name, and are numbered in the order they're defined.
+    /// Commands can be referred to by their name in `table` or as `cmd_XXXX`,
+    /// and `.data` followed by bytes is an [`Instruction::Data`].
+    /// Everything after a `;` on a line is a comment.
+    pub fn parse(text: &str, table: &CommandTable) -> Result<Self, ScriptParseError> {
+        let lines = text.lines().enumerate().filter_map(|(index, line)| {
+            let line = line.split(';').next().unwrap().trim();
+            (!line.is_empty()).then_some((index + 1, line))
+        });
+
+        let mut labels: HashMap<&str, Label> = HashMap::new();
+        for (line_number, line) in lines.clone() {
+            if let Some(name) = line.strip_suffix(':') {
+                let label = Label(labels.len());
+                if labels.insert(name.trim(), label).is_some() {
+                    return Err(ScriptParseError::DuplicateLabel {
+                        line: line_number,
+                        name: name.trim().to_owned(),
+                    });
+                }
+            }
+        }
+        let opcodes: HashMap<&str, u16> = table
+            .0
+            .iter()
+            .filter_map(|(&opcode, info)| Some((info.name.as_deref()?, opcode)))
+            .collect();
+
+        let mut instructions = Vec::new();
+        for (line_number, line) in lines {
+            if let Some(name) = line.strip_suffix(':') {
+                instructions.push(Instruction::Label(labels[name.trim()]));
+                continue;
+            }
+
+            let (name, operands) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let operands: Vec<&str> = if operands.trim().is_empty() {
+                Vec::new()
+            } else {
+                operands.split(',').map(str::trim).collect()
+            };
+            if name == ".data" {
+                instructions.push(Instruction::Data(
+                    operands
+                        .into_iter()
+                        .map(
+                            |operand| match parse_operand(operand, OperandType::U8, &labels) {
+                                Ok(Operand::U8(x)) => Ok(x),
+                                _ => Err(ScriptParseError::InvalidOperand {
+                                    line: line_number,
+                                    operand: operand.to_owned(),
+                                    expected: OperandType::U8,
+                                }),
+                            },
+                        )
+                        .collect::<Result<_, _>>()?,
+                ));
+                continue;
+            }
+            let unknown_command = || ScriptParseError::UnknownCommand {
+                line: line_number,
+                name: name.to_owned(),
+            };
+            let opcode = match opcodes.get(name) {
+                Some(&opcode) => opcode,
+                None => name
+                    .strip_prefix("cmd_")
+                    .and_then(|x| u16::from_str_radix(x, 16).ok())
+                    .ok_or_else(unknown_command)?,
+            };
+            let info = table.get(opcode).ok_or_else(unknown_command)?;
+            if operands.len() != info.operands.len() {
+                return Err(ScriptParseError::OperandCount {
+                    line: line_number,
+                    expected: info.operands.len(),
+                    actual: operands.len(),
+                });
+            }
+
+            instructions.push(Instruction::Command(Command {
+                opcode,
+                operands: info
+                    .operands
+                    .iter()
+                    .zip(operands)
+                    .map(|(&operand_type, operand)| {
+                        parse_operand(operand, operand_type, &labels).map_err(|undefined| {
+                            if undefined {
+                                ScriptParseError::UndefinedLabel {
+                                    line: line_number,
+                                    name: operand[1..].to_owned(),
+                                }
+                            } else {
+                                ScriptParseError::InvalidOperand {
+                                    line: line_number,
+                                    operand: operand.to_owned(),
+                                    expected: operand_type,
+                                }
+                            }
+                        })
+                    })
+                    .collect::<Result<_, _>>()?,
+            }));
+        }
+        Ok(Self { instructions })
+    }
+}
+
+/// On failure, returns whether the operand is an undefined label.
+fn parse_operand(
+    operand: &str,
+    operand_type: OperandType,
+    labels: &HashMap<&str, Label>,
+) -> Result<Operand, bool> {
+    if operand_type.is_jump() {
+        let name = operand.strip_prefix('@').ok_or(false)?;
+        return labels.get(name).copied().map(Operand::Label).ok_or(true);
+    }
+
+    let (negative, digits) = match operand.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, operand),
+    };
+    let magnitude = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => digits.parse(),
+    }
+    .map_err(|_| false)?;
+    let value = if negative { -magnitude } else { magnitude };
+    Ok(match operand_type {
+        OperandType::U8 => Operand::U8(value.try_into().map_err(|_| false)?),
+        OperandType::U16 => Operand::U16(value.try_into().map_err(|_| false)?),
+        OperandType::U32 => Operand::U32(value.try_into().map_err(|_| false)?),
+        OperandType::I8 => Operand::I8(value.try_into().map_err(|_| false)?),
+        OperandType::I16 => Operand::I16(value.try_into().map_err(|_| false)?),
+        OperandType::I32 => Operand::I32(value.try_into().map_err(|_| false)?),
+        OperandType::RelativeJump16 | OperandType::AbsoluteJump32 => unreachable!(),
+    })
+}
+
+enum ReadCommandError {
+    Disassembly(DisassemblyError),
+    Io(io::Error),
+}
+impl From<DisassemblyError> for ReadCommandError {
+    fn from(value: DisassemblyError) -> Self {
+        Self::Disassembly(value)
+    }
+}
+impl From<io::Error> for ReadCommandError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// Returned by [`Script::display`].
+pub struct ScriptDisplay<'a> {
+    script: &'a Script,
+    table: &'a CommandTable,
+}
+
+impl Display for ScriptDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for instruction in &self.script.instructions {
+            match instruction {
+                Instruction::Label(label) => writeln!(f, "{}:", label)?,
+                Instruction::Command(command) => {
+                    match self.table.get(command.opcode).and_then(|x| x.name.as_ref()) {
+                        Some(name) => write!(f, "    {}", name)?,
+                        None => write!(f, "    cmd_{:04X}", command.opcode)?,
+                    }
+                    for (i, operand) in command.operands.iter().enumerate() {
+                        write!(f, "{}{}", if i == 0 { " " } else { ", " }, operand)?;
+                    }
+                    writeln!(f)?;
+                }
+                Instruction::Data(data) => {
+                    write!(f, "    .data")?;
+                    for (i, byte) in data.iter().enumerate() {
+                        write!(f, "{}{:#04X}", if i == 0 { " " } else { ", " }, byte)?;
+                    }
+                    writeln!(f)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
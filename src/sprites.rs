@@ -0,0 +1,924 @@
+use std::{
+    io::{self, Cursor, Read, Write},
+    num::TryFromIntError,
+};
+
+use bitfield_struct::bitfield;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use grid::Grid;
+use itertools::Itertools;
+use rgb::Rgba;
+use thiserror::Error;
+
+use crate::{
+    consts::{STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT, TILE_AREA, TILE_HEIGHT, TILE_WIDTH},
+    map::{
+        PixelSize, Tileset, TilesetTile, TilesetTileDeserializationError,
+        TilesetTileSerializationError,
+    },
+    misc::{DataWithOffsetTable, Palette, PaletteDeserializationError, PaletteLut, Rgb555},
+};
+
+pub const SPRITE_TILESET_PIXEL_SIZE: PixelSize = PixelSize::Nibble;
+
+/// The attributes of a [`SpriteObject`], laid out like those of the DS's OAM.
+#[bitfield(u16)]
+#[derive(PartialEq, Eq, Hash)]
+pub struct SpriteObjectAttributes {
+    /// 0 is square, 1 is wide, 2 is tall.
+    #[bits(2)]
+    pub shape: u8,
+    #[bits(2)]
+    pub size: u8,
+    pub flipped_horizontally: bool,
+    pub flipped_vertically: bool,
+    #[bits(4)]
+    pub palette_offset: u8,
+    #[bits(6)]
+    pub unk: u8,
+}
+
+impl SpriteObjectAttributes {
+    /// The width and height in pixels, or `None` for the invalid shape 3.
+    pub fn dimensions(&self) -> Option<(usize, usize)> {
+        const DIMENSIONS: [[(usize, usize); 4]; 3] = [
+            [(8, 8), (16, 16), (32, 32), (64, 64)],
+            [(16, 8), (32, 8), (32, 16), (64, 32)],
+            [(8, 16), (8, 32), (16, 32), (32, 64)],
+        ];
+        DIMENSIONS
+            .get(usize::from(self.shape()))
+            .map(|x| x[usize::from(self.size())])
+    }
+}
+
+/// A rectangle of consecutive tiles of the sprite's [`Tileset`],
+/// in row-major order, placed relative to the sprite's origin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SpriteObject {
+    pub x: i16,
+    pub y: i16,
+    pub tile: u16,
+    pub attributes: SpriteObjectAttributes,
+}
+
+impl SpriteObject {
+    pub const SIZE: usize = 8;
+
+    pub fn from_reader(mut inp: impl Read) -> io::Result<Self> {
+        Ok(Self {
+            x: inp.read_i16::<LittleEndian>()?,
+            y: inp.read_i16::<LittleEndian>()?,
+            tile: inp.read_u16::<LittleEndian>()?,
+            attributes: inp.read_u16::<LittleEndian>()?.into(),
+        })
+    }
+    pub fn to_writer(&self, mut out: impl Write) -> io::Result<()> {
+        out.write_i16::<LittleEndian>(self.x)?;
+        out.write_i16::<LittleEndian>(self.y)?;
+        out.write_u16::<LittleEndian>(self.tile)?;
+        out.write_u16::<LittleEndian>(self.attributes.into_bits())?;
+
+        Ok(())
+    }
+}
+
+/// A cell, i.e. a frame's picture, assembled from [`SpriteObject`]s.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct SpriteCell {
+    /// The first object is drawn on top.
+    pub objects: Vec<SpriteObject>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AnimationFrame {
+    /// An index into [`Sprite::cells`].
+    pub cell: u16,
+    /// In game frames.
+    pub duration: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Animation {
+    /// The frame which playback continues from after the last one,
+    /// or `None` if the animation stops there.
+    pub loop_start: Option<u16>,
+    pub frames: Vec<AnimationFrame>,
+}
+
+impl Animation {
+    const NO_LOOP: u16 = 0xFFFF;
+}
+
+/// A sprite of a field or battle object.
+///
+/// It's stored as 4 chunks: the 4bpp tiles, the palette, the cells and the animations.
+/// The cells chunk is a `u16` count followed by each cell's `u16` object count
+/// and its objects, while the animations chunk is a `u16` count followed by each
+/// animation's `u16` loop start (`0xFFFF` if it doesn't loop), `u16` frame count
+/// and its frames.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Sprite {
+    pub tileset: Tileset,
+    pub palette: Palette,
+    pub cells: Vec<SpriteCell>,
+    pub animations: Vec<Animation>,
+}
+
+#[derive(Error, Debug)]
+pub enum SpriteDeserializationError {
+    #[error(transparent)]
+    TilesetTileDeserialization(#[from] TilesetTileDeserializationError),
+    #[error(transparent)]
+    PaletteDeserialization(#[from] PaletteDeserializationError),
+    #[error("the {chunk} chunk contains extra bytes")]
+    ExtraBytesInInput { chunk: &'static str },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+#[derive(Error, Debug)]
+pub enum SpriteSerializationError {
+    #[error(transparent)]
+    TilesetTileSerialization(#[from] TilesetTileSerializationError),
+    #[error(transparent)]
+    TryFromInt(#[from] TryFromIntError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl Sprite {
+    pub const NUMBER_OF_CHUNKS: usize = 4;
+
+    /// Allows the zero padding which chunks are aligned with.
+    fn check_consumed(
+        inp: &Cursor<&[u8]>,
+        chunk: &'static str,
+    ) -> Result<(), SpriteDeserializationError> {
+        let rest = &inp.get_ref()[inp.position() as usize..];
+        if rest.len() >= STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT || rest.iter().any(|&x| x != 0) {
+            return Err(SpriteDeserializationError::ExtraBytesInInput { chunk });
+        }
+        Ok(())
+    }
+
+    pub fn from_chunks(
+        tileset: &[u8],
+        palette: &[u8],
+        cells: &[u8],
+        animations: &[u8],
+    ) -> Result<Self, SpriteDeserializationError> {
+        let mut cells = Cursor::new(cells);
+        let num_cells = cells.read_u16::<LittleEndian>()?;
+        let cells_result = (0..num_cells)
+            .map(|_| -> io::Result<_> {
+                let num_objects = cells.read_u16::<LittleEndian>()?;
+                Ok(SpriteCell {
+                    objects: (0..num_objects)
+                        .map(|_| SpriteObject::from_reader(&mut cells))
+                        .collect::<Result<_, _>>()?,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Self::check_consumed(&cells, "cells")?;
+
+        let mut animations = Cursor::new(animations);
+        let num_animations = animations.read_u16::<LittleEndian>()?;
+        let animations_result = (0..num_animations)
+            .map(|_| -> io::Result<_> {
+                let loop_start = animations.read_u16::<LittleEndian>()?;
+                let num_frames = animations.read_u16::<LittleEndian>()?;
+                Ok(Animation {
+                    loop_start: (loop_start != Animation::NO_LOOP).then_some(loop_start),
+                    frames: (0..num_frames)
+                        .map(|_| {
+                            Ok(AnimationFrame {
+                                cell: animations.read_u16::<LittleEndian>()?,
+                                duration: animations.read_u16::<LittleEndian>()?,
+                            })
+                        })
+                        .collect::<io::Result<_>>()?,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Self::check_consumed(&animations, "animations")?;
+
+        Ok(Self {
+            tileset: Tileset::from_bytes(tileset, SPRITE_TILESET_PIXEL_SIZE)?,
+            palette: Palette::from_bytes(palette)?,
+            cells: cells_result,
+            animations: animations_result,
+        })
+    }
+
+    pub fn to_chunks(&self) -> Result<[Vec<u8>; 4], SpriteSerializationError> {
+        let mut cells = Vec::new();
+        cells.write_u16::<LittleEndian>(self.cells.len().try_into()?)?;
+        for cell in &self.cells {
+            cells.write_u16::<LittleEndian>(cell.objects.len().try_into()?)?;
+            for object in &cell.objects {
+                object.to_writer(&mut cells)?;
+            }
+        }
+
+        let mut animations = Vec::new();
+        animations.write_u16::<LittleEndian>(self.animations.len().try_into()?)?;
+        for animation in &self.animations {
+            animations
+                .write_u16::<LittleEndian>(animation.loop_start.unwrap_or(Animation::NO_LOOP))?;
+            animations.write_u16::<LittleEndian>(animation.frames.len().try_into()?)?;
+            for frame in &animation.frames {
+                animations.write_u16::<LittleEndian>(frame.cell)?;
+                animations.write_u16::<LittleEndian>(frame.duration)?;
+            }
+        }
+
+        Ok([
+            self.tileset.to_bytes(SPRITE_TILESET_PIXEL_SIZE)?,
+            self.palette.to_bytes(),
+            cells,
+            animations,
+        ])
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SpriteRenderError {
+    #[error("frame {frame} uses cell {cell}, which doesn't exist")]
+    MissingCell { frame: usize, cell: usize },
+    #[error("object {object} of cell {cell} has the invalid shape {shape}")]
+    InvalidShape {
+        cell: usize,
+        object: usize,
+        shape: u8,
+    },
+    #[error("object {object} of cell {cell} uses tile {tile}, which doesn't exist")]
+    MissingTile {
+        cell: usize,
+        object: usize,
+        tile: usize,
+    },
+    #[error("object {object} of cell {cell} uses color {index}, which isn't in the palette")]
+    ColorNotInPalette {
+        cell: usize,
+        object: usize,
+        index: usize,
+    },
+}
+
+/// A frame of [`AnimationImages`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AnimationImage {
+    pub image: Grid<Rgba<u8>>,
+    /// In game frames.
+    pub duration: u16,
+}
+
+/// An [`Animation`] rendered by [`Sprite::render_animation`].
+///
+/// All frames are the same size, and the sprite's origin is at `origin` in all of them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AnimationImages {
+    pub origin: (usize, usize),
+    pub frames: Vec<AnimationImage>,
+    pub loop_start: Option<u16>,
+}
+
+impl Sprite {
+    /// The bounding box of the valid objects of `cell`, relative to the sprite's origin,
+    /// as `(left, top, right, bottom)`, or `None` if it has none.
+    pub fn cell_bounds(cell: &SpriteCell) -> Option<(i32, i32, i32, i32)> {
+        cell.objects
+            .iter()
+            .filter_map(|object| {
+                let (width, height) = object.attributes.dimensions()?;
+                let (x, y) = (i32::from(object.x), i32::from(object.y));
+                Some((x, y, x + width as i32, y + height as i32))
+            })
+            .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))
+    }
+
+    /// Draws the cell `cell_index` onto `image`, with the sprite's origin at `origin`.
+    /// Pixels outside of `image` are skipped.
+    ///
+    /// Panics if there's no such cell.
+    pub fn draw_cell(
+        &self,
+        cell_index: usize,
+        image: &mut Grid<Rgba<u8>>,
+        origin: (i32, i32),
+    ) -> Result<(), SpriteRenderError> {
+        self.draw_cell_with_lut(cell_index, &PaletteLut::from(&self.palette), image, origin)
+    }
+    /// Like [`Self::draw_cell`], but with the colors taken from `lut`
+    /// instead of [`Self::palette`], so that they aren't converted on every call.
+    pub fn draw_cell_with_lut(
+        &self,
+        cell_index: usize,
+        lut: &PaletteLut,
+        image: &mut Grid<Rgba<u8>>,
+        origin: (i32, i32),
+    ) -> Result<(), SpriteRenderError> {
+        let cell = &self.cells[cell_index];
+        // The first object is on top, so it's drawn last.
+        for (object_index, object) in cell.objects.iter().enumerate().rev() {
+            let attributes = object.attributes;
+            let (width, height) =
+                attributes
+                    .dimensions()
+                    .ok_or(SpriteRenderError::InvalidShape {
+                        cell: cell_index,
+                        object: object_index,
+                        shape: attributes.shape(),
+                    })?;
+            let tiles_per_row = width / TILE_WIDTH;
+            for y in 0..height {
+                for x in 0..width {
+                    let (source_x, source_y) = (
+                        if attributes.flipped_horizontally() {
+                            width - 1 - x
+                        } else {
+                            x
+                        },
+                        if attributes.flipped_vertically() {
+                            height - 1 - y
+                        } else {
+                            y
+                        },
+                    );
+                    let tile = usize::from(object.tile)
+                        + source_y / TILE_HEIGHT * tiles_per_row
+                        + source_x / TILE_WIDTH;
+                    let pixel = self
+                        .tileset
+                        .0
+                        .get(tile)
+                        .ok_or(SpriteRenderError::MissingTile {
+                            cell: cell_index,
+                            object: object_index,
+                            tile,
+                        })?
+                        .0[source_y % TILE_HEIGHT * TILE_WIDTH + source_x % TILE_WIDTH];
+                    if pixel == 0 {
+                        continue;
+                    }
+                    let index = usize::from(attributes.palette_offset()) * 16 + usize::from(pixel);
+                    let Some(color) = lut.get(index) else {
+                        return Err(SpriteRenderError::ColorNotInPalette {
+                            cell: cell_index,
+                            object: object_index,
+                            index,
+                        });
+                    };
+                    let (target_x, target_y) = (
+                        origin.0 + i32::from(object.x) + x as i32,
+                        origin.1 + i32::from(object.y) + y as i32,
+                    );
+                    let (Ok(target_x), Ok(target_y)) =
+                        (usize::try_from(target_x), usize::try_from(target_y))
+                    else {
+                        continue;
+                    };
+                    if let Some(target) = image.get_mut(target_y, target_x) {
+                        *target = color;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Renders every frame of `animation`, sized to fit all of its cells.
+    pub fn render_animation(
+        &self,
+        animation: &Animation,
+    ) -> Result<AnimationImages, SpriteRenderError> {
+        // The origin is always included, so that the frames line up.
+        let (mut left, mut top, mut right, mut bottom) = (0, 0, 0, 0);
+        for (frame_index, frame) in animation.frames.iter().enumerate() {
+            let cell =
+                self.cells
+                    .get(usize::from(frame.cell))
+                    .ok_or(SpriteRenderError::MissingCell {
+                        frame: frame_index,
+                        cell: frame.cell.into(),
+                    })?;
+            if let Some(bounds) = Self::cell_bounds(cell) {
+                (left, top) = (left.min(bounds.0), top.min(bounds.1));
+                (right, bottom) = (right.max(bounds.2), bottom.max(bounds.3));
+            }
+        }
+
+        let lut = PaletteLut::from(&self.palette);
+        Ok(AnimationImages {
+            origin: ((-left) as usize, (-top) as usize),
+            frames: animation
+                .frames
+                .iter()
+                .map(|frame| {
+                    let mut image = Grid::init(
+                        (bottom - top) as usize,
+                        (right - left) as usize,
+                        Rgba::new(0, 0, 0, 0),
+                    );
+                    self.draw_cell_with_lut(frame.cell.into(), &lut, &mut image, (-left, -top))?;
+                    Ok(AnimationImage {
+                        image,
+                        duration: frame.duration,
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+            loop_start: animation.loop_start,
+        })
+    }
+}
+
+#[cfg(feature = "gif")]
+impl AnimationImages {
+    /// Encodes the frames as an animated GIF, assuming the game runs at 60 FPS.
+    ///
+    /// GIFs can only loop from the first frame, so an animation which loops
+    /// from a later one loops entirely instead.
+    pub fn to_gif(&self, out: impl Write) -> Result<(), gif::EncodingError> {
+        let (width, height) = self
+            .frames
+            .first()
+            .map_or((0, 0), |x| (x.image.cols(), x.image.rows()));
+        let (width, height) = (
+            u16::try_from(width).unwrap_or(u16::MAX),
+            u16::try_from(height).unwrap_or(u16::MAX),
+        );
+        let mut encoder = gif::Encoder::new(out, width, height, &[])?;
+        encoder.set_repeat(if self.loop_start.is_some() {
+            gif::Repeat::Infinite
+        } else {
+            gif::Repeat::Finite(0)
+        })?;
+        for frame in &self.frames {
+            let mut pixels: Vec<u8> = frame
+                .image
+                .iter()
+                .flat_map(|x| [x.r, x.g, x.b, x.a])
+                .collect();
+            let mut gif_frame = gif::Frame::from_rgba(width, height, &mut pixels);
+            gif_frame.delay = ((u32::from(frame.duration) * 100 + 30) / 60)
+                .try_into()
+                .unwrap_or(u16::MAX);
+            gif_frame.dispose = gif::DisposalMethod::Background;
+            encoder.write_frame(&gif_frame)?;
+        }
+        Ok(())
+    }
+}
+
+/// How [`Sprite::import_cell`] maps the colors of the image to the palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ImportPalette {
+    /// Use the closest color of the existing palette.
+    #[default]
+    Quantize,
+    /// Replace the palette with the colors of the image.
+    /// This affects every cell which uses the same palette offset.
+    Regenerate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ImportOptions {
+    /// Which 16-color part of the palette the new objects use.
+    pub palette_offset: u8,
+    pub palette: ImportPalette,
+}
+
+#[derive(Error, Debug)]
+pub enum SpriteImportError {
+    #[error("there's no cell {0}")]
+    MissingCell(usize),
+    #[error("the palette offset {0} is too large")]
+    InvalidPaletteOffset(u8),
+    #[error("the palette has no colors at offset {0}")]
+    EmptyPalette(u8),
+    #[error("the image has {0} colors, but a palette offset fits only 15")]
+    TooManyColors(usize),
+    #[error(transparent)]
+    TryFromInt(#[from] TryFromIntError),
+}
+
+impl Sprite {
+    pub const COLORS_PER_PALETTE_OFFSET: usize = 16;
+
+    /// Replaces the objects of the cell `cell_index` with 8x8 ones covering
+    /// the opaque pixels of `image`, in which the sprite's origin is at `origin`.
+    ///
+    /// Pixels with an alpha below 0x80 are transparent.
+    /// Identical tiles already in the tileset are reused, and other ones are appended.
+    /// The animations are left untouched.
+    pub fn import_cell(
+        &mut self,
+        cell_index: usize,
+        image: &Grid<Rgba<u8>>,
+        origin: (usize, usize),
+        options: &ImportOptions,
+    ) -> Result<(), SpriteImportError> {
+        if cell_index >= self.cells.len() {
+            return Err(SpriteImportError::MissingCell(cell_index));
+        }
+        if usize::from(options.palette_offset) >= Self::COLORS_PER_PALETTE_OFFSET {
+            return Err(SpriteImportError::InvalidPaletteOffset(
+                options.palette_offset,
+            ));
+        }
+        let palette_start = usize::from(options.palette_offset) * Self::COLORS_PER_PALETTE_OFFSET;
+        let colors: Grid<Option<Rgb555>> = Grid::from_vec(
+            image
+                .iter()
+                .map(|x| (x.a >= 0x80).then(|| x.rgb().into()))
+                .collect(),
+            image.cols(),
+        );
+
+        let mut palette = self.palette.clone();
+        if options.palette == ImportPalette::Regenerate {
+            let unique: Vec<Rgb555> = colors.iter().flatten().copied().unique().collect();
+            if unique.len() >= Self::COLORS_PER_PALETTE_OFFSET {
+                return Err(SpriteImportError::TooManyColors(unique.len()));
+            }
+            if palette.0.len() < palette_start + Self::COLORS_PER_PALETTE_OFFSET {
+                palette.0.resize(
+                    palette_start + Self::COLORS_PER_PALETTE_OFFSET,
+                    Rgb555::new(0, 0, 0),
+                );
+            }
+            palette.0[palette_start + 1..][..unique.len()].copy_from_slice(&unique);
+        }
+        let choices: Vec<(u8, Rgb555)> = palette
+            .0
+            .iter()
+            .enumerate()
+            .skip(palette_start + 1)
+            .take(Self::COLORS_PER_PALETTE_OFFSET - 1)
+            .map(|(i, &color)| ((i - palette_start) as u8, color))
+            .collect();
+        if choices.is_empty() {
+            return Err(SpriteImportError::EmptyPalette(options.palette_offset));
+        }
+        let quantize = |color: Rgb555| {
+            let distance = |other: Rgb555| {
+                [
+                    (color.r(), other.r()),
+                    (color.g(), other.g()),
+                    (color.b(), other.b()),
+                ]
+                .into_iter()
+                .map(|(a, b)| u32::from(a.abs_diff(b)).pow(2))
+                .sum::<u32>()
+            };
+            choices
+                .iter()
+                .min_by_key(|(_, other)| distance(*other))
+                .unwrap()
+                .0
+        };
+
+        // The tiles are aligned to the origin.
+        let (origin_x, origin_y) = (origin.0 as i64, origin.1 as i64);
+        let tile_range = |origin: i64, len: usize, tile_len: usize| {
+            let tile_len = tile_len as i64;
+            (-origin).div_euclid(tile_len)
+                ..(len as i64 - origin + tile_len - 1).div_euclid(tile_len)
+        };
+        let mut tileset = self.tileset.0.clone();
+        let mut objects = Vec::new();
+        for tile_y in tile_range(origin_y, colors.rows(), TILE_HEIGHT) {
+            for tile_x in tile_range(origin_x, colors.cols(), TILE_WIDTH) {
+                let (x, y) = (tile_x * TILE_WIDTH as i64, tile_y * TILE_HEIGHT as i64);
+                let mut tile = TilesetTile([0; TILE_AREA]);
+                for (i, pixel) in tile.0.iter_mut().enumerate() {
+                    let (Ok(row), Ok(col)) = (
+                        usize::try_from(origin_y + y + (i / TILE_WIDTH) as i64),
+                        usize::try_from(origin_x + x + (i % TILE_WIDTH) as i64),
+                    ) else {
+                        continue;
+                    };
+                    if let Some(Some(color)) = colors.get(row, col) {
+                        *pixel = quantize(*color);
+                    }
+                }
+                if tile.0.iter().all(|&x| x == 0) {
+                    continue;
+                }
+
+                let tile_index = tileset.iter().position(|x| *x == tile).unwrap_or_else(|| {
+                    tileset.push(tile);
+                    tileset.len() - 1
+                });
+                objects.push(SpriteObject {
+                    x: x.try_into()?,
+                    y: y.try_into()?,
+                    tile: tile_index.try_into()?,
+                    attributes: SpriteObjectAttributes::new()
+                        .with_palette_offset(options.palette_offset),
+                });
+            }
+        }
+
+        self.tileset.0 = tileset;
+        self.palette = palette;
+        self.cells[cell_index].objects = objects;
+        Ok(())
+    }
+}
+
+/// Decodes a PNG file into an image for [`Sprite::import_cell`].
+#[cfg(feature = "png")]
+pub fn image_from_png(inp: impl Read) -> Result<Grid<Rgba<u8>>, png::DecodingError> {
+    let mut decoder = png::Decoder::new(inp);
+    decoder.set_transformations(
+        png::Transformations::normalize_to_color8() | png::Transformations::ALPHA,
+    );
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0u8; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)?;
+    let pixels: Vec<Rgba<u8>> = match info.color_type {
+        png::ColorType::GrayscaleAlpha => buf[..info.buffer_size()]
+            .chunks_exact(2)
+            .map(|x| Rgba::new(x[0], x[0], x[0], x[1]))
+            .collect(),
+        // With the `ALPHA` transformation, all other images are expanded into RGBA.
+        _ => buf[..info.buffer_size()]
+            .chunks_exact(4)
+            .map(|x| Rgba::new(x[0], x[1], x[2], x[3]))
+            .collect(),
+    };
+    Ok(Grid::from_vec(pixels, info.width as usize))
+}
+
+/// Encodes an image, such as a rendered [`FieldMapChunk`](crate::map::FieldMapChunk),
+/// as an RGBA PNG file.
+#[cfg(feature = "png")]
+#[inline]
+pub fn image_to_png(image: &Grid<Rgba<u8>>, out: impl Write) -> Result<(), png::EncodingError> {
+    image_to_png_with_text(image, &[], out)
+}
+/// Like [`image_to_png`], but with `tEXt` chunks of keywords and Latin-1 text.
+#[cfg(feature = "png")]
+pub fn image_to_png_with_text(
+    image: &Grid<Rgba<u8>>,
+    text: &[(String, String)],
+    out: impl Write,
+) -> Result<(), png::EncodingError> {
+    let (width, height) = (
+        u32::try_from(image.cols()).unwrap_or(u32::MAX),
+        u32::try_from(image.rows()).unwrap_or(u32::MAX),
+    );
+    let mut encoder = png::Encoder::new(out, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    for (keyword, text) in text {
+        encoder.add_text_chunk(keyword.clone(), text.clone())?;
+    }
+    let mut writer = encoder.write_header()?;
+    let pixels: Vec<u8> = image.iter().flat_map(|x| [x.r, x.g, x.b, x.a]).collect();
+    writer.write_image_data(&pixels)?;
+    writer.finish()
+}
+
+/// Reads the keywords and text of the `tEXt` and `iTXt` chunks of a PNG file
+/// which come before the image data.
+#[cfg(feature = "png")]
+pub fn png_text(inp: impl Read) -> Result<Vec<(String, String)>, png::DecodingError> {
+    let reader = png::Decoder::new(inp).read_info()?;
+    let info = reader.info();
+    let mut text: Vec<(String, String)> = info
+        .uncompressed_latin1_text
+        .iter()
+        .map(|x| (x.keyword.clone(), x.text.clone()))
+        .collect();
+    for chunk in &info.utf8_text {
+        text.push((chunk.keyword.clone(), chunk.get_text()?));
+    }
+    Ok(text)
+}
+
+/// An image of indices into its own palette, as stored in indexed PNG and BMP files.
+///
+/// Unlike [`Sprite::import_cell`], importing it with [`Tileset::from_indexed_image`]
+/// and [`Self::to_palette`] keeps the order of the palette.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IndexedImage {
+    pub pixels: Grid<u8>,
+    pub palette: Vec<Rgba<u8>>,
+}
+
+#[derive(Error, Debug)]
+pub enum IndexedImageDecodingError {
+    #[error("the image isn't indexed")]
+    NotIndexed,
+    #[error("invalid or unsupported BMP file: {0}")]
+    InvalidBmp(&'static str),
+    #[cfg(feature = "png")]
+    #[error(transparent)]
+    Png(#[from] png::DecodingError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Unpacks the indices of a row of pixels, packed from the most significant bit.
+fn unpack_indices(
+    row: &[u8],
+    bits_per_pixel: usize,
+    width: usize,
+) -> impl Iterator<Item = u8> + '_ {
+    let mask = ((1u16 << bits_per_pixel) - 1) as u8;
+    (0..width).map(move |x| {
+        let bit = x * bits_per_pixel;
+        (row[bit / 8] >> (8 - bits_per_pixel - bit % 8)) & mask
+    })
+}
+
+impl IndexedImage {
+    /// The palette converted to [`Rgb555`], in its original order.
+    pub fn to_palette(&self) -> Palette {
+        Palette(self.palette.iter().map(|x| Rgb555::from(x.rgb())).collect())
+    }
+
+    /// Decodes an indexed PNG file of any bit depth.
+    /// Colors without an entry in its `tRNS` chunk are opaque.
+    #[cfg(feature = "png")]
+    pub fn from_png(inp: impl Read) -> Result<Self, IndexedImageDecodingError> {
+        let mut reader = png::Decoder::new(inp).read_info()?;
+        let mut buf = vec![0u8; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf)?;
+        if info.color_type != png::ColorType::Indexed {
+            return Err(IndexedImageDecodingError::NotIndexed);
+        }
+        let width = info.width as usize;
+        let bits_per_pixel = info.bit_depth as usize;
+        let pixels: Vec<u8> = buf[..info.buffer_size()]
+            .chunks_exact(info.line_size)
+            .flat_map(|row| unpack_indices(row, bits_per_pixel, width))
+            .collect();
+
+        let png_info = reader.info();
+        let alphas = png_info.trns.as_deref().unwrap_or_default();
+        let palette = png_info
+            .palette
+            .as_deref()
+            .ok_or(IndexedImageDecodingError::NotIndexed)?
+            .chunks_exact(3)
+            .enumerate()
+            .map(|(i, x)| Rgba::new(x[0], x[1], x[2], alphas.get(i).copied().unwrap_or(0xFF)))
+            .collect();
+        Ok(Self {
+            pixels: Grid::from_vec(pixels, width),
+            palette,
+        })
+    }
+
+    /// Decodes an uncompressed BMP file with 1, 4 or 8 bits per pixel.
+    /// As BMP palettes have no alpha, all colors are opaque.
+    pub fn from_bmp(mut inp: impl Read) -> Result<Self, IndexedImageDecodingError> {
+        const FILE_HEADER_SIZE: usize = 14;
+        const MIN_INFO_HEADER_SIZE: u32 = 40;
+
+        let mut data = Vec::new();
+        inp.read_to_end(&mut data)?;
+        let mut header = Cursor::new(&data[..]);
+        if header.read_u16::<LittleEndian>()? != u16::from_le_bytes(*b"BM") {
+            return Err(IndexedImageDecodingError::InvalidBmp("wrong magic number"));
+        }
+        header.set_position(10);
+        let pixels_offset = header.read_u32::<LittleEndian>()? as usize;
+        let info_header_size = header.read_u32::<LittleEndian>()?;
+        if info_header_size < MIN_INFO_HEADER_SIZE {
+            return Err(IndexedImageDecodingError::InvalidBmp(
+                "unsupported header version",
+            ));
+        }
+        let width = header.read_i32::<LittleEndian>()?;
+        let height = header.read_i32::<LittleEndian>()?;
+        header.read_u16::<LittleEndian>()?;
+        let bits_per_pixel = header.read_u16::<LittleEndian>()?;
+        if bits_per_pixel > 8 {
+            return Err(IndexedImageDecodingError::NotIndexed);
+        }
+        if ![1, 4, 8].contains(&bits_per_pixel) {
+            return Err(IndexedImageDecodingError::InvalidBmp(
+                "unsupported bits per pixel",
+            ));
+        }
+        if header.read_u32::<LittleEndian>()? != 0 {
+            return Err(IndexedImageDecodingError::InvalidBmp("compressed"));
+        }
+        header.set_position(header.position() + 12);
+        let num_colors = match header.read_u32::<LittleEndian>()? {
+            0 => 1 << bits_per_pixel,
+            x => x as usize,
+        };
+
+        let palette_offset = FILE_HEADER_SIZE + info_header_size as usize;
+        let palette = data
+            .get(palette_offset..palette_offset + num_colors * 4)
+            .ok_or(IndexedImageDecodingError::InvalidBmp("truncated palette"))?
+            .chunks_exact(4)
+            .map(|x| Rgba::new(x[2], x[1], x[0], 0xFF))
+            .collect();
+
+        // Positive heights are stored from the bottom row up.
+        let (width, bottom_up) = (width.unsigned_abs() as usize, height > 0);
+        let height = height.unsigned_abs() as usize;
+        let bits_per_pixel = usize::from(bits_per_pixel);
+        let stride = (width * bits_per_pixel).div_ceil(32) * 4;
+        let rows = data
+            .get(pixels_offset..)
+            .and_then(|x| x.get(..stride * height))
+            .ok_or(IndexedImageDecodingError::InvalidBmp("truncated pixels"))?
+            .chunks_exact(stride);
+        let rows: Vec<&[u8]> = if bottom_up {
+            rows.rev().collect()
+        } else {
+            rows.collect()
+        };
+        let pixels = rows
+            .into_iter()
+            .flat_map(|row| unpack_indices(row, bits_per_pixel, width))
+            .collect();
+        Ok(Self {
+            pixels: Grid::from_vec(pixels, width),
+            palette,
+        })
+    }
+}
+
+/// A file of [`Sprite`]s, stored as a [`DataWithOffsetTable`]
+/// with [`Sprite::NUMBER_OF_CHUNKS`] chunks per sprite.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct SpriteArchive {
+    pub sprites: Vec<Sprite>,
+    pub padding: Vec<u8>,
+}
+
+#[derive(Error, Debug)]
+pub enum SpriteArchiveFromTableError {
+    #[error(
+        "the number of chunks of the input ({0}) isn't divisible by {n}",
+        n = Sprite::NUMBER_OF_CHUNKS
+    )]
+    InvalidNumberOfChunks(usize),
+    #[error("failed to deserialize sprite {index}")]
+    Sprite {
+        index: usize,
+        #[source]
+        source: SpriteDeserializationError,
+    },
+}
+#[derive(Error, Debug)]
+pub enum SpriteArchiveIntoTableError {
+    #[error("failed to serialize sprite {index}")]
+    Sprite {
+        index: usize,
+        #[source]
+        source: SpriteSerializationError,
+    },
+}
+
+impl TryFrom<DataWithOffsetTable> for SpriteArchive {
+    type Error = SpriteArchiveFromTableError;
+
+    fn try_from(value: DataWithOffsetTable) -> Result<Self, Self::Error> {
+        if !value.chunks.len().is_multiple_of(Sprite::NUMBER_OF_CHUNKS) {
+            return Err(Self::Error::InvalidNumberOfChunks(value.chunks.len()));
+        }
+
+        Ok(Self {
+            sprites: value
+                .chunks
+                .chunks_exact(Sprite::NUMBER_OF_CHUNKS)
+                .enumerate()
+                .map(|(index, chunks)| {
+                    Sprite::from_chunks(&chunks[0], &chunks[1], &chunks[2], &chunks[3])
+                        .map_err(|source| Self::Error::Sprite { index, source })
+                })
+                .collect::<Result<_, _>>()?,
+            padding: value.footer,
+        })
+    }
+}
+impl TryFrom<SpriteArchive> for DataWithOffsetTable {
+    type Error = SpriteArchiveIntoTableError;
+
+    fn try_from(value: SpriteArchive) -> Result<Self, Self::Error> {
+        Ok(Self {
+            chunks: value
+                .sprites
+                .iter()
+                .enumerate()
+                .map(|(index, sprite)| {
+                    sprite
+                        .to_chunks()
+                        .map_err(|source| Self::Error::Sprite { index, source })
+                })
+                .flatten_ok()
+                .collect::<Result<_, _>>()?,
+            footer: value.padding,
+        })
+    }
+}
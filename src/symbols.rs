@@ -0,0 +1,183 @@
+//! Export of the RAM addresses of the data managed by this crate, for assembly hacks
+//! which need to refer to it, as a symbol file or a GNU linker script fragment.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use thiserror::Error;
+
+use crate::{
+    consts::{
+        FEVENT_OFFSET_TABLE_ADDRESS, FEVENT_OFFSET_TABLE_LENGTH_ADDRESS,
+        FIELD_MAP_CHUNK_TABLE_ADDRESS, FMAPDATA_OFFSET_TABLE_ADDRESS,
+        FMAPDATA_OFFSET_TABLE_LENGTH_ADDRESS, NUMBER_OF_FIELD_MAPS,
+        TREASURE_INFO_OFFSET_TABLE_ADDRESS, TREASURE_INFO_OFFSET_TABLE_LENGTH_ADDRESS,
+    },
+    misc::OverlayTableLocation,
+    rom::OverlayEntry,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Symbol {
+    pub name: String,
+    pub address: u32,
+    /// In bytes, if it's known.
+    pub size: Option<u32>,
+}
+
+#[derive(Error, Debug)]
+pub enum SymbolError {
+    #[error("the RAM address of overlay {0} isn't known")]
+    UnknownOverlay(u32),
+    #[error("the address of {0} doesn't fit into 32 bits")]
+    AddressTooLarge(String),
+}
+
+/// A list of [`Symbol`]s, whose addresses in overlays are resolved
+/// with the RAM addresses of an overlay table.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct SymbolTable {
+    pub symbols: Vec<Symbol>,
+    overlay_addresses: BTreeMap<u32, u32>,
+}
+
+impl SymbolTable {
+    /// Starts with a symbol for each overlay and the fixed locations of [`crate::consts`]
+    /// in the overlays which are in `overlays`, such as the `y9.bin` of an extracted ROM.
+    pub fn new(overlays: &[OverlayEntry]) -> Self {
+        let mut table = Self {
+            symbols: Vec::new(),
+            overlay_addresses: overlays
+                .iter()
+                .map(|x| (x.overlay_number, x.ram_address))
+                .collect(),
+        };
+        for overlay in overlays {
+            table.symbols.push(Symbol {
+                name: format!("overlay_{}", overlay.overlay_number),
+                address: overlay.ram_address,
+                size: Some(overlay.ram_size),
+            });
+        }
+
+        let field_map_chunk_table_size = NUMBER_OF_FIELD_MAPS as u64 * 5 * 4;
+        for (name, overlay_number, offset, size) in [
+            (
+                "fmapdata_offset_table_length",
+                3,
+                FMAPDATA_OFFSET_TABLE_LENGTH_ADDRESS,
+                Some(4),
+            ),
+            (
+                "fmapdata_offset_table",
+                3,
+                FMAPDATA_OFFSET_TABLE_ADDRESS,
+                None,
+            ),
+            (
+                "field_map_chunk_table",
+                3,
+                FIELD_MAP_CHUNK_TABLE_ADDRESS,
+                Some(field_map_chunk_table_size),
+            ),
+            (
+                "fevent_offset_table_length",
+                3,
+                FEVENT_OFFSET_TABLE_LENGTH_ADDRESS,
+                Some(4),
+            ),
+            ("fevent_offset_table", 3, FEVENT_OFFSET_TABLE_ADDRESS, None),
+            (
+                "treasure_info_offset_table_length",
+                4,
+                TREASURE_INFO_OFFSET_TABLE_LENGTH_ADDRESS,
+                Some(4),
+            ),
+            (
+                "treasure_info_offset_table",
+                4,
+                TREASURE_INFO_OFFSET_TABLE_ADDRESS,
+                None,
+            ),
+        ] {
+            if table.overlay_addresses.contains_key(&overlay_number) {
+                // The constants are small enough not to overflow.
+                table
+                    .add_in_overlay(name, overlay_number, offset, size)
+                    .unwrap();
+            }
+        }
+        table
+    }
+
+    /// Adds a symbol at `offset` into overlay `overlay_number`.
+    pub fn add_in_overlay(
+        &mut self,
+        name: impl Into<String>,
+        overlay_number: u32,
+        offset: u64,
+        size: Option<u64>,
+    ) -> Result<(), SymbolError> {
+        let name = name.into();
+        let ram_address = *self
+            .overlay_addresses
+            .get(&overlay_number)
+            .ok_or(SymbolError::UnknownOverlay(overlay_number))?;
+        let (Some(address), Ok(size)) = (
+            u64::from(ram_address)
+                .checked_add(offset)
+                .and_then(|x| u32::try_from(x).ok()),
+            size.map(u32::try_from).transpose(),
+        ) else {
+            return Err(SymbolError::AddressTooLarge(name));
+        };
+        self.symbols.push(Symbol {
+            name,
+            address,
+            size,
+        });
+        Ok(())
+    }
+
+    /// Adds a symbol for a table of [`OverlayRecord`](crate::misc::OverlayRecord)s.
+    pub fn add_table(
+        &mut self,
+        name: impl Into<String>,
+        location: &OverlayTableLocation,
+    ) -> Result<(), SymbolError> {
+        self.add_in_overlay(
+            name,
+            location.overlay_number,
+            location.address,
+            Some((location.entry_size * location.count) as u64),
+        )
+    }
+
+    fn sorted(&self) -> Vec<&Symbol> {
+        let mut symbols: Vec<&Symbol> = self.symbols.iter().collect();
+        symbols.sort_by_key(|x| x.address);
+        symbols
+    }
+
+    /// One `ADDRESS name` line per symbol, sorted by address,
+    /// like the `.sym` files of no$gba.
+    pub fn to_sym(&self) -> String {
+        let mut sym = String::new();
+        for symbol in self.sorted() {
+            writeln!(sym, "{:08X} {}", symbol.address, symbol.name).unwrap();
+        }
+        sym
+    }
+
+    /// A fragment of a GNU linker script which defines each symbol,
+    /// and `<name>_size` for those with a known size.
+    pub fn to_linker_script(&self) -> String {
+        let mut script = String::from("/* Generated by mnllib. */\n");
+        for symbol in self.sorted() {
+            writeln!(script, "{} = 0x{:08X};", symbol.name, symbol.address).unwrap();
+            if let Some(size) = symbol.size {
+                writeln!(script, "{}_size = 0x{:X};", symbol.name, size).unwrap();
+            }
+        }
+        script
+    }
+}
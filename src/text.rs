@@ -0,0 +1,718 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Write},
+    num::ParseIntError,
+};
+
+use thiserror::Error;
+
+use crate::{
+    misc::{
+        DataWithOffsetTable, DataWithOffsetTableDeserializationError,
+        DataWithOffsetTableSerializationError, MaybeSerialized,
+    },
+    utils::none_if_empty,
+};
+
+/// The bytes which every message ends with.
+pub const MESSAGE_TERMINATOR: [u8; 3] = [0xFF, 0x0A, 0x00];
+
+/// A list of encoded messages, e.g. all messages of a room in one language.
+///
+/// It's stored as a [`DataWithOffsetTable`] whose last offset points to the last message,
+/// so the footer of the table consists of the last message followed by padding.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct MessageList {
+    pub messages: Vec<Vec<u8>>,
+    pub padding: Vec<u8>,
+    /// Whether the footer consists of only padding, with the last message
+    /// being a chunk of its own, so that it's written back the same way.
+    pub padding_only_footer: bool,
+}
+
+impl From<DataWithOffsetTable> for MessageList {
+    fn from(mut value: DataWithOffsetTable) -> Self {
+        // The padding starts after the last terminator of the footer,
+        // or is the whole footer if there's no last message.
+        let last_message_len = value
+            .footer
+            .windows(MESSAGE_TERMINATOR.len())
+            .rposition(|x| x == MESSAGE_TERMINATOR)
+            .map_or(0, |i| i + MESSAGE_TERMINATOR.len());
+        let padding = value.footer.split_off(last_message_len);
+        let padding_only_footer = value.footer.is_empty();
+        if !padding_only_footer {
+            value.chunks.push(value.footer);
+        }
+        Self {
+            messages: value.chunks,
+            padding,
+            padding_only_footer,
+        }
+    }
+}
+impl From<MessageList> for DataWithOffsetTable {
+    fn from(mut value: MessageList) -> Self {
+        let mut footer = if value.padding_only_footer {
+            Vec::new()
+        } else {
+            value.messages.pop().unwrap_or_default()
+        };
+        footer.extend(value.padding);
+        Self {
+            chunks: value.messages,
+            footer,
+        }
+    }
+}
+
+impl MessageList {
+    /// Deserializes `data` if it looks like a message list, i.e. it's a valid
+    /// [`DataWithOffsetTable`] with at least one message, all of which end with
+    /// [`MESSAGE_TERMINATOR`].
+    pub fn recognize(data: &[u8]) -> Option<Self> {
+        let list = Self::from(DataWithOffsetTable::from_reader(data).ok()?);
+        list.messages
+            .last()?
+            .ends_with(&MESSAGE_TERMINATOR)
+            .then_some(())?;
+        list.messages
+            .iter()
+            .all(|x| x.is_empty() || x.ends_with(&MESSAGE_TERMINATOR))
+            .then_some(list)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, DataWithOffsetTableSerializationError> {
+        let mut buf = Vec::new();
+        DataWithOffsetTable::from(self.clone()).to_writer(&mut buf, None, true)?;
+        Ok(buf)
+    }
+}
+
+/// Identifies a message inside a [`MessageListSet`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MessageId {
+    pub list: usize,
+    pub message: usize,
+}
+
+impl Display for MessageId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.list, self.message)
+    }
+}
+
+/// A [`DataWithOffsetTable`] whose chunks are mostly [`MessageList`]s.
+///
+/// `mfset_*.dat` files are one of these, with a list per language,
+/// while the rooms of `*Mes*.dat` files are one of these each,
+/// alongside some other data.
+/// Only the chunks which [`MessageList::recognize`] accepts are deserialized.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct MessageListSet {
+    pub lists: Vec<MaybeSerialized<MessageList>>,
+    pub padding: Vec<u8>,
+}
+
+#[derive(Error, Debug)]
+pub enum MessageListSetIntoTableError {
+    #[error("failed to serialize message list {index}")]
+    MessageList {
+        index: usize,
+        #[source]
+        source: DataWithOffsetTableSerializationError,
+    },
+}
+
+impl From<DataWithOffsetTable> for MessageListSet {
+    fn from(value: DataWithOffsetTable) -> Self {
+        Self {
+            lists: value
+                .chunks
+                .into_iter()
+                .map(|chunk| match MessageList::recognize(&chunk) {
+                    Some(list) => MaybeSerialized::Deserialized(list),
+                    None => MaybeSerialized::Serialized(chunk),
+                })
+                .collect(),
+            padding: value.footer,
+        }
+    }
+}
+impl TryFrom<MessageListSet> for DataWithOffsetTable {
+    type Error = MessageListSetIntoTableError;
+
+    fn try_from(value: MessageListSet) -> Result<Self, Self::Error> {
+        Ok(Self {
+            chunks: value
+                .lists
+                .into_iter()
+                .enumerate()
+                .map(|(index, list)| {
+                    list.into_serialized_with(MessageList::to_bytes)
+                        .map_err(|source| Self::Error::MessageList { index, source })
+                })
+                .collect::<Result<_, _>>()?,
+            footer: value.padding,
+        })
+    }
+}
+
+impl MessageListSet {
+    pub fn get(&self, id: MessageId) -> Option<&Vec<u8>> {
+        self.lists
+            .get(id.list)?
+            .as_deserialized()?
+            .messages
+            .get(id.message)
+    }
+    pub fn get_mut(&mut self, id: MessageId) -> Option<&mut Vec<u8>> {
+        self.lists
+            .get_mut(id.list)?
+            .as_deserialized_mut()?
+            .messages
+            .get_mut(id.message)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (MessageId, &Vec<u8>)> {
+        self.lists
+            .iter()
+            .enumerate()
+            .filter_map(|(list_index, list)| Some((list_index, list.as_deserialized()?)))
+            .flat_map(|(list_index, list)| {
+                list.messages
+                    .iter()
+                    .enumerate()
+                    .map(move |(message_index, message)| {
+                        (
+                            MessageId {
+                                list: list_index,
+                                message: message_index,
+                            },
+                            message,
+                        )
+                    })
+            })
+    }
+}
+
+/// Identifies a message inside a [`MessageArchive`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ArchiveMessageId {
+    pub set: usize,
+    pub id: MessageId,
+}
+
+impl Display for ArchiveMessageId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.set, self.id)
+    }
+}
+
+/// A [`DataWithOffsetTable`] whose non-empty chunks are [`MessageListSet`]s,
+/// such as `*Mes*.dat` files, which have one set per room.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct MessageArchive {
+    pub sets: Vec<Option<MessageListSet>>,
+    pub padding: Vec<u8>,
+}
+
+#[derive(Error, Debug)]
+pub enum MessageArchiveFromTableError {
+    #[error("failed to deserialize message list set {index}")]
+    DataWithOffsetTableDeserialization {
+        index: usize,
+        #[source]
+        source: DataWithOffsetTableDeserializationError,
+    },
+}
+#[derive(Error, Debug)]
+pub enum MessageArchiveIntoTableError {
+    #[error("failed to serialize message list set {index}")]
+    DataWithOffsetTableSerialization {
+        index: usize,
+        #[source]
+        source: DataWithOffsetTableSerializationError,
+    },
+    #[error("failed to serialize message list set {index}")]
+    MessageListSet {
+        index: usize,
+        #[source]
+        source: MessageListSetIntoTableError,
+    },
+}
+
+impl TryFrom<DataWithOffsetTable> for MessageArchive {
+    type Error = MessageArchiveFromTableError;
+
+    fn try_from(value: DataWithOffsetTable) -> Result<Self, Self::Error> {
+        Ok(Self {
+            sets: value
+                .chunks
+                .into_iter()
+                .enumerate()
+                .map(|(index, chunk)| {
+                    none_if_empty(chunk)
+                        .map(|x| {
+                            Ok(DataWithOffsetTable::from_reader(&x[..])
+                                .map_err(|source| {
+                                    Self::Error::DataWithOffsetTableDeserialization {
+                                        index,
+                                        source,
+                                    }
+                                })?
+                                .into())
+                        })
+                        .transpose()
+                })
+                .collect::<Result<_, _>>()?,
+            padding: value.footer,
+        })
+    }
+}
+impl TryFrom<MessageArchive> for DataWithOffsetTable {
+    type Error = MessageArchiveIntoTableError;
+
+    fn try_from(value: MessageArchive) -> Result<Self, Self::Error> {
+        Ok(Self {
+            chunks: value
+                .sets
+                .into_iter()
+                .enumerate()
+                .map(|(index, set)| {
+                    let mut buf = Vec::new();
+                    if let Some(set) = set {
+                        DataWithOffsetTable::try_from(set)
+                            .map_err(|source| Self::Error::MessageListSet { index, source })?
+                            .to_writer(&mut buf, None, true)
+                            .map_err(|source| Self::Error::DataWithOffsetTableSerialization {
+                                index,
+                                source,
+                            })?;
+                    }
+                    Ok(buf)
+                })
+                .collect::<Result<_, _>>()?,
+            footer: value.padding,
+        })
+    }
+}
+
+impl MessageArchive {
+    pub fn get(&self, id: ArchiveMessageId) -> Option<&Vec<u8>> {
+        self.sets.get(id.set)?.as_ref()?.get(id.id)
+    }
+    pub fn get_mut(&mut self, id: ArchiveMessageId) -> Option<&mut Vec<u8>> {
+        self.sets.get_mut(id.set)?.as_mut()?.get_mut(id.id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ArchiveMessageId, &Vec<u8>)> {
+        self.sets
+            .iter()
+            .enumerate()
+            .filter_map(|(set_index, set)| Some((set_index, set.as_ref()?)))
+            .flat_map(|(set_index, set)| {
+                set.iter()
+                    .map(move |(id, message)| (ArchiveMessageId { set: set_index, id }, message))
+            })
+    }
+}
+
+/// The byte which starts a two-byte control code.
+pub const CONTROL_CODE_PREFIX: u8 = 0xFF;
+
+#[derive(Error, Debug)]
+pub enum TextDecodingError {
+    #[error("no mapping for the code {code:#04X} at offset {offset:#X}")]
+    UnmappableCode { offset: usize, code: u8 },
+}
+#[derive(Error, Debug)]
+pub enum TextEncodingError {
+    #[error("no mapping for the character {character:?} at offset {offset}")]
+    UnmappableCharacter { offset: usize, character: char },
+}
+#[derive(Error, Debug)]
+pub enum CharacterTableParseError {
+    #[error("line {line} is not of the form `CODE=TEXT`")]
+    InvalidLine { line: usize },
+    #[error("invalid code on line {line}")]
+    InvalidCode {
+        line: usize,
+        #[source]
+        source: ParseIntError,
+    },
+}
+
+/// A bidirectional mapping between byte sequences (codes) and text (graphemes),
+/// used to decode message bytes into [`String`]s and to encode them back.
+///
+/// Both directions match greedily, preferring the longest code or text.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CharacterTable {
+    decoding: HashMap<Vec<u8>, String>,
+    encoding: HashMap<String, Vec<u8>>,
+    max_code_len: usize,
+    max_text_len: usize,
+}
+
+impl CharacterTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The table for the text of the western releases: printable ASCII and
+    /// Latin-1, a newline for `FF 00` and `{XX}`/`{FFXX}` for everything else,
+    /// so that any message made of whole codes can be decoded and re-encoded.
+    ///
+    /// `{` itself is `{7B}`, so that it can't be mistaken for the start of an escape.
+    pub fn standard() -> Self {
+        let mut table = Self::new();
+        for code in 0x00..CONTROL_CODE_PREFIX {
+            let character = char::from(code);
+            if character != '{'
+                && (character == ' ' || character.is_ascii_graphic() || code >= 0xA0)
+            {
+                table.insert([code], character);
+            } else {
+                table.insert([code], format!("{{{:02X}}}", code));
+            }
+        }
+        for code in 0x00..=0xFF {
+            table.insert(
+                [CONTROL_CODE_PREFIX, code],
+                format!("{{{:02X}{:02X}}}", CONTROL_CODE_PREFIX, code),
+            );
+        }
+        table.insert([CONTROL_CODE_PREFIX, 0x00], "\n");
+        table
+    }
+
+    /// Parses a table in the common `.tbl` format: one `CODE=TEXT` mapping per line,
+    /// where `CODE` is hexadecimal and `\n` and `\\` in `TEXT` are escapes.
+    /// Empty lines are ignored.
+    pub fn from_tbl(tbl: &str) -> Result<Self, CharacterTableParseError> {
+        let mut table = Self::new();
+        for (index, line) in tbl.lines().enumerate() {
+            let line_number = index + 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (code, text) = line
+                .split_once('=')
+                .filter(|(code, text)| !code.is_empty() && code.len() % 2 == 0 && !text.is_empty())
+                .ok_or(CharacterTableParseError::InvalidLine { line: line_number })?;
+            let code = (0..code.len())
+                .step_by(2)
+                .map(|i| {
+                    code.get(i..i + 2)
+                        .ok_or(CharacterTableParseError::InvalidLine { line: line_number })
+                        .and_then(|x| {
+                            u8::from_str_radix(x, 16).map_err(|source| {
+                                CharacterTableParseError::InvalidCode {
+                                    line: line_number,
+                                    source,
+                                }
+                            })
+                        })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            table.insert(code, unescape_tbl_text(text));
+        }
+        Ok(table)
+    }
+
+    /// Serializes the table in the format accepted by [`Self::from_tbl`], sorted by code.
+    pub fn to_tbl(&self) -> String {
+        let mut entries: Vec<_> = self.decoding.iter().collect();
+        entries.sort();
+        let mut tbl = String::new();
+        for (code, text) in entries {
+            for byte in code {
+                write!(tbl, "{:02X}", byte).unwrap();
+            }
+            tbl.push('=');
+            tbl.push_str(&text.replace('\\', "\\\\").replace('\n', "\\n"));
+            tbl.push('\n');
+        }
+        tbl
+    }
+
+    /// Maps `code` to `text` and vice versa.
+    /// Other codes which are mapped to `text` keep decoding to it,
+    /// but `text` will be encoded as `code` from now on.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either `code` or `text` is empty.
+    pub fn insert(&mut self, code: impl Into<Vec<u8>>, text: impl Into<String>) {
+        let (code, text) = (code.into(), text.into());
+        assert!(!code.is_empty() && !text.is_empty());
+        self.max_code_len = self.max_code_len.max(code.len());
+        self.max_text_len = self.max_text_len.max(text.len());
+        if let Some(old_text) = self.decoding.insert(code.clone(), text.clone()) {
+            if self.encoding.get(&old_text) == Some(&code) {
+                self.encoding.remove(&old_text);
+            }
+        }
+        self.encoding.insert(text, code);
+    }
+
+    pub fn get_text(&self, code: &[u8]) -> Option<&str> {
+        self.decoding.get(code).map(String::as_str)
+    }
+    pub fn get_code(&self, text: &str) -> Option<&[u8]> {
+        self.encoding.get(text).map(Vec::as_slice)
+    }
+
+    pub fn decode(&self, data: &[u8]) -> Result<String, TextDecodingError> {
+        let mut result = String::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let (len, text) = (1..=self.max_code_len.min(data.len() - offset))
+                .rev()
+                .find_map(|len| Some((len, self.decoding.get(&data[offset..offset + len])?)))
+                .ok_or(TextDecodingError::UnmappableCode {
+                    offset,
+                    code: data[offset],
+                })?;
+            result.push_str(text);
+            offset += len;
+        }
+        Ok(result)
+    }
+
+    /// `offset` in the returned error is a byte offset into `text`.
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, TextEncodingError> {
+        Ok(self
+            .encode_codes(text)?
+            .into_iter()
+            .flat_map(|(_, code)| code)
+            .copied()
+            .collect())
+    }
+
+    /// Returns the code of each grapheme of `text` along with its byte offset.
+    fn encode_codes<'a>(&'a self, text: &str) -> Result<Vec<(usize, &'a [u8])>, TextEncodingError> {
+        let mut result = Vec::new();
+        let mut offset = 0;
+        while offset < text.len() {
+            let (len, code) = (1..=self.max_text_len.min(text.len() - offset))
+                .rev()
+                .filter(|&len| text.is_char_boundary(offset + len))
+                .find_map(|len| Some((len, self.encoding.get(&text[offset..offset + len])?)))
+                .ok_or_else(|| TextEncodingError::UnmappableCharacter {
+                    offset,
+                    character: text[offset..].chars().next().unwrap(),
+                })?;
+            result.push((offset, code.as_slice()));
+            offset += len;
+        }
+        Ok(result)
+    }
+
+    /// Like [`Self::encode`], but also inserts line and box breaks between words
+    /// so that every line fits into the textbox, according to `options`.
+    ///
+    /// Existing breaks in `text` are kept.
+    pub fn encode_wrapped(
+        &self,
+        text: &str,
+        widths: &impl GlyphWidths,
+        options: &WrapOptions,
+    ) -> Result<WrappedText, TextEncodingError> {
+        let codes = self.encode_codes(text)?;
+        let mut wrapper = Wrapper {
+            options,
+            result: WrappedText::default(),
+            line_width: 0,
+            lines_in_box: 1,
+            line_warned: false,
+        };
+        let mut spaces: Vec<&[u8]> = Vec::new();
+        let mut word: Vec<&[u8]> = Vec::new();
+        let mut word_offset = 0;
+        for (offset, code) in codes {
+            if code == options.space
+                || code == options.line_break
+                || options.box_break.as_deref() == Some(code)
+            {
+                if !word.is_empty() {
+                    wrapper.push_word(widths, &spaces, &word, word_offset);
+                    spaces.clear();
+                    word.clear();
+                }
+                if code == options.space {
+                    spaces.push(code);
+                } else {
+                    spaces
+                        .iter()
+                        .for_each(|x| wrapper.result.data.extend_from_slice(x));
+                    spaces.clear();
+                    wrapper.push_break(code != options.line_break, offset);
+                }
+            } else {
+                if word.is_empty() {
+                    word_offset = offset;
+                }
+                word.push(code);
+            }
+        }
+        if !word.is_empty() {
+            wrapper.push_word(widths, &spaces, &word, word_offset);
+            spaces.clear();
+        }
+        spaces
+            .iter()
+            .for_each(|x| wrapper.result.data.extend_from_slice(x));
+        Ok(wrapper.result)
+    }
+}
+
+/// The widths of the glyphs of a font, in pixels.
+pub trait GlyphWidths {
+    /// Returns the width of the glyph for the character `code`.
+    fn glyph_width(&self, code: &[u8]) -> u32;
+}
+
+impl<F: Fn(&[u8]) -> u32> GlyphWidths for F {
+    #[inline]
+    fn glyph_width(&self, code: &[u8]) -> u32 {
+        self(code)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum WrapMode {
+    /// Insert breaks where necessary.
+    #[default]
+    Wrap,
+    /// Only report where breaks would be necessary.
+    WarnOnly,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WrapOptions {
+    /// In pixels.
+    pub box_width: u32,
+    /// If this and `box_break` are set, box breaks are inserted instead of
+    /// line breaks once a box is full.
+    pub lines_per_box: Option<usize>,
+    pub space: Vec<u8>,
+    pub line_break: Vec<u8>,
+    pub box_break: Option<Vec<u8>>,
+    pub mode: WrapMode,
+}
+
+impl WrapOptions {
+    pub fn new(box_width: u32) -> Self {
+        Self {
+            box_width,
+            lines_per_box: None,
+            space: vec![b' '],
+            line_break: vec![CONTROL_CODE_PREFIX, 0x00],
+            box_break: None,
+            mode: WrapMode::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WrapWarning {
+    /// The line which contains the character at `offset` of the text
+    /// is `width` pixels wide, which is more than the textbox.
+    LineTooWide { offset: usize, width: u32 },
+    /// The line break at `offset` of the text starts a line which doesn't fit into the textbox.
+    TooManyLines { offset: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct WrappedText {
+    pub data: Vec<u8>,
+    pub warnings: Vec<WrapWarning>,
+}
+
+struct Wrapper<'a> {
+    options: &'a WrapOptions,
+    result: WrappedText,
+    line_width: u32,
+    lines_in_box: usize,
+    line_warned: bool,
+}
+
+impl Wrapper<'_> {
+    fn push_word(
+        &mut self,
+        widths: &impl GlyphWidths,
+        spaces: &[&[u8]],
+        word: &[&[u8]],
+        offset: usize,
+    ) {
+        let width = |codes: &[&[u8]]| codes.iter().map(|x| widths.glyph_width(x)).sum::<u32>();
+        let (spaces_width, word_width) = (width(spaces), width(word));
+        if self.options.mode == WrapMode::Wrap
+            && self.line_width > 0
+            && self.line_width + spaces_width + word_width > self.options.box_width
+        {
+            let new_box = self.options.box_break.is_some()
+                && self
+                    .options
+                    .lines_per_box
+                    .is_some_and(|x| self.lines_in_box >= x);
+            self.push_break(new_box, offset);
+        } else {
+            spaces
+                .iter()
+                .for_each(|x| self.result.data.extend_from_slice(x));
+            self.line_width += spaces_width;
+        }
+        word.iter()
+            .for_each(|x| self.result.data.extend_from_slice(x));
+        self.line_width += word_width;
+        if self.line_width > self.options.box_width && !self.line_warned {
+            self.result.warnings.push(WrapWarning::LineTooWide {
+                offset,
+                width: self.line_width,
+            });
+            self.line_warned = true;
+        }
+    }
+
+    fn push_break(&mut self, new_box: bool, offset: usize) {
+        if new_box {
+            self.result
+                .data
+                .extend_from_slice(self.options.box_break.as_deref().unwrap());
+            self.lines_in_box = 1;
+        } else {
+            self.result.data.extend_from_slice(&self.options.line_break);
+            self.lines_in_box += 1;
+            if self
+                .options
+                .lines_per_box
+                .is_some_and(|x| self.lines_in_box > x)
+            {
+                self.result
+                    .warnings
+                    .push(WrapWarning::TooManyLines { offset });
+            }
+        }
+        self.line_width = 0;
+        self.line_warned = false;
+    }
+}
+
+fn unescape_tbl_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(character) = chars.next() {
+        match character {
+            '\\' => match chars.next() {
+                Some('n') => result.push('\n'),
+                Some(other) => result.push(other),
+                None => result.push('\\'),
+            },
+            _ => result.push(character),
+        }
+    }
+    result
+}
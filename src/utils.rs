@@ -1,3 +1,8 @@
+use std::{
+    hash::Hasher,
+    io::{self, Write},
+};
+
 #[inline]
 pub fn none_if_empty<I, T: AsRef<[I]>>(value: T) -> Option<T> {
     if value.as_ref().is_empty() {
@@ -16,6 +21,37 @@ pub fn necessary_padding_for(number: usize, alignment: usize) -> usize {
     (alignment - number % alignment) % alignment
 }
 
+/// Large enough for [`STANDARD_FILE_ALIGNMENT`](crate::consts::STANDARD_FILE_ALIGNMENT)
+/// padding to be written at once.
+static ZEROS: [u8; 0x200] = [0; 0x200];
+
+/// Writes `count` zero bytes, without allocating a buffer for them.
+pub fn write_zeros(mut out: impl Write, mut count: usize) -> io::Result<()> {
+    while count > 0 {
+        let len = count.min(ZEROS.len());
+        out.write_all(&ZEROS[..len])?;
+        count -= len;
+    }
+    Ok(())
+}
+
+/// A writer which discards everything written to it, only counting the bytes,
+/// for computing how large some output would be without producing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ByteCounter(pub usize);
+
+impl Write for ByteCounter {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 pub trait AlignToElements {
     fn align_to_elements(&mut self, alignment: usize);
 }
@@ -54,3 +90,131 @@ pub fn option_to_u32_or_max_try_into<T: TryInto<u32>>(value: Option<T>) -> Resul
         value.map(|x| x.try_into()).transpose()?,
     ))
 }
+
+/// A 64-bit FNV-1a hasher, whose output is stable across platforms and Rust versions,
+/// unlike [`std::hash::DefaultHasher`]'s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fnv1aHasher(u64);
+
+impl Fnv1aHasher {
+    const OFFSET_BASIS: u64 = 0xCBF29CE484222325;
+    const PRIME: u64 = 0x00000100000001B3;
+}
+impl Default for Fnv1aHasher {
+    #[inline]
+    fn default() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+}
+impl Hasher for Fnv1aHasher {
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0
+    }
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(Self::PRIME);
+        }
+    }
+}
+
+/// Hashes `data` with [`Fnv1aHasher`], for detecting changes between versions of a file.
+#[inline]
+pub fn hash_bytes(data: &[u8]) -> u64 {
+    let mut hasher = Fnv1aHasher::default();
+    hasher.write(data);
+    hasher.finish()
+}
+
+/// Returns the indexes at which `old` and `new` differ,
+/// including those only present in one of them.
+///
+/// Elements are compared by position, so inserting or removing one
+/// changes every index after it unless the following elements are equal.
+pub fn changed_indexes<T: PartialEq>(old: &[T], new: &[T]) -> Vec<usize> {
+    (0..old.len().max(new.len()))
+        .filter(|&i| old.get(i) != new.get(i))
+        .collect()
+}
+
+/// Describes how the indexes of a list's elements changed
+/// after an element was inserted or removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IndexRemap {
+    Inserted(usize),
+    Removed(usize),
+}
+
+impl IndexRemap {
+    /// Returns the new index of the element previously at `index`,
+    /// or `None` if it was the removed one.
+    #[inline]
+    pub fn apply(self, index: usize) -> Option<usize> {
+        match self {
+            Self::Inserted(inserted) if index >= inserted => Some(index + 1),
+            Self::Removed(removed) if index == removed => None,
+            Self::Removed(removed) if index > removed => Some(index - 1),
+            _ => Some(index),
+        }
+    }
+}
+
+/// Bytes as space-separated hexadecimal, which is more compact than arrays.
+#[cfg(feature = "serde")]
+pub(crate) mod hex_bytes {
+    use itertools::Itertools;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer, T: AsRef<[u8]> + ?Sized>(
+        data: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&data.as_ref().iter().map(|x| format!("{x:02X}")).join(" "))
+    }
+    /// Also deserializes into arrays, which must have the right length.
+    pub fn deserialize<'de, D: Deserializer<'de>, T: TryFrom<Vec<u8>>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let digits: Vec<char> = String::deserialize(deserializer)?
+            .chars()
+            .filter(|x| !x.is_whitespace())
+            .collect();
+        if !digits.len().is_multiple_of(2) {
+            return Err(D::Error::custom("odd number of hexadecimal digits"));
+        }
+        digits
+            .chunks_exact(2)
+            .map(|x| {
+                u8::from_str_radix(&x.iter().collect::<String>(), 16)
+                    .map_err(|_| D::Error::custom("invalid hexadecimal byte"))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .try_into()
+            .map_err(|_| D::Error::custom("wrong number of bytes"))
+    }
+}
+/// A list of [`hex_bytes`].
+#[cfg(feature = "serde")]
+pub(crate) mod hex_chunks {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize)]
+    #[serde(transparent)]
+    struct ChunkRef<'a>(#[serde(with = "super::hex_bytes")] &'a [u8]);
+    #[derive(Deserialize)]
+    #[serde(transparent)]
+    struct Chunk(#[serde(with = "super::hex_bytes")] Vec<u8>);
+
+    pub fn serialize<S: Serializer>(chunks: &[Vec<u8>], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(chunks.iter().map(|x| ChunkRef(x)))
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Vec<u8>>, D::Error> {
+        Ok(Vec::<Chunk>::deserialize(deserializer)?
+            .into_iter()
+            .map(|x| x.0)
+            .collect())
+    }
+}
@@ -0,0 +1,85 @@
+use std::{borrow::Cow, io};
+
+#[cfg(any(feature = "fs", feature = "zip"))]
+use crate::misc::ProjectPaths;
+use crate::rom::NdsRom;
+#[cfg(feature = "zip")]
+use crate::zip::{path_to_name, ZipArchive, ZipError};
+#[cfg(feature = "fs")]
+use std::fs;
+#[cfg(feature = "zip")]
+use std::path::PathBuf;
+
+/// Read access to the files of a game, wherever they're stored,
+/// so that subsystems can be loaded the same way from each.
+pub trait Vfs {
+    /// `path` is relative to the root of the game's filesystem,
+    /// e.g. `FMap/FMapData.dat`.
+    fn read_file(&self, path: &str) -> io::Result<Cow<'_, [u8]>>;
+    /// Returns the decompressed data of an ARM9 overlay.
+    fn read_overlay(&self, overlay_number: u32) -> io::Result<Cow<'_, [u8]>>;
+}
+
+/// An extracted dump in a directory.
+#[cfg(feature = "fs")]
+impl Vfs for ProjectPaths {
+    fn read_file(&self, path: &str) -> io::Result<Cow<'_, [u8]>> {
+        fs::read(self.data_path(path)).map(Cow::Owned)
+    }
+    fn read_overlay(&self, overlay_number: u32) -> io::Result<Cow<'_, [u8]>> {
+        fs::read(self.overlay_path(overlay_number)).map(Cow::Owned)
+    }
+}
+
+impl Vfs for NdsRom {
+    fn read_file(&self, path: &str) -> io::Result<Cow<'_, [u8]>> {
+        self.file(path).map(Cow::Borrowed)
+    }
+    fn read_overlay(&self, overlay_number: u32) -> io::Result<Cow<'_, [u8]>> {
+        self.overlay(overlay_number)
+    }
+}
+
+/// An extracted dump inside of a ZIP archive.
+#[cfg(feature = "zip")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ZipVfs {
+    pub archive: ZipArchive,
+    /// Where the files are inside of the archive, with `/` as the separator.
+    /// The root is usually empty.
+    pub paths: ProjectPaths,
+}
+
+#[cfg(feature = "zip")]
+impl ZipVfs {
+    /// Uses the standard layout at the root of the archive.
+    pub fn new(archive: ZipArchive) -> Self {
+        Self {
+            archive,
+            paths: ProjectPaths::new(""),
+        }
+    }
+
+    fn read(&self, path: PathBuf) -> io::Result<Cow<'_, [u8]>> {
+        let name = path_to_name(&path);
+        match self.archive.file(&name) {
+            Ok(Some(data)) => Ok(Cow::Owned(data)),
+            Ok(None) => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no file {name:?} in archive"),
+            )),
+            Err(ZipError::Io(error)) => Err(error),
+            Err(error) => Err(io::Error::new(io::ErrorKind::InvalidData, error)),
+        }
+    }
+}
+
+#[cfg(feature = "zip")]
+impl Vfs for ZipVfs {
+    fn read_file(&self, path: &str) -> io::Result<Cow<'_, [u8]>> {
+        self.read(self.paths.data_path(path))
+    }
+    fn read_overlay(&self, overlay_number: u32) -> io::Result<Cow<'_, [u8]>> {
+        self.read(self.paths.overlay_path(overlay_number))
+    }
+}
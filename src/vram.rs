@@ -0,0 +1,143 @@
+//! Arranging field maps the way the DS's 2D engine reads them from VRAM,
+//! to compare with the memory viewers of emulators.
+
+use thiserror::Error;
+
+use crate::{
+    map::{FieldMapChunk, PixelSize, Tileset, TilesetTileSerializationError},
+    utils::necessary_padding_for,
+};
+
+/// Character (tile) data starts at multiples of this.
+pub const CHARACTER_BASE_BLOCK_SIZE: usize = 0x4000;
+/// Screen (tile map) data starts at multiples of this,
+/// and every block holds [`SCREEN_BLOCK_TILES`] by [`SCREEN_BLOCK_TILES`] tiles.
+pub const SCREEN_BASE_BLOCK_SIZE: usize = 0x800;
+pub const SCREEN_BLOCK_TILES: usize = 32;
+/// 16 palettes of 256 colors.
+pub const EXTENDED_PALETTE_SLOT_SIZE: usize = 0x2000;
+
+/// Where a layer of a [`VramDump`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VramLayer {
+    /// The offset of the tileset into [`VramDump::bg_vram`].
+    pub character_base: usize,
+    /// The offset of the tile layer into [`VramDump::bg_vram`].
+    pub screen_base: usize,
+    /// The size of the tile layer, in [`SCREEN_BLOCK_TILES`] by
+    /// [`SCREEN_BLOCK_TILES`] blocks, as (columns, rows).
+    pub screen_blocks: (usize, usize),
+    pub pixel_size: PixelSize,
+}
+
+/// The tilesets, tile layers and palettes of a field map,
+/// laid out as BG VRAM and BG extended palette slots.
+///
+/// Where the game puts each layer isn't known, so the tilesets start at the first free
+/// character base blocks, followed by the tile layers at the next free screen base
+/// blocks, which are recorded in [`Self::layers`]. Since every layer has its own
+/// 256-color palette, palette `n` is put into extended palette slot `n`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VramDump {
+    /// Mapped at `0x06000000`.
+    pub bg_vram: Vec<u8>,
+    /// [`EXTENDED_PALETTE_SLOT_SIZE`] bytes per slot.
+    pub bg_extended_palettes: Vec<u8>,
+    /// `None` for layers without a tile layer or tileset.
+    pub layers: [Option<VramLayer>; 3],
+}
+
+#[derive(Error, Debug)]
+pub enum VramDumpError {
+    #[error("palette {layer} has {len} colors, but a slot fits only {max}", max = EXTENDED_PALETTE_SLOT_SIZE / 2)]
+    PaletteTooLarge { layer: usize, len: usize },
+    #[error("failed to serialize tileset {layer}")]
+    TilesetSerialization {
+        layer: usize,
+        #[source]
+        source: TilesetTileSerializationError,
+    },
+}
+
+impl VramDump {
+    pub fn new(
+        map_chunk: &FieldMapChunk,
+        tilesets: [Option<&Tileset>; 3],
+    ) -> Result<Self, VramDumpError> {
+        let pixel_sizes = map_chunk
+            .properties
+            .tilesets_properties
+            .tileset_pixel_sizes();
+        let mut bg_vram = Vec::new();
+        let mut layers: [Option<VramLayer>; 3] = [None; 3];
+
+        for layer in 0..3 {
+            let (Some(_), Some(tileset)) = (&map_chunk.tile_layers[layer], tilesets[layer]) else {
+                continue;
+            };
+            let character_base = bg_vram.len();
+            bg_vram.extend(
+                tileset
+                    .to_bytes(pixel_sizes[layer])
+                    .map_err(|source| VramDumpError::TilesetSerialization { layer, source })?,
+            );
+            bg_vram.resize(
+                bg_vram.len() + necessary_padding_for(bg_vram.len(), CHARACTER_BASE_BLOCK_SIZE),
+                0,
+            );
+            layers[layer] = Some(VramLayer {
+                character_base,
+                screen_base: 0,
+                screen_blocks: (0, 0),
+                pixel_size: pixel_sizes[layer],
+            });
+        }
+        for (tile_layer, vram_layer) in map_chunk.tile_layers.iter().zip(&mut layers) {
+            let (Some(tile_layer), Some(vram_layer)) = (tile_layer, vram_layer) else {
+                continue;
+            };
+            let screen_blocks = (
+                tile_layer.cols().div_ceil(SCREEN_BLOCK_TILES),
+                tile_layer.rows().div_ceil(SCREEN_BLOCK_TILES),
+            );
+            vram_layer.screen_base = bg_vram.len();
+            vram_layer.screen_blocks = screen_blocks;
+            // Blocks are in row-major order, like those of 512x512 pixel backgrounds.
+            for block_row in 0..screen_blocks.1 {
+                for block_col in 0..screen_blocks.0 {
+                    for row in 0..SCREEN_BLOCK_TILES {
+                        for col in 0..SCREEN_BLOCK_TILES {
+                            let tile = tile_layer.get(
+                                block_row * SCREEN_BLOCK_TILES + row,
+                                block_col * SCREEN_BLOCK_TILES + col,
+                            );
+                            bg_vram.extend(tile.map_or([0; 2], |x| x.into_bits().to_le_bytes()));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut bg_extended_palettes = vec![0; EXTENDED_PALETTE_SLOT_SIZE * 3];
+        for (layer, palette) in map_chunk.palettes.iter().enumerate() {
+            let Some(palette) = palette else {
+                continue;
+            };
+            let data = palette.to_bytes();
+            if data.len() > EXTENDED_PALETTE_SLOT_SIZE {
+                return Err(VramDumpError::PaletteTooLarge {
+                    layer,
+                    len: palette.0.len(),
+                });
+            }
+            bg_extended_palettes[layer * EXTENDED_PALETTE_SLOT_SIZE..][..data.len()]
+                .copy_from_slice(&data);
+        }
+
+        Ok(Self {
+            bg_vram,
+            bg_extended_palettes,
+            layers,
+        })
+    }
+}
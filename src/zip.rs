@@ -0,0 +1,257 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Read, Write},
+    path::{Component, Path},
+};
+
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use thiserror::Error;
+
+use crate::patch::crc32;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034B50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014B50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054B50;
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+const LOCAL_HEADER_SIZE: usize = 30;
+const CENTRAL_HEADER_SIZE: usize = 46;
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+/// 1980-01-01, the earliest date which can be stored.
+const DOS_DATE: u16 = 0x0021;
+/// Marks names as UTF-8.
+const FLAG_UTF8: u16 = 1 << 11;
+
+/// The name of `path` inside of an archive, with `/` as the separator.
+///
+/// Anything but normal components, such as `..`, is left out.
+pub(crate) fn path_to_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|x| match x {
+            Component::Normal(x) => Some(x.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[derive(Error, Debug)]
+pub enum ZipError {
+    #[error("the end of central directory record is missing")]
+    MissingEndOfCentralDirectory,
+    #[error("the central directory is truncated or corrupted")]
+    InvalidCentralDirectory,
+    #[error("the local header of {0:?} is truncated or corrupted")]
+    InvalidLocalHeader(String),
+    #[error("{name:?} uses the unsupported compression method {method}")]
+    UnsupportedCompressionMethod { name: String, method: u16 },
+    #[error("the checksum of {0:?} doesn't match")]
+    ChecksumMismatch(String),
+    #[error("the archive is too large for ZIP64-less archives")]
+    TooLarge,
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct ZipEntry {
+    method: u16,
+    crc32: u32,
+    compressed_size: usize,
+    uncompressed_size: usize,
+    local_header_offset: usize,
+}
+
+/// A ZIP archive in memory, of which files are decompressed on demand.
+///
+/// Only stored and deflated files are supported, and no ZIP64.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ZipArchive {
+    data: Vec<u8>,
+    entries: BTreeMap<String, ZipEntry>,
+}
+
+impl ZipArchive {
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, ZipError> {
+        let end = (0..=data.len().saturating_sub(END_OF_CENTRAL_DIRECTORY_SIZE))
+            .rev()
+            .take(usize::from(u16::MAX) + 1)
+            .find(|&i| {
+                data.len() - i >= END_OF_CENTRAL_DIRECTORY_SIZE
+                    && LittleEndian::read_u32(&data[i..]) == END_OF_CENTRAL_DIRECTORY_SIGNATURE
+            })
+            .ok_or(ZipError::MissingEndOfCentralDirectory)?;
+        let num_entries = LittleEndian::read_u16(&data[end + 10..]);
+        let mut offset = LittleEndian::read_u32(&data[end + 16..]) as usize;
+
+        let mut entries = BTreeMap::new();
+        for _ in 0..num_entries {
+            let header = data
+                .get(offset..offset + CENTRAL_HEADER_SIZE)
+                .filter(|x| LittleEndian::read_u32(x) == CENTRAL_HEADER_SIGNATURE)
+                .ok_or(ZipError::InvalidCentralDirectory)?;
+            let name_len = usize::from(LittleEndian::read_u16(&header[28..]));
+            let extra_len = usize::from(LittleEndian::read_u16(&header[30..]));
+            let comment_len = usize::from(LittleEndian::read_u16(&header[32..]));
+            let name_start = offset + CENTRAL_HEADER_SIZE;
+            let name = data
+                .get(name_start..name_start + name_len)
+                .ok_or(ZipError::InvalidCentralDirectory)?;
+            let entry = ZipEntry {
+                method: LittleEndian::read_u16(&header[10..]),
+                crc32: LittleEndian::read_u32(&header[16..]),
+                compressed_size: LittleEndian::read_u32(&header[20..]) as usize,
+                uncompressed_size: LittleEndian::read_u32(&header[24..]) as usize,
+                local_header_offset: LittleEndian::read_u32(&header[42..]) as usize,
+            };
+            // Directories don't need to be listed.
+            let name = String::from_utf8_lossy(name).into_owned();
+            if !name.ends_with('/') {
+                entries.insert(name, entry);
+            }
+            offset = name_start + name_len + extra_len + comment_len;
+        }
+        Ok(Self { data, entries })
+    }
+
+    /// The names of all files, in order.
+    pub fn file_names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+    #[inline]
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    /// Decompresses the file `name`, returning `None` if there's no such file.
+    pub fn file(&self, name: &str) -> Result<Option<Vec<u8>>, ZipError> {
+        let Some(entry) = self.entries.get(name) else {
+            return Ok(None);
+        };
+        let invalid_local_header = || ZipError::InvalidLocalHeader(name.to_owned());
+        let offset = entry.local_header_offset;
+        let header = self
+            .data
+            .get(offset..offset + LOCAL_HEADER_SIZE)
+            .filter(|x| LittleEndian::read_u32(x) == LOCAL_HEADER_SIGNATURE)
+            .ok_or_else(invalid_local_header)?;
+        let data_start = offset
+            + LOCAL_HEADER_SIZE
+            + usize::from(LittleEndian::read_u16(&header[26..]))
+            + usize::from(LittleEndian::read_u16(&header[28..]));
+        let compressed = self
+            .data
+            .get(data_start..data_start + entry.compressed_size)
+            .ok_or_else(invalid_local_header)?;
+
+        let data = match entry.method {
+            METHOD_STORED => compressed.to_vec(),
+            METHOD_DEFLATED => {
+                let mut data = Vec::with_capacity(entry.uncompressed_size);
+                DeflateDecoder::new(compressed).read_to_end(&mut data)?;
+                data
+            }
+            method => {
+                return Err(ZipError::UnsupportedCompressionMethod {
+                    name: name.to_owned(),
+                    method,
+                })
+            }
+        };
+        if data.len() != entry.uncompressed_size || crc32(&data) != entry.crc32 {
+            return Err(ZipError::ChecksumMismatch(name.to_owned()));
+        }
+        Ok(Some(data))
+    }
+}
+
+/// Writes a ZIP archive, deflating files which get smaller that way.
+#[derive(Debug)]
+pub struct ZipWriter<W: Write> {
+    out: W,
+    offset: usize,
+    central_directory: Vec<u8>,
+    num_entries: u16,
+}
+
+impl<W: Write> ZipWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            offset: 0,
+            central_directory: Vec::new(),
+            num_entries: 0,
+        }
+    }
+
+    /// `name` uses `/` as the separator.
+    pub fn add_file(&mut self, name: &str, data: &[u8]) -> Result<(), ZipError> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        let deflated = encoder.finish()?;
+        let (method, stored) = if deflated.len() < data.len() {
+            (METHOD_DEFLATED, &deflated[..])
+        } else {
+            (METHOD_STORED, data)
+        };
+        let checksum = crc32(data);
+        let too_large = |_| ZipError::TooLarge;
+        let (compressed_size, uncompressed_size, offset, name_len): (u32, u32, u32, u16) = (
+            stored.len().try_into().map_err(too_large)?,
+            data.len().try_into().map_err(too_large)?,
+            self.offset.try_into().map_err(too_large)?,
+            name.len().try_into().map_err(too_large)?,
+        );
+        self.num_entries = self.num_entries.checked_add(1).ok_or(ZipError::TooLarge)?;
+
+        // The fields shared by the local and central headers.
+        let mut common = Vec::with_capacity(26);
+        common.write_u16::<LittleEndian>(20)?; // Version needed to extract.
+        common.write_u16::<LittleEndian>(FLAG_UTF8)?;
+        common.write_u16::<LittleEndian>(method)?;
+        common.write_u16::<LittleEndian>(0)?; // Time.
+        common.write_u16::<LittleEndian>(DOS_DATE)?;
+        common.write_u32::<LittleEndian>(checksum)?;
+        common.write_u32::<LittleEndian>(compressed_size)?;
+        common.write_u32::<LittleEndian>(uncompressed_size)?;
+        common.write_u16::<LittleEndian>(name_len)?;
+        common.write_u16::<LittleEndian>(0)?; // Extra field length.
+
+        self.out.write_u32::<LittleEndian>(LOCAL_HEADER_SIGNATURE)?;
+        self.out.write_all(&common)?;
+        self.out.write_all(name.as_bytes())?;
+        self.out.write_all(stored)?;
+        self.offset += LOCAL_HEADER_SIZE + name.len() + stored.len();
+
+        let central = &mut self.central_directory;
+        central.write_u32::<LittleEndian>(CENTRAL_HEADER_SIGNATURE)?;
+        central.write_u16::<LittleEndian>(20)?; // Version made by.
+        central.write_all(&common)?;
+        central.write_u16::<LittleEndian>(0)?; // Comment length.
+        central.write_u16::<LittleEndian>(0)?; // Disk number.
+        central.write_u16::<LittleEndian>(0)?; // Internal attributes.
+        central.write_u32::<LittleEndian>(0)?; // External attributes.
+        central.write_u32::<LittleEndian>(offset)?;
+        central.write_all(name.as_bytes())?;
+        Ok(())
+    }
+
+    /// Writes the central directory, and returns the writer.
+    pub fn finish(mut self) -> Result<W, ZipError> {
+        let too_large = |_| ZipError::TooLarge;
+        let size: u32 = self.central_directory.len().try_into().map_err(too_large)?;
+        let offset: u32 = self.offset.try_into().map_err(too_large)?;
+        self.out.write_all(&self.central_directory)?;
+        self.out
+            .write_u32::<LittleEndian>(END_OF_CENTRAL_DIRECTORY_SIGNATURE)?;
+        self.out.write_u16::<LittleEndian>(0)?; // Disk number.
+        self.out.write_u16::<LittleEndian>(0)?; // Disk with the central directory.
+        self.out.write_u16::<LittleEndian>(self.num_entries)?;
+        self.out.write_u16::<LittleEndian>(self.num_entries)?;
+        self.out.write_u32::<LittleEndian>(size)?;
+        self.out.write_u32::<LittleEndian>(offset)?;
+        self.out.write_u16::<LittleEndian>(0)?; // Comment length.
+        Ok(self.out)
+    }
+}
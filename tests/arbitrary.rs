@@ -0,0 +1,36 @@
+#![cfg(feature = "arbitrary")]
+
+use arbitrary::{Arbitrary, Unstructured};
+use mnllib::{map::FieldMapChunk, misc::DataWithOffsetTable};
+
+fn pseudo_random_bytes(seed: u64, len: usize) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 56) as u8
+        })
+        .collect()
+}
+
+#[test]
+fn round_trip_arbitrary_field_map_chunks() {
+    for seed in 0..64 {
+        let data = pseudo_random_bytes(seed, 4096);
+        let chunk = FieldMapChunk::arbitrary(&mut Unstructured::new(&data)).unwrap();
+
+        let mut serialized = Vec::new();
+        DataWithOffsetTable::try_from(chunk.clone())
+            .unwrap()
+            .to_writer(&mut serialized, None, true)
+            .unwrap();
+
+        assert_eq!(
+            FieldMapChunk::try_from(DataWithOffsetTable::from_reader(&serialized[..]).unwrap())
+                .unwrap(),
+            chunk
+        );
+    }
+}
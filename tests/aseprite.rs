@@ -0,0 +1,219 @@
+#![cfg(feature = "aseprite")]
+
+use mnllib::{
+    aseprite::{AsepriteCelContent, AsepriteError, AsepriteFile, AsepriteLayerKind},
+    map::Tileset,
+    misc::Palette,
+    sprites::{ImportOptions, Sprite, SpriteCell},
+};
+use rgb::Rgba;
+
+fn chunk(chunk_type: u16, data: &[u8]) -> Vec<u8> {
+    [
+        &(data.len() as u32 + 6).to_le_bytes()[..],
+        &chunk_type.to_le_bytes(),
+        data,
+    ]
+    .concat()
+}
+fn frame(duration: u16, chunks: &[Vec<u8>]) -> Vec<u8> {
+    let data = chunks.concat();
+    [
+        &(data.len() as u32 + 16).to_le_bytes()[..],
+        &0xF1FAu16.to_le_bytes(),
+        &(chunks.len() as u16).to_le_bytes(),
+        &duration.to_le_bytes(),
+        &[0, 0],
+        &(chunks.len() as u32).to_le_bytes(),
+        &data,
+    ]
+    .concat()
+}
+fn layer(flags: u16, name: &str) -> Vec<u8> {
+    let mut data = [flags, 0, 0, 0, 0, 0]
+        .iter()
+        .flat_map(|x| x.to_le_bytes())
+        .collect::<Vec<_>>();
+    data.extend([0xFF, 0, 0, 0]);
+    data.extend((name.len() as u16).to_le_bytes());
+    data.extend(name.as_bytes());
+    chunk(0x2004, &data)
+}
+fn cel(layer: u16, x: i16, y: i16, cel_type: u16, data: &[u8]) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend(layer.to_le_bytes());
+    header.extend(x.to_le_bytes());
+    header.extend(y.to_le_bytes());
+    header.push(0xFF);
+    header.extend(cel_type.to_le_bytes());
+    header.extend([0; 7]);
+    chunk(0x2005, &[&header[..], data].concat())
+}
+fn image_cel_data(width: u16, height: u16, pixels: &[u8]) -> Vec<u8> {
+    [&width.to_le_bytes()[..], &height.to_le_bytes(), pixels].concat()
+}
+/// A zlib stream with a single uncompressed block.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    let len = data.len() as u16;
+    [
+        &[0x78, 0x01, 0x01][..],
+        &len.to_le_bytes(),
+        &(!len).to_le_bytes(),
+        data,
+        &((b << 16) | a).to_be_bytes(),
+    ]
+    .concat()
+}
+
+/// An indexed 16x8 image with a background layer, a hidden layer and a top layer,
+/// and 2 frames.
+fn test_file() -> Vec<u8> {
+    let mut header = vec![0u8; 128];
+    header[4..6].copy_from_slice(&0xA5E0u16.to_le_bytes());
+    header[6..8].copy_from_slice(&2u16.to_le_bytes());
+    header[8..10].copy_from_slice(&16u16.to_le_bytes());
+    header[10..12].copy_from_slice(&8u16.to_le_bytes());
+    header[12..14].copy_from_slice(&8u16.to_le_bytes());
+
+    let mut palette = [4u32, 0, 3]
+        .iter()
+        .flat_map(|x| x.to_le_bytes())
+        .collect::<Vec<_>>();
+    palette.extend([0; 8]);
+    for color in [
+        [0, 0, 0, 0],
+        [0xF8, 0, 0, 0xFF],
+        [0, 0xF8, 0, 0xFF],
+        [0, 0, 0xF8, 0xFF],
+    ] {
+        palette.extend([0, 0]);
+        palette.extend(color);
+    }
+
+    let mut background = vec![1u8; 16 * 8];
+    background[0] = 0;
+    let mut top = vec![2u8; 8 * 8];
+    top[63] = 0;
+    let frames = [
+        frame(
+            100,
+            &[
+                chunk(0x2019, &palette),
+                layer(1, "background"),
+                layer(0, "hidden"),
+                layer(1, "top"),
+                cel(0, 0, 0, 0, &image_cel_data(16, 8, &background)),
+                cel(1, 0, 0, 0, &image_cel_data(16, 8, &[3; 16 * 8])),
+                cel(2, 8, 0, 2, &image_cel_data(8, 8, &zlib_stored(&top))[..]),
+            ],
+        ),
+        frame(
+            50,
+            &[
+                cel(0, 0, 0, 1, &0u16.to_le_bytes()),
+                cel(2, -1, -1, 0, &image_cel_data(2, 2, &[3; 4])),
+            ],
+        ),
+    ];
+    [header, frames.concat()].concat()
+}
+
+#[test]
+fn read_aseprite_file() {
+    let file = AsepriteFile::from_reader(&test_file()[..]).unwrap();
+    assert_eq!((file.width, file.height), (16, 8));
+    assert_eq!(file.palette.len(), 4);
+    assert_eq!(file.palette[1], Rgba::new(0xF8, 0, 0, 0xFF));
+    assert_eq!(
+        file.layers.iter().map(|x| &x.name[..]).collect::<Vec<_>>(),
+        ["background", "hidden", "top"]
+    );
+    assert!(file
+        .layers
+        .iter()
+        .all(|x| x.kind == AsepriteLayerKind::Image));
+    assert!(!file.layers[1].is_visible());
+    assert_eq!(
+        file.frames.iter().map(|x| x.duration).collect::<Vec<_>>(),
+        [100, 50]
+    );
+    assert_eq!(
+        file.frames[1].cels[0].content,
+        AsepriteCelContent::Linked(0)
+    );
+
+    let (transparent, red, green, blue) = (
+        Rgba::new(0, 0, 0, 0),
+        file.palette[1],
+        file.palette[2],
+        file.palette[3],
+    );
+    let image = file.render_frame(0).unwrap();
+    assert_eq!(image.size(), (8, 16));
+    assert_eq!(image[(0, 0)], transparent);
+    assert_eq!(image[(0, 1)], red);
+    assert_eq!(image[(0, 8)], green);
+    assert_eq!(image[(7, 15)], red);
+    let image = file.render_frame(1).unwrap();
+    assert_eq!(image[(0, 0)], blue);
+    assert_eq!(image[(0, 1)], red);
+    assert_eq!(image[(0, 8)], red);
+    assert!(matches!(
+        file.render_frame(2),
+        Err(AsepriteError::MissingFrame(2))
+    ));
+}
+
+#[test]
+fn import_aseprite_file() {
+    let file = AsepriteFile::from_reader(&test_file()[..]).unwrap();
+    let palette = file.to_palette();
+
+    let Tileset(tiles) = file.frame_to_tileset(0, &palette).unwrap();
+    assert_eq!(tiles.len(), 2);
+    assert_eq!(&tiles[0].0[..2], &[0, 1]);
+    assert!(tiles[1].0[..63].iter().all(|&x| x == 2));
+    assert_eq!(tiles[1].0[63], 1);
+
+    let mut sprite = Sprite {
+        tileset: Tileset(Vec::new()),
+        palette: Palette(
+            palette
+                .0
+                .iter()
+                .copied()
+                .chain(std::iter::repeat(palette.0[0]))
+                .take(16)
+                .collect(),
+        ),
+        cells: vec![SpriteCell::default(); 3],
+        animations: Vec::new(),
+    };
+    file.import_into_sprite(&mut sprite, 1, (0, 0), &ImportOptions::default())
+        .unwrap();
+    assert!(sprite.cells[0].objects.is_empty());
+    assert_eq!(sprite.cells[1].objects.len(), 2);
+    assert_eq!(sprite.cells[2].objects.len(), 2);
+    assert_eq!(sprite.tileset.0.len(), 4);
+}
+
+#[test]
+fn read_invalid_aseprite_file() {
+    let mut data = test_file();
+    data[4] = 0;
+    assert!(matches!(
+        AsepriteFile::from_reader(&data[..]),
+        Err(AsepriteError::InvalidMagic(0xA500))
+    ));
+    data = test_file();
+    data[12] = 24;
+    assert!(matches!(
+        AsepriteFile::from_reader(&data[..]),
+        Err(AsepriteError::UnsupportedColorDepth(24))
+    ));
+}
@@ -0,0 +1,278 @@
+use mnllib::audio::{
+    Sseq, SseqDeserializationError, SseqEvent, Swar, SwarDeserializationError, Swav,
+    WavDeserializationError, WaveEncoding,
+};
+
+fn note(key: u8, duration: u32) -> SseqEvent {
+    SseqEvent::Note {
+        key,
+        velocity: 100,
+        duration,
+    }
+}
+
+fn notes(sseq: &Sseq) -> Vec<(u8, u32)> {
+    sseq.events
+        .iter()
+        .filter_map(|(_, event)| match *event {
+            SseqEvent::Note { key, duration, .. } => Some((key, duration)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn rebuild_sseq() {
+    let mut sseq = Sseq::from_tracks(&[
+        vec![
+            SseqEvent::Tempo(120),
+            SseqEvent::ProgramChange(200),
+            note(60, 0x1234),
+            SseqEvent::Random {
+                command: 0x40,
+                velocity: Some(90),
+                min: -5,
+                max: 10,
+            },
+            SseqEvent::FromVariable {
+                command: SseqEvent::PAN,
+                velocity: None,
+                variable: 3,
+            },
+            SseqEvent::Variable {
+                command: 0xB0,
+                variable: 3,
+                value: -1,
+            },
+            SseqEvent::If,
+            SseqEvent::Short {
+                command: 0xE3,
+                value: 300,
+            },
+            SseqEvent::EndOfTrack,
+        ],
+        vec![SseqEvent::Rest(96), SseqEvent::EndOfTrack],
+    ])
+    .unwrap();
+    sseq.padding = vec![0; 2];
+    assert_eq!(sseq.events[0].1, SseqEvent::AllocateTracks(0b11));
+    assert!(matches!(
+        sseq.events[1].1,
+        SseqEvent::OpenTrack { track: 1, offset } if sseq.events.iter().any(|x| x.0 == offset && x.1 == SseqEvent::Rest(96))
+    ));
+
+    let data = sseq.to_bytes().unwrap();
+    assert_eq!(&data[..4], b"SSEQ");
+    let parsed = Sseq::from_bytes(&data).unwrap();
+    assert_eq!(parsed, sseq);
+    assert_eq!(parsed.to_bytes().unwrap(), data);
+
+    let mut invalid = data.clone();
+    invalid.truncate(data.len() - 2);
+    invalid.push(0xF0);
+    assert!(matches!(
+        Sseq::from_bytes(&invalid),
+        Err(SseqDeserializationError::UnknownCommand { command: 0xF0, .. })
+    ));
+}
+
+#[test]
+fn sseq_to_midi() {
+    // Offsets: 0, 3, 5, 8, 9, 13, 14, 17.
+    let sseq = Sseq::from_tracks(&[vec![
+        SseqEvent::Tempo(150),
+        SseqEvent::Byte {
+            command: SseqEvent::LOOP_START,
+            value: 2,
+        },
+        note(60, 24),
+        SseqEvent::LoopEnd,
+        SseqEvent::Call(14),
+        SseqEvent::EndOfTrack,
+        note(64, 48),
+        SseqEvent::Return,
+    ]])
+    .unwrap();
+    assert_eq!(sseq.events[6], (14, note(64, 48)));
+
+    let midi = sseq.to_midi().unwrap();
+    assert_eq!(&midi[..4], b"MThd");
+    let imported = Sseq::from_midi(&midi).unwrap();
+    assert_eq!(notes(&imported), [(60, 24), (60, 24), (64, 48)]);
+    assert!(imported
+        .events
+        .iter()
+        .any(|(_, x)| *x == SseqEvent::Tempo(150)));
+    // Notes wait for each other by default.
+    assert_eq!(
+        imported
+            .events
+            .iter()
+            .filter_map(|(_, x)| match x {
+                SseqEvent::Rest(duration) => Some(*duration),
+                _ => None,
+            })
+            .collect::<Vec<_>>(),
+        [24, 24]
+    );
+
+    // Infinite loops are played once.
+    let sseq = Sseq::from_tracks(&[vec![
+        SseqEvent::Byte {
+            command: SseqEvent::LOOP_START,
+            value: 0,
+        },
+        note(60, 24),
+        SseqEvent::LoopEnd,
+        SseqEvent::EndOfTrack,
+    ]])
+    .unwrap();
+    let imported = Sseq::from_midi(&sseq.to_midi().unwrap()).unwrap();
+    assert_eq!(notes(&imported), [(60, 24)]);
+}
+
+#[test]
+fn midi_round_trip() {
+    let sseq = Sseq::from_tracks(&[
+        vec![
+            SseqEvent::Byte {
+                command: SseqEvent::NOTE_WAIT,
+                value: 0,
+            },
+            SseqEvent::ProgramChange(5),
+            SseqEvent::Byte {
+                command: SseqEvent::VOLUME,
+                value: 100,
+            },
+            note(60, 48),
+            note(64, 48),
+            SseqEvent::Rest(48),
+            SseqEvent::Byte {
+                command: SseqEvent::PITCH_BEND,
+                value: -32i8 as u8,
+            },
+            note(67, 96),
+            SseqEvent::EndOfTrack,
+        ],
+        vec![
+            SseqEvent::Byte {
+                command: SseqEvent::NOTE_WAIT,
+                value: 0,
+            },
+            SseqEvent::Byte {
+                command: SseqEvent::BEND_RANGE,
+                value: 12,
+            },
+            SseqEvent::Byte {
+                command: SseqEvent::PAN,
+                value: 20,
+            },
+            SseqEvent::Rest(24),
+            note(36, 24),
+            SseqEvent::EndOfTrack,
+        ],
+    ])
+    .unwrap();
+
+    let midi = sseq.to_midi().unwrap();
+    let imported = Sseq::from_midi(&midi).unwrap();
+    assert_eq!(imported, sseq);
+    assert_eq!(imported.to_midi().unwrap(), midi);
+}
+
+fn sine_wave(len: usize) -> Vec<i16> {
+    (0..len)
+        .map(|i| ((i as f64 / 16.0).sin() * 12000.0) as i16)
+        .collect()
+}
+
+#[test]
+fn swar_round_trip() {
+    let samples = sine_wave(200);
+    let swar = Swar {
+        waves: vec![
+            Swav::encode(&samples, 22050, WaveEncoding::Pcm8),
+            Swav::encode(&samples, 32000, WaveEncoding::Pcm16),
+            Swav::encode(&samples, 16000, WaveEncoding::ImaAdpcm),
+        ],
+        reserved: [0; 32],
+    };
+    let data = swar.to_bytes().unwrap();
+    assert!(data.starts_with(b"SWAR"));
+    let parsed = Swar::from_bytes(&data).unwrap();
+    assert_eq!(parsed, swar);
+    assert_eq!(parsed.to_bytes().unwrap(), data);
+
+    let swav = &parsed.waves[2];
+    assert_eq!(swav.timer, (Swav::TIMER_CLOCK / 16000) as u16);
+    assert_eq!((swav.loop_offset, swav.loop_length), (1, 25));
+    assert_eq!(Swav::from_bytes(&swav.to_bytes().unwrap()).unwrap(), *swav);
+
+    // PCM16 is lossless, PCM8 keeps the high byte and IMA-ADPCM stays close.
+    assert_eq!(parsed.waves[1].decode(), samples);
+    let pcm8 = parsed.waves[0].decode();
+    assert!(pcm8
+        .iter()
+        .zip(&samples)
+        .all(|(a, b)| (i32::from(*a) - i32::from(*b)).abs() < 0x100));
+    let adpcm = swav.decode();
+    assert_eq!(adpcm.len(), 200);
+    let error = adpcm
+        .iter()
+        .zip(&samples)
+        .skip(16)
+        .map(|(a, b)| (i32::from(*a) - i32::from(*b)).abs())
+        .max()
+        .unwrap();
+    assert!(error < 1000, "error {error}");
+
+    let mut broken = data.clone();
+    broken[0x10 + 8 + 32 + 4 + 3] = 0xFF;
+    assert!(matches!(
+        Swar::from_bytes(&broken),
+        Err(SwarDeserializationError::InvalidOffset { index: 0 })
+    ));
+    broken = data;
+    broken[0x10 + 8 + 32 + 4 + 4 * 3] = 7;
+    assert!(matches!(
+        Swar::from_bytes(&broken),
+        Err(SwarDeserializationError::Wave { index: 0, .. })
+    ));
+}
+
+#[test]
+fn wav_round_trip() {
+    let samples = sine_wave(100);
+    let swav = Swav::encode(&samples, 11025, WaveEncoding::Pcm16);
+    let wav = swav.to_wav().unwrap();
+    assert!(wav.starts_with(b"RIFF"));
+    assert_eq!(wav.len(), 44 + 200);
+    assert_eq!(Swav::from_wav(&wav, WaveEncoding::Pcm16).unwrap(), swav);
+
+    // 8-bit stereo, with an extra chunk before the data.
+    let mut stereo = Vec::new();
+    stereo.extend_from_slice(b"RIFF\0\0\0\0WAVEfmt ");
+    stereo.extend_from_slice(&16u32.to_le_bytes());
+    for value in [1u16, 2] {
+        stereo.extend_from_slice(&value.to_le_bytes());
+    }
+    stereo.extend_from_slice(&8000u32.to_le_bytes());
+    stereo.extend_from_slice(&16000u32.to_le_bytes());
+    for value in [2u16, 8] {
+        stereo.extend_from_slice(&value.to_le_bytes());
+    }
+    stereo.extend_from_slice(b"LIST\x03\0\0\0abc\0data\x04\0\0\0");
+    stereo.extend_from_slice(&[0x80, 0x80, 0xC0, 0xA0]);
+    let imported = Swav::from_wav(&stereo, WaveEncoding::Pcm16).unwrap();
+    assert_eq!(imported.sample_rate, 8000);
+    assert_eq!(imported.decode(), [0, 0x3000]);
+
+    stereo[20] = 3;
+    assert!(matches!(
+        Swav::from_wav(&stereo, WaveEncoding::Pcm16),
+        Err(WavDeserializationError::UnsupportedFormat {
+            format: 3,
+            bits_per_sample: 8
+        })
+    ));
+}
@@ -0,0 +1,105 @@
+#![cfg(feature = "fs")]
+
+use std::fs;
+
+use mnllib::{
+    crossref::CrossRef,
+    event::FieldEvents,
+    misc::{ProjectPaths, SaveOptions},
+    project::Project,
+    script::{CommandTable, OperandType, ScriptMatch},
+};
+
+#[test]
+fn cross_reference_project() {
+    let original_paths = ProjectPaths::new("tests");
+    let paths = ProjectPaths::new(
+        std::env::temp_dir().join(format!("mnllib-crossref-test-{}", std::process::id())),
+    );
+    for path in [
+        original_paths.data_path("FMap/FMapData.dat"),
+        original_paths.data_path("Treasure/TreasureInfo.dat"),
+        original_paths.overlay_path(3),
+        original_paths.overlay_path(4),
+    ] {
+        let new_path = paths
+            .root
+            .join(path.strip_prefix(&original_paths.root).unwrap());
+        fs::create_dir_all(new_path.parent().unwrap()).unwrap();
+        fs::copy(path, new_path).unwrap();
+    }
+    fs::create_dir_all(paths.data_path("FEvent")).unwrap();
+    #[rustfmt::skip]
+    FieldEvents {
+        chunks: vec![
+            vec![0x01, 0x00, 0x02, 0x00, 0x00, 0x00], vec![], vec![],
+            vec![], vec![], vec![],
+            vec![0x01, 0x00, 0x02, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00], vec![], vec![],
+        ],
+        padding: Vec::new(),
+    }
+    .save_to(&paths, false, &SaveOptions::default())
+    .unwrap();
+    let mut project = Project::open(paths.clone());
+
+    let mut table = CommandTable::default();
+    table.insert(0x0000, Some("end"), []);
+    table.insert(0x0001, Some("warp"), [OperandType::U16]);
+    table.get_mut(0x0001).unwrap().map_operands.push(0);
+    let mut crossref = CrossRef::new(table);
+
+    let maps = project.field_maps().unwrap().maps.clone();
+    let chunk = maps[0].map_chunk_index;
+    let expected: Vec<usize> = (0..maps.len())
+        .filter(|&x| {
+            maps[x].map_chunk_index == chunk || maps[x].tileset_indexes.contains(&Some(chunk))
+        })
+        .collect();
+    assert_eq!(
+        crossref.maps_using_chunk(&mut project, chunk).unwrap(),
+        expected
+    );
+
+    assert_eq!(
+        crossref.scripts_warping_to(&mut project, 2).unwrap(),
+        [
+            ScriptMatch {
+                script: 0,
+                instruction: 0,
+                opcode: 0x0001
+            },
+            ScriptMatch {
+                script: 2,
+                instruction: 0,
+                opcode: 0x0001
+            }
+        ]
+    );
+    assert_eq!(
+        crossref.scripts_warping_to(&mut project, 1).unwrap(),
+        [ScriptMatch {
+            script: 2,
+            instruction: 1,
+            opcode: 0x0001
+        }]
+    );
+    assert!(crossref
+        .scripts_warping_to(&mut project, 3)
+        .unwrap()
+        .is_empty());
+
+    let unused_chunk = project.field_maps().unwrap().fmapdata_chunks.len();
+    project.field_maps_mut().unwrap().maps[0].map_chunk_index = unused_chunk;
+    assert!(crossref
+        .maps_using_chunk(&mut project, unused_chunk)
+        .unwrap()
+        .is_empty());
+    crossref.invalidate();
+    assert_eq!(
+        crossref
+            .maps_using_chunk(&mut project, unused_chunk)
+            .unwrap(),
+        [0]
+    );
+    fs::remove_dir_all(&paths.root).unwrap();
+}
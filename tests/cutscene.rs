@@ -0,0 +1,87 @@
+use mnllib::{
+    cutscene::{
+        CameraKeyframe, CutsceneData, CutsceneDataFromTableError, CutsceneTrack,
+        CutsceneTrackDeserializationError, PathPoint, Track, Vector3,
+    },
+    misc::DataWithOffsetTable,
+};
+
+fn sample_data() -> CutsceneData {
+    CutsceneData {
+        tracks: vec![
+            Track::Camera(CutsceneTrack {
+                actor: CutsceneTrack::<CameraKeyframe>::NO_ACTOR,
+                flags: 0,
+                keyframes: vec![
+                    CameraKeyframe {
+                        duration: 30,
+                        interpolation: 0,
+                        position: Vector3 {
+                            x: 10 * Vector3::ONE,
+                            y: -2 * Vector3::ONE,
+                            z: 0,
+                        },
+                        target: Vector3::default(),
+                    },
+                    CameraKeyframe {
+                        duration: 90,
+                        interpolation: 1,
+                        position: Vector3::default(),
+                        target: Vector3 { x: 1, y: 2, z: 3 },
+                    },
+                ],
+                extra: Vec::new(),
+            }),
+            Track::Path(CutsceneTrack {
+                actor: 2,
+                flags: 1,
+                keyframes: vec![PathPoint {
+                    duration: 16,
+                    animation: 4,
+                    position: Vector3 { x: -5, y: 0, z: 7 },
+                }],
+                extra: vec![0xAB, 0xCD],
+            }),
+            Track::Unknown(vec![7, 0, 1, 2, 3, 4]),
+        ],
+        padding: Vec::new(),
+    }
+}
+
+#[test]
+fn rebuild_cutscene_data() {
+    let data = sample_data();
+    let table = DataWithOffsetTable::try_from(data.clone()).unwrap();
+    assert_eq!(
+        table.chunks[0].len(),
+        CutsceneTrack::<CameraKeyframe>::HEADER_SIZE + 2 * 28
+    );
+    assert_eq!(&table.chunks[1][..8], &[1, 0, 1, 0, 2, 0, 1, 0]);
+    assert_eq!(CutsceneData::try_from(table).unwrap(), data);
+
+    let Track::Camera(camera) = &data.tracks[0] else {
+        unreachable!()
+    };
+    assert_eq!(camera.duration(), 120);
+    assert!(!camera.looped());
+    let Track::Path(path) = &data.tracks[1] else {
+        unreachable!()
+    };
+    assert!(path.looped());
+}
+
+#[test]
+fn truncated_track() {
+    let mut table = DataWithOffsetTable::try_from(sample_data()).unwrap();
+    table.chunks[1].truncate(20);
+    assert!(matches!(
+        CutsceneData::try_from(table),
+        Err(CutsceneDataFromTableError::Track {
+            index: 1,
+            source: CutsceneTrackDeserializationError::TooShort {
+                min: 24,
+                actual: 20
+            }
+        })
+    ));
+}
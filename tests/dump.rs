@@ -0,0 +1,62 @@
+use std::{fs, io::Cursor, path::PathBuf};
+
+use mnllib::{
+    dump::{dump_compressed, AnnotatedDump, Dump},
+    map::FieldMaps,
+    misc::{filesystem_standard_data_path, filesystem_standard_overlay_path, DataWithOffsetTable},
+};
+use rstest::rstest;
+
+fn assert_fully_annotated(dump: &AnnotatedDump) {
+    let mut offset = 0;
+    for annotation in &dump.annotations {
+        assert!(annotation.range.start >= offset);
+        offset = annotation.range.end;
+    }
+    assert!(offset <= dump.data.len());
+    assert!(!dump.to_string().is_empty());
+}
+
+#[rstest]
+fn dump_data_with_offset_table_file(
+    #[files("tests/data/data/**/*Mes*.dat")]
+    #[files("tests/data/data/**/mfset_*.dat")]
+    path: PathBuf,
+) {
+    let original_data = fs::read(path).unwrap();
+    let dump = DataWithOffsetTable::from_reader(&original_data[..])
+        .unwrap()
+        .dump()
+        .unwrap();
+
+    assert_eq!(dump.data, original_data);
+    assert_fully_annotated(&dump);
+}
+
+#[rstest]
+fn dump_compressed_field_map_chunk() {
+    let field_maps = FieldMaps::from_files(
+        &fs::read(PathBuf::from("tests").join(filesystem_standard_data_path("FMap/FMapData.dat")))
+            .unwrap()[..],
+        &fs::read(
+            PathBuf::from("tests").join(filesystem_standard_data_path("Treasure/TreasureInfo.dat")),
+        )
+        .unwrap()[..],
+        Cursor::new(
+            fs::read(PathBuf::from("tests").join(filesystem_standard_overlay_path(3))).unwrap(),
+        ),
+        Cursor::new(
+            fs::read(PathBuf::from("tests").join(filesystem_standard_overlay_path(4))).unwrap(),
+        ),
+    )
+    .unwrap();
+    let chunk = field_maps.fmapdata_chunks[field_maps.maps[0].map_chunk_index]
+        .to_compressed()
+        .unwrap();
+
+    let dump = dump_compressed(&chunk).unwrap();
+
+    assert_eq!(dump.data, &chunk[..]);
+    assert_eq!(dump.annotations.last().unwrap().range.end, chunk.len());
+    assert_fully_annotated(&dump);
+}
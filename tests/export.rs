@@ -0,0 +1,128 @@
+#![cfg(feature = "fs")]
+
+use grid::Grid;
+use mnllib::{
+    export::MapExport,
+    map::FieldMaps,
+    misc::{PaletteLut, ProjectPaths},
+};
+use rgb::Rgba;
+
+#[test]
+fn export_field_map() {
+    let field_maps = FieldMaps::load_from(&ProjectPaths::new("tests")).unwrap();
+    let (map_chunk, tilesets) = field_maps.decode_map(0).unwrap();
+    let export = MapExport::new(&map_chunk, tilesets.each_ref().map(Option::as_ref), 16).unwrap();
+    let luts = map_chunk
+        .palettes
+        .each_ref()
+        .map(|x| x.as_ref().map(PaletteLut::from));
+    assert_eq!(
+        MapExport::with_luts(
+            &map_chunk,
+            tilesets.each_ref().map(Option::as_ref),
+            luts.each_ref().map(Option::as_ref),
+            16
+        )
+        .unwrap(),
+        export
+    );
+    assert_eq!(
+        (export.width, export.height),
+        (
+            map_chunk.properties.width.into(),
+            map_chunk.properties.height.into()
+        )
+    );
+
+    // Drawing the tiles out of the tilesets must give the same image as rendering the map.
+    let mut image = Grid::init(export.height * 8, export.width * 8, Rgba::new(0, 0, 0, 0));
+    for layer in &export.layers {
+        let tileset = &export.tilesets[layer.tileset];
+        assert_eq!(layer.tiles.len(), export.width * export.height);
+        for (index, tile) in layer.tiles.iter().enumerate() {
+            let Some(tile) = tile else {
+                continue;
+            };
+            assert!(tile.id < tileset.tile_count);
+            for y in 0..8 {
+                for x in 0..8 {
+                    let source_x = if tile.flipped_horizontally { 7 - x } else { x };
+                    let source_y = if tile.flipped_vertically { 7 - y } else { y };
+                    let color = tileset.image[(
+                        tile.id / tileset.columns * 8 + source_y,
+                        tile.id % tileset.columns * 8 + source_x,
+                    )];
+                    if color.a != 0 {
+                        image[(index / export.width * 8 + y, index % export.width * 8 + x)] = color;
+                    }
+                }
+            }
+        }
+    }
+    assert_eq!(image, field_maps.render_map(0).unwrap());
+
+    let json = export.to_json(|index| format!("map \"0\"/{index}.png"));
+    assert!(json.starts_with("{\n  \"format\": \"mnllib-map\",\n  \"version\": 1,\n"));
+    assert!(json.contains("{\"image\": \"map \\\"0\\\"/0.png\", \"columns\": 16, "));
+    assert_eq!(
+        json.matches("\"source_layer\"").count(),
+        export.layers.len()
+    );
+    assert!(json.ends_with("    ]}\n  ]\n}\n"));
+}
+
+#[test]
+fn export_field_map_with_metadata() {
+    use mnllib::{
+        export::{render_map_layers, MapImageMetadata},
+        patch::crc32,
+    };
+
+    let field_maps = FieldMaps::load_from(&ProjectPaths::new("tests")).unwrap();
+    let (image, metadata) = render_map_layers(&field_maps, 0, [true, false, true]).unwrap();
+    let (map_chunk, mut tilesets) = field_maps.decode_map(0).unwrap();
+    tilesets[1] = None;
+    assert_eq!(
+        image,
+        map_chunk
+            .render(tilesets.each_ref().map(Option::as_ref))
+            .unwrap()
+    );
+
+    let map = &field_maps.maps[0];
+    let chunk_crc32 = |index: usize| {
+        crc32(
+            &field_maps.fmapdata_chunks[index]
+                .to_uncompressed(true)
+                .unwrap(),
+        )
+    };
+    assert_eq!(metadata.map_index, 0);
+    assert_eq!(metadata.layers, [true, false, true]);
+    assert_eq!(metadata.map_chunk_crc32, chunk_crc32(map.map_chunk_index));
+    assert_eq!(
+        metadata.tileset_crc32s,
+        map.tileset_indexes.map(|x| x.map(chunk_crc32))
+    );
+
+    let text = metadata.to_text();
+    assert!(text
+        .iter()
+        .all(|(keyword, _)| keyword.starts_with("mnllib:")));
+    assert_eq!(MapImageMetadata::from_text(&text), Some(metadata.clone()));
+    assert_eq!(MapImageMetadata::from_text(&text[1..]), None);
+
+    #[cfg(feature = "png")]
+    {
+        use mnllib::export::{map_image_metadata_from_png, map_image_to_png};
+
+        let mut png = Vec::new();
+        map_image_to_png(&image, &metadata, &mut png).unwrap();
+        assert_eq!(
+            map_image_metadata_from_png(&png[..]).unwrap(),
+            Some(metadata)
+        );
+        assert_eq!(mnllib::sprites::image_from_png(&png[..]).unwrap(), image);
+    }
+}
@@ -0,0 +1,99 @@
+use mnllib::{
+    flags::{FlagDatabase, FlagId, FlagInfo, FlagKind, FlagReference, FlagReferences},
+    script::{CommandTable, OperandType, Script},
+};
+
+#[test]
+fn flag_display() {
+    assert_eq!(FlagId(0).to_string(), "flag_0x0000");
+    assert_eq!(FlagId(0x0123).to_string(), "flag_0x0123");
+}
+
+#[test]
+fn flag_references() {
+    let mut table = CommandTable::default();
+    table.insert(0x0000, Some("end"), []);
+    table.insert(
+        0x0001,
+        Some("set_flag"),
+        [OperandType::U16, OperandType::I8],
+    );
+    table.insert(
+        0x0002,
+        Some("branch_if_flags"),
+        [OperandType::U8, OperandType::U16, OperandType::I16],
+    );
+    table.get_mut(0x0001).unwrap().flag_operands = vec![0];
+    table.get_mut(0x0002).unwrap().flag_operands = vec![1, 2];
+    #[rustfmt::skip]
+    let script = Script::disassemble(&[
+        0x01, 0x00, 0x34, 0x12, 0x01,
+        0x02, 0x00, 0x07, 0x34, 0x12, 0xFF, 0xFF,
+        0x00, 0x00,
+    ], &table).unwrap();
+
+    let mut references = FlagReferences::default();
+    references.add_script(5, &script, &table);
+    assert_eq!(
+        references.get(FlagId(0x1234)),
+        [
+            FlagReference::Script {
+                script: 5,
+                instruction: 0,
+                opcode: 0x0001
+            },
+            FlagReference::Script {
+                script: 5,
+                instruction: 1,
+                opcode: 0x0002
+            },
+        ]
+    );
+    // Negative values aren't flags.
+    assert_eq!(references.0.len(), 1);
+}
+
+#[test]
+fn flag_database() {
+    let mut database = FlagDatabase::default();
+    database.insert(
+        FlagId(0x10),
+        FlagInfo {
+            name: Some("met_starlow".to_owned()),
+            kind: FlagKind::Story,
+            description: None,
+        },
+    );
+    database.insert(
+        FlagId(0x200),
+        FlagInfo {
+            kind: FlagKind::Treasure,
+            ..Default::default()
+        },
+    );
+    assert_eq!(database.name(FlagId(0x10)), Some("met_starlow"));
+    assert_eq!(database.name(FlagId(0x200)), None);
+    assert_eq!(database.find("met_starlow"), Some(FlagId(0x10)));
+    assert_eq!(database.find("missing"), None);
+
+    #[cfg(feature = "toml")]
+    {
+        let parsed = FlagDatabase::from_toml(
+            r#"
+            [flags.0x0010]
+            name = "met_starlow"
+            kind = "story"
+
+            [flags.512]
+            kind = "treasure"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(parsed, database);
+        assert_eq!(
+            FlagDatabase::from_toml(&database.to_toml().unwrap()).unwrap(),
+            database
+        );
+        assert!(FlagDatabase::from_toml("[flags.0xZZ]").is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use grid::Grid;
+use mnllib::{
+    font::{AddGlyphError, Font, FontFromTilesetError, Glyph, RenderError, RenderOptions},
+    map::{Tileset, TilesetTile},
+    misc::{Palette, PaletteLut, Rgb555},
+    text::{CharacterTable, GlyphWidths, WrapOptions},
+};
+use rgb::Rgba;
+
+fn test_font() -> Font {
+    let mut tiles = vec![TilesetTile([0; 64]); 0x43];
+    let mut widths = vec![0; 0x43];
+    tiles[usize::from(b'A')] = TilesetTile([1; 64]);
+    widths[usize::from(b'A')] = 4;
+    tiles[usize::from(b'B')] = TilesetTile([2; 64]);
+    widths[usize::from(b'B')] = 8;
+    Font::from_tileset(&Tileset(tiles), &widths, (1, 1)).unwrap()
+}
+
+fn test_palette() -> Palette {
+    Palette(vec![
+        Rgb555::new(0, 0, 0),
+        Rgb555::new(31, 0, 0),
+        Rgb555::new(0, 31, 0),
+        Rgb555::new(0, 0, 31),
+        Rgb555::new(31, 31, 31),
+    ])
+}
+
+#[test]
+fn font_tileset_round_trip() {
+    let font = test_font();
+    let (tileset, widths) = font.to_tileset();
+
+    assert_eq!(Font::from_tileset(&tileset, &widths, (1, 1)).unwrap(), font);
+    assert!(Font::from_tileset(&tileset, &widths[1..], (1, 1)).is_err());
+    assert!(matches!(
+        Font::from_tileset(&tileset, &widths, (0, 2)),
+        Err(FontFromTilesetError::EmptyCell { cell_size: (0, 2) })
+    ));
+}
+
+#[test]
+fn render_text() {
+    let font = test_font();
+    let palette = test_palette();
+    let transparent = Rgba::new(0, 0, 0, 0);
+    let (red, green, white) = (
+        Rgba::new(0xF8, 0, 0, 0xFF),
+        Rgba::new(0, 0xF8, 0, 0xFF),
+        Rgba::new(0xF8, 0xF8, 0xF8, 0xFF),
+    );
+    let mut options = RenderOptions::default();
+    options.color_codes.insert(b'+', 3);
+    options.control_code_arguments.insert(0x11, 1);
+
+    let image = font
+        .render(
+            b"AB\xff\x00A\xff+A\xff\x11\x01\xff\n\x00B",
+            &palette,
+            &options,
+        )
+        .unwrap();
+    assert_eq!((image.rows(), image.cols()), (16, 12));
+    assert_eq!(image[(0, 0)], red);
+    assert_eq!(image[(0, 4)], green);
+    assert_eq!(image[(0, 11)], green);
+    assert_eq!(image[(8, 0)], red);
+    assert_eq!(image[(8, 4)], white);
+    assert_eq!(image[(8, 11)], white);
+    assert_eq!(image[(15, 0)], red);
+
+    options.width = Some(12);
+    let image = font.render(b"A", &palette, &options).unwrap();
+    assert_eq!(
+        font.render_with_lut(b"A", &PaletteLut::from(&palette), &options)
+            .unwrap(),
+        image
+    );
+    assert_eq!((image.rows(), image.cols()), (8, 12));
+    assert_eq!(image[(0, 7)], red);
+    assert_eq!(image[(0, 8)], transparent);
+
+    assert!(matches!(
+        font.render(b"AC", &palette, &options),
+        Err(RenderError::MissingGlyph {
+            offset: 1,
+            code: b'C'
+        })
+    ));
+    options.line_break.clear();
+    assert!(matches!(
+        font.render(b"A", &palette, &options),
+        Err(RenderError::EmptyLineBreak)
+    ));
+}
+
+#[test]
+fn wrap_with_font_widths() {
+    let font = test_font();
+
+    assert_eq!(font.glyph_width(b"A"), 4);
+    assert_eq!(font.glyph_width(b"\xff\x00"), 0);
+    assert_eq!(
+        CharacterTable::standard()
+            .encode_wrapped("AA BB", &font, &WrapOptions::new(16))
+            .unwrap()
+            .data,
+        b"AA\xff\x00BB"
+    );
+}
+
+#[test]
+fn add_glyphs() {
+    let mut font = test_font();
+    let mut table = CharacterTable::standard();
+    let glyph = |value| Glyph {
+        width: 6,
+        pixels: Grid::init(8, 8, value),
+    };
+
+    let code = font
+        .add_glyphs([(glyph(3), "Ж"), (glyph(4), "Я")], &mut table)
+        .unwrap();
+    assert_eq!(code, 0x43);
+    assert_eq!(table.encode("AЖЯ").unwrap(), b"A\x43\x44");
+    assert_eq!(table.decode(b"\x44").unwrap(), "Я");
+    assert_eq!(font.glyph_width(b"\x44"), 6);
+    assert_eq!(font.to_tileset().1[0x44], 6);
+
+    assert!(matches!(
+        font.add_glyphs(
+            [
+                (glyph(1), "Ш"),
+                (
+                    Glyph {
+                        width: 6,
+                        pixels: Grid::new(16, 8)
+                    },
+                    "Щ"
+                )
+            ],
+            &mut table
+        ),
+        Err(AddGlyphError::WrongSize { index: 1, .. })
+    ));
+    assert_eq!(font.glyphs.len(), 0x45);
+    assert!(table.encode("Ш").is_err());
+
+    assert!(matches!(
+        font.add_glyphs(
+            (0..Font::MAX_GLYPHS).map(|i| (glyph(1), format!("<{}>", i))),
+            &mut table
+        ),
+        Err(AddGlyphError::TooManyGlyphs)
+    ));
+}
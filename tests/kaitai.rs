@@ -0,0 +1,54 @@
+use std::collections::HashSet;
+
+use mnllib::{
+    kaitai::{all_ksy, data_with_offset_table_ksy, record_ksy},
+    layout::{HasLayout, LAYOUTS},
+    map::FieldMapProperties,
+    misc::OffsetTableEntrySize,
+};
+
+#[test]
+fn ksy_ids_are_unique() {
+    let definitions = all_ksy();
+    assert_eq!(definitions.len(), LAYOUTS.len() + 4);
+    let mut ids = HashSet::new();
+    for (id, ksy) in &definitions {
+        assert!(ids.insert(id), "{id} is defined twice");
+        assert!(
+            ksy.starts_with(&format!("meta:\n  id: {id}\n")),
+            "{id} has the wrong ID:\n{ksy}"
+        );
+        assert!(
+            id.chars()
+                .all(|x| x.is_ascii_lowercase() || x.is_ascii_digit() || x == '_'),
+            "{id} isn't a valid Kaitai Struct ID"
+        );
+    }
+}
+
+#[test]
+fn record_ksy_has_all_fields() {
+    let ksy = record_ksy(&FieldMapProperties::LAYOUT);
+    let ids: Vec<&str> = ksy
+        .lines()
+        .filter_map(|x| x.trim().strip_prefix("- id: "))
+        .collect();
+    assert_eq!(
+        ids,
+        [
+            "width",
+            "height",
+            "unk_0x04",
+            "tilesets_properties",
+            "unk_0x06"
+        ]
+    );
+    assert!(ksy.contains("doc: '`FieldMapProperties`'\n"));
+}
+
+#[test]
+fn offset_table_ksy_entry_size() {
+    let ksy = data_with_offset_table_ksy(OffsetTableEntrySize::U16);
+    assert!(ksy.contains("repeat-expr: first_offset / 2\n"));
+    assert!(ksy.contains("    type: u2\n"));
+}
@@ -0,0 +1,133 @@
+use mnllib::{
+    layout::{Endianness, FieldLayout, FieldType, HasLayout, StructLayout, Trailing, LAYOUTS},
+    map::FieldMapProperties,
+    rom::OverlayEntry,
+};
+
+/// Writes a distinct value into each field in turn, and checks that
+/// the parser puts it into the field of the same name.
+fn check_against_parser(layout: &StructLayout, parse: impl Fn(&[u8]) -> String) {
+    let (element, count_field) = match layout.trailing {
+        Trailing::Repeated {
+            element,
+            count_field,
+            ..
+        } => (Some(element), count_field),
+        _ => (None, None),
+    };
+    let size = layout.size + element.map_or(0, |x| x.size);
+    let fields = layout
+        .fields
+        .iter()
+        .filter(|x| Some(x.name) != count_field)
+        .map(|x| (x, x.offset))
+        .chain(
+            element
+                .into_iter()
+                .flat_map(|x| x.fields)
+                .map(|x| (x, layout.size + x.offset)),
+        );
+    for (field, offset) in fields {
+        let mut data = vec![0u8; size];
+        if let Some(count_field) = count_field {
+            data[layout.field(count_field).unwrap().offset] = 1;
+        }
+        let (bytes, value): (&[u8], &str) = match field.field_type {
+            FieldType::U8 | FieldType::I8 => (&[0x5A], "90"),
+            FieldType::U16 | FieldType::I16 => (&[0x34, 0x12], "4660"),
+            FieldType::U32 | FieldType::I32 => (&[0x78, 0x56, 0x34, 0x12], "305419896"),
+            FieldType::Bytes(_) => continue,
+        };
+        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        let debug = parse(&data);
+        let key = format!(" {}: ", field.name);
+        let start = debug
+            .find(&key)
+            .unwrap_or_else(|| panic!("{}.{} not in {debug}", layout.name, field.name))
+            + key.len();
+        let rest = &debug[start..];
+        let end = rest.find([',', '}']).unwrap_or(rest.len());
+        assert!(
+            rest[..end].contains(value),
+            "{}.{} is {}",
+            layout.name,
+            field.name,
+            &rest[..end]
+        );
+    }
+}
+
+#[test]
+fn layouts_match_parsers() {
+    check_against_parser(&FieldMapProperties::LAYOUT, |data| {
+        format!("{:?}", FieldMapProperties::from_reader(data).unwrap())
+    });
+    check_against_parser(&OverlayEntry::LAYOUT, |data| {
+        format!("{:?}", OverlayEntry::from_reader(data).unwrap())
+    });
+}
+
+#[test]
+fn layouts_are_consistent() {
+    for layout in LAYOUTS {
+        let mut end = 0;
+        for field in layout.fields {
+            assert!(
+                field.offset >= end,
+                "{}.{} overlaps",
+                layout.name,
+                field.name
+            );
+            end = field.offset + field.field_type.size();
+        }
+        assert_eq!(end, layout.size, "{}", layout.name);
+    }
+}
+
+#[test]
+fn layout_display() {
+    const ELEMENT: StructLayout = StructLayout {
+        name: "Element",
+        size: 2,
+        endianness: Endianness::Little,
+        fields: &[FieldLayout::new("value", 0, FieldType::I16)],
+        trailing: Trailing::Nothing,
+    };
+    const LAYOUT: StructLayout = StructLayout {
+        name: "Record",
+        size: 8,
+        endianness: Endianness::Little,
+        fields: &[
+            FieldLayout::new("id", 0, FieldType::U16),
+            FieldLayout::new("amount", 2, FieldType::U32),
+            FieldLayout::new("num_elements", 6, FieldType::U8),
+            FieldLayout::new("unk_0x07", 7, FieldType::Bytes(1)),
+        ],
+        trailing: Trailing::Repeated {
+            name: "elements",
+            element: &ELEMENT,
+            count_field: Some("num_elements"),
+        },
+    };
+    assert_eq!(
+        LAYOUT.to_string(),
+        "struct Record { // 0x8 bytes, little-endian
+    /* 0x00 */ id: u16,
+    /* 0x02 */ amount: u32,
+    /* 0x06 */ num_elements: u8,
+    /* 0x07 */ unk_0x07: [u8; 1],
+    /* 0x08 */ elements: [Element; num_elements],
+    extra: [u8],
+}"
+    );
+    assert_eq!(
+        FieldMapProperties::LAYOUT.to_string(),
+        "struct FieldMapProperties { // 0xC bytes, little-endian
+    /* 0x00 */ width: u16,
+    /* 0x02 */ height: u16,
+    /* 0x04 */ unk_0x04: u8,
+    /* 0x05 */ tilesets_properties: [u8; 1],
+    /* 0x06 */ unk_0x06: [u8; 6],
+}"
+    );
+}
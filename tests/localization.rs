@@ -0,0 +1,109 @@
+use std::fs;
+
+use mnllib::{
+    localization::{
+        diff_languages, diff_lists, merge_translations, LocalizationOptions, MergeConflict,
+        MessageDiff, MessageDifference,
+    },
+    misc::DataWithOffsetTable,
+    text::{MessageList, MessageListSet, MESSAGE_TERMINATOR},
+};
+
+fn message(parts: &[&[u8]]) -> Vec<u8> {
+    [parts.concat(), MESSAGE_TERMINATOR.to_vec()].concat()
+}
+
+fn list(messages: Vec<Vec<u8>>) -> MessageList {
+    MessageList {
+        messages,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn diff_message_lists() {
+    let reference = list(vec![
+        message(&[b"\xFF\x20Hello!\xFF\x00Bye."]),
+        message(&[b"Same"]),
+        message(&[b"\xFF\x30Red\xFF\x31 text"]),
+        message(&[b"Gone"]),
+    ]);
+    let target = list(vec![
+        message(&[b"\xFF\x20Bonjour !"]),
+        message(&[b"Same"]),
+        message(&[b"Texte rouge"]),
+        Vec::new(),
+        message(&[b"New"]),
+    ]);
+    let diff = |message, difference| MessageDiff {
+        message,
+        difference,
+    };
+    assert_eq!(
+        diff_lists(&reference, &target, &LocalizationOptions::default()),
+        [
+            diff(1, MessageDifference::Identical),
+            diff(2, MessageDifference::ControlCodesDiffer),
+            diff(3, MessageDifference::Missing),
+            diff(4, MessageDifference::Extra),
+        ]
+    );
+    assert_eq!(
+        diff_lists(
+            &reference,
+            &target,
+            &LocalizationOptions {
+                ignored_control_codes: Default::default(),
+            }
+        )[0],
+        diff(0, MessageDifference::ControlCodesDiffer)
+    );
+}
+
+#[test]
+fn merge_message_lists() {
+    let mut target = list(vec![
+        message(&[b"\xFF\x20\xFF\x21Hello!\xFF\x22"]),
+        message(&[b"\xFF\x30Red\xFF\x31 text"]),
+        message(&[b"\xFF\x30Red\xFF\x31 text"]),
+        message(&[b"Plain"]),
+    ]);
+    let translated = list(vec![
+        b"Hallo!".to_vec(),
+        message(&[b"\xFF\x30Rot\xFF\x31er Text"]),
+        message(&[b"Roter Text"]),
+        Vec::new(),
+        message(&[b"Neu"]),
+    ]);
+    let conflicts = merge_translations(&mut target, &translated, &LocalizationOptions::default());
+    assert_eq!(
+        conflicts,
+        [MergeConflict::ControlCodesDiffer { message: 2 }]
+    );
+    assert_eq!(
+        target.messages,
+        [
+            message(&[b"\xFF\x20\xFF\x21Hallo!\xFF\x22"]),
+            message(&[b"\xFF\x30Rot\xFF\x31er Text"]),
+            message(&[b"\xFF\x30Red\xFF\x31 text"]),
+            message(&[b"Plain"]),
+            message(&[b"Neu"]),
+        ]
+    );
+}
+
+#[test]
+fn diff_mfset_languages() {
+    let set = MessageListSet::from(
+        DataWithOffsetTable::from_reader(
+            &fs::read("tests/data/data/BData/mfset_UItmN.dat").unwrap()[..],
+        )
+        .unwrap(),
+    );
+    let options = LocalizationOptions::default();
+    assert!(diff_languages(&set, 2, 2, &options)
+        .unwrap()
+        .iter()
+        .all(|x| x.difference == MessageDifference::Identical));
+    assert!(diff_languages(&set, 2, 1000, &options).is_err());
+}
@@ -0,0 +1,96 @@
+use byteorder::{ByteOrder, LittleEndian};
+use mnllib::{
+    minigame::{MinigameDataFile, MinigameRecordsError},
+    misc::OverlayRecord,
+};
+
+/// A record starting with a little-endian `u16` and an `i16`, followed by the rest of the entry.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+struct TestRecord {
+    kind: u16,
+    x: i16,
+    extra: Vec<u8>,
+}
+
+impl OverlayRecord for TestRecord {
+    const MIN_SIZE: usize = 4;
+
+    fn from_bytes(data: &[u8]) -> Self {
+        Self {
+            kind: LittleEndian::read_u16(&data[0..]),
+            x: LittleEndian::read_i16(&data[2..]),
+            extra: data[Self::MIN_SIZE..].to_vec(),
+        }
+    }
+    fn to_bytes(&self, size: usize) -> Vec<u8> {
+        let mut data = vec![0u8; size];
+        LittleEndian::write_u16(&mut data[0..], self.kind);
+        LittleEndian::write_i16(&mut data[2..], self.x);
+        data[Self::MIN_SIZE..].copy_from_slice(&self.extra);
+        data
+    }
+}
+
+#[test]
+fn minigame_records() {
+    let mut file = MinigameDataFile {
+        chunks: vec![
+            vec![
+                1, 0, 0xF0, 0xFF, 0x20, 0, 0xAA, 0xBB, 2, 0, 3, 0, 4, 0, 0, 0,
+            ],
+            vec![9; 12],
+        ],
+        padding: Vec::new(),
+    };
+
+    let mut objects = file.records::<TestRecord>(0, 8).unwrap();
+    assert_eq!(
+        objects[0],
+        TestRecord {
+            kind: 1,
+            x: -16,
+            extra: vec![0x20, 0, 0xAA, 0xBB],
+        }
+    );
+    assert!(matches!(
+        file.records::<TestRecord>(1, 8),
+        Err(MinigameRecordsError::InvalidChunkSize {
+            chunk: 1,
+            len: 12,
+            entry_size: 8
+        })
+    ));
+    assert!(matches!(
+        file.records::<TestRecord>(0, 2),
+        Err(MinigameRecordsError::EntryTooSmall { .. })
+    ));
+    assert!(matches!(
+        file.records::<TestRecord>(2, 8),
+        Err(MinigameRecordsError::ChunkOutOfBounds(2))
+    ));
+
+    objects.push(TestRecord {
+        kind: 5,
+        extra: vec![0, 0, 0, 0],
+        ..Default::default()
+    });
+    file.set_records(0, 8, &objects).unwrap();
+    assert_eq!(file.chunks[0].len(), 24);
+    assert_eq!(&file.chunks[0][16..], &[5, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(file.records::<TestRecord>(0, 8).unwrap(), objects);
+
+    #[cfg(feature = "fs")]
+    {
+        use mnllib::misc::{ProjectPaths, SaveOptions};
+
+        let paths = ProjectPaths::new(
+            std::env::temp_dir().join(format!("mnllib-minigame-test-{}", std::process::id())),
+        );
+        std::fs::create_dir_all(paths.data_path("")).unwrap();
+        file.save_to(&paths, "MGame.dat", &SaveOptions::default())
+            .unwrap();
+        let loaded = MinigameDataFile::load_from(&paths, "MGame.dat").unwrap();
+        std::fs::remove_dir_all(&paths.root).unwrap();
+        assert_eq!(loaded.chunks, file.chunks);
+    }
+}
@@ -0,0 +1,227 @@
+#![cfg(feature = "fs")]
+
+use std::fs;
+
+use mnllib::{
+    misc::{DataWithOffsetTable, ProjectPaths},
+    modpack::{Edit, EditError, ModApplyError, ModMergeError, ModPackage},
+    project::Project,
+    text::{MessageId, MessageListSet, MESSAGE_TERMINATOR},
+};
+
+#[test]
+fn apply_mod_package() {
+    let original_paths = ProjectPaths::new("tests");
+    let paths = ProjectPaths::new(
+        std::env::temp_dir().join(format!("mnllib-modpack-test-{}", std::process::id())),
+    );
+    for path in [
+        original_paths.data_path("BData/mfset_UItmN.dat"),
+        original_paths.data_path("BAI/BMes_cf.dat"),
+        original_paths.overlay_path(3),
+    ] {
+        let new_path = paths
+            .root
+            .join(path.strip_prefix(&original_paths.root).unwrap());
+        fs::create_dir_all(new_path.parent().unwrap()).unwrap();
+        fs::copy(path, new_path).unwrap();
+    }
+    let text = [b"Mod".as_slice(), &MESSAGE_TERMINATOR].concat();
+    let package = ModPackage {
+        name: "Test".to_owned(),
+        description: None,
+        edits: vec![
+            Edit::ListMessage {
+                file: "BData/mfset_UItmN.dat".to_owned(),
+                list: 2,
+                message: 0,
+                text: text.clone(),
+            },
+            Edit::Chunk {
+                file: "BAI/BMes_cf.dat".to_owned(),
+                chunk: 0,
+                data: Vec::new(),
+            },
+        ],
+    };
+    let mut project = Project::open(paths.clone());
+    package.apply(&mut project).unwrap();
+    project.save().unwrap();
+
+    let names = MessageListSet::from(
+        DataWithOffsetTable::from_reader(
+            &fs::read(paths.data_path("BData/mfset_UItmN.dat")).unwrap()[..],
+        )
+        .unwrap(),
+    );
+    assert_eq!(
+        names.get(MessageId {
+            list: 2,
+            message: 0
+        }),
+        Some(&text)
+    );
+    let archive = DataWithOffsetTable::from_reader(
+        &fs::read(paths.data_path("BAI/BMes_cf.dat")).unwrap()[..],
+    )
+    .unwrap();
+    assert!(archive.chunks[0].is_empty());
+
+    let invalid = ModPackage {
+        edits: vec![
+            package.edits[0].clone(),
+            Edit::Chunk {
+                file: "BAI/BMes_cf.dat".to_owned(),
+                chunk: archive.chunks.len() + 1,
+                data: Vec::new(),
+            },
+        ],
+        ..Default::default()
+    };
+    assert!(matches!(
+        invalid.apply(&mut project),
+        Err(ModApplyError::Edit {
+            index: 1,
+            source: EditError::OutOfBounds { what: "chunk", .. }
+        })
+    ));
+
+    // The chunk would be overwritten by the message list when saving.
+    let mixed = ModPackage {
+        edits: vec![
+            package.edits[0].clone(),
+            Edit::Chunk {
+                file: "BData/mfset_UItmN.dat".to_owned(),
+                chunk: 0,
+                data: Vec::new(),
+            },
+        ],
+        ..Default::default()
+    };
+    let mut project = Project::open(paths.clone());
+    assert!(matches!(
+        mixed.apply(&mut project),
+        Err(ModApplyError::MixedFileEdits {
+            first: 0,
+            second: 1,
+            ..
+        })
+    ));
+    assert!(!project.is_modified());
+    fs::remove_dir_all(&paths.root).unwrap();
+
+    #[cfg(feature = "toml")]
+    {
+        let parsed = ModPackage::from_toml(
+            r#"
+            name = "Test"
+
+            [[edits]]
+            kind = "list_message"
+            file = "BData/mfset_UItmN.dat"
+            list = 2
+            message = 0
+            text = "2C 4F 44 FF0A"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(parsed.edits.len(), 1);
+        let mut package = package.clone();
+        package.edits.push(Edit::FieldMap {
+            map: 3,
+            tileset_indexes: [Some(1), None, Some(2)],
+            map_chunk_index: 4,
+            treasure_data_index: None,
+        });
+        assert_eq!(
+            ModPackage::from_toml(&package.to_toml().unwrap()).unwrap(),
+            package
+        );
+        assert!(ModPackage::from_toml(
+            "name = \"x\"\n[[edits]]\nkind = \"chunk\"\nfile = \"a\"\nchunk = 0\ndata = \"ABC\""
+        )
+        .is_err());
+    }
+}
+
+#[test]
+fn merge_mod_packages() {
+    let mut base = Project::open(ProjectPaths::new("tests"));
+    let unchanged_chunk = base.data_file("BAI/BMes_ji.dat").unwrap().chunks[1].clone();
+
+    let message = |message, text: &[u8]| Edit::ListMessage {
+        file: "BData/mfset_UItmN.dat".to_owned(),
+        list: 2,
+        message,
+        text: [text, &MESSAGE_TERMINATOR].concat(),
+    };
+    let chunk = |chunk, data: &[u8]| Edit::Chunk {
+        file: "BAI/BMes_ji.dat".to_owned(),
+        chunk,
+        data: data.to_vec(),
+    };
+    let ours = ModPackage {
+        name: "Ours".to_owned(),
+        description: None,
+        edits: vec![
+            message(0, b"First"),
+            chunk(2, &[1; 8]),
+            message(0, b"Ours"),
+            chunk(1, &unchanged_chunk),
+        ],
+    };
+    let theirs = ModPackage {
+        name: "Theirs".to_owned(),
+        description: None,
+        edits: vec![
+            Edit::Chunk {
+                file: "BAI/BMes_cf.dat".to_owned(),
+                chunk: 0,
+                data: vec![1, 2, 3],
+            },
+            message(0, b"Theirs"),
+            chunk(2, &[1; 8]),
+            chunk(1, &[2; 8]),
+        ],
+    };
+
+    let merge = ModPackage::merge(&mut base, &ours, &theirs).unwrap();
+    assert!(!base.is_modified());
+    assert_eq!(merge.package.name, "Ours + Theirs");
+    assert_eq!(
+        merge.package.edits,
+        [
+            chunk(2, &[1; 8]),
+            theirs.edits[0].clone(),
+            chunk(1, &[2; 8])
+        ]
+    );
+    assert_eq!(merge.conflicts.len(), 1);
+    let conflict = &merge.conflicts[0];
+    assert_eq!(conflict.target, message(0, b"").target());
+    assert_eq!(
+        (&conflict.ours, &conflict.theirs),
+        (&message(0, b"Ours"), &message(0, b"Theirs"))
+    );
+    assert_eq!(
+        conflict.to_string(),
+        "both packages change message 2/0 of BData/mfset_UItmN.dat differently"
+    );
+
+    let invalid = ModPackage {
+        edits: vec![
+            message(0, b"x"),
+            chunk(9999, &[0; 8]),
+            Edit::Chunk {
+                file: "Missing.dat".to_owned(),
+                chunk: 0,
+                data: Vec::new(),
+            },
+        ],
+        ..Default::default()
+    };
+    assert!(matches!(
+        ModPackage::merge(&mut base, &invalid, &theirs),
+        Err(ModMergeError::Edit { index: 2, .. })
+    ));
+}
@@ -0,0 +1,90 @@
+use mnllib::{
+    consts::STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT,
+    misc::DataWithOffsetTable,
+    objects::{
+        BattleObject, BattleObjectFile, FieldObject, FieldObjectFile, Hitbox,
+        ObjectDeserializationError, ObjectFileFromTableError,
+    },
+};
+
+fn write_table(table: DataWithOffsetTable) -> Vec<u8> {
+    let mut data = Vec::new();
+    table
+        .to_writer(
+            &mut data,
+            Some(STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT),
+            true,
+        )
+        .unwrap();
+    data
+}
+
+#[test]
+fn rebuild_field_object_file() {
+    let file = FieldObjectFile {
+        objects: vec![
+            FieldObject {
+                sprite: 0x12,
+                animation: 3,
+                hitbox: Hitbox {
+                    x: -8,
+                    y: -16,
+                    width: 16,
+                    height: 16,
+                    depth: 24,
+                },
+                behavior: 7,
+                extra: vec![1, 2, 3, 4],
+            },
+            FieldObject::default(),
+        ],
+        padding: Vec::new(),
+    };
+    let data = write_table(file.clone().into());
+    let parsed =
+        FieldObjectFile::try_from(DataWithOffsetTable::from_reader(&data[..]).unwrap()).unwrap();
+    assert_eq!(parsed, file);
+    assert_eq!(write_table(parsed.into()), data);
+
+    let bytes = file.objects[0].to_bytes();
+    assert_eq!(bytes.len(), FieldObject::MIN_SIZE + 4);
+    assert_eq!(&bytes[..6], &[0x12, 0, 3, 0, 0xF8, 0xFF]);
+
+    let mut table = DataWithOffsetTable::from(file);
+    table.chunks[1].truncate(4);
+    assert!(matches!(
+        FieldObjectFile::try_from(table),
+        Err(ObjectFileFromTableError::Object {
+            index: 1,
+            source: ObjectDeserializationError::TooShort {
+                min: 0x10,
+                actual: 4
+            }
+        })
+    ));
+}
+
+#[test]
+fn rebuild_battle_object_file() {
+    let file = BattleObjectFile {
+        objects: vec![
+            BattleObject {
+                sprite: 0x40,
+                palette: 2,
+                effects: [5, BattleObject::NO_EFFECT, 9, BattleObject::NO_EFFECT],
+                extra: vec![0xAA; 6],
+            },
+            BattleObject::default(),
+        ],
+        padding: vec![0; 4],
+    };
+    assert_eq!(file.objects[0].used_effects().collect::<Vec<_>>(), [5, 9]);
+    let table = DataWithOffsetTable::from(file.clone());
+    assert_eq!(table.chunks[0].len(), BattleObject::MIN_SIZE + 6);
+    assert_eq!(BattleObjectFile::try_from(table.clone()).unwrap(), file);
+
+    let data = write_table(table);
+    let parsed =
+        BattleObjectFile::try_from(DataWithOffsetTable::from_reader(&data[..]).unwrap()).unwrap();
+    assert_eq!(write_table(parsed.into()), data);
+}
@@ -0,0 +1,89 @@
+use std::fs;
+
+use mnllib::patch::{
+    apply_bps, apply_ips, crc32, create_bps, create_ips, create_patch, BpsApplicationError,
+    PatchFormat,
+};
+
+fn modified_versions(original: &[u8]) -> Vec<Vec<u8>> {
+    let mut edited = original.to_vec();
+    edited[0x10] ^= 0xFF;
+    edited[0x12] ^= 0xFF;
+    edited[0x1000..0x1100].fill(0xAB);
+    edited[0x2000..0x12000].copy_from_slice(&[0x55; 0x10000]);
+    let last = edited.len() - 1;
+    edited[last] ^= 1;
+
+    let mut extended = edited.clone();
+    extended.extend_from_slice(b"appended");
+    let mut truncated = edited.clone();
+    truncated.truncate(original.len() / 2);
+    vec![original.to_vec(), edited, extended, truncated]
+}
+
+#[test]
+fn patch_round_trip() {
+    let original = fs::read("tests/data/overlay.dec/overlay_0003.dec.bin").unwrap();
+    for modified in modified_versions(&original) {
+        let ips = create_ips(&original, &modified).unwrap();
+        assert_eq!(apply_ips(&original, &ips).unwrap(), modified);
+        let bps = create_bps(&original, &modified);
+        assert_eq!(apply_bps(&original, &bps).unwrap(), modified);
+        assert!(bps.len() < modified.len().max(0x100));
+        assert_eq!(
+            create_patch(PatchFormat::Bps, &original, &modified).unwrap(),
+            bps
+        );
+    }
+    assert_eq!(create_ips(&original, &original).unwrap(), b"PATCHEOF");
+
+    let bps = create_bps(&original, &modified_versions(&original)[1]);
+    assert!(matches!(
+        apply_bps(&original[1..], &bps),
+        Err(BpsApplicationError::SourceMismatch)
+    ));
+    let mut corrupted = bps.clone();
+    corrupted[8] ^= 1;
+    assert!(matches!(
+        apply_bps(&original, &corrupted),
+        Err(BpsApplicationError::PatchChecksumMismatch)
+    ));
+}
+
+#[test]
+fn ips_footer_offset() {
+    let original = vec![0u8; 0x454F50];
+    let mut modified = original.clone();
+    modified[0x454F46] = 1;
+    let ips = create_ips(&original, &modified).unwrap();
+    assert_eq!(&ips[5..8], &[0x45, 0x4F, 0x45]);
+    assert_eq!(apply_ips(&original, &ips).unwrap(), modified);
+
+    let mut too_large = vec![0u8; 0x1000001];
+    too_large[0x1000000] = 1;
+    assert!(create_ips(&[], &too_large).is_err());
+}
+
+#[test]
+fn bps_copy_actions() {
+    assert_eq!(crc32(b"123456789"), 0xCBF43926);
+
+    // Reads "abc" from the source, then copies it overlappingly from the target.
+    let mut patch = b"BPS1".to_vec();
+    patch.extend_from_slice(&[0x83, 0x88, 0x80, 0x88, 0x93, 0x80]);
+    patch.extend_from_slice(&crc32(b"abc").to_le_bytes());
+    patch.extend_from_slice(&crc32(b"abcabcab").to_le_bytes());
+    patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+    assert_eq!(apply_bps(b"abc", &patch).unwrap(), b"abcabcab");
+
+    // Claims a target of about 32 GiB, but only reads "abc" from the source.
+    let mut patch = b"BPS1".to_vec();
+    patch.extend_from_slice(&[0x83, 0, 0, 0, 0, 0, 0x80, 0x80, 0x88]);
+    patch.extend_from_slice(&crc32(b"abc").to_le_bytes());
+    patch.extend_from_slice(&crc32(b"abc").to_le_bytes());
+    patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+    assert!(matches!(
+        apply_bps(b"abc", &patch),
+        Err(BpsApplicationError::TargetChecksumMismatch)
+    ));
+}
@@ -0,0 +1,120 @@
+use mnllib::{
+    profile::{GameProfile, GameTitle, Region},
+    rom::NdsHeader,
+};
+#[cfg(feature = "fs")]
+use {
+    mnllib::{
+        consts::FIELD_MAP_CHUNK_TABLE_ADDRESS,
+        misc::ProjectPaths,
+        patch::crc32,
+        profile::{OverlaySignature, BOWSERS_INSIDE_STORY_NORTH_AMERICA, BUILTIN_SIGNATURES},
+    },
+    std::fs,
+};
+
+#[test]
+fn detect_game_profile() {
+    let mut raw = vec![0u8; NdsHeader::SIZE];
+    raw[..0x10].copy_from_slice(b"MARIO&LUIGI3CLJE");
+    raw[NdsHeader::ROM_VERSION_OFFSET] = 1;
+    let header = NdsHeader::from_bytes(&raw).unwrap();
+    let profile = GameProfile::from_header(&header).unwrap();
+    assert_eq!(
+        profile,
+        GameProfile {
+            title: GameTitle::BowsersInsideStory,
+            region: Region::NorthAmerica,
+            version: 1,
+        }
+    );
+    assert_eq!(&profile.game_code(), b"CLJE");
+    assert_eq!(profile.to_string(), "CLJE (rev 1)");
+    assert_eq!(
+        GameProfile::from_game_code(*b"ARMX", 0).map(|x| x.region),
+        Some(Region::Other(b'X'))
+    );
+    assert_eq!(GameProfile::from_game_code(*b"AMCE", 0), None);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+fn detect_dump_game_profile() {
+    let mut raw = vec![0u8; NdsHeader::SIZE];
+    raw[..0x10].copy_from_slice(b"MARIO&LUIGI3CLJE");
+    raw[NdsHeader::ROM_VERSION_OFFSET] = 1;
+    let profile = GameProfile::from_game_code(*b"CLJE", 1).unwrap();
+
+    let original_paths = ProjectPaths::new("tests");
+    let overlay3 = fs::read(original_paths.overlay_path(3)).unwrap();
+    let japan = GameProfile {
+        region: Region::Japan,
+        ..profile
+    };
+    let signatures = [
+        OverlaySignature {
+            profile: japan,
+            overlay_number: 3,
+            crc32: !crc32(&overlay3),
+        },
+        OverlaySignature {
+            profile: japan,
+            overlay_number: 99,
+            crc32: 0,
+        },
+        OverlaySignature {
+            profile,
+            overlay_number: 3,
+            crc32: crc32(&overlay3),
+        },
+    ];
+    assert_eq!(
+        GameProfile::detect_dump(&original_paths, &signatures).unwrap(),
+        Some(profile)
+    );
+    assert_eq!(
+        GameProfile::detect_dump(&original_paths, &signatures[..2]).unwrap(),
+        None
+    );
+
+    let paths = ProjectPaths::new(
+        std::env::temp_dir().join(format!("mnllib-profile-test-{}", std::process::id())),
+    );
+    fs::create_dir_all(&paths.root).unwrap();
+    raw[0x0F] = b'J';
+    fs::write(paths.root.join("header.bin"), &raw).unwrap();
+    assert_eq!(
+        GameProfile::detect_dump(&paths, &signatures).unwrap(),
+        Some(GameProfile {
+            version: 1,
+            ..japan
+        })
+    );
+    fs::remove_dir_all(&paths.root).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[test]
+fn detect_builtin_game_profile() {
+    let profile = GameProfile::detect_dump(&ProjectPaths::new("tests"), BUILTIN_SIGNATURES)
+        .unwrap()
+        .unwrap();
+    assert_eq!(profile, BOWSERS_INSIDE_STORY_NORTH_AMERICA);
+    assert_eq!(&profile.game_code(), b"CLJE");
+    assert_eq!(
+        profile.table_addresses().unwrap().field_map_chunk_table,
+        FIELD_MAP_CHUNK_TABLE_ADDRESS
+    );
+    // Every overlay with a signature identifies the dump on its own.
+    for signature in BUILTIN_SIGNATURES {
+        let overlay =
+            fs::read(ProjectPaths::new("tests").overlay_path(signature.overlay_number)).unwrap();
+        assert_eq!(crc32(&overlay), signature.crc32);
+    }
+
+    let japan = GameProfile {
+        region: Region::Japan,
+        ..profile
+    };
+    assert_eq!(japan.table_addresses(), None);
+}
@@ -0,0 +1,136 @@
+#![cfg(feature = "fs")]
+
+use std::fs;
+
+use mnllib::{misc::ProjectPaths, project::Project, text::MESSAGE_TERMINATOR};
+
+/// Copies the files used by the tests into a new directory.
+fn copy_test_project(name: &str) -> (ProjectPaths, ProjectPaths) {
+    let original_paths = ProjectPaths::new("tests");
+    let paths = ProjectPaths::new(
+        std::env::temp_dir().join(format!("mnllib-{name}-test-{}", std::process::id())),
+    );
+    let files = [
+        original_paths.data_path("BMap/BMap.dat"),
+        original_paths.data_path("BAI/BMes_ji.dat"),
+        original_paths.overlay_path(3),
+    ];
+    for path in &files {
+        let new_path = paths
+            .root
+            .join(path.strip_prefix(&original_paths.root).unwrap());
+        fs::create_dir_all(new_path.parent().unwrap()).unwrap();
+        fs::copy(path, new_path).unwrap();
+    }
+    (original_paths, paths)
+}
+
+#[test]
+fn project_saves_modified_subsystems() {
+    let (original_paths, paths) = copy_test_project("project");
+
+    let mut project = Project::open(paths.clone());
+    assert!(!project.battle_maps().unwrap().maps.is_empty());
+    assert!(!project.is_modified());
+    // Only modified subsystems are written, so this must survive saving.
+    fs::write(paths.data_path("BMap/BMap.dat"), b"untouched").unwrap();
+
+    let (id, _) = project
+        .messages("BAI/BMes_ji.dat")
+        .unwrap()
+        .iter()
+        .next()
+        .unwrap();
+    let message = [b"Hi".as_slice(), &MESSAGE_TERMINATOR].concat();
+    *project
+        .messages_mut("BAI/BMes_ji.dat")
+        .unwrap()
+        .get_mut(id)
+        .unwrap() = message.clone();
+    assert!(project.is_modified());
+    project.save().unwrap();
+    assert!(!project.is_modified());
+
+    assert_eq!(
+        fs::read(paths.data_path("BMap/BMap.dat")).unwrap(),
+        b"untouched"
+    );
+    let overlay3 = fs::read(paths.overlay_path(3)).unwrap();
+    let original_overlay3 = fs::read(original_paths.overlay_path(3)).unwrap();
+    assert_eq!(overlay3, original_overlay3);
+
+    let mut reopened = Project::open(paths.clone());
+    assert_eq!(
+        reopened
+            .messages("BAI/BMes_ji.dat")
+            .unwrap()
+            .get(id)
+            .unwrap(),
+        &message
+    );
+    fs::remove_dir_all(&paths.root).unwrap();
+}
+
+#[test]
+#[cfg(feature = "zip")]
+fn project_archive_round_trip() {
+    let (_, paths) = copy_test_project("project-archive-export");
+    let (_, imported_paths) = copy_test_project("project-archive-import");
+    let archive_path = paths.root.join("wip.zip");
+
+    let mut project = Project::open(paths.clone());
+    let (id, _) = project
+        .messages("BAI/BMes_ji.dat")
+        .unwrap()
+        .iter()
+        .next()
+        .unwrap();
+    let message = [b"Hi".as_slice(), &MESSAGE_TERMINATOR].concat();
+    *project
+        .messages_mut("BAI/BMes_ji.dat")
+        .unwrap()
+        .get_mut(id)
+        .unwrap() = message.clone();
+    project.export_archive(&archive_path).unwrap();
+    // Exporting doesn't save.
+    assert!(project.is_modified());
+
+    let mut imported = Project::open(imported_paths.clone());
+    assert_ne!(
+        imported
+            .messages("BAI/BMes_ji.dat")
+            .unwrap()
+            .get(id)
+            .unwrap(),
+        &message
+    );
+    imported.import_archive(&archive_path).unwrap();
+    assert_eq!(
+        imported
+            .messages("BAI/BMes_ji.dat")
+            .unwrap()
+            .get(id)
+            .unwrap(),
+        &message
+    );
+
+    project.save().unwrap();
+    for path in ["BAI/BMes_ji.dat", "BMap/BMap.dat"] {
+        assert_eq!(
+            fs::read(imported_paths.data_path(path)).unwrap(),
+            fs::read(paths.data_path(path)).unwrap(),
+        );
+    }
+    assert_eq!(
+        fs::read(imported_paths.overlay_path(3)).unwrap(),
+        fs::read(paths.overlay_path(3)).unwrap(),
+    );
+
+    imported.messages_mut("BAI/BMes_ji.dat").unwrap();
+    assert!(matches!(
+        imported.import_archive(&archive_path),
+        Err(mnllib::project::ProjectError::UnsavedModifications)
+    ));
+    fs::remove_dir_all(&paths.root).unwrap();
+    fs::remove_dir_all(&imported_paths.root).unwrap();
+}
@@ -1,19 +1,52 @@
 use std::{
+    collections::HashSet,
     fmt::{Debug, Display},
     fs::{self},
-    io::{Cursor, Write},
+    hash::Hasher,
+    io::{Cursor, Read, Write},
     path::{Path, PathBuf},
 };
 
+use grid::Grid;
 use mnllib::{
-    consts::STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT,
-    map::{BattleMap, BattleMapFile, FieldMapChunk, FieldMaps, Tileset},
+    compress,
+    consts::{
+        BATTLE_MAP_HEIGHT, BATTLE_MAP_WIDTH, FEVENT_OFFSET_TABLE_ADDRESS,
+        FMAPDATA_OFFSET_TABLE_ADDRESS, FMAPDATA_OFFSET_TABLE_LENGTH_ADDRESS,
+        NUMBER_OF_FEVENT_CHUNKS_PER_MAP, NUMBER_OF_FIELD_MAPS,
+        STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT, STANDARD_FILE_ALIGNMENT,
+        TREASURE_INFO_OFFSET_TABLE_LENGTH_ADDRESS,
+    },
+    event::FieldEvents,
+    map::{
+        BattleMap, BattleMapFile, FieldMapChunk, FieldMapChunkFromTableError, FieldMapChunkProblem,
+        FieldMapProperties, FieldMaps, FieldMapsFile, FieldMapsFromFilesError,
+        FieldMapsRenderError, FieldMapsToFilesError, GiantBattleMap, GiantBattleMapFile,
+        GiantBattleMapFileFromTableError, GiantBattleMapFormat, PixelSize, Tile, TileLayer,
+        TileLayerDeserializationError, TileLayerRef, Tileset, TilesetRef, TilesetTile,
+        TilesetTileSerializationError,
+    },
     misc::{
-        filesystem_standard_data_path, filesystem_standard_overlay_path, DataWithOffsetTable,
-        MaybeCompressedData, MaybeSerialized,
+        filesystem_standard_data_path, filesystem_standard_overlay_path, Bgr555, ChunkAlignment,
+        CompressionCache, CompressionCacheError, DataWithOffsetTable,
+        DataWithOffsetTableDeserializationError, DataWithOffsetTableSerializationError,
+        DecompressedChunkCache, MaybeCompressedData, MaybeSerialized, OffsetTableEntrySize,
+        PackedDataWithOffsetTable, Palette, PaletteLut, Rgb555, SalvageProblem,
     },
+    text::{MessageArchive, MessageListSet, MESSAGE_TERMINATOR},
+    utils::{changed_indexes, hash_bytes, Fnv1aHasher},
+    Compressor, Decompressor,
 };
+use rgb::{Rgb, Rgba};
 use rstest::rstest;
+#[cfg(feature = "fs")]
+use {
+    mnllib::{
+        map::FieldMapsIndex,
+        misc::{BackupOptions, ProjectPaths, SaveOptions},
+    },
+    std::num::NonZeroUsize,
+};
 
 fn test_path(path: impl AsRef<Path>) -> PathBuf {
     Path::new("tests").join(path)
@@ -24,6 +57,16 @@ fn test_fs_data_path(filename: impl Display) -> PathBuf {
 fn test_fs_overlay_path(overlay_number: impl Display) -> PathBuf {
     test_path(filesystem_standard_overlay_path(overlay_number))
 }
+/// Like [`FieldMaps::load_from`], which needs the `fs` feature.
+fn test_field_maps() -> FieldMaps {
+    FieldMaps::from_files(
+        &fs::read(test_fs_data_path("FMap/FMapData.dat")).unwrap()[..],
+        &fs::read(test_fs_data_path("Treasure/TreasureInfo.dat")).unwrap()[..],
+        Cursor::new(fs::read(test_fs_overlay_path(3)).unwrap()),
+        Cursor::new(fs::read(test_fs_overlay_path(4)).unwrap()),
+    )
+    .unwrap()
+}
 
 fn rebuild_through_data_with_offset_table<T>(original_data: &[u8], new_data: impl Write)
 where
@@ -61,6 +104,207 @@ fn rebuild_data_with_offset_table_file(
     assert_eq!(new_data, original_data);
 }
 
+#[rstest]
+fn data_with_offset_table_computed_size(
+    #[files("tests/data/data/**/*Mes*.dat")]
+    #[files("tests/data/data/**/mfset_*.dat")]
+    path: PathBuf,
+) {
+    let original_data = fs::read(path).unwrap();
+    let table = DataWithOffsetTable::from_reader(&original_data[..]).unwrap();
+    assert_eq!(
+        table.computed_size(None, true).unwrap(),
+        original_data.len()
+    );
+}
+
+#[rstest]
+fn rebuild_packed_data_with_offset_table_file(
+    #[files("tests/data/data/**/*Mes*.dat")]
+    #[files("tests/data/data/**/mfset_*.dat")]
+    path: PathBuf,
+) {
+    let original_data = fs::read(path).unwrap();
+    let packed = PackedDataWithOffsetTable::from_reader(&original_data[..]).unwrap();
+    let unpacked = DataWithOffsetTable::from_reader(&original_data[..]).unwrap();
+    assert!(packed
+        .chunks
+        .iter()
+        .eq(unpacked.chunks.iter().map(Vec::as_slice)));
+
+    let mut new_data: Vec<u8> = Vec::new();
+    packed.to_writer(&mut new_data, None, true).unwrap();
+    assert_eq!(new_data, original_data);
+    assert_eq!(DataWithOffsetTable::from(packed), unpacked);
+}
+
+#[rstest]
+fn packed_data_with_offset_table_huge_offset() {
+    // A single chunk claiming to be almost 4 GiB large, followed by 2 bytes.
+    let data = [8, 0, 0, 0, 0xF0, 0xFF, 0xFF, 0xFF, 1, 2];
+    assert!(matches!(
+        PackedDataWithOffsetTable::from_reader(&data[..]),
+        Err(DataWithOffsetTableDeserializationError::Chunk {
+            index: 0,
+            offset: 8,
+            ..
+        })
+    ));
+}
+
+#[rstest]
+fn rebuild_message_archive(#[files("tests/data/data/**/*Mes*.dat")] path: PathBuf) {
+    let original_data = fs::read(path).unwrap();
+    let mut new_data: Vec<u8> = Vec::new();
+
+    rebuild_through_data_with_offset_table::<MessageArchive>(&original_data, &mut new_data);
+
+    assert_eq!(new_data, original_data);
+}
+
+#[rstest]
+fn rebuild_message_list_set(#[files("tests/data/data/**/mfset_*.dat")] path: PathBuf) {
+    let original_data = fs::read(path).unwrap();
+    let mut new_data: Vec<u8> = Vec::new();
+
+    rebuild_through_data_with_offset_table::<MessageListSet>(&original_data, &mut new_data);
+
+    assert_eq!(new_data, original_data);
+}
+
+#[rstest]
+fn message_archive_ids(#[files("tests/data/data/**/*Mes*.dat")] path: PathBuf) {
+    let archive = MessageArchive::try_from(
+        DataWithOffsetTable::from_reader(&fs::read(path).unwrap()[..]).unwrap(),
+    )
+    .unwrap();
+
+    let mut num_messages = 0;
+    for (id, message) in archive.iter() {
+        assert!(message.ends_with(&MESSAGE_TERMINATOR));
+        assert_eq!(archive.get(id), Some(message));
+        num_messages += 1;
+    }
+    assert_ne!(num_messages, 0);
+}
+
+#[rstest]
+fn rebuild_data_with_offset_table_16_bit(#[files("tests/data/data/**/mfset_*.dat")] path: PathBuf) {
+    let original_table = DataWithOffsetTable::from_reader(&fs::read(path).unwrap()[..]).unwrap();
+    let mut data_16_bit: Vec<u8> = Vec::new();
+
+    original_table
+        .to_writer_with_entry_size(&mut data_16_bit, OffsetTableEntrySize::U16, None, true)
+        .unwrap();
+
+    assert_eq!(
+        DataWithOffsetTable::from_reader_with_entry_size(
+            &data_16_bit[..],
+            OffsetTableEntrySize::U16
+        )
+        .unwrap(),
+        original_table
+    );
+}
+
+#[rstest]
+fn reject_data_with_offset_table_overflow() {
+    let table = DataWithOffsetTable {
+        chunks: vec![vec![1; 0x10], vec![2; 0x10000]],
+        footer: Vec::new(),
+    };
+
+    assert!(matches!(
+        table.to_writer_with_entry_size(&mut Vec::new(), OffsetTableEntrySize::U16, None, true),
+        Err(DataWithOffsetTableSerializationError::OffsetOverflow {
+            index: 2,
+            offset: 0x10016,
+            limit: 0xFFFF,
+        })
+    ));
+    assert!(table
+        .to_writer_with_entry_size(&mut Vec::new(), OffsetTableEntrySize::U32, None, true)
+        .is_ok());
+}
+
+#[rstest]
+fn read_damaged_data_with_offset_table_leniently() {
+    let table = DataWithOffsetTable {
+        chunks: vec![vec![1, 2, 3, 4], vec![5, 6]],
+        footer: Vec::new(),
+    };
+    let mut data: Vec<u8> = Vec::new();
+    table.to_writer(&mut data, None, true).unwrap();
+
+    let (lenient_table, report) = DataWithOffsetTable::from_reader_lenient(&data[..]).unwrap();
+    assert_eq!(lenient_table, table);
+    assert!(report.is_clean());
+
+    data.pop();
+    assert!(DataWithOffsetTable::from_reader(&data[..]).is_err());
+    let (lenient_table, report) = DataWithOffsetTable::from_reader_lenient(&data[..]).unwrap();
+    assert_eq!(lenient_table.chunks, [vec![1, 2, 3, 4], vec![5]]);
+    assert_eq!(
+        report.problems,
+        [SalvageProblem::TruncatedChunk {
+            index: 1,
+            offset: 16,
+            expected_size: 2,
+            actual_size: 1,
+        }]
+    );
+
+    let (lenient_table, report) = DataWithOffsetTable::from_reader_lenient(&data[..6]).unwrap();
+    assert!(lenient_table.chunks.is_empty());
+    assert_eq!(
+        report.problems,
+        [SalvageProblem::TruncatedOffsetTable { num_offsets: 1 }]
+    );
+}
+
+#[rstest]
+fn read_data_with_offset_table_with_decreasing_offset_leniently() {
+    let table = DataWithOffsetTable {
+        chunks: vec![vec![1, 2, 3, 4], vec![5, 6], vec![7, 8, 9], vec![10]],
+        footer: vec![11, 12],
+    };
+    let mut data: Vec<u8> = Vec::new();
+    table.to_writer(&mut data, None, true).unwrap();
+    assert_eq!(&data[..4], 20u32.to_le_bytes());
+    data[4..8].copy_from_slice(&0xFFFFu32.to_le_bytes());
+
+    assert!(DataWithOffsetTable::from_reader(&data[..]).is_err());
+    let (lenient_table, report) = DataWithOffsetTable::from_reader_lenient(&data[..]).unwrap();
+    assert_eq!(
+        lenient_table.chunks,
+        [
+            vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+            vec![],
+            vec![7, 8, 9],
+            vec![10],
+        ]
+    );
+    assert_eq!(lenient_table.footer, [11, 12]);
+    assert_eq!(
+        report.problems,
+        [
+            SalvageProblem::TruncatedChunk {
+                index: 0,
+                offset: 20,
+                expected_size: 0xFFFF - 20,
+                actual_size: 12,
+            },
+            SalvageProblem::DecreasingOffset {
+                index: 2,
+                offset: 26,
+                previous_offset: 0xFFFF,
+            },
+            SalvageProblem::UnreliableChunk { index: 0 },
+            SalvageProblem::UnreliableChunk { index: 2 },
+        ]
+    );
+}
+
 #[rstest]
 fn rebuild_field_maps() {
     let original_fmapdata = fs::read(test_fs_data_path("FMap/FMapData.dat")).unwrap();
@@ -95,6 +339,749 @@ fn rebuild_field_maps() {
     assert_eq!(new_overlay4, original_overlay4);
 }
 
+#[rstest]
+fn compute_field_maps_sizes() {
+    let fmapdata = fs::read(test_fs_data_path("FMap/FMapData.dat")).unwrap();
+    let treasure_info = fs::read(test_fs_data_path("Treasure/TreasureInfo.dat")).unwrap();
+    let overlay3 = fs::read(test_fs_overlay_path(3)).unwrap();
+    let overlay4 = fs::read(test_fs_overlay_path(4)).unwrap();
+    let field_maps = FieldMaps::from_files(
+        &fmapdata[..],
+        &treasure_info[..],
+        Cursor::new(&overlay3),
+        Cursor::new(&overlay4),
+    )
+    .unwrap();
+    let length_at = |overlay: &[u8], address: u64| {
+        let address = address as usize;
+        u32::from_le_bytes(overlay[address..address + 4].try_into().unwrap())
+    };
+
+    let sizes = field_maps.computed_sizes(true, None).unwrap();
+    assert_eq!(sizes.fmapdata, fmapdata.len());
+    assert_eq!(sizes.treasure_info, treasure_info.len());
+    assert_eq!(
+        sizes.fmapdata_offset_table,
+        length_at(&overlay3, FMAPDATA_OFFSET_TABLE_LENGTH_ADDRESS)
+    );
+    assert_eq!(
+        sizes.treasure_info_offset_table,
+        length_at(&overlay4, TREASURE_INFO_OFFSET_TABLE_LENGTH_ADDRESS)
+    );
+}
+
+#[rstest]
+fn read_truncated_field_maps_leniently() {
+    let fmapdata = fs::read(test_fs_data_path("FMap/FMapData.dat")).unwrap();
+    let treasure_info = fs::read(test_fs_data_path("Treasure/TreasureInfo.dat")).unwrap();
+    let overlay3 = fs::read(test_fs_overlay_path(3)).unwrap();
+    let overlay4 = fs::read(test_fs_overlay_path(4)).unwrap();
+    let truncated_fmapdata = &fmapdata[..fmapdata.len() / 2];
+
+    let field_maps = FieldMaps::from_files(
+        &fmapdata[..],
+        &treasure_info[..],
+        Cursor::new(&overlay3),
+        Cursor::new(&overlay4),
+    )
+    .unwrap();
+    assert!(FieldMaps::from_files(
+        truncated_fmapdata,
+        &treasure_info[..],
+        Cursor::new(&overlay3),
+        Cursor::new(&overlay4),
+    )
+    .is_err());
+    let (salvaged, report) = FieldMaps::from_files_lenient(
+        truncated_fmapdata,
+        &treasure_info[..],
+        Cursor::new(&overlay3),
+        Cursor::new(&overlay4),
+    )
+    .unwrap();
+
+    assert!(report.treasure_info.is_clean());
+    assert_eq!(salvaged.treasure_data, field_maps.treasure_data);
+    let Some(SalvageProblem::TruncatedChunk {
+        index: first_truncated,
+        ..
+    }) = report.fmapdata.problems.first().cloned()
+    else {
+        panic!("the truncated chunks should be reported");
+    };
+    assert!(report
+        .fmapdata
+        .problems
+        .iter()
+        .all(|x| matches!(x, SalvageProblem::TruncatedChunk { .. })));
+    assert_eq!(
+        salvaged.fmapdata_chunks.len(),
+        field_maps.fmapdata_chunks.len()
+    );
+    assert_eq!(
+        salvaged.fmapdata_chunks[..first_truncated],
+        field_maps.fmapdata_chunks[..first_truncated]
+    );
+}
+
+/// `FEvent.dat` isn't part of the test data, so its contents are made up
+/// to match the offset table in overlay 3.
+#[rstest]
+fn rebuild_field_events() {
+    let original_overlay3 = fs::read(test_fs_overlay_path(3)).unwrap();
+    let last_offset_address = FEVENT_OFFSET_TABLE_ADDRESS as usize
+        + 4 * NUMBER_OF_FIELD_MAPS * NUMBER_OF_FEVENT_CHUNKS_PER_MAP;
+    let fevent_size = u32::from_le_bytes(
+        original_overlay3[last_offset_address..last_offset_address + 4]
+            .try_into()
+            .unwrap(),
+    );
+    let original_fevent: Vec<u8> = (0..fevent_size + 0x100).map(|x| x as u8).collect();
+
+    let mut new_fevent: Vec<u8> = Vec::new();
+    let mut new_overlay3 = original_overlay3.clone();
+
+    let field_events =
+        FieldEvents::from_files(&original_fevent[..], Cursor::new(&original_overlay3)).unwrap();
+    assert_eq!(field_events.num_maps(), NUMBER_OF_FIELD_MAPS);
+    assert_eq!(field_events.padding.len(), 0x100);
+    field_events
+        .to_files(&mut new_fevent, Cursor::new(&mut new_overlay3), false)
+        .unwrap();
+
+    assert_eq!(new_fevent, original_fevent);
+    assert_eq!(new_overlay3, original_overlay3);
+}
+
+#[rstest]
+fn field_maps_insert_and_remove_chunks() {
+    let original_fmapdata = fs::read(test_fs_data_path("FMap/FMapData.dat")).unwrap();
+    let original_treasure_info = fs::read(test_fs_data_path("Treasure/TreasureInfo.dat")).unwrap();
+    let original_overlay3 = fs::read(test_fs_overlay_path(3)).unwrap();
+    let original_overlay4 = fs::read(test_fs_overlay_path(4)).unwrap();
+
+    let original_field_maps = FieldMaps::from_files(
+        &original_fmapdata[..],
+        &original_treasure_info[..],
+        Cursor::new(&original_overlay3),
+        Cursor::new(&original_overlay4),
+    )
+    .unwrap();
+    let mut field_maps = original_field_maps.clone();
+
+    let remap = field_maps.insert_fmapdata_chunk(0, MaybeCompressedData::Uncompressed(Vec::new()));
+    assert_eq!(
+        field_maps.maps[0].map_chunk_index,
+        remap
+            .apply(original_field_maps.maps[0].map_chunk_index)
+            .unwrap()
+    );
+    assert!(field_maps
+        .remove_fmapdata_chunk(field_maps.maps[0].map_chunk_index)
+        .is_err());
+    field_maps.remove_fmapdata_chunk(0).unwrap();
+    field_maps.insert_treasure_data(0, Vec::new());
+    field_maps.remove_treasure_data(0).unwrap();
+
+    assert_eq!(field_maps, original_field_maps);
+}
+
+#[cfg(feature = "fs")]
+#[rstest]
+fn rebuild_field_maps_through_project_paths() {
+    let original_paths = ProjectPaths::new("tests");
+    let new_paths =
+        ProjectPaths::new(std::env::temp_dir().join(format!("mnllib-test-{}", std::process::id())));
+    let files = [
+        original_paths.data_path("FMap/FMapData.dat"),
+        original_paths.data_path("Treasure/TreasureInfo.dat"),
+        original_paths.overlay_path(3),
+        original_paths.overlay_path(4),
+    ];
+    for path in &files {
+        let new_path = new_paths
+            .root
+            .join(path.strip_prefix(&original_paths.root).unwrap());
+        fs::create_dir_all(new_path.parent().unwrap()).unwrap();
+        fs::copy(path, new_path).unwrap();
+    }
+
+    FieldMaps::load_from(&original_paths)
+        .unwrap()
+        .save_to(
+            &new_paths,
+            true,
+            &SaveOptions {
+                atomic: true,
+                backup: Some(BackupOptions {
+                    directory: new_paths.root.join("backups"),
+                    retention: NonZeroUsize::new(1),
+                }),
+            },
+        )
+        .unwrap();
+
+    for path in &files {
+        let new_path = new_paths
+            .root
+            .join(path.strip_prefix(&original_paths.root).unwrap());
+        assert_eq!(fs::read(new_path).unwrap(), fs::read(path).unwrap());
+    }
+    assert_eq!(
+        fs::read_dir(new_paths.root.join("backups"))
+            .unwrap()
+            .count(),
+        1
+    );
+    fs::remove_dir_all(&new_paths.root).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[rstest]
+fn back_up_files() {
+    let root = std::env::temp_dir().join(format!("mnllib-backup-test-{}", std::process::id()));
+    let directory = root.join("backups");
+    let file = root.join("file.bin");
+    fs::create_dir_all(&directory).unwrap();
+    fs::create_dir(directory.join("unrelated")).unwrap();
+    let options = BackupOptions {
+        directory: directory.clone(),
+        retention: NonZeroUsize::new(2),
+    };
+
+    // Backups in quick succession get distinct directories.
+    let mut backups = Vec::new();
+    for version in 0..4u8 {
+        fs::write(&file, [version]).unwrap();
+        backups.push(
+            options
+                .back_up([file.as_path(), &root.join("missing.bin")])
+                .unwrap(),
+        );
+    }
+    assert_eq!(backups.iter().collect::<HashSet<_>>().len(), 4);
+
+    // Only the newest backups are kept, besides other directories.
+    let mut remaining: Vec<PathBuf> = fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    remaining.sort();
+    assert_eq!(
+        remaining,
+        [
+            backups[2].clone(),
+            backups[3].clone(),
+            directory.join("unrelated")
+        ]
+    );
+    assert_eq!(fs::read(backups[3].join("file.bin")).unwrap(), [3]);
+    assert!(!backups[3].join("missing.bin").exists());
+
+    let keep_all = BackupOptions {
+        retention: None,
+        ..options.clone()
+    };
+    keep_all.back_up([file.as_path()]).unwrap();
+    assert_eq!(fs::read_dir(&directory).unwrap().count(), 4);
+
+    // The newest backup is kept even if the clock went back since the previous one.
+    let future = directory.join(format!("backup-{:020}-{:010}", u64::MAX, 0));
+    fs::create_dir(&future).unwrap();
+    let backup = options.back_up([file.as_path()]).unwrap();
+    assert!(backup.exists());
+    assert!(future.exists());
+    assert_eq!(fs::read_dir(&directory).unwrap().count(), 3);
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[rstest]
+fn commit_pending_files() {
+    let root = std::env::temp_dir().join(format!("mnllib-commit-test-{}", std::process::id()));
+    let (existing, new, directory) = (root.join("a.bin"), root.join("b.bin"), root.join("c"));
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(&existing, [1]).unwrap();
+    let options = SaveOptions {
+        atomic: true,
+        backup: None,
+    };
+    let write = |paths: [&Path; 2]| {
+        let (pending, files, []) = options.open_files(paths, []).unwrap();
+        for mut file in files {
+            file.write_all(&[2]).unwrap();
+        }
+        pending.commit()
+    };
+    let file_names = || {
+        let mut names: Vec<_> = fs::read_dir(&root)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        names
+    };
+
+    // A directory can't be replaced by a file, so the other files are rolled back.
+    assert!(write([&existing, &directory]).is_err());
+    assert!(write([&new, &directory]).is_err());
+    assert_eq!(fs::read(&existing).unwrap(), [1]);
+    assert_eq!(file_names(), ["a.bin", "c"]);
+
+    write([&existing, &new]).unwrap();
+    assert_eq!(fs::read(&existing).unwrap(), [2]);
+    assert_eq!(fs::read(&new).unwrap(), [2]);
+    assert_eq!(file_names(), ["a.bin", "b.bin", "c"]);
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[rstest]
+fn rebuild_field_maps_cached() {
+    let original_fmapdata = fs::read(test_fs_data_path("FMap/FMapData.dat")).unwrap();
+    let original_treasure_info = fs::read(test_fs_data_path("Treasure/TreasureInfo.dat")).unwrap();
+    let original_overlay3 = fs::read(test_fs_overlay_path(3)).unwrap();
+    let original_overlay4 = fs::read(test_fs_overlay_path(4)).unwrap();
+
+    let mut field_maps = FieldMaps::from_files(
+        &original_fmapdata[..],
+        &original_treasure_info[..],
+        Cursor::new(&original_overlay3),
+        Cursor::new(&original_overlay4),
+    )
+    .unwrap();
+    for map in &field_maps.maps {
+        let chunk = &mut field_maps.fmapdata_chunks[map.map_chunk_index];
+        let original_chunk = chunk.clone();
+        chunk.make_cached(true).unwrap();
+        assert!(chunk.content_eq(&original_chunk, true).unwrap());
+    }
+
+    let mut new_fmapdata: Vec<u8> = Vec::new();
+    let mut new_treasure_info: Vec<u8> = Vec::new();
+    let mut new_overlay3 = original_overlay3.clone();
+    let mut new_overlay4 = original_overlay4.clone();
+    field_maps
+        .to_files(
+            &mut new_fmapdata,
+            &mut new_treasure_info,
+            Cursor::new(&mut new_overlay3),
+            Cursor::new(&mut new_overlay4),
+            true,
+        )
+        .unwrap();
+
+    assert_eq!(new_fmapdata, original_fmapdata);
+    assert_eq!(new_treasure_info, original_treasure_info);
+    assert_eq!(new_overlay3, original_overlay3);
+    assert_eq!(new_overlay4, original_overlay4);
+}
+
+#[rstest]
+fn field_maps_pad_unaligned_chunks() {
+    let original_fmapdata = fs::read(test_fs_data_path("FMap/FMapData.dat")).unwrap();
+    let original_treasure_info = fs::read(test_fs_data_path("Treasure/TreasureInfo.dat")).unwrap();
+    let original_overlay3 = fs::read(test_fs_overlay_path(3)).unwrap();
+    let original_overlay4 = fs::read(test_fs_overlay_path(4)).unwrap();
+
+    let mut field_maps = FieldMaps::from_files(
+        &original_fmapdata[..],
+        &original_treasure_info[..],
+        Cursor::new(&original_overlay3),
+        Cursor::new(&original_overlay4),
+    )
+    .unwrap();
+    field_maps.treasure_data[0] = vec![1, 2, 3];
+
+    let mut new_fmapdata = Vec::new();
+    let mut new_treasure_info = Vec::new();
+    field_maps
+        .to_files(
+            &mut new_fmapdata,
+            &mut new_treasure_info,
+            Cursor::new(original_overlay3.clone()),
+            Cursor::new(original_overlay4.clone()),
+            true,
+        )
+        .unwrap();
+
+    assert_eq!(new_fmapdata, original_fmapdata);
+    assert_eq!(&new_treasure_info[..4], &[1, 2, 3, 0]);
+    assert_eq!(new_treasure_info.len() % STANDARD_FILE_ALIGNMENT, 0);
+}
+
+#[rstest]
+fn field_maps_errors_have_context() {
+    let original_fmapdata = fs::read(test_fs_data_path("FMap/FMapData.dat")).unwrap();
+    let original_treasure_info = fs::read(test_fs_data_path("Treasure/TreasureInfo.dat")).unwrap();
+    let original_overlay3 = fs::read(test_fs_overlay_path(3)).unwrap();
+    let original_overlay4 = fs::read(test_fs_overlay_path(4)).unwrap();
+
+    let mut overlay3 = original_overlay3.clone();
+    let second_offset = usize::try_from(FMAPDATA_OFFSET_TABLE_ADDRESS).unwrap() + 2 * 4;
+    overlay3[second_offset..][..4].copy_from_slice(&0u32.to_le_bytes());
+    assert!(matches!(
+        FieldMaps::from_files(
+            &original_fmapdata[..],
+            &original_treasure_info[..],
+            Cursor::new(&overlay3),
+            Cursor::new(&original_overlay4),
+        ),
+        Err(FieldMapsFromFilesError::DecreasingOffset {
+            file: FieldMapsFile::FMapData,
+            index: 2,
+            offset: 0,
+            ..
+        })
+    ));
+
+    let mut field_maps = FieldMaps::from_files(
+        &original_fmapdata[..],
+        &original_treasure_info[..],
+        Cursor::new(&original_overlay3),
+        Cursor::new(&original_overlay4),
+    )
+    .unwrap();
+    assert!(matches!(
+        field_maps.to_files(
+            &mut Vec::new(),
+            &mut [][..],
+            Cursor::new(original_overlay3.clone()),
+            Cursor::new(original_overlay4.clone()),
+            true,
+        ),
+        Err(FieldMapsToFilesError::File {
+            file: FieldMapsFile::TreasureInfo,
+            ..
+        })
+    ));
+    field_maps.maps[7].map_chunk_index = usize::MAX;
+    assert!(matches!(
+        field_maps.to_files(
+            &mut Vec::new(),
+            &mut Vec::new(),
+            Cursor::new(original_overlay3.clone()),
+            Cursor::new(original_overlay4.clone()),
+            true,
+        ),
+        Err(FieldMapsToFilesError::MapEntry { map_index: 7, .. })
+    ));
+}
+
+#[cfg(feature = "fs")]
+#[rstest]
+fn read_field_map_chunks_on_demand() {
+    let paths = ProjectPaths::new(test_path(""));
+    let field_maps = FieldMaps::load_from(&paths).unwrap();
+    let index = FieldMapsIndex::load_from(&paths).unwrap();
+    assert_eq!(index.maps, field_maps.maps);
+    assert_eq!(
+        index.num_fmapdata_chunks(),
+        field_maps.fmapdata_chunks.len()
+    );
+    assert_eq!(index.num_treasure_data(), field_maps.treasure_data.len());
+
+    let mut fmapdata = fs::File::open(test_fs_data_path("FMap/FMapData.dat")).unwrap();
+    let mut treasure_info = fs::File::open(test_fs_data_path("Treasure/TreasureInfo.dat")).unwrap();
+    for i in [5, 0, field_maps.fmapdata_chunks.len() - 1] {
+        assert_eq!(
+            index.read_fmapdata_chunk(&mut fmapdata, i).unwrap(),
+            field_maps.fmapdata_chunks[i]
+        );
+    }
+    assert_eq!(
+        index.read_treasure_data(&mut treasure_info, 1).unwrap(),
+        field_maps.treasure_data[1]
+    );
+    assert!(matches!(
+        index.read_fmapdata_chunk(&mut fmapdata, field_maps.fmapdata_chunks.len()),
+        Err(FieldMapsFromFilesError::NoSuchChunk {
+            file: FieldMapsFile::FMapData,
+            ..
+        })
+    ));
+}
+
+#[cfg(feature = "fs")]
+#[rstest]
+fn read_field_map_chunks_packed() {
+    let paths = ProjectPaths::new(test_path(""));
+    let field_maps = FieldMaps::load_from(&paths).unwrap();
+    let index = FieldMapsIndex::load_from(&paths).unwrap();
+
+    let fmapdata = index
+        .read_fmapdata_packed(fs::File::open(test_fs_data_path("FMap/FMapData.dat")).unwrap())
+        .unwrap();
+    assert_eq!(fmapdata.len(), field_maps.fmapdata_chunks.len());
+    for (packed, chunk) in fmapdata.iter().zip(&field_maps.fmapdata_chunks) {
+        let MaybeCompressedData::Compressed(chunk) = chunk else {
+            unreachable!();
+        };
+        assert_eq!(packed, &chunk[..]);
+    }
+    let treasure_data = index
+        .read_treasure_data_packed(
+            fs::File::open(test_fs_data_path("Treasure/TreasureInfo.dat")).unwrap(),
+        )
+        .unwrap();
+    assert_eq!(treasure_data.to_vecs(), field_maps.treasure_data);
+}
+
+#[rstest]
+fn reuse_compressor() {
+    let field_maps = test_field_maps();
+    let mut compressor = Compressor::new();
+    for chunk in &field_maps.fmapdata_chunks[..16] {
+        let MaybeCompressedData::Compressed(original) = chunk else {
+            unreachable!();
+        };
+        let uncompressed = chunk.to_uncompressed(true).unwrap();
+        let compressed = compressor.compress_to_vec(&uncompressed).unwrap();
+
+        let mut buf = Cursor::new(Vec::new());
+        compress(&uncompressed, &mut buf).unwrap();
+        assert_eq!(compressed, buf.into_inner());
+        // The chunks are padded after the compressed data.
+        assert_eq!(&original[..compressed.len()], compressed);
+        assert!(original[compressed.len()..].iter().all(|&x| x == 0));
+    }
+}
+
+#[rstest]
+fn compress_field_map_chunks_in_parallel() {
+    let field_maps = test_field_maps();
+    // Compressing is slow, so only the smallest chunks are recompressed.
+    let mut chunks: Vec<MaybeCompressedData> = field_maps.fmapdata_chunks[..16].to_vec();
+    chunks.sort_by_key(|x| x.to_compressed().unwrap().len());
+    for chunk in &mut chunks[..8] {
+        chunk.make_uncompressed(true).unwrap();
+    }
+
+    let compressed = MaybeCompressedData::to_compressed_parallel(&chunks, None).unwrap();
+    assert_eq!(compressed.len(), chunks.len());
+    for (chunk, data) in chunks.iter().zip(&compressed) {
+        assert_eq!(chunk.to_compressed().unwrap(), *data);
+    }
+}
+
+#[rstest]
+fn hash_chunks() {
+    // From the reference test vectors of FNV-1a.
+    assert_eq!(hash_bytes(b""), 0xCBF29CE484222325);
+    assert_eq!(hash_bytes(b"a"), 0xAF63DC4C8601EC8C);
+    assert_eq!(hash_bytes(b"foobar"), 0x85944171F73967E8);
+    let mut hasher = Fnv1aHasher::default();
+    hasher.write(b"foo");
+    hasher.write(b"bar");
+    assert_eq!(hasher.finish(), hash_bytes(b"foobar"));
+
+    let data = vec![0x12, 0x34, 0x12, 0x34, 0x56];
+    let compressed = MaybeCompressedData::Compressed(
+        MaybeCompressedData::Uncompressed(data.clone())
+            .to_compressed()
+            .unwrap()
+            .into_owned(),
+    );
+    assert_eq!(compressed.content_digest(true).unwrap(), hash_bytes(&data));
+    assert_eq!(
+        MaybeCompressedData::Uncompressed(data)
+            .content_digest(true)
+            .unwrap(),
+        hash_bytes(&[0x12, 0x34, 0x12, 0x34, 0x56])
+    );
+
+    let original = DataWithOffsetTable {
+        chunks: vec![vec![1], vec![2, 2], Vec::new(), vec![3]],
+        footer: Vec::new(),
+    };
+    let hashes = original.chunk_hashes();
+    assert_eq!(hashes[1], hash_bytes(&[2, 2]));
+    assert_eq!(changed_indexes(&hashes, &original.chunk_hashes()), []);
+
+    let mut modified = original.clone();
+    modified.chunks[1].push(2);
+    modified.footer.push(0);
+    assert_eq!(changed_indexes(&hashes, &modified.chunk_hashes()), [1]);
+
+    // Every chunk after an inserted or removed one is moved, so it counts as changed.
+    let mut inserted = original.clone();
+    inserted.insert_chunk(1, vec![4]);
+    assert_eq!(
+        changed_indexes(&hashes, &inserted.chunk_hashes()),
+        [1, 2, 3, 4]
+    );
+    let mut removed = original.clone();
+    removed.remove_chunk(1);
+    assert_eq!(changed_indexes(&hashes, &removed.chunk_hashes()), [1, 2, 3]);
+    let mut appended = original;
+    appended.chunks.push(vec![5]);
+    assert_eq!(changed_indexes(&hashes, &appended.chunk_hashes()), [4]);
+}
+
+#[rstest]
+fn align_chunks_exactly() {
+    let original = DataWithOffsetTable {
+        chunks: vec![vec![1, 2, 3], vec![4], vec![5, 6]],
+        footer: vec![0xEE],
+    };
+    let per_chunk = |index: usize, num_chunks: usize| (index + 2 == num_chunks).then_some(8);
+    #[rustfmt::skip]
+    let cases: [(ChunkAlignment, &[u8]); 3] = [
+        (
+            ChunkAlignment::AllButLast { others: Some(4), last: None },
+            &[16, 0, 0, 0, 20, 0, 0, 0, 24, 0, 0, 0, 26, 0, 0, 0,
+              1, 2, 3, 0, 4, 0, 0, 0, 5, 6, 0xEE],
+        ),
+        (
+            ChunkAlignment::AllButLast { others: None, last: Some(4) },
+            &[16, 0, 0, 0, 19, 0, 0, 0, 20, 0, 0, 0, 24, 0, 0, 0,
+              1, 2, 3, 4, 5, 6, 0, 0, 0xEE],
+        ),
+        (
+            ChunkAlignment::PerChunk(&per_chunk),
+            &[16, 0, 0, 0, 19, 0, 0, 0, 27, 0, 0, 0, 29, 0, 0, 0,
+              1, 2, 3, 4, 0, 0, 0, 0, 0, 0, 0, 5, 6, 0xEE],
+        ),
+    ];
+    for (alignment, expected) in cases {
+        let mut buf = Vec::new();
+        original.to_writer(&mut buf, alignment, true).unwrap();
+        assert_eq!(buf, expected, "{alignment:?}");
+        assert_eq!(
+            original.computed_size(alignment, true).unwrap(),
+            expected.len()
+        );
+
+        // Aligning in-place pads the chunks the same way.
+        let mut aligned = original.clone();
+        aligned.align_chunks(alignment);
+        let mut buf = Vec::new();
+        aligned.to_writer(&mut buf, None, true).unwrap();
+        assert_eq!(buf, expected, "{alignment:?}");
+        let mut realigned = aligned.clone();
+        realigned.align_chunks(alignment);
+        assert_eq!(realigned, aligned);
+    }
+
+    // A single chunk is the last one.
+    let single = DataWithOffsetTable {
+        chunks: vec![vec![1]],
+        footer: Vec::new(),
+    };
+    let mut buf = Vec::new();
+    single
+        .to_writer(
+            &mut buf,
+            ChunkAlignment::AllButLast {
+                others: Some(4),
+                last: None,
+            },
+            false,
+        )
+        .unwrap();
+    assert_eq!(buf, [8, 0, 0, 0, 9, 0, 0, 0, 1]);
+}
+
+#[rstest]
+fn compress_field_map_chunks_with_cache() {
+    let original_fmapdata = fs::read(test_fs_data_path("FMap/FMapData.dat")).unwrap();
+    let original_overlay3 = fs::read(test_fs_overlay_path(3)).unwrap();
+    let original_overlay4 = fs::read(test_fs_overlay_path(4)).unwrap();
+    let mut field_maps = FieldMaps::from_files(
+        &original_fmapdata[..],
+        &fs::read(test_fs_data_path("Treasure/TreasureInfo.dat")).unwrap()[..],
+        Cursor::new(&original_overlay3),
+        Cursor::new(&original_overlay4),
+    )
+    .unwrap();
+    let index = (0..16)
+        .min_by_key(|&i| field_maps.fmapdata_chunks[i].to_compressed().unwrap().len())
+        .unwrap();
+    let chunk = &mut field_maps.fmapdata_chunks[index];
+    chunk.make_uncompressed(true).unwrap();
+    let compressed = chunk.to_compressed().unwrap().into_owned();
+
+    let cache = CompressionCache::new();
+    let mut new_fmapdata = Vec::new();
+    field_maps
+        .to_files_with_cache(
+            &mut new_fmapdata,
+            Vec::new(),
+            Cursor::new(original_overlay3.clone()),
+            Cursor::new(original_overlay4.clone()),
+            true,
+            Some(&cache),
+        )
+        .unwrap();
+    assert_eq!(new_fmapdata, original_fmapdata);
+    assert_eq!(cache.len(), 1);
+
+    let mut saved = Vec::new();
+    cache.to_writer(&mut saved).unwrap();
+    let cache = CompressionCache::from_reader(&saved[..]).unwrap();
+    assert_eq!(
+        field_maps.fmapdata_chunks[index]
+            .to_compressed_cached(&cache)
+            .unwrap(),
+        compressed
+    );
+    assert_eq!(cache.len(), 1);
+    let uncompressed = MaybeCompressedData::Uncompressed(vec![1, 2, 3]);
+    assert_eq!(
+        uncompressed.to_compressed_cached(&cache).unwrap(),
+        uncompressed.to_compressed().unwrap()
+    );
+    assert_eq!(cache.len(), 2);
+
+    saved[0] = b'X';
+    assert!(matches!(
+        CompressionCache::from_reader(&saved[..]),
+        Err(CompressionCacheError::InvalidMagic(_))
+    ));
+}
+
+#[rstest]
+fn read_field_map_chunks_lazily() {
+    let field_maps = FieldMaps::from_files(
+        &fs::read(test_fs_data_path("FMap/FMapData.dat")).unwrap()[..],
+        &fs::read(test_fs_data_path("Treasure/TreasureInfo.dat")).unwrap()[..],
+        Cursor::new(fs::read(test_fs_overlay_path(3)).unwrap()),
+        Cursor::new(fs::read(test_fs_overlay_path(4)).unwrap()),
+    )
+    .unwrap();
+    let table = DataWithOffsetTable {
+        chunks: field_maps
+            .fmapdata_chunks
+            .iter()
+            .map(|chunk| chunk.to_compressed().unwrap().into_owned())
+            .collect(),
+        footer: Vec::new(),
+    };
+
+    for (index, chunk) in field_maps.fmapdata_chunks.iter().enumerate() {
+        let uncompressed = chunk.to_uncompressed(true).unwrap();
+        let mut data = Vec::new();
+        chunk.reader(true).unwrap().read_to_end(&mut data).unwrap();
+        assert_eq!(data, &uncompressed[..]);
+
+        let mut decompressor = Decompressor::new(table.chunk_reader(index).unwrap(), true).unwrap();
+        assert_eq!(
+            decompressor.uncompressed_size() as usize,
+            uncompressed.len()
+        );
+        data.clear();
+        // Small reads, so that blocks are decompressed partway through them.
+        let mut buf = [0; 100];
+        loop {
+            match decompressor.read(&mut buf).unwrap() {
+                0 => break,
+                len => data.extend_from_slice(&buf[..len]),
+            }
+        }
+        assert_eq!(data, &uncompressed[..]);
+    }
+    assert!(table.chunk_reader(table.chunks.len()).is_none());
+}
+
 #[rstest]
 #[ignore = "compression and decompression of all chunks is very slow"]
 fn rebuild_field_maps_full() {
@@ -173,6 +1160,437 @@ fn rebuild_field_maps_full() {
     assert_eq!(new_overlay4, original_overlay4);
 }
 
+#[rstest]
+fn render_field_maps_with_chunk_cache() {
+    let field_maps = test_field_maps();
+    let map = &field_maps.maps[0];
+    let map_chunk = &field_maps.fmapdata_chunks[map.map_chunk_index];
+    let num_chunks = 1 + map
+        .tileset_indexes
+        .iter()
+        .flatten()
+        .collect::<HashSet<_>>()
+        .len();
+    let mut cache = DecompressedChunkCache::new(num_chunks);
+
+    assert_eq!(
+        field_maps.render_map_cached(0, &mut cache).unwrap(),
+        field_maps.render_map(0).unwrap()
+    );
+    assert_eq!(cache.len(), num_chunks);
+    assert!(cache.contains(map_chunk));
+    assert_eq!(
+        field_maps.decode_map_cached(0, &mut cache).unwrap(),
+        field_maps.decode_map(0).unwrap()
+    );
+    assert_eq!(cache.len(), num_chunks);
+
+    let modified = MaybeCompressedData::Compressed(
+        MaybeCompressedData::Uncompressed(vec![1, 2, 3])
+            .to_compressed()
+            .unwrap()
+            .into_owned(),
+    );
+    assert!(!cache.contains(&modified));
+    assert_eq!(&cache.get(&modified, true).unwrap()[..], &[1, 2, 3]);
+    // The map chunk is decompressed first, so it's the least recently used.
+    assert!(!cache.contains(map_chunk));
+    assert!(cache.contains(&modified));
+    cache.set_capacity(1);
+    assert_eq!(cache.len(), 1);
+    assert!(cache.contains(&modified));
+}
+
+#[rstest]
+fn borrow_field_map_tiles() {
+    let field_maps = test_field_maps();
+    let map = &field_maps.maps[0];
+    let table = DataWithOffsetTable::from_reader(Cursor::new(
+        field_maps.fmapdata_chunks[map.map_chunk_index]
+            .to_uncompressed(true)
+            .unwrap(),
+    ))
+    .unwrap();
+    let map_chunk = FieldMapChunk::try_from(table.clone()).unwrap();
+    let width = usize::from(map_chunk.properties.width);
+
+    for (data, tile_layer) in table.chunks.iter().zip(&map_chunk.tile_layers) {
+        let Some(tile_layer) = tile_layer else {
+            continue;
+        };
+        let view = TileLayerRef::new(data, width).unwrap();
+        assert_eq!((view.rows(), view.cols()), tile_layer.size());
+        assert_eq!(view.get(1, 2), tile_layer.get(1, 2).copied());
+        assert_eq!(view.get(0, width), None);
+        assert_eq!(&view.to_tile_layer(), tile_layer);
+    }
+    assert!(matches!(
+        TileLayerRef::new(&[0; 6], 2),
+        Err(TileLayerDeserializationError::InvalidInputLength {
+            expected_multiple_of: 4,
+            actual: 6
+        })
+    ));
+
+    let pixel_sizes = map_chunk
+        .properties
+        .tilesets_properties
+        .tileset_pixel_sizes();
+    for (index, pixel_size) in map.tileset_indexes.iter().zip(pixel_sizes) {
+        let Some(index) = index else {
+            continue;
+        };
+        let data = field_maps.fmapdata_chunks[*index]
+            .to_uncompressed(true)
+            .unwrap();
+        let tileset = Tileset::from_bytes(&data, pixel_size).unwrap();
+        let view = TilesetRef::new(&data, pixel_size).unwrap();
+        assert_eq!(view.len(), tileset.0.len());
+        assert_eq!(view.tile(1).as_ref(), tileset.0.get(1));
+        assert_eq!(view.pixel(1, 63), tileset.0.get(1).map(|x| x.0[63]));
+        assert_eq!(view.tile(view.len()), None);
+        assert_eq!(view.to_tileset(), tileset);
+    }
+    assert!(TilesetRef::new(&[0; 33], PixelSize::Nibble).is_err());
+}
+
+#[rstest]
+fn validate_field_map_chunk() {
+    let field_maps = test_field_maps();
+    let map = &field_maps.maps[0];
+    let mut map_chunk = FieldMapChunk::try_from(
+        DataWithOffsetTable::from_reader(Cursor::new(
+            field_maps.fmapdata_chunks[map.map_chunk_index]
+                .to_uncompressed(true)
+                .unwrap(),
+        ))
+        .unwrap(),
+    )
+    .unwrap();
+    let pixel_sizes = map_chunk
+        .properties
+        .tilesets_properties
+        .tileset_pixel_sizes();
+    let tilesets: Vec<_> = (0..3)
+        .map(|layer| {
+            map.tileset_indexes[layer].map(|index| {
+                Tileset::from_bytes(
+                    &field_maps.fmapdata_chunks[index]
+                        .to_uncompressed(true)
+                        .unwrap(),
+                    pixel_sizes[layer],
+                )
+                .unwrap()
+            })
+        })
+        .collect();
+    let tilesets = [
+        tilesets[0].as_ref(),
+        tilesets[1].as_ref(),
+        tilesets[2].as_ref(),
+    ];
+    let layer = (0..3)
+        .find(|&x| map_chunk.tile_layers[x].is_some() && tilesets[x].is_some())
+        .unwrap();
+    assert_eq!(map_chunk.validate(tilesets), []);
+
+    let (cols, rows) = (
+        usize::from(map_chunk.properties.width),
+        usize::from(map_chunk.properties.height),
+    );
+    let tileset_len = tilesets[layer].unwrap().0.len();
+    let tile_layer = map_chunk.tile_layers[layer].as_mut().unwrap();
+    tile_layer[(0, 0)] = Tile::new().with_tileset_tile_id(tileset_len.try_into().unwrap());
+    tile_layer.remove_row(rows - 1);
+    map_chunk.palettes[layer] = Some(Palette(vec![Rgb555::new(0, 0, 0); 300]));
+    assert_eq!(
+        map_chunk.validate(tilesets),
+        [
+            FieldMapChunkProblem::PaletteTooLarge { layer, len: 300 },
+            FieldMapChunkProblem::WrongLayerSize {
+                layer,
+                cols,
+                rows: rows - 1,
+                expected_cols: cols,
+                expected_rows: rows,
+            },
+            FieldMapChunkProblem::TileNotInTileset {
+                layer,
+                row: 0,
+                col: 0,
+                tileset_tile_id: tileset_len.try_into().unwrap(),
+                tileset_len,
+            },
+        ]
+    );
+
+    map_chunk.palettes[layer] = Some(Palette(vec![Rgb555::new(0, 0, 0); 1]));
+    let problems = map_chunk.validate(tilesets);
+    assert!(problems.iter().any(
+        |x| matches!(x, FieldMapChunkProblem::ColorNotInPalette { layer: l, .. } if *l == layer)
+    ));
+    map_chunk.palettes[layer] = None;
+    assert!(map_chunk
+        .validate(tilesets)
+        .contains(&FieldMapChunkProblem::MissingPalette { layer }));
+}
+
+#[rstest]
+fn tile_helpers() {
+    let tile: Tile = "03Ah-2".parse().unwrap();
+    assert_eq!(tile.apply_flip(true, true).to_string(), "03A-v2");
+    assert_eq!(tile.apply_flip(false, false), tile);
+    assert_eq!(
+        tile.with_remapped_tileset_id(|id| id + 1).to_string(),
+        "03Bh-2"
+    );
+    assert!(tile.is_blank(0x3A));
+    assert!(!tile.is_blank(0));
+}
+
+#[rstest]
+fn reject_misaligned_tile_layers() {
+    assert!(matches!(
+        TileLayer::from_bytes(&[0; 5], 2),
+        Err(TileLayerDeserializationError::InvalidInputLength {
+            expected_multiple_of: 4,
+            actual: 5
+        })
+    ));
+    assert_eq!(TileLayer::from_bytes(&[0; 8], 2).unwrap().size(), (2, 2));
+
+    let field_maps = test_field_maps();
+    let mut table = DataWithOffsetTable::from_reader(Cursor::new(
+        field_maps.fmapdata_chunks[field_maps.maps[0].map_chunk_index]
+            .to_uncompressed(true)
+            .unwrap(),
+    ))
+    .unwrap();
+    let index = table.chunks[..3]
+        .iter()
+        .position(|x| !x.is_empty())
+        .unwrap();
+    let layer = &mut table.chunks[index];
+    layer.truncate(layer.len() - 2);
+    assert!(matches!(
+        FieldMapChunk::try_from(table.clone()),
+        Err(FieldMapChunkFromTableError::TileLayerDeserialization { index: i, .. }) if i == index
+    ));
+
+    table.chunks[6].truncate(3);
+    let error = FieldMapChunk::try_from(table).unwrap_err();
+    assert!(matches!(
+        error,
+        FieldMapChunkFromTableError::PropertiesDeserialization(_)
+    ));
+    assert_eq!(
+        error.to_string(),
+        "failed to deserialize the properties in chunk 6"
+    );
+}
+
+#[rstest]
+fn write_field_map_tiles() {
+    let field_maps = test_field_maps();
+    let map = &field_maps.maps[0];
+    let table = DataWithOffsetTable::from_reader(Cursor::new(
+        field_maps.fmapdata_chunks[map.map_chunk_index]
+            .to_uncompressed(true)
+            .unwrap(),
+    ))
+    .unwrap();
+    let map_chunk = FieldMapChunk::try_from(table.clone()).unwrap();
+    for (data, tile_layer) in table.chunks.iter().zip(&map_chunk.tile_layers) {
+        let Some(tile_layer) = tile_layer else {
+            continue;
+        };
+        let mut buf = Vec::new();
+        tile_layer.write_to(&mut buf).unwrap();
+        assert_eq!(&buf, data);
+    }
+    let palette = map_chunk.palettes[0].as_ref().unwrap();
+    let mut buf = Vec::new();
+    palette.write_to(&mut buf).unwrap();
+    assert_eq!(buf, table.chunks[3]);
+
+    let pixel_size = map_chunk
+        .properties
+        .tilesets_properties
+        .tileset_pixel_sizes()[0];
+    let data = field_maps.fmapdata_chunks[map.tileset_indexes[0].unwrap()]
+        .to_uncompressed(true)
+        .unwrap();
+    let tileset = Tileset::from_bytes(&data, pixel_size).unwrap();
+    let mut buf = Vec::new();
+    tileset.write_to(&mut buf, pixel_size).unwrap();
+    assert_eq!(buf, tileset.to_bytes(pixel_size).unwrap());
+    assert_eq!(buf.len(), tileset.0.len() * pixel_size.tile_size());
+
+    let mut tile = TilesetTile([0; 64]);
+    tile.0[5] = 0x10;
+    assert!(matches!(
+        tile.write_to(&mut Vec::new(), PixelSize::Nibble),
+        Err(TilesetTileSerializationError::PixelValueTooLarge { .. })
+    ));
+}
+
+#[rstest]
+fn render_field_map() {
+    let field_maps = test_field_maps();
+    let mut map_chunk = FieldMapChunk::try_from(
+        DataWithOffsetTable::from_reader(Cursor::new(
+            field_maps.fmapdata_chunks[field_maps.maps[0].map_chunk_index]
+                .to_uncompressed(true)
+                .unwrap(),
+        ))
+        .unwrap(),
+    )
+    .unwrap();
+    let (width, height) = (
+        usize::from(map_chunk.properties.width),
+        usize::from(map_chunk.properties.height),
+    );
+
+    let image = field_maps.render_map(0).unwrap();
+    assert_eq!((image.cols(), image.rows()), (width * 8, height * 8));
+    assert!(matches!(
+        field_maps.render_map(field_maps.maps.len()),
+        Err(FieldMapsRenderError::NoSuchMap(_))
+    ));
+
+    let mut tile = TilesetTile([0; 64]);
+    tile.0[0] = 1;
+    let tileset = Tileset(vec![tile]);
+    let mut layer = TileLayer(Grid::init(height, width, Tile::new()));
+    layer[(0, 1)] = Tile::new().with_flipped_horizontally(true);
+    map_chunk.tile_layers = [Some(layer), None, None];
+    map_chunk
+        .properties
+        .tilesets_properties
+        .set_tileset_pixel_sizes([PixelSize::Nibble; 3]);
+    let palette = map_chunk.palettes[0].clone().unwrap();
+    let image = map_chunk.render([Some(&tileset), None, None]).unwrap();
+    assert_eq!(image[(0, 0)], palette.color_as_rgba8888(1));
+    assert_eq!(image[(0, 1)].a, 0);
+    assert_eq!(image[(0, 15)], palette.color_as_rgba8888(1));
+    assert_eq!(image[(0, 8)].a, 0);
+    let lut = PaletteLut::from(&palette);
+    assert_eq!(
+        map_chunk
+            .render_with_luts([Some(&tileset), None, None], [Some(&lut), None, None])
+            .unwrap(),
+        image
+    );
+    // Layers without a lookup table are skipped, like layers without a palette.
+    assert!(map_chunk
+        .render_with_luts([Some(&tileset), None, None], [None; 3])
+        .unwrap()
+        .iter()
+        .all(|x| x.a == 0));
+}
+
+#[rstest]
+fn convert_colors() {
+    for value in 0..32 {
+        let color = Rgb555::new(value, 31 - value, value / 2);
+        let rgb = Rgb::<u8>::from(color);
+        assert_eq!(
+            (rgb.r, rgb.g, rgb.b),
+            (value * 8, (31 - value) * 8, value / 2 * 8)
+        );
+        assert_eq!(Rgb555::from(rgb), color);
+        assert_eq!(Rgba::<u8>::from(color), rgb.with_alpha(0xFF));
+        assert_eq!(Rgb555::from(rgb.with_alpha(0)), color);
+
+        let color = Bgr555::from(color);
+        assert_eq!(Rgb::<u8>::from(color), rgb);
+        assert_eq!(Bgr555::from(rgb.with_alpha(0x80)), color);
+    }
+
+    // The low 3 bits are dropped.
+    assert_eq!(
+        Rgb555::from(Rgb::new(0xFF, 0x07, 0x80)),
+        Rgb555::new(31, 0, 16)
+    );
+    assert_eq!(
+        Bgr555::from(Rgba::new(0xFF, 0x07, 0x80, 0xFF)),
+        Bgr555::new(31, 0, 16)
+    );
+    assert_eq!(
+        Rgb555::new(31, 0, 16).into_bits().to_le_bytes(),
+        [0x1F, 0x40]
+    );
+    assert_eq!(
+        Bgr555::new(31, 0, 16).into_bits().to_le_bytes(),
+        [0x10, 0x7C]
+    );
+}
+
+#[rstest]
+fn palette_lut() {
+    let mut palette = Palette(vec![
+        Rgb555::new(31, 31, 31),
+        Rgb555::new(31, 0, 0),
+        Rgb555::new(0, 16, 1),
+    ]);
+    let lut = PaletteLut::from(&palette);
+    assert_eq!(
+        lut.0,
+        [
+            Rgba::new(0xF8, 0xF8, 0xF8, 0),
+            Rgba::new(0xF8, 0, 0, 0xFF),
+            Rgba::new(0, 0x80, 0x08, 0xFF),
+        ]
+    );
+    assert_eq!(lut.get(3), None);
+
+    palette.0[1] = Rgb555::new(0, 0, 31);
+    assert_eq!(lut.get(1), Some(Rgba::new(0xF8, 0, 0, 0xFF)));
+    assert_eq!(
+        PaletteLut::from(&palette).get(1),
+        Some(palette.color_as_rgba8888(1))
+    );
+}
+
+#[cfg(feature = "toml")]
+#[rstest]
+fn field_map_chunk_toml_round_trip() {
+    let field_maps = test_field_maps();
+    let table = |map: usize| {
+        DataWithOffsetTable::from_reader(Cursor::new(
+            field_maps.fmapdata_chunks[field_maps.maps[map].map_chunk_index]
+                .to_uncompressed(true)
+                .unwrap(),
+        ))
+        .unwrap()
+    };
+    for map in [0, 1] {
+        let map_chunk = FieldMapChunk::try_from(table(map)).unwrap();
+        let text = map_chunk.to_toml().unwrap();
+        let parsed = FieldMapChunk::from_toml(&text).unwrap();
+        assert_eq!(
+            DataWithOffsetTable::try_from(parsed).unwrap(),
+            DataWithOffsetTable::try_from(map_chunk).unwrap()
+        );
+    }
+    assert!(FieldMapChunk::from_toml("tile_layers = [[\"03Ax-0\"], [], []]").is_err());
+}
+
+#[cfg(feature = "toml")]
+#[rstest]
+fn battle_map_toml_round_trip() {
+    let original_data = fs::read(test_fs_data_path("BMap/BMap.dat")).unwrap();
+    let mut battle_map_file =
+        BattleMapFile::try_from(DataWithOffsetTable::from_reader(&original_data[..]).unwrap())
+            .unwrap();
+    let map = &mut battle_map_file.maps[0];
+    let text = map.to_toml().unwrap();
+    map.tileset
+        .get_or_deserialize_with(BattleMap::deserialize_tileset)
+        .unwrap();
+    assert_eq!(&BattleMap::from_toml(&text).unwrap(), map);
+}
+
 #[rstest]
 fn rebuild_battle_map_file() {
     let original_data = fs::read(test_fs_data_path("BMap/BMap.dat")).unwrap();
@@ -183,6 +1601,18 @@ fn rebuild_battle_map_file() {
     assert_eq!(new_data, original_data);
 }
 
+#[rstest]
+fn rebuild_battle_map_file_by_reference() {
+    let original_data = fs::read(test_fs_data_path("BMap/BMap.dat")).unwrap();
+    let table = DataWithOffsetTable::from_reader(&original_data[..]).unwrap();
+    let battle_map_file = BattleMapFile::try_from(table.clone()).unwrap();
+
+    assert_eq!(battle_map_file.to_offset_table().unwrap(), table);
+    let mut new_data: Vec<u8> = Vec::new();
+    battle_map_file.to_writer(&mut new_data).unwrap();
+    assert_eq!(new_data, original_data);
+}
+
 #[rstest]
 #[ignore = "compression and decompression of all tilesets is very slow"]
 fn rebuild_battle_map_file_full() {
@@ -213,3 +1643,125 @@ fn rebuild_battle_map_file_full() {
 
     assert_eq!(new_data, original_data);
 }
+
+#[rstest]
+fn default_map_structures() {
+    let properties = FieldMapProperties {
+        width: 0x20,
+        height: 0x18,
+        ..Default::default()
+    };
+    assert_eq!(
+        properties.tilesets_properties.tileset_pixel_sizes(),
+        [PixelSize::Byte; 3]
+    );
+    let mut data: Vec<u8> = Vec::new();
+    properties.to_writer(&mut data).unwrap();
+    assert_eq!(data, [0x20, 0, 0x18, 0, 0xFF, 0xF7, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(
+        FieldMapProperties::from_reader(&data[..]).unwrap(),
+        properties
+    );
+
+    let mut map = BattleMap::default();
+    assert!(map
+        .tile_layers
+        .iter()
+        .all(|x| x.size() == (BATTLE_MAP_HEIGHT, BATTLE_MAP_WIDTH)));
+    let tileset = map
+        .tileset
+        .serialize_with(BattleMap::serialize_tileset)
+        .unwrap()
+        .into_owned();
+    map.tileset = MaybeSerialized::Serialized(tileset);
+    assert_eq!(
+        map.tileset
+            .get_or_deserialize_with(BattleMap::deserialize_tileset)
+            .unwrap()
+            .0
+            .len(),
+        1
+    );
+}
+
+#[rstest]
+fn maybe_serialized_accessors() {
+    let parse = |data: &[u8]| data.try_into().map(u32::from_le_bytes);
+    let write = |value: &u32| Ok::<_, ()>(value.to_le_bytes().to_vec());
+
+    let mut value = MaybeSerialized::Serialized(vec![1, 0, 0, 0]);
+    assert_eq!(value.as_deserialized(), None);
+    assert!(value.as_deserialized_mut().is_none());
+    assert_eq!(*value.serialize_with(write).unwrap(), [1, 0, 0, 0]);
+    *value.get_or_deserialize_with(parse).unwrap() += 1;
+    assert_eq!(value.as_deserialized(), Some(&2));
+    // The data is only deserialized once.
+    value
+        .get_or_deserialize_with(|_| Err::<u32, ()>(()))
+        .unwrap();
+    *value.as_deserialized_mut().unwrap() = 3;
+    assert_eq!(*value.serialize_with(write).unwrap(), [3, 0, 0, 0]);
+    assert_eq!(value.into_serialized_with(write).unwrap(), [3, 0, 0, 0]);
+
+    let mut invalid = MaybeSerialized::<u32>::Serialized(vec![1]);
+    assert!(invalid.get_or_deserialize_with(parse).is_err());
+    assert_eq!(invalid, MaybeSerialized::Serialized(vec![1]));
+}
+
+#[rstest]
+fn rebuild_giant_battle_map_file() {
+    let format = GiantBattleMapFormat {
+        tileset_pixel_size: PixelSize::Byte,
+        width: 4,
+        num_tile_layers: 2,
+        num_trailing_chunks: 1,
+    };
+    let tileset = Tileset(vec![
+        TilesetTile(std::array::from_fn(|i| i as u8)),
+        TilesetTile(std::array::from_fn(|i| 0xFF - (i as u8 % 3))),
+    ]);
+    let layer = |x: u8| [x, 0].repeat(4 * 3);
+    let table = DataWithOffsetTable {
+        chunks: vec![
+            vec![1, 2, 3, 4],
+            GiantBattleMap::serialize_tileset(&tileset, &format).unwrap(),
+            vec![0x1F, 0x00, 0xE0, 0x03],
+            layer(1),
+            layer(2),
+            vec![5, 6],
+            vec![0xEE; 8],
+        ],
+        footer: Vec::new(),
+    };
+
+    let mut file = GiantBattleMapFile::from_table(table.clone(), format).unwrap();
+    assert_eq!(file.maps.len(), 1);
+    assert_eq!(file.unk_trailing, [vec![0xEE; 8]]);
+    let map = &mut file.maps[0];
+    assert_eq!(map.tile_layers.len(), 2);
+    assert_eq!(map.tile_layers[1].size(), (3, 4));
+    assert_eq!(
+        map.tile_layers[1][(2, 3)].tileset_tile_id(),
+        2,
+        "tile layers should be {} tiles wide",
+        format.width
+    );
+    assert_eq!(
+        map.tileset
+            .get_or_deserialize_with(|x| GiantBattleMap::deserialize_tileset(x, &format))
+            .unwrap(),
+        &tileset
+    );
+    assert_eq!(DataWithOffsetTable::try_from(file.clone()).unwrap(), table);
+
+    file.maps[0].tile_layers.pop();
+    assert!(DataWithOffsetTable::try_from(file).is_err());
+
+    let mut table = table;
+    table.chunks.pop();
+    table.chunks.pop();
+    assert!(matches!(
+        GiantBattleMapFile::from_table(table, format),
+        Err(GiantBattleMapFileFromTableError::InvalidNumberOfChunks(5))
+    ));
+}
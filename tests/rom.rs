@@ -0,0 +1,372 @@
+use std::{
+    fs,
+    io::{self, Cursor},
+};
+
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+#[cfg(feature = "fs")]
+use mnllib::misc::{ProjectPaths, SaveOptions};
+use mnllib::{
+    blz_compress, blz_decompress,
+    map::FieldMaps,
+    misc::{OverlayRecord, OverlayTableError, OverlayTableLocation},
+    rom::{
+        crc16, NdsRom, NdsRomDeserializationError, NdsRomSerializationError, Overlay,
+        OverlayAddressError, OverlayEntry,
+    },
+};
+
+/// A record starting with a little-endian `u16`, with the rest of the entry zeroed.
+struct U16Record(u16);
+
+impl OverlayRecord for U16Record {
+    const MIN_SIZE: usize = 2;
+
+    fn from_bytes(data: &[u8]) -> Self {
+        Self(LittleEndian::read_u16(data))
+    }
+    fn to_bytes(&self, size: usize) -> Vec<u8> {
+        let mut data = vec![0u8; size];
+        LittleEndian::write_u16(&mut data, self.0);
+        data
+    }
+}
+
+fn align(data: &mut Vec<u8>) {
+    data.resize(data.len().next_multiple_of(4), 0);
+}
+
+/// Builds a minimal ROM with overlays 3 and 4 and the field map files.
+fn build_test_rom() -> Vec<u8> {
+    let files = [
+        fs::read("tests/data/overlay.dec/overlay_0003.dec.bin").unwrap(),
+        fs::read("tests/data/overlay.dec/overlay_0004.dec.bin").unwrap(),
+        fs::read("tests/data/data/FMap/FMapData.dat").unwrap(),
+        fs::read("tests/data/data/Treasure/TreasureInfo.dat").unwrap(),
+    ];
+
+    let mut fnt = Vec::new();
+    let subtables: [&[u8]; 3] = [
+        b"\x84FMap\x01\xF0\x88Treasure\x02\xF0\0",
+        b"\x0CFMapData.dat\0",
+        b"\x10TreasureInfo.dat\0",
+    ];
+    let mut subtable_offset = 3 * 8;
+    for (i, (first_file_id, parent)) in [(2u16, 3u16), (2, 0xF000), (3, 0xF000)]
+        .into_iter()
+        .enumerate()
+    {
+        fnt.write_u32::<LittleEndian>(subtable_offset).unwrap();
+        fnt.write_u16::<LittleEndian>(first_file_id).unwrap();
+        fnt.write_u16::<LittleEndian>(parent).unwrap();
+        subtable_offset += subtables[i].len() as u32;
+    }
+    fnt.extend(subtables.concat());
+
+    let mut rom = vec![0u8; 0x200];
+    rom[..12].copy_from_slice(b"MNL TEST ROM");
+    let fnt_offset = rom.len();
+    rom.extend(&fnt);
+    align(&mut rom);
+    let fat_offset = rom.len();
+    rom.resize(fat_offset + files.len() * 8, 0);
+    let overlay_table_offset = rom.len();
+    for (file_id, overlay_number) in [3, 4].into_iter().enumerate() {
+        let entry = [overlay_number, 0x02000000, 0, 0, 0, 0, file_id as u32, 0];
+        for field in entry {
+            rom.write_u32::<LittleEndian>(field).unwrap();
+        }
+    }
+    for (file_id, file) in files.iter().enumerate() {
+        let start = rom.len() as u32;
+        rom.extend(file);
+        let fat_entry = fat_offset + file_id * 8;
+        LittleEndian::write_u32(&mut rom[fat_entry..], start);
+        let end = rom.len() as u32;
+        LittleEndian::write_u32(&mut rom[fat_entry + 4..], end);
+        align(&mut rom);
+    }
+
+    for (offset, value) in [
+        (0x40, fnt_offset),
+        (0x44, fnt.len()),
+        (0x48, fat_offset),
+        (0x4C, files.len() * 8),
+        (0x50, overlay_table_offset),
+        (0x54, 2 * OverlayEntry::SIZE),
+        (0x80, rom.len()),
+    ] {
+        LittleEndian::write_u32(&mut rom[offset..], value as u32);
+    }
+    rom
+}
+
+#[test]
+fn read_rom() {
+    let rom = NdsRom::from_bytes(build_test_rom()).unwrap();
+    assert_eq!(&rom.header.game_title, b"MNL TEST ROM");
+    assert_eq!(
+        rom.file_names.keys().collect::<Vec<_>>(),
+        ["FMap/FMapData.dat", "Treasure/TreasureInfo.dat"]
+    );
+    assert_eq!(
+        rom.file("FMap/FMapData.dat").unwrap(),
+        fs::read("tests/data/data/FMap/FMapData.dat").unwrap()
+    );
+    assert_eq!(
+        rom.overlay(4).unwrap(),
+        fs::read("tests/data/overlay.dec/overlay_0004.dec.bin").unwrap()
+    );
+    assert_eq!(
+        rom.file("FEvent/FEvent.dat").unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+    assert_eq!(rom.overlay(5).unwrap_err().kind(), io::ErrorKind::NotFound);
+
+    #[cfg(feature = "fs")]
+    assert_eq!(
+        FieldMaps::load_from_rom(&rom).unwrap(),
+        FieldMaps::load_from(&ProjectPaths::new("tests")).unwrap()
+    );
+}
+
+#[test]
+fn invalid_rom() {
+    let mut data = build_test_rom();
+    assert!(matches!(
+        NdsRom::from_bytes(data[..0x100].to_vec()),
+        Err(NdsRomDeserializationError::InvalidHeader)
+    ));
+
+    let fat_offset = LittleEndian::read_u32(&data[0x48..]) as usize;
+    LittleEndian::write_u32(&mut data[fat_offset + 8 + 4..], u32::MAX);
+    assert!(matches!(
+        NdsRom::from_bytes(data.clone()),
+        Err(NdsRomDeserializationError::FileOutOfBounds { file_id: 1 })
+    ));
+
+    let mut data = build_test_rom();
+    LittleEndian::write_u32(&mut data[0x44..], u32::MAX);
+    assert!(matches!(
+        NdsRom::from_bytes(data),
+        Err(NdsRomDeserializationError::RegionOutOfBounds("FNT"))
+    ));
+}
+
+#[test]
+fn rebuild_rom() {
+    assert_eq!(crc16(b"123456789"), 0x4B37);
+
+    let mut rom = NdsRom::from_bytes(build_test_rom()).unwrap();
+    let rebuilt = rom.to_bytes().unwrap();
+    assert_eq!(rebuilt.len(), rom.image.len());
+    assert_eq!(rebuilt[0x200..], rom.image[0x200..]);
+    assert_eq!(
+        LittleEndian::read_u16(&rebuilt[0x15E..]),
+        crc16(&rebuilt[..0x15E])
+    );
+    let reread = NdsRom::from_bytes(rebuilt.clone()).unwrap();
+    assert_eq!(reread.to_bytes().unwrap(), rebuilt);
+
+    // The device capacity byte is kept if it's already large enough.
+    let mut huge = reread.clone();
+    huge.header.raw[0x14] = 46;
+    assert_eq!(huge.to_bytes().unwrap()[0x14], 46);
+    huge.header.raw[0x14] = 47;
+    assert!(matches!(
+        huge.to_bytes(),
+        Err(NdsRomSerializationError::DeviceCapacityOverflow(47))
+    ));
+
+    // Growing a file moves the following ones.
+    let original_fat = rom.fat.clone();
+    let mut fmapdata = rom.file("FMap/FMapData.dat").unwrap().to_vec();
+    fmapdata.extend([0xAB; 0x300]);
+    rom.replace_file("FMap/FMapData.dat", fmapdata.clone())
+        .unwrap();
+    let treasure_info = b"shrunk".to_vec();
+    rom.replace_file("Treasure/TreasureInfo.dat", treasure_info.clone())
+        .unwrap();
+    let mut overlay3 = rom.overlay(3).unwrap().to_vec();
+    overlay3.truncate(0x100);
+    rom.replace_overlay(3, overlay3.clone()).unwrap();
+
+    let mut rebuilt = NdsRom::from_bytes(rom.to_bytes().unwrap()).unwrap();
+    assert_eq!(rebuilt.file("FMap/FMapData.dat").unwrap(), fmapdata);
+    assert_eq!(
+        rebuilt.file("Treasure/TreasureInfo.dat").unwrap(),
+        treasure_info
+    );
+    assert_eq!(rebuilt.overlay(3).unwrap(), overlay3);
+    assert_eq!(rebuilt.arm9_overlays[0].ram_size, 0x100);
+    assert!((0..3).all(|i| rebuilt.fat[i].start == original_fat[i].start));
+    assert_eq!(
+        (rebuilt.fat[3].start - original_fat[3].start) as usize % NdsRom::FILE_ALIGNMENT,
+        0
+    );
+    assert!(rebuilt.fat[3].start >= rebuilt.fat[2].end);
+    assert!(rebuilt.header.rom_size >= rebuilt.fat[3].end);
+    assert!(rebuilt
+        .replace_file("FEvent/FEvent.dat", Vec::new())
+        .is_err());
+}
+
+#[cfg(feature = "fs")]
+#[test]
+fn patch_field_maps_in_rom() {
+    let mut rom = NdsRom::from_bytes(build_test_rom()).unwrap();
+    let field_maps = FieldMaps::load_from_rom(&rom).unwrap();
+    field_maps.save_to_rom(&mut rom, true).unwrap();
+
+    let path = std::env::temp_dir().join(format!("mnllib-test-{}.nds", std::process::id()));
+    rom.save(&path, &SaveOptions::default()).unwrap();
+    let patched = NdsRom::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(FieldMaps::load_from_rom(&patched).unwrap(), field_maps);
+}
+
+#[test]
+fn blz_round_trip() {
+    for path in [
+        "tests/data/overlay.dec/overlay_0003.dec.bin",
+        "tests/data/overlay.dec/overlay_0004.dec.bin",
+    ] {
+        let data = fs::read(path).unwrap();
+        let compressed = blz_compress(&data).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(compressed.len() % 4, 0);
+        assert_eq!(blz_decompress(&compressed).unwrap(), data);
+    }
+
+    let zeros = vec![0u8; 0x1000];
+    let compressed = blz_compress(&zeros).unwrap();
+    assert!(compressed.len() < 0x200);
+    assert_eq!(blz_decompress(&compressed).unwrap(), zeros);
+
+    let mut state = 0x12345678u32;
+    let noise = (0..0x400)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(blz_compress(&noise), None);
+    assert_eq!(blz_compress(&[]), None);
+    // A zero size increase means the data isn't compressed.
+    assert_eq!(
+        blz_decompress(&[1, 2, 3, 4, 0, 0, 0, 0]).unwrap(),
+        [1, 2, 3, 4, 0, 0, 0, 0]
+    );
+    assert!(blz_decompress(&[0xFF; 8]).is_err());
+}
+
+#[test]
+fn compressed_overlays() {
+    let mut rom = NdsRom::from_bytes(build_test_rom()).unwrap();
+    let field_maps = FieldMaps::load_from_rom(&rom).unwrap();
+    let overlay3 = rom.overlay(3).unwrap().into_owned();
+    let overlay_table_region = rom.header.arm9_overlays;
+    let overlay_table =
+        &rom.image[overlay_table_region.offset as usize..][..overlay_table_region.size as usize];
+    assert_eq!(
+        OverlayEntry::table_to_bytes(&OverlayEntry::table_from_bytes(overlay_table).unwrap()),
+        overlay_table
+    );
+
+    // Overlays which were compressed are recompressed.
+    rom.arm9_overlays[0].flags |= OverlayEntry::FLAG_COMPRESSED;
+    rom.replace_overlay(3, overlay3.clone()).unwrap();
+    let entry = rom.arm9_overlays[0];
+    assert!(entry.is_compressed());
+    assert_eq!(entry.ram_size as usize, overlay3.len());
+    assert_eq!(
+        entry.compressed_size() as usize,
+        rom.replaced_files[&0].len()
+    );
+    assert!(rom.replaced_files[&0].len() < overlay3.len());
+
+    let mut rebuilt = NdsRom::from_bytes(rom.to_bytes().unwrap()).unwrap();
+    assert_eq!(rebuilt.arm9_overlays[0], entry);
+    assert_eq!(rebuilt.overlay(3).unwrap(), overlay3);
+    assert_eq!(FieldMaps::load_from_rom(&rebuilt).unwrap(), field_maps);
+    field_maps.save_to_rom(&mut rebuilt, true).unwrap();
+    assert!(rebuilt.arm9_overlays[0].is_compressed());
+    assert!(!rebuilt.arm9_overlays[1].is_compressed());
+
+    let file = rebuilt.replaced_files.get_mut(&0).unwrap();
+    let footer = file.len() - 8;
+    file[footer..footer + 4].copy_from_slice(&[0xFF; 4]);
+    assert_eq!(
+        rebuilt.overlay(3).unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    );
+}
+
+#[test]
+fn overlay_addresses() {
+    let mut rom = NdsRom::from_bytes(build_test_rom()).unwrap();
+    let mut overlay = Overlay::from_rom(&rom, 4).unwrap();
+    assert_eq!(overlay.ram_address, 0x02000000);
+    #[cfg(feature = "fs")]
+    assert_eq!(
+        overlay,
+        Overlay::load(&ProjectPaths::new("tests"), 4, 0x02000000).unwrap()
+    );
+    let end = overlay.end_address() as u32;
+    assert!(overlay.contains_address(0x02000000) && !overlay.contains_address(end));
+
+    assert_eq!(
+        overlay.read_at_address(0x02000010, 4).unwrap(),
+        &overlay.data[0x10..0x14]
+    );
+    overlay.write_at_address(end - 4, &[1, 2, 3, 4]).unwrap();
+    assert_eq!(overlay.data[overlay.data.len() - 4..], [1, 2, 3, 4]);
+    for (address, len) in [(0x01FFFFFF, 1), (end - 2, 4), (end, 0x10)] {
+        assert!(matches!(
+            overlay.read_at_address(address, len),
+            Err(OverlayAddressError::OutOfBounds {
+                overlay_number: 4,
+                ..
+            })
+        ));
+    }
+    assert!(overlay.write_at_address(end - 1, &[0; 2]).is_err());
+
+    // Tables can be located by their RAM address.
+    let location = OverlayTableLocation::at_address(&overlay, 0x02001000, 0x20, 16).unwrap();
+    assert_eq!(location.address, 0x1000);
+    let mut records: Vec<U16Record> = location.read(Cursor::new(&overlay.data)).unwrap();
+    records[0].0 = 1234;
+    location
+        .write(&records, Cursor::new(&mut overlay.data))
+        .unwrap();
+    assert_eq!(
+        overlay.read_at_address(0x02001000, 2).unwrap(),
+        [0xD2, 0x04]
+    );
+    assert!(OverlayTableLocation::at_address(&overlay, end - 0x10, 0x20, 1).is_err());
+    assert!(matches!(
+        location.write(&records[1..], Cursor::new(&mut overlay.data)),
+        Err(OverlayTableError::WrongCount {
+            expected: 16,
+            actual: 15
+        })
+    ));
+    let tiny = OverlayTableLocation {
+        entry_size: 1,
+        ..location
+    };
+    assert!(matches!(
+        tiny.read::<U16Record>(Cursor::new(&overlay.data)),
+        Err(OverlayTableError::EntryTooSmall {
+            entry_size: 1,
+            min_size: 2
+        })
+    ));
+
+    overlay.to_rom(&mut rom).unwrap();
+    let rebuilt = NdsRom::from_bytes(rom.to_bytes().unwrap()).unwrap();
+    assert_eq!(Overlay::from_rom(&rebuilt, 4).unwrap(), overlay);
+}
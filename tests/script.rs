@@ -0,0 +1,326 @@
+use mnllib::{
+    battle::BattleScriptFile,
+    flags::FlagId,
+    misc::DataWithOffsetTable,
+    script::{
+        search_scripts, AssemblyError, Command, CommandTable, DisassemblyError, Instruction, Label,
+        Operand, OperandType, Script, ScriptMatch, ScriptParseError, ScriptQuery,
+    },
+};
+
+fn test_command_table() -> CommandTable {
+    let mut table = CommandTable::default();
+    table.insert(0x0000, Some("end"), []);
+    table.insert(
+        0x0001,
+        Some("set_flag"),
+        [OperandType::U16, OperandType::I8],
+    );
+    table.insert(0x0002, Some("jump"), [OperandType::RelativeJump16]);
+    table.insert(
+        0x0003,
+        None::<String>,
+        [OperandType::U32, OperandType::AbsoluteJump32],
+    );
+    table
+}
+
+#[test]
+fn disassemble_script() {
+    let table = test_command_table();
+    #[rustfmt::skip]
+    let data = [
+        0x01, 0x00, 0x34, 0x12, 0xFF,
+        0x02, 0x00, 0xF7, 0xFF,
+        0x03, 0x00, 0x78, 0x56, 0x34, 0x12, 0x15, 0x00, 0x00, 0x00,
+        0x00, 0x00,
+    ];
+
+    let script = Script::disassemble(&data, &table).unwrap();
+    assert_eq!(
+        script.instructions,
+        [
+            Instruction::Label(Label(0)),
+            Instruction::Command(Command {
+                opcode: 0x0001,
+                operands: vec![Operand::U16(0x1234), Operand::I8(-1)],
+            }),
+            Instruction::Command(Command {
+                opcode: 0x0002,
+                operands: vec![Operand::Label(Label(0))],
+            }),
+            Instruction::Command(Command {
+                opcode: 0x0003,
+                operands: vec![Operand::U32(0x12345678), Operand::Label(Label(1))],
+            }),
+            Instruction::Command(Command {
+                opcode: 0x0000,
+                operands: vec![],
+            }),
+            Instruction::Label(Label(1)),
+        ]
+    );
+    assert_eq!(
+        script.display(&table).to_string(),
+        "label_0:\n    set_flag 0x1234, -1\n    jump @label_0\n    \
+         cmd_0003 0x12345678, @label_1\n    end\nlabel_1:\n"
+    );
+}
+
+#[test]
+fn disassemble_invalid_script() {
+    let table = test_command_table();
+
+    assert!(matches!(
+        Script::disassemble(&[0x04, 0x00], &table),
+        Err(DisassemblyError::UnknownOpcode {
+            offset: 0,
+            opcode: 0x0004
+        })
+    ));
+    assert!(matches!(
+        Script::disassemble(&[0x00, 0x00, 0x01, 0x00, 0x34], &table),
+        Err(DisassemblyError::Command { offset: 2, .. })
+    ));
+    assert!(matches!(
+        Script::disassemble(&[0x02, 0x00, 0xFF, 0xFF, 0x00, 0x00], &table),
+        Err(DisassemblyError::InvalidJumpTarget {
+            offset: 0,
+            target: 3
+        })
+    ));
+}
+
+#[test]
+fn assemble_script() {
+    let table = test_command_table();
+    #[rustfmt::skip]
+    let data = [
+        0x01, 0x00, 0x34, 0x12, 0xFF,
+        0x02, 0x00, 0xF7, 0xFF,
+        0x03, 0x00, 0x78, 0x56, 0x34, 0x12, 0x15, 0x00, 0x00, 0x00,
+        0x00, 0x00,
+    ];
+
+    let script = Script::disassemble(&data, &table).unwrap();
+    assert_eq!(script.assemble(&table).unwrap(), data);
+    assert_eq!(
+        Script::parse(&script.display(&table).to_string(), &table).unwrap(),
+        script
+    );
+
+    let script = Script::parse(
+        "start: ; The beginning.\n\
+         \n\
+         set_flag 4660, -0x1\n\
+         jump @start\n\
+         cmd_0003 0x12345678, @end\n\
+         end\n\
+         end:\n",
+        &table,
+    )
+    .unwrap();
+    assert_eq!(script.assemble(&table).unwrap(), data);
+}
+
+#[test]
+fn assemble_invalid_script() {
+    let table = test_command_table();
+
+    assert!(matches!(
+        Script::parse("set_flag 0x10000, 0", &table),
+        Err(ScriptParseError::InvalidOperand { line: 1, .. })
+    ));
+    assert!(matches!(
+        Script::parse("end\njump @nowhere", &table),
+        Err(ScriptParseError::UndefinedLabel { line: 2, .. })
+    ));
+    assert!(matches!(
+        Script::parse("a:\na:", &table),
+        Err(ScriptParseError::DuplicateLabel { line: 2, .. })
+    ));
+    assert!(matches!(
+        Script::parse("frobnicate", &table),
+        Err(ScriptParseError::UnknownCommand { line: 1, .. })
+    ));
+    assert!(matches!(
+        Script::parse("end 1", &table),
+        Err(ScriptParseError::OperandCount {
+            line: 1,
+            expected: 0,
+            actual: 1
+        })
+    ));
+
+    let jump = |label| {
+        Instruction::Command(Command {
+            opcode: 0x0002,
+            operands: vec![Operand::Label(label)],
+        })
+    };
+    assert!(matches!(
+        Script {
+            instructions: vec![jump(Label(0))]
+        }
+        .assemble(&table),
+        Err(AssemblyError::UndefinedLabel { index: 0, .. })
+    ));
+    assert!(matches!(
+        Script {
+            instructions: vec![
+                Instruction::Label(Label(0)),
+                Instruction::Command(Command {
+                    opcode: 0x0001,
+                    operands: vec![Operand::U8(1), Operand::I8(1)],
+                }),
+            ]
+        }
+        .assemble(&table),
+        Err(AssemblyError::OperandType {
+            index: 1,
+            operand_index: 0,
+            expected: OperandType::U16
+        })
+    ));
+    let mut instructions = vec![Instruction::Label(Label(0))];
+    instructions.extend((0..0x4000).map(|_| {
+        Instruction::Command(Command {
+            opcode: 0x0000,
+            operands: vec![],
+        })
+    }));
+    instructions.push(jump(Label(0)));
+    assert!(matches!(
+        Script { instructions }.assemble(&table),
+        Err(AssemblyError::JumpOutOfRange { index: 0x4001, .. })
+    ));
+}
+
+#[test]
+fn disassemble_unknown_opcodes_leniently() {
+    let table = test_command_table();
+    let data = [0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x12];
+
+    let script = Script::disassemble_lenient(&data, &table).unwrap();
+    assert_eq!(
+        script.instructions[2..],
+        [
+            Instruction::Command(Command {
+                opcode: 0x0000,
+                operands: vec![],
+            }),
+            Instruction::Data(vec![0x04, 0x00, 0x12]),
+        ]
+    );
+    let text = script.display(&table).to_string();
+    assert!(text.ends_with("    end\n    .data 0x04, 0x00, 0x12\n"));
+    assert_eq!(Script::parse(&text, &table).unwrap(), script);
+    assert_eq!(script.assemble(&table).unwrap(), data);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn command_table_from_toml() {
+    let table = mnllib::script::CommandTable::from_toml(
+        r#"
+        [commands.0x0000]
+        name = "end"
+
+        [commands.0x0001]
+        name = "set_flag"
+        operands = ["u16", "i8"]
+
+        [commands.0x0002]
+        name = "jump"
+        operands = ["relative_jump16"]
+
+        [commands.3]
+        operands = ["u32", "absolute_jump32"]
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(table, test_command_table());
+    assert_eq!(
+        mnllib::script::CommandTable::from_toml(&table.to_toml().unwrap()).unwrap(),
+        table
+    );
+    assert!(mnllib::script::CommandTable::from_toml("[commands.0xZZ]").is_err());
+}
+
+#[test]
+fn rebuild_battle_script_file() {
+    let table = test_command_table();
+    let original = DataWithOffsetTable {
+        chunks: vec![
+            vec![0x01, 0x00, 0x34, 0x12, 0xFF, 0x00, 0x00],
+            vec![],
+            vec![0x02, 0x00, 0x00, 0x00, 0x42, 0x42, 0x42],
+        ],
+        footer: vec![0; 3],
+    };
+
+    let mut file = BattleScriptFile::from(original.clone());
+    file.disassemble_all(&table).unwrap();
+    assert!(matches!(
+        file.scripts[2].as_deserialized().unwrap().instructions.last(),
+        Some(Instruction::Data(data)) if data == &[0x42; 3]
+    ));
+    assert_eq!(file.to_table(&table).unwrap(), original);
+}
+
+#[test]
+fn search_script_references() {
+    let mut table = test_command_table();
+    table.get_mut(0x0001).unwrap().flag_operands.push(0);
+    table.insert(0x0004, Some("show_message"), [OperandType::U16]);
+    table.get_mut(0x0004).unwrap().message_operands.push(0);
+    table.insert(0x0005, Some("warp"), [OperandType::I16]);
+    table.get_mut(0x0005).unwrap().map_operands.push(0);
+    let command = |opcode, operands| Instruction::Command(Command { opcode, operands });
+    let scripts = [
+        Script {
+            instructions: vec![
+                command(0x0001, vec![Operand::U16(0x1234), Operand::I8(5)]),
+                command(0x0004, vec![Operand::U16(5)]),
+                Instruction::Data(vec![0x05]),
+            ],
+        },
+        Script {
+            instructions: vec![
+                Instruction::Label(Label(0)),
+                command(0x0005, vec![Operand::I16(5)]),
+                command(0x0005, vec![Operand::I16(-1)]),
+                command(0x0000, vec![]),
+            ],
+        },
+    ];
+
+    assert_eq!(
+        scripts[0].find(ScriptQuery::Flag(FlagId(0x1234)), &table),
+        [0]
+    );
+    // The `i8` operand of `set_flag` isn't a flag.
+    assert!(scripts[0]
+        .find(ScriptQuery::Flag(FlagId(5)), &table)
+        .is_empty());
+    assert_eq!(scripts[0].find(ScriptQuery::Message(5), &table), [1]);
+    assert!(scripts[1].find(ScriptQuery::Map(0xFFFF), &table).is_empty());
+    let indexed = || scripts.iter().enumerate();
+    assert_eq!(
+        search_scripts(indexed(), ScriptQuery::Map(5), &table),
+        [ScriptMatch {
+            script: 1,
+            instruction: 1,
+            opcode: 0x0005
+        }]
+    );
+    assert_eq!(
+        search_scripts(indexed(), ScriptQuery::Opcode(0x0000), &table),
+        [ScriptMatch {
+            script: 1,
+            instruction: 3,
+            opcode: 0x0000
+        }]
+    );
+}
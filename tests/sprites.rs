@@ -0,0 +1,415 @@
+use grid::Grid;
+use mnllib::{
+    consts::STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT,
+    map::{Tileset, TilesetTile},
+    misc::{DataWithOffsetTable, Palette, Rgb555},
+    sprites::{
+        Animation, AnimationFrame, ImportOptions, ImportPalette, Sprite, SpriteArchive,
+        SpriteArchiveFromTableError, SpriteCell, SpriteDeserializationError, SpriteImportError,
+        SpriteObject, SpriteObjectAttributes, SpriteRenderError,
+    },
+};
+use rgb::Rgba;
+
+fn test_sprite() -> Sprite {
+    Sprite {
+        tileset: Tileset(
+            (0..4u8)
+                .map(|i| TilesetTile(std::array::from_fn(|j| (i + j as u8) % 16)))
+                .collect(),
+        ),
+        palette: Palette((0..16).map(|i| Rgb555::new(i, 31 - i, i / 2)).collect()),
+        cells: vec![
+            SpriteCell {
+                objects: vec![SpriteObject {
+                    x: -8,
+                    y: -16,
+                    tile: 0,
+                    attributes: SpriteObjectAttributes::new().with_shape(2).with_size(0),
+                }],
+            },
+            SpriteCell {
+                objects: vec![
+                    SpriteObject {
+                        x: -8,
+                        y: -8,
+                        tile: 2,
+                        attributes: SpriteObjectAttributes::new().with_flipped_horizontally(true),
+                    },
+                    SpriteObject {
+                        x: 0,
+                        y: -8,
+                        tile: 3,
+                        attributes: SpriteObjectAttributes::new().with_unk(0x2A),
+                    },
+                ],
+            },
+        ],
+        animations: vec![
+            Animation {
+                loop_start: Some(0),
+                frames: vec![
+                    AnimationFrame {
+                        cell: 0,
+                        duration: 8,
+                    },
+                    AnimationFrame {
+                        cell: 1,
+                        duration: 4,
+                    },
+                ],
+            },
+            Animation {
+                loop_start: None,
+                frames: vec![AnimationFrame {
+                    cell: 1,
+                    duration: 1,
+                }],
+            },
+        ],
+    }
+}
+
+fn write_table(table: DataWithOffsetTable) -> Vec<u8> {
+    let mut data = Vec::new();
+    table
+        .to_writer(
+            &mut data,
+            Some(STANDARD_DATA_WITH_OFFSET_TABLE_ALIGNMENT),
+            true,
+        )
+        .unwrap();
+    data
+}
+
+#[test]
+fn rebuild_sprite_archive() {
+    let archive = SpriteArchive {
+        sprites: vec![test_sprite(), Sprite::default()],
+        padding: vec![0; 4],
+    };
+    let original_data = write_table(archive.clone().try_into().unwrap());
+
+    let parsed =
+        SpriteArchive::try_from(DataWithOffsetTable::from_reader(&original_data[..]).unwrap())
+            .unwrap();
+    assert_eq!(parsed, archive);
+    assert_eq!(
+        parsed.sprites[0].cells[0].objects[0]
+            .attributes
+            .dimensions(),
+        Some((8, 16))
+    );
+    assert_eq!(write_table(parsed.try_into().unwrap()), original_data);
+}
+
+#[test]
+fn invalid_sprite_archive() {
+    let mut table: DataWithOffsetTable = SpriteArchive {
+        sprites: vec![test_sprite()],
+        padding: Vec::new(),
+    }
+    .try_into()
+    .unwrap();
+
+    table.chunks[2].extend([0; 4]);
+    assert!(matches!(
+        SpriteArchive::try_from(table.clone()),
+        Err(SpriteArchiveFromTableError::Sprite {
+            index: 0,
+            source: SpriteDeserializationError::ExtraBytesInInput { chunk: "cells" }
+        })
+    ));
+
+    table.chunks.pop();
+    assert!(matches!(
+        SpriteArchive::try_from(table),
+        Err(SpriteArchiveFromTableError::InvalidNumberOfChunks(3))
+    ));
+}
+
+#[test]
+fn render_sprite_animation() {
+    let sprite = test_sprite();
+    let images = sprite.render_animation(&sprite.animations[0]).unwrap();
+
+    assert_eq!(images.origin, (8, 16));
+    assert_eq!(images.loop_start, Some(0));
+    assert_eq!(
+        images.frames.iter().map(|x| x.duration).collect::<Vec<_>>(),
+        [8, 4]
+    );
+    let (first, second) = (&images.frames[0].image, &images.frames[1].image);
+    assert_eq!((first.cols(), first.rows()), (16, 16));
+    assert_eq!((second.cols(), second.rows()), (16, 16));
+    // Pixel 0 is transparent.
+    assert_eq!(first[(0, 0)], Rgba::new(0, 0, 0, 0));
+    assert_eq!(first[(0, 1)], sprite.palette.color_as_rgba8888(1));
+    // The second tile of the tall object.
+    assert_eq!(first[(8, 1)], sprite.palette.color_as_rgba8888(2));
+    assert_eq!(first[(0, 8)], Rgba::new(0, 0, 0, 0));
+    // Flipped horizontally.
+    assert_eq!(second[(8, 0)], sprite.palette.color_as_rgba8888(9));
+    assert_eq!(second[(8, 8)], sprite.palette.color_as_rgba8888(3));
+    assert_eq!(second[(0, 0)], Rgba::new(0, 0, 0, 0));
+
+    let mut broken = sprite.clone();
+    broken.cells[1].objects[1].tile = 100;
+    assert!(matches!(
+        broken.render_animation(&broken.animations[0]),
+        Err(SpriteRenderError::MissingTile {
+            cell: 1,
+            object: 1,
+            tile: 100
+        })
+    ));
+    assert!(matches!(
+        sprite.render_animation(&Animation {
+            loop_start: None,
+            frames: vec![AnimationFrame {
+                cell: 5,
+                duration: 1
+            }],
+        }),
+        Err(SpriteRenderError::MissingCell { frame: 0, cell: 5 })
+    ));
+}
+
+#[cfg(feature = "gif")]
+#[test]
+fn sprite_animation_to_gif() {
+    let sprite = test_sprite();
+    let mut data = Vec::new();
+    sprite
+        .render_animation(&sprite.animations[0])
+        .unwrap()
+        .to_gif(&mut data)
+        .unwrap();
+    assert!(data.starts_with(b"GIF89a"));
+}
+
+#[test]
+fn import_sprite_cell() {
+    let mut sprite = test_sprite();
+    let original = sprite.render_animation(&sprite.animations[0]).unwrap();
+    let num_tiles = sprite.tileset.0.len();
+
+    // Swap the cells' pictures.
+    sprite
+        .import_cell(
+            0,
+            &original.frames[1].image,
+            original.origin,
+            &ImportOptions::default(),
+        )
+        .unwrap();
+    sprite
+        .import_cell(
+            1,
+            &original.frames[0].image,
+            original.origin,
+            &ImportOptions::default(),
+        )
+        .unwrap();
+    // Only the flipped tile is new.
+    assert_eq!(sprite.tileset.0.len(), num_tiles + 1);
+    assert_eq!(sprite.animations, test_sprite().animations);
+    let imported = sprite.render_animation(&sprite.animations[0]).unwrap();
+    assert_eq!(imported.origin, original.origin);
+    assert_eq!(imported.frames[0].image, original.frames[1].image);
+    assert_eq!(imported.frames[1].image, original.frames[0].image);
+
+    // Colors which aren't in the palette are quantized.
+    let mut image = Grid::init(8, 8, Rgba::new(0, 0, 0, 0));
+    image[(0, 0)] = Rgba::new(0xFF, 0x00, 0x00, 0xFF);
+    image[(0, 1)] = Rgba::new(0x10, 0xFF, 0x00, 0xC0);
+    sprite
+        .import_cell(0, &image, (0, 0), &ImportOptions::default())
+        .unwrap();
+    let object = sprite.cells[0].objects[0];
+    assert_eq!(sprite.cells[0].objects.len(), 1);
+    assert_eq!((object.x, object.y), (0, 0));
+    let tile = &sprite.tileset.0[usize::from(object.tile)];
+    assert_eq!(&tile.0[..3], &[15, 1, 0]);
+
+    sprite
+        .import_cell(
+            0,
+            &image,
+            (0, 0),
+            &ImportOptions {
+                palette_offset: 1,
+                palette: ImportPalette::Regenerate,
+            },
+        )
+        .unwrap();
+    assert_eq!(sprite.palette.0.len(), 32);
+    assert_eq!(sprite.palette.0[17], Rgb555::new(31, 0, 0));
+    assert_eq!(sprite.palette.0[18], Rgb555::new(2, 31, 0));
+    let object = sprite.cells[0].objects[0];
+    assert_eq!(object.attributes.palette_offset(), 1);
+    assert_eq!(
+        &sprite.tileset.0[usize::from(object.tile)].0[..3],
+        &[1, 2, 0]
+    );
+
+    assert!(matches!(
+        sprite.import_cell(5, &image, (0, 0), &ImportOptions::default()),
+        Err(SpriteImportError::MissingCell(5))
+    ));
+    assert!(matches!(
+        sprite.import_cell(
+            0,
+            &image,
+            (0, 0),
+            &ImportOptions {
+                palette_offset: 2,
+                palette: ImportPalette::Quantize
+            }
+        ),
+        Err(SpriteImportError::EmptyPalette(2))
+    ));
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn import_sprite_cell_from_png() {
+    use mnllib::sprites::{image_from_png, image_to_png};
+
+    let mut sprite = test_sprite();
+    let original = sprite.render_animation(&sprite.animations[0]).unwrap();
+    let image = &original.frames[1].image;
+
+    let mut data = Vec::new();
+    image_to_png(image, &mut data).unwrap();
+    let decoded = image_from_png(&data[..]).unwrap();
+    assert_eq!(&decoded, image);
+
+    sprite
+        .import_cell(0, &decoded, original.origin, &ImportOptions::default())
+        .unwrap();
+    let imported = sprite.render_animation(&sprite.animations[0]).unwrap();
+    assert_eq!(imported.frames[0].image, imported.frames[1].image);
+}
+
+/// A 16x8 image with a 32-color palette, whose left tile uses the first palette row
+/// and whose right tile uses the second one.
+fn indexed_test_pixels() -> Grid<u8> {
+    Grid::from_vec(
+        (0..8 * 16)
+            .map(|i| match (i % 16 < 8, i % 3) {
+                (_, 0) => 0,
+                (true, x) => x,
+                (false, x) => 16 + x,
+            })
+            .collect(),
+        16,
+    )
+}
+
+fn bmp_file(pixels: &Grid<u8>, num_colors: u32) -> Vec<u8> {
+    let (width, height) = (pixels.cols() as u32, pixels.rows() as u32);
+    let pixels_offset = 14 + 40 + num_colors * 4;
+    let mut data = Vec::new();
+    data.extend(b"BM");
+    data.extend((pixels_offset + width * height).to_le_bytes());
+    data.extend([0; 4]);
+    data.extend(pixels_offset.to_le_bytes());
+    for x in [40, width, height] {
+        data.extend(x.to_le_bytes());
+    }
+    data.extend(1u16.to_le_bytes());
+    data.extend(8u16.to_le_bytes());
+    data.extend([0; 16]);
+    data.extend(num_colors.to_le_bytes());
+    data.extend(0u32.to_le_bytes());
+    for i in 0..num_colors as u8 {
+        data.extend([i * 8, 0xFF - i * 8, i, 0]);
+    }
+    // Stored from the bottom row up, with the width already a multiple of 4.
+    for row in (0..pixels.rows()).rev() {
+        data.extend(pixels.iter_row(row));
+    }
+    data
+}
+
+#[test]
+fn import_indexed_bmp() {
+    use mnllib::{map::PixelSize, sprites::IndexedImage};
+
+    let pixels = indexed_test_pixels();
+    let image = IndexedImage::from_bmp(&bmp_file(&pixels, 32)[..]).unwrap();
+    assert_eq!(image.pixels, pixels);
+    assert_eq!(image.palette.len(), 32);
+    assert_eq!(
+        image.palette[17],
+        Rgba::new(17, 0xFF - 17 * 8, 17 * 8, 0xFF)
+    );
+    let palette = image.to_palette();
+    assert_eq!(palette.0[17], Rgb555::from(image.palette[17].rgb()));
+
+    let (Tileset(tiles), palette_rows) =
+        Tileset::from_indexed_image(&image.pixels, PixelSize::Nibble).unwrap();
+    assert_eq!(palette_rows, [0, 1]);
+    assert_eq!(&tiles[0].0[..4], &[0, 1, 2, 0]);
+    assert_eq!(&tiles[1].0[..4], &[2, 0, 1, 2]);
+    let (Tileset(tiles), palette_rows) =
+        Tileset::from_indexed_image(&image.pixels, PixelSize::Byte).unwrap();
+    assert_eq!(palette_rows, [0, 0]);
+    assert_eq!(&tiles[1].0[..4], &[18, 0, 17, 18]);
+
+    let mut mixed = pixels.clone();
+    mixed[(7, 15)] = 3;
+    assert!(matches!(
+        Tileset::from_indexed_image(&mixed, PixelSize::Nibble),
+        Err(mnllib::map::TilesetFromIndexedImageError::MultiplePaletteRows { tile: 1 })
+    ));
+    assert!(matches!(
+        IndexedImage::from_bmp(&b"PNG"[..]),
+        Err(mnllib::sprites::IndexedImageDecodingError::InvalidBmp(_))
+    ));
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn import_indexed_png() {
+    use mnllib::sprites::{image_from_png, IndexedImage, IndexedImageDecodingError};
+
+    // 4 bits per pixel, so only the first palette row.
+    let pixels = Grid::from_vec(indexed_test_pixels().iter().map(|x| x & 0x0F).collect(), 16);
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, 16, 8);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Four);
+    encoder.set_palette(&[0, 0, 0, 0xF8, 0, 0, 0, 0xF8, 0][..]);
+    encoder.set_trns(&[0][..]);
+    let mut writer = encoder.write_header().unwrap();
+    writer
+        .write_image_data(
+            &pixels
+                .flatten()
+                .chunks_exact(2)
+                .map(|x| (x[0] << 4) | x[1])
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+    writer.finish().unwrap();
+
+    let image = IndexedImage::from_png(&data[..]).unwrap();
+    assert_eq!(image.pixels, pixels);
+    assert_eq!(
+        image.palette,
+        [
+            Rgba::new(0, 0, 0, 0),
+            Rgba::new(0xF8, 0, 0, 0xFF),
+            Rgba::new(0, 0xF8, 0, 0xFF)
+        ]
+    );
+
+    let mut rgba = Vec::new();
+    mnllib::sprites::image_to_png(&image_from_png(&data[..]).unwrap(), &mut rgba).unwrap();
+    assert!(matches!(
+        IndexedImage::from_png(&rgba[..]),
+        Err(IndexedImageDecodingError::NotIndexed)
+    ));
+}
@@ -0,0 +1,53 @@
+use mnllib::{
+    consts::FIELD_MAP_CHUNK_TABLE_ADDRESS,
+    misc::OverlayTableLocation,
+    rom::OverlayEntry,
+    symbols::{SymbolError, SymbolTable},
+};
+
+#[test]
+fn export_symbols() {
+    let overlays = [3, 4].map(|overlay_number| OverlayEntry {
+        overlay_number,
+        ram_address: 0x0200_0000 + overlay_number * 0x10_0000,
+        ram_size: 0x8_0000,
+        ..Default::default()
+    });
+    let mut symbols = SymbolTable::new(&overlays[..1]);
+    symbols
+        .add_table(
+            "item_table",
+            &OverlayTableLocation {
+                overlay_number: 3,
+                address: 0x2000,
+                entry_size: 0xC,
+                count: 32,
+            },
+        )
+        .unwrap();
+    assert!(matches!(
+        symbols.add_in_overlay("x", 4, 0, None),
+        Err(SymbolError::UnknownOverlay(4))
+    ));
+    assert!(matches!(
+        symbols.add_in_overlay("x", 3, u64::from(u32::MAX), None),
+        Err(SymbolError::AddressTooLarge(_))
+    ));
+
+    let sym = symbols.to_sym();
+    let lines: Vec<&str> = sym.lines().collect();
+    assert_eq!(lines[0], "02300000 overlay_3");
+    assert_eq!(lines[1], "02302000 item_table");
+    assert!(lines.contains(
+        &format!(
+            "{:08X} field_map_chunk_table",
+            0x0230_0000 + FIELD_MAP_CHUNK_TABLE_ADDRESS
+        )
+        .as_str()
+    ));
+    assert!(!sym.contains("treasure_info"));
+
+    let script = SymbolTable::new(&overlays).to_linker_script();
+    assert!(script.contains("overlay_4 = 0x02400000;\noverlay_4_size = 0x80000;\n"));
+    assert!(script.contains("treasure_info_offset_table_length_size = 0x4;\n"));
+}
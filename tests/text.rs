@@ -0,0 +1,144 @@
+use std::{fs, path::PathBuf};
+
+use mnllib::{
+    misc::DataWithOffsetTable,
+    text::{
+        CharacterTable, MessageArchive, MessageList, MessageListSet, TextDecodingError, WrapMode,
+        WrapOptions, WrapWarning,
+    },
+};
+use rstest::rstest;
+
+#[rstest]
+fn decode_and_encode_messages(
+    #[files("tests/data/data/**/*Mes*.dat")]
+    #[files("tests/data/data/**/mfset_*.dat")]
+    path: PathBuf,
+) {
+    let table = CharacterTable::standard();
+    let data = DataWithOffsetTable::from_reader(&fs::read(&path).unwrap()[..]).unwrap();
+    let messages: Vec<Vec<u8>> = if path.to_str().unwrap().contains("Mes") {
+        let archive = MessageArchive::try_from(data).unwrap();
+        archive.iter().map(|(_, x)| x.clone()).collect()
+    } else {
+        let set = MessageListSet::from(data);
+        set.iter().map(|(_, x)| x.clone()).collect()
+    };
+
+    let wrap_options = WrapOptions::new(u32::MAX);
+    for message in messages {
+        let text = table.decode(&message).unwrap();
+        assert_eq!(table.encode(&text).unwrap(), message);
+
+        let wrapped = table
+            .encode_wrapped(&text, &|_: &[u8]| 1, &wrap_options)
+            .unwrap();
+        assert_eq!(wrapped.data, message);
+        assert!(wrapped.warnings.is_empty());
+    }
+}
+
+#[test]
+fn message_list_footer_layouts() {
+    for (chunks, footer) in [
+        // The footer holds only padding.
+        (
+            vec![b"A\xff\n\x00".to_vec(), b"B\xff\n\x00".to_vec()],
+            vec![0; 4],
+        ),
+        // The footer holds the last message, as in the game's files.
+        (
+            vec![b"A\xff\n\x00".to_vec()],
+            b"B\xff\n\x00\x00\x00".to_vec(),
+        ),
+    ] {
+        let mut data = Vec::new();
+        DataWithOffsetTable { chunks, footer }
+            .to_writer(&mut data, None, true)
+            .unwrap();
+        let list = MessageList::from(DataWithOffsetTable::from_reader(&data[..]).unwrap());
+        assert_eq!(list.messages.len(), 2);
+        assert_eq!(list.to_bytes().unwrap(), data);
+    }
+}
+
+#[test]
+fn standard_character_table() {
+    let table = CharacterTable::standard();
+
+    assert_eq!(
+        table
+            .decode(b"\x12\x03\xa1Esto no me gusta!\xff\x00\xff\x11\x01\xff\n\x00")
+            .unwrap(),
+        "{12}{03}¡Esto no me gusta!\n{FF11}{01}{FF0A}{00}"
+    );
+    assert_eq!(table.decode(b"{01}").unwrap(), "{7B}01}");
+    assert_eq!(table.encode("{7B}01}").unwrap(), b"{01}");
+    assert!(table.encode("{").is_err());
+    assert!(matches!(
+        table.decode(b"Hi\xff"),
+        Err(TextDecodingError::UnmappableCode {
+            offset: 2,
+            code: 0xFF
+        })
+    ));
+}
+
+#[test]
+fn character_table_from_tbl() {
+    let table = CharacterTable::from_tbl("41=A\n42=B\n\nFF00=\\n\n4142=[AB]\n").unwrap();
+
+    assert_eq!(table.decode(b"AAB\xff\x00B").unwrap(), "A[AB]\nB");
+    assert_eq!(table.encode("A[AB]\nB").unwrap(), b"AAB\xff\x00B");
+    assert_eq!(table.encode("AB").unwrap(), b"AB");
+    assert_eq!(
+        table.encode("AC").unwrap_err().to_string(),
+        "no mapping for the character 'C' at offset 1"
+    );
+    assert_eq!(CharacterTable::from_tbl(&table.to_tbl()).unwrap(), table);
+    assert!(CharacterTable::from_tbl("4=A").is_err());
+    assert!(CharacterTable::from_tbl("ZZ=A").is_err());
+}
+
+#[test]
+fn wrap_text() {
+    let table = CharacterTable::standard();
+    let widths = |_: &[u8]| 1;
+    let mut options = WrapOptions::new(7);
+
+    let wrapped = table
+        .encode_wrapped("aaa bbb ccc ddd\neeeeeeeee", &widths, &options)
+        .unwrap();
+    assert_eq!(wrapped.data, b"aaa bbb\xff\x00ccc ddd\xff\x00eeeeeeeee");
+    assert_eq!(
+        wrapped.warnings,
+        [WrapWarning::LineTooWide {
+            offset: 16,
+            width: 9
+        }]
+    );
+
+    options.lines_per_box = Some(2);
+    options.box_break = Some(b"\xff\x11\x00".to_vec());
+    let wrapped = table
+        .encode_wrapped("aaa bbb ccc ddd eee", &widths, &options)
+        .unwrap();
+    assert_eq!(wrapped.data, b"aaa bbb\xff\x00ccc ddd\xff\x11\x00eee");
+    assert!(wrapped.warnings.is_empty());
+
+    options.mode = WrapMode::WarnOnly;
+    let wrapped = table
+        .encode_wrapped("aaa bbb ccc\nddd\neee", &widths, &options)
+        .unwrap();
+    assert_eq!(wrapped.data, table.encode("aaa bbb ccc\nddd\neee").unwrap());
+    assert_eq!(
+        wrapped.warnings,
+        [
+            WrapWarning::LineTooWide {
+                offset: 8,
+                width: 11
+            },
+            WrapWarning::TooManyLines { offset: 15 }
+        ]
+    );
+}
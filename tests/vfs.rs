@@ -0,0 +1,84 @@
+#![cfg(feature = "fs")]
+
+use std::io;
+
+use mnllib::{event::FieldEvents, map::FieldMaps, misc::ProjectPaths, vfs::Vfs};
+
+#[test]
+fn load_through_vfs() {
+    let paths = ProjectPaths::new("tests");
+    let vfs: &dyn Vfs = &paths;
+    assert_eq!(
+        FieldMaps::load_from_vfs(vfs).unwrap(),
+        FieldMaps::load_from(&paths).unwrap()
+    );
+    assert_eq!(
+        vfs.read_file("FMap/FMapData.dat").unwrap(),
+        std::fs::read("tests/data/data/FMap/FMapData.dat").unwrap()
+    );
+    assert_eq!(
+        vfs.read_file("FEvent/FEvent.dat").unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+    assert!(FieldEvents::load_from_vfs(vfs).is_err());
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn zip_vfs() {
+    use mnllib::{
+        vfs::ZipVfs,
+        zip::{ZipArchive, ZipError, ZipWriter},
+    };
+
+    let paths = ProjectPaths::new("tests");
+    let mut writer = ZipWriter::new(Vec::new());
+    for name in ["FMap/FMapData.dat", "Treasure/TreasureInfo.dat"] {
+        writer
+            .add_file(
+                &format!("data/data/{name}"),
+                &paths.read_file(name).unwrap(),
+            )
+            .unwrap();
+    }
+    for overlay_number in [3, 4] {
+        writer
+            .add_file(
+                &format!("data/overlay.dec/overlay_{overlay_number:04}.dec.bin"),
+                &paths.read_overlay(overlay_number).unwrap(),
+            )
+            .unwrap();
+    }
+    writer.add_file("empty.bin", &[]).unwrap();
+    writer.add_file("stored.bin", b"ABCstore").unwrap();
+    let data = writer.finish().unwrap();
+
+    let archive = ZipArchive::from_bytes(data.clone()).unwrap();
+    assert_eq!(archive.file_names().count(), 6);
+    assert_eq!(archive.file("empty.bin").unwrap(), Some(Vec::new()));
+    assert_eq!(archive.file("missing.bin").unwrap(), None);
+    let vfs = ZipVfs::new(archive);
+    assert_eq!(
+        FieldMaps::load_from_vfs(&vfs).unwrap(),
+        FieldMaps::load_from(&paths).unwrap()
+    );
+    assert_eq!(
+        vfs.read_overlay(5).unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+
+    // Files which don't get smaller are stored, so their data can be corrupted directly.
+    let mut corrupted = data.clone();
+    let position = corrupted.windows(8).position(|x| x == b"ABCstore").unwrap();
+    corrupted[position] = b'X';
+    assert!(matches!(
+        ZipArchive::from_bytes(corrupted)
+            .unwrap()
+            .file("stored.bin"),
+        Err(ZipError::ChecksumMismatch(_))
+    ));
+    assert!(matches!(
+        ZipArchive::from_bytes(data[..data.len() - 1].to_vec()),
+        Err(ZipError::MissingEndOfCentralDirectory)
+    ));
+}
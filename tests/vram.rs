@@ -0,0 +1,64 @@
+#![cfg(feature = "fs")]
+
+use mnllib::{
+    map::FieldMaps,
+    misc::ProjectPaths,
+    vram::{
+        VramDump, CHARACTER_BASE_BLOCK_SIZE, EXTENDED_PALETTE_SLOT_SIZE, SCREEN_BASE_BLOCK_SIZE,
+    },
+};
+
+#[test]
+fn dump_field_map_vram() {
+    let field_maps = FieldMaps::load_from(&ProjectPaths::new("tests")).unwrap();
+    let (map_chunk, tilesets) = field_maps.decode_map(0).unwrap();
+    let dump = VramDump::new(&map_chunk, tilesets.each_ref().map(Option::as_ref)).unwrap();
+    assert_eq!(
+        dump.bg_extended_palettes.len(),
+        EXTENDED_PALETTE_SLOT_SIZE * 3
+    );
+
+    let mut num_layers = 0;
+    for (index, layer) in dump.layers.iter().enumerate() {
+        let Some(layer) = layer else {
+            assert!(map_chunk.tile_layers[index].is_none() || tilesets[index].is_none());
+            continue;
+        };
+        num_layers += 1;
+        assert_eq!(layer.character_base % CHARACTER_BASE_BLOCK_SIZE, 0);
+        assert_eq!(layer.screen_base % SCREEN_BASE_BLOCK_SIZE, 0);
+
+        let characters = tilesets[index]
+            .as_ref()
+            .unwrap()
+            .to_bytes(layer.pixel_size)
+            .unwrap();
+        assert_eq!(
+            &dump.bg_vram[layer.character_base..][..characters.len()],
+            &characters[..]
+        );
+
+        let tile_layer = map_chunk.tile_layers[index].as_ref().unwrap();
+        let (block_cols, block_rows) = layer.screen_blocks;
+        assert_eq!(block_cols, tile_layer.cols().div_ceil(32));
+        assert_eq!(block_rows, tile_layer.rows().div_ceil(32));
+        for ((row, col), tile) in tile_layer.indexed_iter() {
+            let block = row / 32 * block_cols + col / 32;
+            let offset =
+                layer.screen_base + block * SCREEN_BASE_BLOCK_SIZE + (row % 32 * 32 + col % 32) * 2;
+            assert_eq!(
+                &dump.bg_vram[offset..offset + 2],
+                &tile.into_bits().to_le_bytes()
+            );
+        }
+
+        if let Some(palette) = &map_chunk.palettes[index] {
+            let colors = palette.to_bytes();
+            assert_eq!(
+                &dump.bg_extended_palettes[index * EXTENDED_PALETTE_SLOT_SIZE..][..colors.len()],
+                &colors[..]
+            );
+        }
+    }
+    assert!(num_layers > 0);
+}